@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::time::Instant;
+use std::{path::PathBuf, rc::Rc};
+
+use qt_charts::qt_core::{Key, KeyboardModifier, QTimer, SlotNoArgs, SlotOfBool};
+use qt_widgets::{
+    cpp_core::Ptr,
+    qt_core::{qs, QBox},
+    qt_gui::QKeySequence,
+    QApplication, QCheckBox, QHBoxLayout, QPushButton, QShortcut, QTabWidget, QVBoxLayout, QWidget,
+};
+
+use radiothing_qt::app_settings::{self, AppSettings, Autosave, DEFAULT_SETTINGS};
+use radiothing_qt::gui_groups::{habhub_group::HabhubGroup, ApplyMode};
+use radiothing_qt::session::DeviceSession;
+use radiothing_qt::worker::worker_manager::{frame_event_budget, DeviceManager};
+
+// the interval the events are polled and the graphs are redrawn at, about 60 times a second
+const POLL_INTERVAL_MS: u64 = 16;
+
+#[allow(unused)]
+struct App {
+    root: QBox<QWidget>,
+    tabs: QBox<QTabWidget>,
+    v_layout_right: QBox<QVBoxLayout>,
+    habhub_group: Rc<HabhubGroup>,
+    // the changes in every group of every device are either sent right away or only with Apply
+    auto_update: QBox<QCheckBox>,
+    apply_mode: Rc<ApplyMode>,
+    add_device: QBox<QPushButton>,
+    restore_defaults: QBox<QPushButton>,
+
+    // the first session is saved to the settings file together with the app, every other one to a file next to it
+    sessions: Rc<RefCell<Vec<DeviceSession>>>,
+    settings: Rc<AppSettings>,
+    save_path: Option<PathBuf>,
+    autosave: RefCell<Autosave>,
+}
+
+impl App {
+    unsafe fn new() -> Self {
+        let (settings, devices, save_path) = app_settings::get_settings();
+        let settings = Rc::new(settings);
+
+        let root = QWidget::new_0a();
+
+        let h_layout = QHBoxLayout::new_1a(&root);
+
+        let tabs = QTabWidget::new_0a();
+        h_layout.add_widget(&tabs);
+
+        let apply_mode = Rc::new(ApplyMode::new(settings.auto_update));
+
+        // the habhub group is shared by all the sessions, every one of them feeds it its decoded text
+        let (habhub_group, habhub_widget) = HabhubGroup::new(settings.clone(), apply_mode.clone());
+
+        let sessions: Rc<RefCell<Vec<DeviceSession>>> = Rc::new(RefCell::new(Vec::new()));
+        add_session(
+            tabs.as_ptr(),
+            &sessions,
+            settings.clone(),
+            apply_mode.clone(),
+            &habhub_group,
+        );
+        for device in devices {
+            add_session(
+                tabs.as_ptr(),
+                &sessions,
+                Rc::new(device),
+                apply_mode.clone(),
+                &habhub_group,
+            );
+        }
+
+        // RIGHT
+        let v_layout_right = QVBoxLayout::new_0a();
+        h_layout.add_layout_1a(&v_layout_right);
+
+        v_layout_right.add_widget(habhub_widget);
+
+        let auto_update = QCheckBox::from_q_string(&qs("Automatic update"));
+        auto_update.set_checked(settings.auto_update);
+        auto_update.set_tool_tip(&qs(
+            "Send every change to the devices as soon as it is made, otherwise only when Apply is pressed",
+        ));
+        v_layout_right.add_widget(&auto_update);
+
+        let a = apply_mode.clone();
+        auto_update
+            .toggled()
+            .connect(&SlotOfBool::new(&root, move |checked| {
+                a.set_automatic(checked)
+            }));
+
+        let add_device = QPushButton::from_q_string(&qs("Add device"));
+        v_layout_right.add_widget(&add_device);
+
+        let restore_defaults = QPushButton::from_q_string(&qs("Restore defaults"));
+        v_layout_right.add_widget(&restore_defaults);
+
+        v_layout_right.add_stretch_0a();
+
+        let (s, t, a, h) = (
+            sessions.clone(),
+            tabs.as_ptr(),
+            apply_mode.clone(),
+            habhub_group.clone(),
+        );
+        add_device
+            .clicked()
+            .connect(&SlotNoArgs::new(&root, move || {
+                // a new device starts from the defaults, it is saved with the others from then on
+                let index = add_session(t, &s, Rc::new(DEFAULT_SETTINGS), a.clone(), &h);
+                t.set_current_index(index);
+            }));
+
+        // nothing is sent to the running devices, the defaults are saved on exit like any other change
+        let (s, h, u) = (sessions.clone(), habhub_group.clone(), auto_update.as_ptr());
+        restore_defaults
+            .clicked()
+            .connect(&SlotNoArgs::new(&root, move || {
+                for session in s.borrow().iter() {
+                    session.apply_settings(&DEFAULT_SETTINGS);
+                }
+                h.apply_settings(&DEFAULT_SETTINGS);
+                u.set_checked(DEFAULT_SETTINGS.auto_update);
+            }));
+
+        // arrows tune by the fine step, with shift by the coarse one, space starts and stops the receiving
+        let shift = KeyboardModifier::ShiftModifier.to_int();
+        let tuning = [
+            (Key::KeyLeft.to_int(), -1.0, false),
+            (Key::KeyRight.to_int(), 1.0, false),
+            (Key::KeyLeft.to_int() | shift, -1.0, true),
+            (Key::KeyRight.to_int() | shift, 1.0, true),
+        ];
+        for &(key, direction, coarse) in &tuning {
+            add_shortcut(&root, key, &sessions, tabs.as_ptr(), move |session| {
+                session.step_frequency(direction, coarse)
+            });
+        }
+        add_shortcut(
+            &root,
+            Key::KeySpace.to_int(),
+            &sessions,
+            tabs.as_ptr(),
+            |session| session.toggle_run(),
+        );
+
+        // the position is restored only together with the size, a fresh config lets the window manager place the window
+        if settings.window_width > 0 && settings.window_height > 0 {
+            root.resize_2a(settings.window_width, settings.window_height);
+            root.move_2a(settings.window_x, settings.window_y);
+        }
+
+        root.show();
+
+        Self {
+            root,
+            tabs,
+            v_layout_right,
+            habhub_group,
+            auto_update,
+            apply_mode,
+            add_device,
+            restore_defaults,
+
+            sessions,
+            autosave: RefCell::new(Autosave::new(settings.autosave_s)),
+            settings,
+            save_path,
+        }
+    }
+    // every device gets the same share of the budget, adding devices doesn't slow the interface down
+    unsafe fn poll_events(&self) {
+        let sessions = self.sessions.borrow();
+        let budget = frame_event_budget(
+            self.settings.event_budget_ms,
+            POLL_INTERVAL_MS,
+            sessions.len(),
+        );
+
+        for session in sessions.iter() {
+            session.poll_events(budget);
+        }
+    }
+    // the settings are written every few seconds if they changed so that a crash doesn't lose them
+    unsafe fn autosave(&self) {
+        let path = match &self.save_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut autosave = self.autosave.borrow_mut();
+        if !autosave.due(Instant::now()) {
+            return;
+        }
+
+        let settings = self.collect_settings();
+        let devices = self.collect_device_settings();
+        let serialized = std::iter::once(&settings)
+            .chain(&devices)
+            .map(AppSettings::pretty_serialize)
+            .collect::<String>();
+        if !autosave.changed(&serialized) {
+            return;
+        }
+
+        match app_settings::save_all_settings(path, &settings, &devices) {
+            Ok(()) => log::debug!("Saved the settings to '{}'", path.to_string_lossy()),
+            Err(e) => {
+                log::error!("Error saving config to '{}': {}", path.to_string_lossy(), e);
+                autosave.write_failed();
+            }
+        }
+    }
+    unsafe fn collect_settings(&self) -> AppSettings {
+        let mut settings = DEFAULT_SETTINGS;
+        self.sessions.borrow()[0].populate_settings(&mut settings);
+        self.habhub_group.populate_settings(&mut settings);
+        settings.auto_update = self.auto_update.is_checked();
+
+        // x() and y() include the window frame just like move_2a() expects
+        settings.window_x = self.root.x();
+        settings.window_y = self.root.y();
+        settings.window_width = self.root.width();
+        settings.window_height = self.root.height();
+
+        let [left, middle, right] = self.sessions.borrow()[0].splitter_sizes();
+        settings.left_width = left;
+        settings.middle_width = middle;
+        settings.right_width = right;
+
+        // not editable in the gui, keep the loaded value
+        settings.event_budget_ms = self.settings.event_budget_ms;
+        settings.autosave_s = self.settings.autosave_s;
+
+        settings
+    }
+    // the settings of every session but the first, only the fields of the groups in the session are used from them
+    unsafe fn collect_device_settings(&self) -> Vec<AppSettings> {
+        self.sessions.borrow()[1..]
+            .iter()
+            .map(|session| {
+                let mut settings = DEFAULT_SETTINGS;
+                session.populate_settings(&mut settings);
+
+                let [left, middle, right] = session.splitter_sizes();
+                settings.left_width = left;
+                settings.middle_width = middle;
+                settings.right_width = right;
+
+                settings
+            })
+            .collect()
+    }
+}
+
+// creates the session of the next device in its own tab and returns the index of the tab
+// the first session has the default worker name, every other one is numbered
+unsafe fn add_session(
+    tabs: Ptr<QTabWidget>,
+    sessions: &RefCell<Vec<DeviceSession>>,
+    settings: Rc<AppSettings>,
+    apply_mode: Rc<ApplyMode>,
+    habhub_group: &Rc<HabhubGroup>,
+) -> i32 {
+    let number = sessions.borrow().len() + 1;
+    let device = match number {
+        1 => DeviceManager::new(),
+        number => DeviceManager::new_named(&format!("Worker thread {}", number)),
+    };
+
+    let session = DeviceSession::new(device, settings.clone(), apply_mode);
+    session.set_habhub_group(habhub_group.clone());
+
+    let AppSettings {
+        left_width,
+        middle_width,
+        right_width,
+        ..
+    } = *settings;
+    if left_width > 0 && middle_width > 0 && right_width > 0 {
+        session.set_splitter_sizes([left_width, middle_width, right_width]);
+    }
+
+    let index = tabs.add_tab_2a(session.page(), &qs(format!("Device {}", number)));
+    sessions.borrow_mut().push(session);
+
+    index
+}
+
+// runs the action on the session of the current tab whenever the key is pressed in the window
+// the widget with the focus gets the keys it uses itself first, like the arrows in a spinbox
+unsafe fn add_shortcut(
+    root: &QBox<QWidget>,
+    key: i32,
+    sessions: &Rc<RefCell<Vec<DeviceSession>>>,
+    tabs: Ptr<QTabWidget>,
+    action: impl Fn(&DeviceSession) + 'static,
+) {
+    let shortcut = QShortcut::new_2a(&QKeySequence::from_int(key), root);
+
+    let s = sessions.clone();
+    shortcut
+        .activated()
+        .connect(&SlotNoArgs::new(root, move || {
+            if let Some(session) = s.borrow().get(tabs.current_index() as usize) {
+                action(session);
+            }
+        }));
+}
+
+// shows the window and runs the Qt event loop until the window is closed, the settings are saved on exit
+pub fn run() -> ! {
+    soapysdr::configure_logging();
+
+    QApplication::init(|qapp| unsafe {
+        let app = Rc::new(App::new());
+
+        let timer = QTimer::new_1a(&app.root);
+        timer.set_interval(POLL_INTERVAL_MS as i32);
+
+        let a = app.clone();
+        timer.timeout().connect(&SlotNoArgs::new(&timer, move || {
+            a.poll_events();
+            a.autosave();
+        }));
+
+        qapp.about_to_quit()
+            .connect(&SlotNoArgs::new(qapp, move || {
+                if let Some(path) = &app.save_path {
+                    let settings = app.collect_settings();
+                    let devices = app.collect_device_settings();
+
+                    // the application quits regardless, the previous config is still there
+                    if let Err(e) = app_settings::save_all_settings(path, &settings, &devices) {
+                        log::error!("Error saving config to '{}': {}", path.to_string_lossy(), e);
+                    }
+                }
+            }));
+
+        timer.start_0a();
+
+        QApplication::exec()
+    })
+}
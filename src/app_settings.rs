@@ -1,21 +1,76 @@
-use std::path::PathBuf;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use crate::{decoder::Decoder, settings::Settings};
+use crate::{
+    decoder::Decoder,
+    dsp::window_functions::{WindowKind, WINDOW_KINDS},
+    favorites::Favorite,
+    iq_source::{IqFormat, IQ_FORMATS},
+    settings::{Field, Settings},
+    units,
+    worker::worker::format_device_list,
+};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AppSettings {
     pub auto_device: bool,
     pub device_filter: String,
     pub device: String,
+    pub device_serial: String,
 
     pub auto_update: bool,
+    pub auto_run: bool,
     pub frequency: f64,
+    pub frequency_unit: String,
     pub samplerate: f64,
     pub gain: f64,
     pub automatic_gain: bool,
     pub automatic_dc_offset: bool,
+    pub iq_correction: bool,
+    pub clock_source: String,
+    pub buffer_samples: u32,
+    pub settle_us: u32,
+    pub receive_timeout_us: u32,
+    pub worker_event_budget_us: u32,
+    pub fine_step_hz: f64,
+    pub coarse_step_hz: f64,
+    pub favorites: Vec<Favorite>,
+
+    pub decoder: String,
+    pub baudrate: f32,
+    pub stop_bits: f32,
+    pub freq_shift: f32,
+    pub cutoff_hz: f32,
+    pub transition_width: f32,
+    pub decimation: u32,
+    pub freq_offset: f32,
+    pub squelch_threshold: f32,
+    pub burst_gap_ms: u32,
+    pub raw_bits: bool,
+    pub deemphasis_us: u32,
+
+    pub telemetry_log: String,
+    pub fft_window: String,
+    pub show_band_plan: bool,
+    pub band_plan: String,
+    pub data_requests_in_flight: u32,
+    pub event_budget_ms: u32,
+    pub pre_trigger_s: f32,
+    pub autosave_s: u32,
 
-    pub decoder: String
+    pub window_x: i32,
+    pub window_y: i32,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub left_width: i32,
+    pub middle_width: i32,
+    pub right_width: i32,
 }
 
 impl AppSettings {
@@ -24,60 +79,652 @@ impl AppSettings {
             auto_device: auto_select_device,
             device_filter,
             device,
+            device_serial,
             auto_update,
+            auto_run,
             frequency,
+            frequency_unit,
             samplerate,
             gain,
             automatic_gain,
             automatic_dc_offset,
-            ..
+            iq_correction,
+            clock_source,
+            buffer_samples,
+            settle_us,
+            receive_timeout_us,
+            worker_event_budget_us,
+            fine_step_hz,
+            coarse_step_hz,
+            favorites,
+            decoder,
+            baudrate,
+            stop_bits,
+            freq_shift,
+            cutoff_hz,
+            transition_width,
+            decimation,
+            freq_offset,
+            squelch_threshold,
+            burst_gap_ms,
+            raw_bits,
+            deemphasis_us,
+            telemetry_log,
+            fft_window,
+            show_band_plan,
+            band_plan,
+            data_requests_in_flight,
+            event_budget_ms,
+            pre_trigger_s,
+            autosave_s,
+            window_x,
+            window_y,
+            window_width,
+            window_height,
+            left_width,
+            middle_width,
+            right_width,
         } = self.clone();
 
         format!(
             r#"auto_device = {:8}      # if true, the application tries to immediatelly select a device without user input
 device_filter = {:8}    # the "args" used to filter the SoapySDR devices, for example 'driver=RTLSDR' or 'hardware=R820T' 
 device = {:8}           # the 'label' field of the device used last time, auto_select_device first tries to find a device with this label
+device_serial = {:8}    # the 'serial' of the device created last time, it is preferred over the label because the label can change
 
-auto_update = {:8}      # whether to update the receiver configuration immediatelly after a value is changed
+auto_update = {:8}      # whether every group sends a changed value to the device immediatelly instead of waiting for Apply
+auto_run = {:8}         # whether to start receiving as soon as a device is created, without pressing run
 
     # values of the different configuration options
     frequency = {} # MHz
+    frequency_unit = "{}" # the unit the frequency is displayed in, one of Hz, kHz, MHz
     samplerate = {} # MSps
     gain = {} # dB
     automatic_gain = "{}"
-    automatic_dc_offset = "{}""#,
+    automatic_dc_offset = "{}"
+    iq_correction = "{}" # corrects the mismatch of the I and Q branches which mirrors strong signals to the other side of the center
+    clock_source = "{}" # the reference the device is clocked from, like "internal", "external" or "gpsdo", empty leaves it to the driver
+    buffer_samples = {} # how many samples are decoded at once, 0 uses the device mtu
+    settle_us = {} # us, how long the spectrum ignores the samples after retuning
+    receive_timeout_us = {} # us, how long a read waits for the samples, timeouts are retried a few times before the receiving stops
+    worker_event_budget_us = {} # us, how long the worker handles the commands between two stream reads, 0 is as long as the device takes to fill its mtu
+    fine_step_hz = {} # Hz, how far the left and right arrow keys tune
+    coarse_step_hz = {} # Hz, how far they tune with shift held
+    favorites = {} # [frequency in MHz, samplerate in MSps, "decoding mode"] of every favorite, recalled with the buttons under the frequency
+
+decoder = {:8}          # the name of the selected decoding mode, for example "None", "Baudot", "WBFM" or "Tone"
+
+    # parameters of the Baudot decoder
+    baudrate = {} # Bd
+    stop_bits = {}
+    freq_shift = {} # Hz
+    cutoff_hz = {} # Hz, how far from the signal the filter reaches, 0 follows the shift
+    transition_width = {} # of the filter samplerate, a sharper filter needs more taps and more cpu
+    decimation = {} # of the filter, 0 computes it from the samplerate and the baudrate
+    freq_offset = "{}" # Hz, where the signal is relative to the receiver frequency, quoted because it can be negative
+
+    # buffers with a lower snr are not decoded, 0 disables the squelch
+    squelch_threshold = {} # dB
+    # the decoded text starts a new timestamped line after this long without any characters, 0 disables it
+    burst_gap_ms = {} # ms
+    raw_bits = "{}" # for debugging, log the bits the decoder slices from the signal at debug level
+
+    # parameters of the WBFM audio, the frequency offset is shared with the Baudot decoder
+    deemphasis_us = {} # us, 50 in most of the world, 75 in the Americas
+
+telemetry_log = {:8}    # the csv file the parsed UKHAS position reports are appended to, empty disables the logging
+fft_window = {:8}       # the window the spectrum is computed with, one of Rectangular, BlackmanHaris
+show_band_plan = {:8}   # draw the bands of the band plan behind the spectrum
+band_plan = {:8}        # the file the band plan is read from, empty uses the built-in amateur, broadcast and ISM bands
+data_requests_in_flight = {} # how many spectra are requested from the worker at once, more update the graphs faster on a slow computer
+event_budget_ms = {} # ms, how long the events of the devices are handled per frame, shared by the devices, 0 is a third of the frame
+pre_trigger_s = {} # s, how much of the IQ received before Record is pressed starts the recording, it is kept in memory
+autosave_s = {} # s, how often the settings are saved while running so that a crash doesn't lose them, 0 saves only on exit
+
+# the position and size of the window, a width or height of 0 leaves the window at its default geometry
+# the position is quoted because it can be negative on multi-monitor setups
+window_x = "{}"
+window_y = "{}"
+window_width = {} # px
+window_height = {} # px
+
+    # the widths of the left, middle and right columns, 0 keeps the default proportions
+    left_width = {} # px
+    middle_width = {} # px
+    right_width = {} # px"#,
             // the data is first formatted into a string before being interpolated into the main string
             // so that the minimum width-format is correct
             format!("\"{}\"", auto_select_device),
             format!("\"{}\"", device_filter),
             format!("\"{}\"", device),
+            format!("\"{}\"", device_serial),
             format!("\"{}\"", auto_update),
+            format!("\"{}\"", auto_run),
             frequency,
+            frequency_unit,
             samplerate,
             gain,
             automatic_gain,
             automatic_dc_offset,
+            iq_correction,
+            clock_source,
+            buffer_samples,
+            settle_us,
+            receive_timeout_us,
+            worker_event_budget_us,
+            fine_step_hz,
+            coarse_step_hz,
+            Field::from(favorites),
+            format!("\"{}\"", decoder),
+            baudrate,
+            stop_bits,
+            freq_shift,
+            cutoff_hz,
+            transition_width,
+            decimation,
+            freq_offset,
+            squelch_threshold,
+            burst_gap_ms,
+            raw_bits,
+            deemphasis_us,
+            format!("\"{}\"", telemetry_log),
+            format!("\"{}\"", fft_window),
+            format!("\"{}\"", show_band_plan),
+            format!("\"{}\"", band_plan),
+            data_requests_in_flight,
+            event_budget_ms,
+            pre_trigger_s,
+            autosave_s,
+            window_x,
+            window_y,
+            window_width,
+            window_height,
+            left_width,
+            middle_width,
+            right_width,
         )
     }
+    // fields which are missing or have the wrong type fall back to their default values,
+    // which are also filled into `settings`
+    pub fn from_settings(settings: &mut Settings) -> Self {
+        // the frequency and samplerate are kept in MHz and MSps, a value written with a unit like 433.92MHz was read in Hz
+        for name in &["frequency", "samplerate"] {
+            if let Some(field) = settings.get_hashmap_mut().get_mut(*name) {
+                *field = field.clone().in_unit(units::to_mega);
+            }
+        }
+
+        macro_rules! settings_from_settings {
+            ($($field:ident),* $(,)*) => {
+                AppSettings {
+                    $(
+                        $field: settings.get_or(stringify!($field), DEFAULT_SETTINGS.$field),
+                    )*
+                }
+            }
+        }
+
+        settings_from_settings! {
+            auto_device,
+            device,
+            device_serial,
+            device_filter,
+            auto_update,
+            auto_run,
+            frequency,
+            frequency_unit,
+            samplerate,
+            gain,
+            automatic_gain,
+            automatic_dc_offset,
+            iq_correction,
+            clock_source,
+            buffer_samples,
+            settle_us,
+            receive_timeout_us,
+            worker_event_budget_us,
+            fine_step_hz,
+            coarse_step_hz,
+            favorites,
+            decoder,
+            baudrate,
+            stop_bits,
+            freq_shift,
+            cutoff_hz,
+            transition_width,
+            decimation,
+            freq_offset,
+            squelch_threshold,
+            burst_gap_ms,
+            raw_bits,
+            deemphasis_us,
+            telemetry_log,
+            fft_window,
+            show_band_plan,
+            band_plan,
+            data_requests_in_flight,
+            event_budget_ms,
+            pre_trigger_s,
+            autosave_s,
+            window_x,
+            window_y,
+            window_width,
+            window_height,
+            left_width,
+            middle_width,
+            right_width,
+        }
+    }
 }
 
 pub const DEFAULT_SETTINGS: AppSettings = AppSettings {
     auto_device: false,
     device_filter: String::new(),
     device: String::new(),
+    device_serial: String::new(),
 
     auto_update: false,
+    auto_run: false,
     frequency: 0.0,
+    frequency_unit: String::new(),
     samplerate: 0.0,
     gain: 0.0,
     automatic_gain: false,
     automatic_dc_offset: false,
+    iq_correction: false,
+    clock_source: String::new(),
+    buffer_samples: 0,
+    settle_us: 10_000,
+    receive_timeout_us: 200_000,
+    worker_event_budget_us: 0,
+    fine_step_hz: 1_000.0,
+    coarse_step_hz: 100_000.0,
+    favorites: Vec::new(),
 
     decoder: String::new(),
+    baudrate: 50.0,
+    stop_bits: 1.5,
+    freq_shift: 425.0,
+    cutoff_hz: 0.0,
+    transition_width: 0.1,
+    decimation: 0,
+    freq_offset: 0.0,
+    squelch_threshold: 0.0,
+    burst_gap_ms: 2000,
+    raw_bits: false,
+    deemphasis_us: 50,
+
+    telemetry_log: String::new(),
+    fft_window: String::new(),
+    show_band_plan: false,
+    band_plan: String::new(),
+    data_requests_in_flight: 1,
+    event_budget_ms: 0,
+    pre_trigger_s: 0.0,
+    autosave_s: 60,
+
+    window_x: 0,
+    window_y: 0,
+    window_width: 0,
+    window_height: 0,
+    left_width: 0,
+    middle_width: 0,
+    right_width: 0,
 };
 
+// the file the config is read from and saved to when only a directory is given
+pub const DEFAULT_CONFIG_NAME: &str = "radiothing_config.txt";
+
+// a path to an existing directory or one ending with a separator gets the default file name, any other path is the file
+pub fn resolve_config_path(path: PathBuf) -> PathBuf {
+    let ends_with_separator = path.to_string_lossy().ends_with(std::path::is_separator);
+
+    if path.is_dir() || ends_with_separator {
+        let file = path.join(DEFAULT_CONFIG_NAME);
+        log::info!(
+            "'{}' is a directory, using '{}'",
+            path.to_string_lossy(),
+            file.to_string_lossy()
+        );
+        file
+    } else {
+        path
+    }
+}
+
+// the contents go to a file next to the target which is then renamed over it
+// a crash or a failed write leaves the previous file as it was, at worst with the temporary file next to it
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut temp_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The path has no file name"))?
+        .to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            // the data has to be on the disk before the rename makes it the config
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+pub fn save_settings(path: &Path, settings: &AppSettings) -> io::Result<()> {
+    write_atomically(path, |file| {
+        file.write_all(settings.pretty_serialize().as_bytes())
+    })
+}
+
+// the config of the device `number`, the first device is in the config itself and every other one in a file next to it
+// 'radiothing_config.txt' has the second device in 'radiothing_config.device2.txt'
+pub fn device_config_path(path: &Path, number: usize) -> PathBuf {
+    if number <= 1 {
+        return path.to_owned();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.device{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}.device{}", stem, number),
+    };
+    path.with_file_name(name)
+}
+
+// the settings of the other devices are saved next to the config, only their device, receiver, decoder and output
+// fields are used, the file after the last device is removed so that a device of an earlier run isn't read back
+pub fn save_all_settings(
+    path: &Path,
+    settings: &AppSettings,
+    devices: &[AppSettings],
+) -> io::Result<()> {
+    save_settings(path, settings)?;
+
+    for (i, device) in devices.iter().enumerate() {
+        save_settings(&device_config_path(path, i + 2), device)?;
+    }
+
+    match fs::remove_file(device_config_path(path, devices.len() + 2)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// the errors are returned as one string with a line for each of them
+pub(crate) fn parse_settings(string: &str) -> Result<AppSettings, String> {
+    let (mut settings, errors) = Settings::new(string);
+
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|e| e.with_source().to_string() + "\n")
+            .collect());
+    }
+
+    let mut deserialized = AppSettings::from_settings(&mut settings);
+    // an unknown window is reported once here and saved as the one actually used
+    deserialized.fft_window = parse_fft_window(&deserialized.fft_window).name().to_owned();
+
+    Ok(deserialized)
+}
+
+// the settings of the devices after the first one, read until a file is missing or broken
+pub fn read_device_settings(path: &Path) -> Vec<AppSettings> {
+    let mut devices = Vec::new();
+
+    loop {
+        let device_path = device_config_path(path, devices.len() + 2);
+        if !device_path.is_file() {
+            return devices;
+        }
+
+        let parsed = fs::read_to_string(&device_path)
+            .map_err(|e| e.to_string())
+            .and_then(|string| parse_settings(&string));
+        match parsed {
+            Ok(settings) => devices.push(settings),
+            Err(e) => {
+                log::error!(
+                    "Error reading the device config at '{}', it and the ones after it are skipped: {}",
+                    device_path.to_string_lossy(),
+                    e
+                );
+                return devices;
+            }
+        }
+    }
+}
+
+// decides when the settings are saved while the application runs, the settings are looked at every interval
+// and written only if they changed since they were written last
+pub struct Autosave {
+    interval: Option<Duration>,
+    last_check: Option<Instant>,
+    last_hash: Option<u64>,
+}
+
+impl Autosave {
+    // an interval of 0 disables the saving
+    pub fn new(interval_s: u32) -> Self {
+        Self {
+            interval: match interval_s {
+                0 => None,
+                s => Some(Duration::from_secs(s as u64)),
+            },
+            last_check: None,
+            last_hash: None,
+        }
+    }
+    // whether the interval passed since the last check, the first call only starts the interval
+    pub fn due(&mut self, now: Instant) -> bool {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            None => return false,
+        };
+
+        match self.last_check {
+            Some(last) if now.duration_since(last) < interval => false,
+            Some(_) => {
+                self.last_check = Some(now);
+                true
+            }
+            None => {
+                self.last_check = Some(now);
+                false
+            }
+        }
+    }
+    // whether the serialized settings differ from the ones written last, they count as written from now on
+    pub fn changed(&mut self, serialized: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        serialized.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let changed = self.last_hash != Some(hash);
+        self.last_hash = Some(hash);
+        changed
+    }
+    // the write failed, the same settings are tried again at the next check
+    pub fn write_failed(&mut self) {
+        self.last_hash = None;
+    }
+}
+
+// the spectrum is computed with this window if the config doesn't name one
+pub const DEFAULT_FFT_WINDOW: WindowKind = WindowKind::BlackmanHaris;
+
+// the name is matched regardless of case, empty means the default
+pub fn parse_fft_window(name: &str) -> WindowKind {
+    if name.is_empty() {
+        return DEFAULT_FFT_WINDOW;
+    }
+
+    match WINDOW_KINDS
+        .iter()
+        .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    {
+        Some(&kind) => kind,
+        None => {
+            log::warn!(
+                "Unknown fft window '{}', using {}",
+                name,
+                DEFAULT_FFT_WINDOW.name()
+            );
+            DEFAULT_FFT_WINDOW
+        }
+    }
+}
+
+pub enum HeadlessInput {
+    // a recording with interleaved little-endian 32-bit float IQ samples
+    File(PathBuf),
+    // samples piped from another tool, like rtl_sdr
+    Stdin(IqFormat),
+    // a synthetic baudot signal with this text, for testing without a receiver or a recording
+    Generator(String),
+}
+
+pub struct HeadlessSettings {
+    pub input: HeadlessInput,
+    pub samplerate: f64,
+    pub mode: String,
+    pub baudrate: f32,
+    pub stop_bits: f32,
+    pub freq_shift: f32,
+    pub cutoff_hz: f32,
+    pub transition_width: f32,
+    pub forced_decim: Option<u32>,
+    pub freq_offset: f32,
+    pub squelch_threshold: f32,
+}
+
+// returns None if the application should start the GUI
+pub fn get_headless_settings() -> Option<HeadlessSettings> {
+    let mut args = pico_args::Arguments::from_env();
+
+    if !args.contains("--headless") {
+        return None;
+    }
+
+    // unlike the config paths these are mandatory so any error is fatal
+    fn exit_on_error<T>(result: Result<T, pico_args::Error>) -> T {
+        match result {
+            Ok(ok) => ok,
+            Err(e) => {
+                log::error!("Error parsing headless args: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn parse_format(name: &str) -> Result<IqFormat, String> {
+        IqFormat::from_name(name).ok_or_else(|| {
+            let names: Vec<_> = IQ_FORMATS.iter().map(|f| f.name()).collect();
+            format!(
+                "unknown sample format '{}', expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+
+    // stdin is read only if its format is given and the generator only if its text is, otherwise the file is mandatory
+    let input = match exit_on_error(args.opt_value_from_fn("--stdin-format", parse_format)) {
+        Some(format) => HeadlessInput::Stdin(format),
+        None => match exit_on_error(args.opt_value_from_str("--generate")) {
+            Some(text) => HeadlessInput::Generator(text),
+            None => HeadlessInput::File(exit_on_error(args.value_from_str("--input"))),
+        },
+    };
+
+    Some(HeadlessSettings {
+        input,
+        samplerate: exit_on_error(args.value_from_str("--samplerate")),
+        mode: exit_on_error(args.opt_value_from_str("--mode"))
+            .unwrap_or_else(|| "baudot".to_owned()),
+        baudrate: exit_on_error(args.opt_value_from_str("--baudrate"))
+            .unwrap_or(DEFAULT_SETTINGS.baudrate),
+        stop_bits: exit_on_error(args.opt_value_from_str("--stop-bits"))
+            .unwrap_or(DEFAULT_SETTINGS.stop_bits),
+        freq_shift: exit_on_error(args.opt_value_from_str("--shift"))
+            .unwrap_or(DEFAULT_SETTINGS.freq_shift),
+        cutoff_hz: exit_on_error(args.opt_value_from_str("--cutoff"))
+            .unwrap_or(DEFAULT_SETTINGS.cutoff_hz),
+        transition_width: exit_on_error(args.opt_value_from_str("--transition-width"))
+            .unwrap_or(DEFAULT_SETTINGS.transition_width),
+        forced_decim: exit_on_error(args.opt_value_from_str("--decimation")),
+        freq_offset: exit_on_error(args.opt_value_from_str("--offset"))
+            .unwrap_or(DEFAULT_SETTINGS.freq_offset),
+        squelch_threshold: exit_on_error(args.opt_value_from_str("--squelch"))
+            .unwrap_or(DEFAULT_SETTINGS.squelch_threshold),
+    })
+}
+
+// returns the path of the config to check if only the config should be validated
+pub fn get_validate_config_path() -> Option<PathBuf> {
+    let mut args = pico_args::Arguments::from_env();
+
+    match args.opt_value_from_str("--validate-config") {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Error parsing args: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// the file the decoded text and telemetry are logged to, if any
+pub fn get_decode_log_path() -> Option<PathBuf> {
+    let mut args = pico_args::Arguments::from_env();
+
+    match args.opt_value_from_str("--decode-log") {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Error parsing args: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// prints every parse error of the config, true if there are none
+pub fn validate_config(path: &Path) -> bool {
+    let string = match std::fs::read_to_string(path) {
+        Ok(string) => string,
+        Err(e) => {
+            log::error!(
+                "Error reading config file at '{}': {}",
+                path.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+
+    let (_settings, errors) = Settings::new(&string);
+    for e in &errors {
+        println!("{}", e.with_source());
+    }
+
+    match errors.len() {
+        0 => println!("'{}' is a valid configuration", path.to_string_lossy()),
+        count => println!("Found {} error(s) in '{}'", count, path.to_string_lossy()),
+    }
+
+    errors.is_empty()
+}
+
 //                      (Settings, Save path)
-pub fn get_settings() -> (AppSettings, Option<PathBuf>) {
+// the settings of the first device and the app, the settings of the other devices and the path to save them to
+pub fn get_settings() -> (AppSettings, Vec<AppSettings>, Option<PathBuf>) {
     const HELP: &str = "\
 Overview: Tool for receiving transmission from weather baloons.
 
@@ -85,11 +732,40 @@ Usage: radiothing [options]
 
 Options:
 --create-config       Write default config file to provided path and immediatelly exit, CWD if empty.
+--list-devices        List the SoapySDR devices matching the provided args and exit,
+                      the device_filter from the config is used if empty.
+--validate-config     Check the config file at the provided path, print any errors and exit,
+                      with a non-zero code if there are any.
 -c, --config          Path to configuration file and/or the path the config will be saved to,
-                      by default the current working directory.
+                      by default the current working directory. A directory gets the file name
+                      radiothing_config.txt, also with --create-config and --save-config.
+                      Every device after the first one is saved next to it, like radiothing_config.device2.txt.
 -i, --ignore-config   Ignore any configuration file, don't save upon exit either.
 -s, --save-config     Path to save the configuration on program exit, by default same as path.
+--decode-log          Append every decoded line and telemetry sentence to this file, one line each with
+                      the time, level, source, frequency, mode and text, separately from the app log.
 -h, --help            Print this help.
+
+Headless options:
+--headless            Decode a recorded file without the GUI, print the decoded text and exit.
+--input               Path to the file with interleaved little-endian 32-bit float IQ samples.
+--stdin-format        Read the samples from stdin instead of a file, in the format cu8, cs16 or cf32,
+                      for example 'rtl_sdr - | radiothing --headless --stdin-format cu8 ...'.
+--generate            Decode a generated baudot signal with this text instead of a file, as a self-test.
+                      It uses the samplerate, baudrate, stop bits, shift and offset given for decoding.
+--samplerate          Samplerate of the recording in Sps.
+--mode                Decoding mode, currently only 'baudot' (the default).
+--baudrate            Baudot baudrate in Bd, by default 50.
+--stop-bits           Baudot stop bit count, by default 1.5.
+--shift               Baudot frequency shift in Hz, by default 425.
+--cutoff              How far from the signal the low-pass filter reaches in Hz, by default 0 which follows the shift.
+--transition-width    Transition width of the low-pass filter relative to its samplerate, by default 0.1,
+                      a narrower one suits small shifts but needs more cpu.
+--decimation          Decimation of the low-pass filter, by default computed from the samplerate and the baudrate,
+                      a lower one needs more cpu but keeps the aliases further from the signal.
+--offset              Frequency of the signal relative to the center of the recording in Hz, by default 0,
+                      the signal is shifted to the center before decoding.
+--squelch             Minimum snr in dB a buffer needs to be decoded, by default 0 which decodes everything.
 ";
 
     // we want to exit on any errors here, also it seems that optional values don't play well with the parsing mechanism
@@ -107,6 +783,71 @@ Options:
         }
     }
 
+    fn read_config(
+        args: &mut pico_args::Arguments,
+    ) -> (AppSettings, Vec<AppSettings>, Option<PathBuf>) {
+        if !args.contains(["-i", "--ignore-config"]) {
+            let path = handle_error(args.opt_value_from_str(["-c", "--config"]))
+                .map(resolve_config_path)
+                .unwrap_or_else(|| std::env::current_dir().unwrap().join(DEFAULT_CONFIG_NAME));
+
+            let save_path = if let Some(save) =
+                handle_error(args.opt_value_from_str(["-s", "--save-config"]))
+            {
+                Some(resolve_config_path(save))
+            } else if args.contains(["-s", "--save-config"]) {
+                Some(path.clone())
+            } else {
+                None
+            };
+
+            log::info!("Reading configuration file at '{}'", path.to_string_lossy());
+
+            match &save_path {
+                Some(path) => log::info!("Save path is '{}'", path.to_string_lossy()),
+                None => log::info!("Will not save config"),
+            }
+
+            if path.is_file() {
+                let string = match std::fs::read_to_string(&path) {
+                    Ok(string) => string,
+                    Err(e) => {
+                        log::error!(
+                            "Error reading config file at '{}': {}",
+                            path.to_string_lossy(),
+                            e
+                        );
+                        return (DEFAULT_SETTINGS, Vec::new(), save_path);
+                    }
+                };
+
+                match parse_settings(&string) {
+                    Ok(settings) => {
+                        let devices = read_device_settings(&path);
+                        return (settings, devices, save_path);
+                    }
+                    Err(errors) => {
+                        log::error!(
+                            "Encountered errors while parsing settings, falling back to defaults:\n{}",
+                            errors
+                        );
+
+                        // the parsed settings can't be assumed to be correct
+                        // fall back to the defaults but set save_config to false so that
+                        // we don't overwrite the bad settings file in case the error there is only minor
+                        return (DEFAULT_SETTINGS, Vec::new(), None);
+                    }
+                }
+            } else {
+                log::error!("Config file at '{}' is not a file", path.to_string_lossy())
+            }
+        } else {
+            log::info!("Ignoring config");
+        }
+
+        return (DEFAULT_SETTINGS, Vec::new(), None);
+    }
+
     let mut args = pico_args::Arguments::from_env();
 
     if args.contains(["-h", "--help"]) {
@@ -116,18 +857,16 @@ Options:
 
     if args.contains("--create-config") {
         // for some reason here the OptionWithoutAValue error isn't emitted? why?
-        let path = handle_error(args.opt_value_from_str("--create-config")).unwrap_or_else(|| {
-            std::env::current_dir()
-                .unwrap()
-                .join("radiothing_config.txt")
-        });
+        let path = handle_error(args.opt_value_from_str("--create-config"))
+            .map(resolve_config_path)
+            .unwrap_or_else(|| std::env::current_dir().unwrap().join(DEFAULT_CONFIG_NAME));
 
         log::info!(
             "Creating default configuration at '{}'",
             path.to_string_lossy()
         );
 
-        match std::fs::write(&path, DEFAULT_SETTINGS.pretty_serialize()) {
+        match save_settings(&path, &DEFAULT_SETTINGS) {
             Err(e) => log::error!(
                 "Error writing config to file at '{}': {}",
                 path.to_string_lossy(),
@@ -139,87 +878,368 @@ Options:
         std::process::exit(0);
     }
 
-    if !args.contains(["-i", "--ignore-config"]) {
-        let path = handle_error(args.opt_value_from_str(["-c", "--config"])).unwrap_or_else(|| {
-            std::env::current_dir()
-                .unwrap()
-                .join("radiothing_config.txt")
-        });
+    // the flag is taken out first so that the filter is the only free argument left after reading the config
+    let list_devices = args.contains("--list-devices");
 
-        let save_path =
-            if let Some(save) = handle_error(args.opt_value_from_str(["-s", "--save-config"])) {
-                Some(save)
-            } else if args.contains(["-s", "--save-config"]) {
-                Some(path.clone())
-            } else {
-                None
-            };
+    let (settings, devices, save_path) = read_config(&mut args);
 
-        log::info!("Reading configuration file at '{}'", path.to_string_lossy());
+    if list_devices {
+        let filter = match args.opt_free_from_str::<String>() {
+            Ok(filter) => filter.unwrap_or_else(|| settings.device_filter.clone()),
+            Err(e) => {
+                log::error!("Error parsing args: {}", e);
+                std::process::exit(1);
+            }
+        };
 
-        match &save_path {
-            Some(path) => log::info!("Save path is '{}'", path.to_string_lossy()),
-            None => log::info!("Will not save config"),
+        match soapysdr::enumerate(filter.as_str()) {
+            Ok(devices) => print!("{}", format_device_list(&devices)),
+            Err(e) => {
+                log::error!("Error listing devices: {}", e);
+                std::process::exit(1);
+            }
         }
 
-        if path.is_file() {
-            let string = match std::fs::read_to_string(&path) {
-                Ok(string) => string,
-                Err(e) => {
-                    log::error!(
-                        "Error reading config file at '{}': {}",
-                        path.to_string_lossy(),
-                        e
-                    );
-                    return (DEFAULT_SETTINGS, save_path);
-                }
-            };
+        std::process::exit(0);
+    }
 
-            let (settings, errors) = Settings::new(string.as_str());
+    (settings, devices, save_path)
+}
 
-            if !errors.is_empty() {
-                let errors_string: String = errors.iter().map(|e| e.to_string() + "\n").collect();
-                log::error!(
-                    "Encountered errors while parsing settings, falling back to defaults:\n{}",
-                    errors_string
-                );
+#[test]
+fn decoder_settings_round_trip() {
+    let settings = AppSettings {
+        decoder: "Baudot".to_owned(),
+        baudrate: 45.45,
+        stop_bits: 2.0,
+        freq_shift: 170.0,
+        cutoff_hz: 250.0,
+        transition_width: 0.02,
+        decimation: 48,
+        freq_offset: -1250.5,
+        squelch_threshold: 12.5,
+        burst_gap_ms: 750,
+        raw_bits: true,
+        deemphasis_us: 75,
+        settle_us: 2500,
+        receive_timeout_us: 500_000,
+        worker_event_budget_us: 3_000,
+        fine_step_hz: 500.0,
+        coarse_step_hz: 25_000.0,
+        favorites: vec![
+            Favorite {
+                frequency: 144.8,
+                samplerate: 2.048,
+                mode: "None".to_owned(),
+            },
+            Favorite {
+                frequency: 434.5,
+                samplerate: 1.0,
+                mode: "Baudot".to_owned(),
+            },
+        ],
+        telemetry_log: "flights/telemetry.csv".to_owned(),
+        fft_window: "Rectangular".to_owned(),
+        show_band_plan: true,
+        band_plan: "bands.conf".to_owned(),
+        data_requests_in_flight: 3,
+        event_budget_ms: 8,
+        pre_trigger_s: 2.5,
+        autosave_s: 300,
+        auto_run: true,
+        iq_correction: true,
+        clock_source: "gpsdo".to_owned(),
+        device_serial: "00000001".to_owned(),
+        ..DEFAULT_SETTINGS
+    };
 
-                // the parsed settings can't be assumed to be correct
-                // fall back to the defaults but set save_config to false so that
-                // we don't overwrite the bad settings file in case the error there is only minor
-                return (DEFAULT_SETTINGS, None);
-            } else {
-                macro_rules! settings_from_settings {
-                        ($($field:ident),* $(,)*) => {
-                            AppSettings {
-                                $(
-                                    $field: settings.get(stringify!($field)).unwrap_or(DEFAULT_SETTINGS.$field),
-                                )*
-                            }
-                        }
-                    }
+    let (mut parsed, errors) = Settings::new(&settings.pretty_serialize());
+    assert!(errors.is_empty());
 
-                let deserialized = settings_from_settings! {
-                    auto_device,
-                    device,
-                    device_filter,
-                    auto_update,
-                    frequency,
-                    samplerate,
-                    gain,
-                    automatic_gain,
-                    automatic_dc_offset,
-                    decoder,
-                };
+    assert_eq!(AppSettings::from_settings(&mut parsed), settings);
+}
 
-                return (deserialized, save_path);
-            }
-        } else {
-            log::error!("Config file at '{}' is not a file", path.to_string_lossy())
+// every field is set to something other than its default, they are listed so that a new one can't be forgotten
+#[cfg(test)]
+pub(crate) fn full_settings() -> AppSettings {
+    AppSettings {
+        auto_device: true,
+        device_filter: "driver=rtlsdr".to_owned(),
+        device: "Generic RTL2832U OEM :: 00000001".to_owned(),
+        device_serial: "00000001".to_owned(),
+
+        auto_update: true,
+        auto_run: true,
+        frequency: 434.25,
+        frequency_unit: "kHz".to_owned(),
+        samplerate: 2.048,
+        gain: 33.8,
+        automatic_gain: true,
+        automatic_dc_offset: true,
+        iq_correction: true,
+        clock_source: "external".to_owned(),
+        buffer_samples: 16384,
+        settle_us: 5_000,
+        receive_timeout_us: 400_000,
+        worker_event_budget_us: 2_000,
+        fine_step_hz: 250.0,
+        coarse_step_hz: 12_500.0,
+        favorites: vec![Favorite {
+            frequency: 434.25,
+            samplerate: 2.048,
+            mode: "Baudot".to_owned(),
+        }],
+
+        decoder: "WBFM".to_owned(),
+        baudrate: 75.0,
+        stop_bits: 1.0,
+        freq_shift: 850.0,
+        cutoff_hz: 600.0,
+        transition_width: 0.05,
+        decimation: 16,
+        freq_offset: 2500.0,
+        squelch_threshold: 6.0,
+        burst_gap_ms: 5000,
+        raw_bits: true,
+        deemphasis_us: 75,
+
+        telemetry_log: "telemetry.csv".to_owned(),
+        fft_window: "Rectangular".to_owned(),
+        show_band_plan: true,
+        band_plan: "plan.conf".to_owned(),
+        data_requests_in_flight: 4,
+        event_budget_ms: 5,
+        pre_trigger_s: 1.5,
+        autosave_s: 0,
+
+        window_x: 40,
+        window_y: -20,
+        window_width: 1600,
+        window_height: 900,
+        left_width: 300,
+        middle_width: 1000,
+        right_width: 250,
+    }
+}
+
+#[test]
+fn all_settings_round_trip() {
+    let settings = full_settings();
+
+    let (mut parsed, errors) = Settings::new(&settings.pretty_serialize());
+    assert!(errors.is_empty());
+    assert_eq!(AppSettings::from_settings(&mut parsed), settings);
+
+    // nothing is left at the default, otherwise a field missing in the file would pass
+    let (mut defaults, _) = Settings::new(&DEFAULT_SETTINGS.pretty_serialize());
+    let defaults = AppSettings::from_settings(&mut defaults);
+    for (line, default) in settings
+        .pretty_serialize()
+        .lines()
+        .zip(defaults.pretty_serialize().lines())
+    {
+        if line.contains(" = ") {
+            assert_ne!(line, default);
         }
-    } else {
-        log::info!("Ignoring config");
     }
+}
+
+#[test]
+fn geometry_settings_round_trip() {
+    let settings = AppSettings {
+        // a window on a monitor left of the primary one
+        window_x: -1600,
+        window_y: 120,
+        window_width: 1280,
+        window_height: 720,
+        left_width: 310,
+        middle_width: 780,
+        right_width: 190,
+        ..DEFAULT_SETTINGS
+    };
+
+    let (mut parsed, errors) = Settings::new(&settings.pretty_serialize());
+    assert!(errors.is_empty());
+
+    assert_eq!(AppSettings::from_settings(&mut parsed), settings);
+}
+
+#[test]
+fn frequency_with_a_unit() {
+    let (mut parsed, errors) = Settings::new(
+        "frequency = 433.92MHz\nsamplerate = 2.048MSps\nfavorites = [[144800kHz, 250kSps, \"None\"], [434.5, 1, \"Baudot\"]]\nfine_step_hz = 1k\n",
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    // the MHz and MSps fields get the value written with the unit, not a million times it
+    let settings = AppSettings::from_settings(&mut parsed);
+    assert!((settings.frequency - 433.92).abs() < 1e-9);
+    assert!((settings.samplerate - 2.048).abs() < 1e-9);
+    assert!((settings.favorites[0].frequency - 144.8).abs() < 1e-9);
+    assert!((settings.favorites[0].samplerate - 0.25).abs() < 1e-9);
+    // plain numbers are already in MHz
+    assert_eq!(settings.favorites[1].frequency, 434.5);
+    // the fields kept in Hz take the unit as it is
+    assert_eq!(settings.fine_step_hz, 1000.0);
+
+    // and they are saved in MHz
+    assert!(settings.pretty_serialize().contains("frequency = 433.92 # MHz"));
+    let (mut reparsed, errors) = Settings::new(&settings.pretty_serialize());
+    assert!(errors.is_empty());
+    assert_eq!(AppSettings::from_settings(&mut reparsed), settings);
+}
+
+#[test]
+fn fft_window_names() {
+    for &kind in WINDOW_KINDS {
+        assert_eq!(parse_fft_window(kind.name()), kind);
+        assert_eq!(parse_fft_window(&kind.name().to_lowercase()), kind);
+    }
+    assert_eq!(parse_fft_window("Rectangular"), WindowKind::Rectangular);
+    assert_eq!(parse_fft_window("BlackmanHaris"), WindowKind::BlackmanHaris);
+
+    assert_eq!(parse_fft_window(""), DEFAULT_FFT_WINDOW);
+    assert_eq!(parse_fft_window("Hamming?"), DEFAULT_FFT_WINDOW);
+    assert_eq!(DEFAULT_FFT_WINDOW, WindowKind::BlackmanHaris);
+}
+
+#[test]
+fn config_path_resolution() {
+    let dir = std::env::temp_dir().join(format!("radiothing_config_dir_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("custom.txt");
+    std::fs::write(&file, "").unwrap();
+
+    // an existing file and a file which is yet to be created are kept as they are
+    assert_eq!(resolve_config_path(file.clone()), file);
+    let missing = dir.join("missing.txt");
+    assert_eq!(resolve_config_path(missing.clone()), missing);
+
+    // a directory gets the default name, whether it exists or is only spelled like one
+    assert_eq!(
+        resolve_config_path(dir.clone()),
+        dir.join(DEFAULT_CONFIG_NAME)
+    );
+    let missing_dir = PathBuf::from(format!(
+        "{}{}",
+        dir.join("missing_dir").to_string_lossy(),
+        std::path::MAIN_SEPARATOR
+    ));
+    assert_eq!(
+        resolve_config_path(missing_dir.clone()),
+        missing_dir.join(DEFAULT_CONFIG_NAME)
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn atomic_config_save() {
+    let dir = std::env::temp_dir().join(format!("radiothing_atomic_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(DEFAULT_CONFIG_NAME);
+    let original = DEFAULT_SETTINGS.pretty_serialize();
+    fs::write(&path, &original).unwrap();
+
+    // the write fails halfway, the config is left alone and so is the directory
+    let result = write_atomically(&path, |file| {
+        file.write_all(&original.as_bytes()[..original.len() / 2])?;
+        Err(io::Error::new(io::ErrorKind::Other, "killed"))
+    });
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let settings = AppSettings {
+        auto_run: true,
+        ..DEFAULT_SETTINGS
+    };
+    save_settings(&path, &settings).unwrap();
+    let (mut parsed, errors) = Settings::new(&fs::read_to_string(&path).unwrap());
+    assert!(errors.is_empty());
+    assert_eq!(AppSettings::from_settings(&mut parsed), settings);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    // a directory that doesn't exist is an error instead of a panic
+    assert!(save_settings(&dir.join("missing").join(DEFAULT_CONFIG_NAME), &settings).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn device_configs_round_trip() {
+    assert_eq!(
+        device_config_path(Path::new("conf/radiothing_config.txt"), 1),
+        Path::new("conf/radiothing_config.txt")
+    );
+    assert_eq!(
+        device_config_path(Path::new("conf/radiothing_config.txt"), 3),
+        Path::new("conf/radiothing_config.device3.txt")
+    );
+    assert_eq!(
+        device_config_path(Path::new("radiothing"), 2),
+        Path::new("radiothing.device2")
+    );
+
+    let dir = std::env::temp_dir().join(format!("radiothing_devices_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(DEFAULT_CONFIG_NAME);
+
+    let device = |serial: &str| AppSettings {
+        auto_device: true,
+        device_serial: serial.to_owned(),
+        fft_window: "Rectangular".to_owned(),
+        ..DEFAULT_SETTINGS
+    };
+    let devices = [device("0002"), device("0003")];
+    save_all_settings(&path, &device("0001"), &devices).unwrap();
+    assert_eq!(read_device_settings(&path), devices);
+
+    // fewer devices than the last time, the extra one isn't read back
+    save_all_settings(&path, &device("0001"), &devices[..1]).unwrap();
+    assert_eq!(read_device_settings(&path), devices[..1]);
+    assert!(device_config_path(&path, 2).is_file());
+    assert!(!device_config_path(&path, 3).exists());
+
+    // a broken file stops the reading
+    fs::write(device_config_path(&path, 2), "auto_device = ").unwrap();
+    save_settings(&device_config_path(&path, 3), &devices[1]).unwrap();
+    assert!(read_device_settings(&path).is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn autosave_throttle() {
+    let start = Instant::now();
+    let at = |s: u64| start + Duration::from_secs(s);
+
+    let mut autosave = Autosave::new(0);
+    assert!(!autosave.due(at(0)));
+    assert!(!autosave.due(at(3600)));
+
+    let mut autosave = Autosave::new(60);
+    // the interval starts with the first check
+    assert!(!autosave.due(at(0)));
+    assert!(!autosave.due(at(59)));
+    assert!(autosave.due(at(60)));
+    assert!(!autosave.due(at(100)));
+    assert!(autosave.due(at(125)));
+
+    let settings = DEFAULT_SETTINGS.pretty_serialize();
+    let moved = AppSettings {
+        window_x: 100,
+        ..DEFAULT_SETTINGS
+    }
+    .pretty_serialize();
+
+    // the settings are written the first time and then only after they change
+    assert!(autosave.changed(&settings));
+    assert!(!autosave.changed(&settings));
+    assert!(autosave.changed(&moved));
+    assert!(!autosave.changed(&moved));
+    assert!(autosave.changed(&settings));
 
-    return (DEFAULT_SETTINGS, None);
+    autosave.write_failed();
+    assert!(autosave.changed(&settings));
 }
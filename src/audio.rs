@@ -0,0 +1,239 @@
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    SampleFormat, SampleRate, Stream, StreamConfig,
+};
+
+use crate::dsp::{resample::LinearResampler, ring_buffer::RingBuffer};
+
+// how much audio is kept for the output at most, the oldest is dropped when the worker delivers faster than it's played
+const AUDIO_QUEUE_SECONDS: f64 = 0.5;
+// after running dry the playback waits for this much audio so that the next late burst doesn't cut it off right away
+const AUDIO_PREBUFFER_SECONDS: f64 = 0.1;
+
+// bridges the bursts of audio from the worker and the steady pull of the audio callback
+// it doesn't know anything about the audio backend, the callback only asks it to fill a buffer
+pub struct AudioQueue {
+    samples: RingBuffer<f32>,
+    prebuffer: usize,
+    // false until prebuffer samples are queued, then true until the queue runs dry
+    playing: bool,
+    underruns: u64,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl AudioQueue {
+    pub fn new(capacity: usize, prebuffer: usize) -> Self {
+        Self {
+            samples: RingBuffer::new(capacity),
+            prebuffer: prebuffer.min(capacity),
+            playing: false,
+            underruns: 0,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+    // the number of times the output wanted more audio than there was
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+    // the number of samples dropped because the queue was full
+    pub fn overruns(&self) -> u64 {
+        self.samples.dropped()
+    }
+    pub fn push(&mut self, samples: &[f32]) {
+        self.samples.push(samples);
+    }
+    // fills the whole dst, with silence where there isn't any audio
+    // the audio is consumed even when muted so that unmuting doesn't play anything old
+    pub fn fill(&mut self, dst: &mut [f32]) {
+        if !self.playing && self.samples.len() >= self.prebuffer.max(1) {
+            self.playing = true;
+        }
+
+        let available = if self.playing {
+            self.samples.len().min(dst.len())
+        } else {
+            0
+        };
+
+        let (audio, silence) = dst.split_at_mut(available);
+        self.samples.read(audio, available);
+        silence.iter_mut().for_each(|x| *x = 0.0);
+
+        if self.playing && !silence.is_empty() {
+            self.underruns += 1;
+            self.playing = false;
+        }
+
+        let gain = if self.muted { 0.0 } else { self.volume };
+        audio.iter_mut().for_each(|x| *x *= gain);
+    }
+}
+
+// plays mono audio on the default output device, the stream stops when this is dropped
+pub struct AudioSink {
+    _stream: Stream,
+    queue: Arc<Mutex<AudioQueue>>,
+    // of the output device
+    samplerate: f64,
+    // the audio is resampled if the device doesn't support its samplerate, (input samplerate, resampler)
+    resampler: Option<(f64, LinearResampler)>,
+    resampled: Vec<f32>,
+}
+
+impl AudioSink {
+    // opens the output at the samplerate of the audio if the device supports it, otherwise at its default one
+    pub fn new(samplerate: f64) -> Result<Self, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("There is no audio output device")?;
+
+        let default = device.default_output_config()?;
+        let rate = samplerate.round() as u32;
+        let supported = device.supported_output_configs()?.find(|range| {
+            range.channels() == default.channels()
+                && range.sample_format() == default.sample_format()
+                && range.min_sample_rate().0 <= rate
+                && rate <= range.max_sample_rate().0
+        });
+        let config = match supported {
+            Some(range) => range.with_sample_rate(SampleRate(rate)),
+            None => default,
+        };
+
+        let output_samplerate = config.sample_rate().0 as f64;
+        let queue = Arc::new(Mutex::new(AudioQueue::new(
+            (output_samplerate * AUDIO_QUEUE_SECONDS) as usize,
+            (output_samplerate * AUDIO_PREBUFFER_SECONDS) as usize,
+        )));
+
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config.config(), queue.clone())?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config.config(), queue.clone())?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config.config(), queue.clone())?,
+        };
+        stream.play()?;
+
+        log::info!(
+            "Playing audio on '{}' at {} Sps",
+            device.name().unwrap_or_default(),
+            output_samplerate
+        );
+
+        Ok(Self {
+            _stream: stream,
+            queue,
+            samplerate: output_samplerate,
+            resampler: None,
+            resampled: Vec::new(),
+        })
+    }
+    pub fn push(&mut self, samples: &[f32], samplerate: f64) {
+        if samplerate == self.samplerate {
+            self.queue.lock().unwrap().push(samples);
+            return;
+        }
+
+        // a new decoder can deliver a different samplerate
+        if self.resampler.as_ref().map(|r| r.0) != Some(samplerate) {
+            self.resampler = Some((
+                samplerate,
+                LinearResampler::new(samplerate, self.samplerate),
+            ));
+        }
+
+        self.resampled.clear();
+        if let Some((_, resampler)) = &mut self.resampler {
+            resampler.process(samples, &mut self.resampled);
+        }
+        self.queue.lock().unwrap().push(&self.resampled);
+    }
+    pub fn set_volume(&self, volume: f32) {
+        self.queue.lock().unwrap().volume = volume;
+    }
+    pub fn set_muted(&self, muted: bool) {
+        self.queue.lock().unwrap().muted = muted;
+    }
+}
+
+// the same mono sample is written to every channel of a frame
+fn build_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    queue: Arc<Mutex<AudioQueue>>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels.max(1) as usize;
+    let mut mono = Vec::new();
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            mono.resize(data.len() / channels, 0.0);
+            queue.lock().unwrap().fill(&mut mono);
+
+            for (frame, sample) in data.chunks_mut(channels).zip(&mono) {
+                frame.iter_mut().for_each(|x| *x = T::from(sample));
+            }
+        },
+        |e| log::warn!("Audio output error: {}", e),
+    )
+}
+
+#[test]
+fn audio_queue_underrun_and_overrun() {
+    let mut queue = AudioQueue::new(100, 20);
+    let mut dst = [1.0; 10];
+
+    // nothing is played before the prebuffer is full
+    queue.push(&[0.5; 15]);
+    queue.fill(&mut dst);
+    assert_eq!(dst, [0.0; 10]);
+    assert_eq!((queue.len(), queue.underruns()), (15, 0));
+
+    queue.push(&[0.5; 10]);
+    queue.fill(&mut dst);
+    assert_eq!(dst, [0.5; 10]);
+    queue.fill(&mut dst);
+    assert_eq!(dst, [0.5; 10]);
+
+    // the rest is played and padded with silence, then the prebuffer has to fill up again
+    queue.fill(&mut dst);
+    assert_eq!(&dst[..5], &[0.5; 5]);
+    assert_eq!(&dst[5..], &[0.0; 5]);
+    assert_eq!(queue.underruns(), 1);
+    assert!(queue.is_empty());
+
+    queue.push(&[0.25; 10]);
+    queue.fill(&mut dst);
+    assert_eq!(dst, [0.0; 10]);
+    assert_eq!(queue.underruns(), 1);
+
+    // a burst larger than the queue keeps only the newest audio
+    let burst: Vec<f32> = (0..150).map(|i| i as f32).collect();
+    queue.push(&burst);
+    assert_eq!(queue.len(), 100);
+    assert_eq!(queue.overruns(), 60);
+    queue.fill(&mut dst);
+    assert_eq!(dst[0], 50.0);
+
+    // muted audio is still consumed
+    queue.volume = 0.5;
+    queue.fill(&mut dst);
+    assert_eq!(dst[0], 30.0);
+    queue.muted = true;
+    queue.fill(&mut dst);
+    assert_eq!(dst, [0.0; 10]);
+    assert_eq!(queue.len(), 70);
+}
@@ -0,0 +1,194 @@
+use std::{convert::TryFrom, ops::Range};
+
+use crate::settings::{Field, Settings};
+
+// a named frequency range drawn behind the spectrum, kept as ["name", start, end] in a band plan file
+// a band plan file only has the list of bands, the unit suffixes of the settings can be used for the frequencies
+//     bands = [["2 m", 144M, 146M], ["70 cm", 430M, 440M]]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Band {
+    pub name: String,
+    pub start_hz: f64,
+    pub end_hz: f64,
+}
+
+impl TryFrom<Field> for Band {
+    type Error = ();
+
+    fn try_from(field: Field) -> Result<Band, Self::Error> {
+        let mut items = Vec::<Field>::try_from(field)?.into_iter();
+
+        let band = Band {
+            name: String::try_from(items.next().ok_or(())?)?,
+            start_hz: f64::try_from(items.next().ok_or(())?)?,
+            end_hz: f64::try_from(items.next().ok_or(())?)?,
+        };
+
+        // an empty or reversed band would never be drawn, it's most likely a typo
+        if items.next().is_some() || band.end_hz <= band.start_hz {
+            return Err(());
+        }
+
+        Ok(band)
+    }
+}
+
+// common amateur, broadcast and ISM allocations, the amateur bands are the widest of the IARU regions
+const DEFAULT_BANDS: &[(&str, f64, f64)] = &[
+    ("160 m", 1.8e6, 2.0e6),
+    ("80 m", 3.5e6, 4.0e6),
+    ("40 m", 7.0e6, 7.3e6),
+    ("30 m", 10.1e6, 10.15e6),
+    ("20 m", 14.0e6, 14.35e6),
+    ("17 m", 18.068e6, 18.168e6),
+    ("15 m", 21.0e6, 21.45e6),
+    ("12 m", 24.89e6, 24.99e6),
+    ("10 m", 28.0e6, 29.7e6),
+    ("6 m", 50.0e6, 54.0e6),
+    ("FM broadcast", 87.5e6, 108.0e6),
+    ("Airband", 108.0e6, 137.0e6),
+    ("2 m", 144.0e6, 148.0e6),
+    ("Marine", 156.0e6, 162.025e6),
+    ("70 cm", 420.0e6, 450.0e6),
+    ("ISM 433", 433.05e6, 434.79e6),
+    ("PMR446", 446.0e6, 446.2e6),
+    ("ISM 868", 863.0e6, 870.0e6),
+    ("ISM 915", 902.0e6, 928.0e6),
+    ("23 cm", 1240.0e6, 1300.0e6),
+    ("GPS L1", 1574.397e6, 1576.443e6),
+];
+
+pub fn default_band_plan() -> Vec<Band> {
+    DEFAULT_BANDS
+        .iter()
+        .map(|&(name, start_hz, end_hz)| Band {
+            name: name.to_owned(),
+            start_hz,
+            end_hz,
+        })
+        .collect()
+}
+
+// every band has to be valid, a partially read plan would silently miss bands
+pub fn parse_band_plan(string: &str) -> Result<Vec<Band>, String> {
+    let (settings, errors) = Settings::new(string);
+
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|e| e.with_source().to_string() + "\n")
+            .collect());
+    }
+
+    match settings.get_field("bands") {
+        Some(field) => Vec::<Band>::try_from(field.clone()).map_err(|_| {
+            "Every band has to be [\"name\", start, end] with start below end.".to_owned()
+        }),
+        None => Err("The band plan has no 'bands' list.".to_owned()),
+    }
+}
+
+// an empty path is the default plan, which is also used when the file can't be read
+pub fn load_band_plan(path: &str) -> Vec<Band> {
+    if path.is_empty() {
+        return default_band_plan();
+    }
+
+    let parsed = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|string| parse_band_plan(&string));
+
+    match parsed {
+        Ok(bands) => bands,
+        Err(e) => {
+            log::warn!(
+                "Error loading the band plan at '{}', using the default one:\n{}",
+                path,
+                e
+            );
+            default_band_plan()
+        }
+    }
+}
+
+// the bands that are at least partly in the span with the index into `bands`
+// the visible part is returned as fractions of the span, 0 at its start and 1 at its end
+pub fn visible_bands(bands: &[Band], span: Range<f64>) -> Vec<(usize, Range<f64>)> {
+    let width = span.end - span.start;
+    if width <= 0.0 {
+        return Vec::new();
+    }
+
+    bands
+        .iter()
+        .enumerate()
+        .filter(|(_, band)| band.start_hz < span.end && band.end_hz > span.start)
+        .map(|(i, band)| {
+            let start = band.start_hz.max(span.start);
+            let end = band.end_hz.min(span.end);
+            (i, (start - span.start) / width..(end - span.start) / width)
+        })
+        .collect()
+}
+
+#[test]
+fn parse_bands() {
+    let bands = parse_band_plan(
+        "# a comment\nbands = [[\"2 m\", 144M, 146M], [\"Local beacon\", 432.1M, 432.2M]]\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        bands,
+        [
+            Band {
+                name: "2 m".to_owned(),
+                start_hz: 144e6,
+                end_hz: 146e6,
+            },
+            Band {
+                name: "Local beacon".to_owned(),
+                start_hz: 432.1e6,
+                end_hz: 432.2e6,
+            },
+        ]
+    );
+
+    assert!(parse_band_plan("bands = [[\"reversed\", 146M, 144M]]").is_err());
+    assert!(parse_band_plan("bands = [[\"missing end\", 144M]]").is_err());
+    assert!(parse_band_plan("bands = [[\"extra\", 144M, 146M, 1]]").is_err());
+    assert!(parse_band_plan("other = 1").is_err());
+    assert!(parse_band_plan("bands = [[\"unclosed\", 144M, 146M]").is_err());
+
+    // the default plan has to pass the same checks
+    for band in default_band_plan() {
+        assert!(band.start_hz < band.end_hz);
+    }
+}
+
+#[test]
+fn visible_band_intersection() {
+    let band = |start_hz, end_hz| Band {
+        name: String::new(),
+        start_hz,
+        end_hz,
+    };
+    let bands = [
+        band(100.0, 200.0),
+        band(350.0, 450.0),
+        band(500.0, 600.0),
+        band(150.0, 900.0),
+        band(0.0, 300.0),
+    ];
+
+    // 300..500, the band ending at the start and the one starting at the end aren't visible
+    let visible = visible_bands(&bands, 300.0..500.0);
+    assert_eq!(visible, [(1, 0.25..0.75), (3, 0.0..1.0)]);
+
+    assert_eq!(visible_bands(&bands, 120.0..160.0).len(), 3);
+    assert_eq!(visible_bands(&bands, 120.0..160.0)[0], (0, 0.0..1.0));
+    assert_eq!(visible_bands(&bands, 190.0..210.0)[0], (0, 0.0..0.5));
+
+    assert!(visible_bands(&bands, 1000.0..2000.0).is_empty());
+    assert!(visible_bands(&bands, 500.0..500.0).is_empty());
+}
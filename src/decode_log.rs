@@ -0,0 +1,224 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::dsp::rtty_decode::DecodedChar;
+
+// the decodes go to their own file instead of the app log on stderr, one line per decode with key=value fields
+//     time=2023-11-14T22:13:20Z level=INFO source=decoder frequency=434500000 mode=Baudot text="$$RADIO,1,..."
+// None until --decode-log opens a file
+static DECODE_LOG: Mutex<Option<File>> = Mutex::new(None);
+
+// what produced the logged text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodeSource {
+    // a line of the decoded text
+    Decoder,
+    // a UKHAS sentence parsed from the decoded text
+    Telemetry,
+}
+
+impl DecodeSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DecodeSource::Decoder => "decoder",
+            DecodeSource::Telemetry => "telemetry",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeRecord<'a> {
+    pub unix_time: u64,
+    // WARN for text which may be garbage, like characters with a damaged stop bit
+    pub level: log::Level,
+    pub source: DecodeSource,
+    // Hz, the frequency the receiver was tuned to
+    pub frequency: f64,
+    pub mode: &'a str,
+    pub text: &'a str,
+}
+
+// an existing log is appended to so that the decodes of every run end up in the same file
+pub fn open_decode_log(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *DECODE_LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+// does nothing without a decode log, every line is flushed right away so that a crash loses nothing
+pub fn log_decode(record: &DecodeRecord) {
+    if let Some(file) = DECODE_LOG.lock().unwrap().as_mut() {
+        let result = file
+            .write_all(format_decode_record(record).as_bytes())
+            .and_then(|_| file.flush());
+
+        if let Err(e) = result {
+            log::error!("Failed to write the decode log: {}", e);
+        }
+    }
+}
+
+pub fn format_decode_record(record: &DecodeRecord) -> String {
+    format!(
+        "time={} level={} source={} frequency={:.0} mode={} text={}\n",
+        utc_timestamp(record.unix_time),
+        record.level,
+        record.source.name(),
+        record.frequency,
+        field_value(record.mode, false),
+        field_value(record.text, true),
+    )
+}
+
+// values with spaces, quotes or line breaks are quoted so that every record stays on one line
+fn field_value(value: &str, always_quote: bool) -> String {
+    let plain = !value.is_empty()
+        && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=' || c == '\\');
+
+    if plain && !always_quote {
+        return value.to_owned();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// ISO 8601 in UTC, the days are converted to a date with http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn utc_timestamp(unix_time: u64) -> String {
+    let seconds = unix_time % (24 * 60 * 60);
+    let z = unix_time / (24 * 60 * 60) + 719_468;
+
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // the year starts in March so that the leap day is at its end
+    let month_from_march = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// collects the decoded characters into lines, a line is suspect if any of its characters is
+#[derive(Default)]
+pub struct DecodedLines {
+    line: String,
+    suspect: bool,
+}
+
+impl DecodedLines {
+    // the completed lines with whether they are suspect
+    pub fn push(&mut self, chars: &[DecodedChar]) -> Vec<(String, bool)> {
+        let mut lines = Vec::new();
+        for c in chars {
+            match c.ch {
+                '\r' | '\n' => {
+                    if !self.line.is_empty() {
+                        lines.push((std::mem::take(&mut self.line), self.suspect));
+                    }
+                    self.suspect = false;
+                }
+                ch => {
+                    self.line.push(ch);
+                    self.suspect |= c.suspect;
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[test]
+fn decode_log_line() {
+    let record = DecodeRecord {
+        unix_time: 1_700_000_000,
+        level: log::Level::Info,
+        source: DecodeSource::Decoder,
+        frequency: 434.5e6,
+        mode: "Baudot",
+        text: "$$RADIO,1,22:13:20,51.5,-0.25,100*1A2B",
+    };
+    assert_eq!(
+        format_decode_record(&record),
+        "time=2023-11-14T22:13:20Z level=INFO source=decoder frequency=434500000 mode=Baudot \
+         text=\"$$RADIO,1,22:13:20,51.5,-0.25,100*1A2B\"\n"
+    );
+
+    // the text can't break the line or the quoting
+    let record = DecodeRecord {
+        unix_time: 0,
+        level: log::Level::Warn,
+        source: DecodeSource::Telemetry,
+        frequency: 144.8e6,
+        mode: "",
+        text: "say \"hi\"\r\n",
+    };
+    assert_eq!(
+        format_decode_record(&record),
+        "time=1970-01-01T00:00:00Z level=WARN source=telemetry frequency=144800000 mode=\"\" \
+         text=\"say \\\"hi\\\"\\r\\n\"\n"
+    );
+
+    // the leap day and the end of a year
+    assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(utc_timestamp(1_704_067_199), "2023-12-31T23:59:59Z");
+}
+
+#[test]
+fn decoded_lines() {
+    use crate::dsp::rtty_decode::Shift;
+
+    let chars = |text: &str, suspect: bool| -> Vec<DecodedChar> {
+        text.chars()
+            .map(|ch| DecodedChar {
+                ch,
+                shift: Shift::Letters,
+                sample_index: 0,
+                suspect,
+            })
+            .collect()
+    };
+
+    let mut lines = DecodedLines::default();
+    assert!(lines.push(&chars("RYRY ", false)).is_empty());
+    assert_eq!(
+        lines.push(&chars("TEST\r\nNEXT", false)),
+        [("RYRY TEST".to_owned(), false)]
+    );
+    assert!(lines.push(&chars("X", true)).is_empty());
+    assert_eq!(
+        lines.push(&chars("\n\nLAST\n", false)),
+        [("NEXTX".to_owned(), true), ("LAST".to_owned(), false)]
+    );
+}
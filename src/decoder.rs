@@ -1,17 +1,99 @@
-use std::{any::Any, fmt::Debug, mem::size_of, ops::Add, rc::Rc};
+use std::{
+    any::Any, collections::VecDeque, error::Error, fmt::Debug, fmt::Display, mem::size_of,
+    ops::Add, rc::Rc, time::Instant,
+};
 
 use num_traits::Zero;
 use rustfft::num_complex::Complex;
 
 use crate::{
     dsp::{
-        fir_filter::FirFilter, multistage_fir::MultistageFir, rtty_decode,
+        fir_filter::FirFilter,
+        fm::{quadrature_demod, Deemphasis},
+        multistage_fir::{precise_decimation, MultistageFir},
+        nco::Nco,
+        rtty_decode,
         window_functions::WindowKind,
     },
-    worker::worker::{DeviceWorker, GuiBoundEvent, RxFormat},
+    worker::worker::{
+        bin_frequency, interpolate_peak, DeviceWorker, GuiBoundEvent, ProcessingStage, RxFormat,
+    },
+    FftData,
 };
 
-pub type DecoderResult<T> = Result<T, &'static str>;
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecoderError {
+    // the worker doesn't have a receiver configured or the decoder wasn't initialized
+    NotConfigured,
+    // the device samplerate is too low to decimate to the samplerate the decoder needs, both in Sps
+    UnsupportedSamplerate { samplerate: f64, required: f64 },
+    // the working memory doesn't have enough space reserved in front of the fresh samples for the filter
+    BufferTooSmall,
+}
+
+impl Display for DecoderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecoderError::NotConfigured => writeln!(f, "The decoder is not configured."),
+            DecoderError::UnsupportedSamplerate {
+                samplerate,
+                required,
+            } => writeln!(
+                f,
+                "The samplerate of {:.0} Sps is below the {:.0} Sps the decoder needs.",
+                samplerate, required
+            ),
+            DecoderError::BufferTooSmall => {
+                writeln!(f, "The sample buffer is too small for the decoder.")
+            }
+        }
+    }
+}
+
+impl Error for DecoderError {}
+
+pub type DecoderResult<T> = Result<T, DecoderError>;
+
+// at most this many bits of a buffer are sent with GuiBoundEvent::RawBits, the rest is dropped
+pub const MAX_RAW_BITS: usize = 8192;
+
+// the audio of the WBFM decoder is decimated to this samplerate or as close to it as the device samplerate allows
+pub const WBFM_AUDIO_SAMPLERATE: f64 = 48000.0;
+// a broadcast station is about 200 kHz wide, it is demodulated at the lowest samplerate which still fits all of it
+const WBFM_MIN_IF_SAMPLERATE: f64 = 200e3;
+// the filter before the demodulation passes this much on both sides of the center
+const WBFM_CHANNEL_HZ: f64 = 100e3;
+const WBFM_IF_TRANSITION_WIDTH: f64 = 0.1;
+// the demodulated signal is full scale at the peak deviation of broadcast FM
+const WBFM_DEVIATION_HZ: f64 = 75e3;
+// only the mono audio is kept, the transition ends before the 19 kHz stereo pilot
+const WBFM_AUDIO_CUTOFF_HZ: f64 = 16e3;
+const WBFM_AUDIO_TRANSITION_HZ: f64 = 4e3;
+// an audio samplerate this close to WBFM_AUDIO_SAMPLERATE is good enough to prefer a cheaper demodulation
+const WBFM_AUDIO_SAMPLERATE_TOLERANCE: f64 = 0.02;
+
+// the tone measurement uses the longest power of two fft which fits in a buffer, up to the max
+// the bins of a shorter one would be too wide for the interpolation to be of any use
+const TONE_MIN_FFT_LEN: usize = 256;
+const TONE_MAX_FFT_LEN: usize = 65536;
+
+// the parameters of a decoder which can be changed while it is running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecoderParams {
+    Baudot {
+        baudrate: f32,
+        stop_bits: f32,
+        shift: f32,
+        cutoff_hz: f32,
+        transition_width: f32,
+        forced_decim: Option<u32>,
+        freq_offset_hz: f32,
+    },
+    Wbfm {
+        deemphasis_us: f32,
+        freq_offset_hz: f32,
+    },
+}
 
 #[derive(Debug)]
 pub enum Decoder {
@@ -19,12 +101,48 @@ pub enum Decoder {
         baudrate: f32,
         stop_bits: f32,
         shift: f32,
+        // how far from the center the filter passes the signal, 0 follows the shift
+        // narrowing it to the selected channel keeps out the neighbouring signals
+        cutoff_hz: f32,
+        // of the last filter stage, relative to its samplerate
+        // a narrower transition separates the tones of a small shift better but the filter needs more taps and more cpu
+        transition_width: f32,
+        // used instead of the decimation computed from the samplerate and the baudrate, if the filter stages can hit it
+        // a lower one costs more cpu but leaves more room between the signal and what aliases into it
+        forced_decim: Option<u32>,
+        // where the signal sits relative to the receiver frequency, it is shifted to the center before filtering
+        // this way the signal can be followed without retuning the device
+        freq_offset_hz: f32,
+        nco: Nco,
         // these are reclaimed from the previous BaudotDecoder if there was any
         letters: bool,
         leftover_bits: Vec<bool>,
+        prev_sample: Complex<RxFormat>,
         // relevant after init on worker
         decim: u32,
     },
+    // wide FM broadcast audio for checking the receiver end to end, nothing is decoded
+    WbfmDecoder {
+        // the time constant of the de-emphasis, 50 us in most of the world and 75 us in the Americas
+        deemphasis_us: f32,
+        freq_offset_hz: f32,
+        nco: Nco,
+        prev_sample: Complex<RxFormat>,
+        // relevant after init on worker
+        deemphasis: Deemphasis,
+        audio_filter: Option<FirFilter>,
+        // the demodulated samples the audio filter didn't consume yet, they are put in front of the next ones
+        audio_buffer: Vec<f32>,
+        decim: u32,
+        audio_decim: u32,
+    },
+    // measures the frequency of the strongest tone for calibrating the frequency error of the device, nothing is decoded
+    // the receiver frequency plus the interpolated offset of the peak is sent with GuiBoundEvent::Measurement
+    ToneMeasure {
+        // relevant after init on worker
+        fft: Option<FftData<RxFormat>>,
+        powers: Vec<RxFormat>,
+    },
 }
 
 impl Decoder {
@@ -57,7 +175,9 @@ impl Decoder {
         }
 
         reclaim_fields! {
-            Decoder::BaudotDecoder, letters, leftover_bits;
+            Decoder::BaudotDecoder, letters, prev_sample, leftover_bits;
+            Decoder::WbfmDecoder, prev_sample;
+            Decoder::ToneMeasure;
         }
 
         Ok(())
@@ -68,41 +188,227 @@ impl Decoder {
         worker: &mut DeviceWorker,
         _during_init: bool,
     ) -> DecoderResult<()> {
-        let state = worker.receive_state.as_ref().unwrap();
+        let state = worker
+            .receive_state
+            .as_ref()
+            .ok_or(DecoderError::NotConfigured)?;
+
+        // a lower one would leave the decimation factor at 0
+        self.check_samplerate(state.samplerate)?;
 
         match self {
             Decoder::BaudotDecoder {
                 shift,
                 baudrate,
+                cutoff_hz,
+                transition_width,
+                forced_decim,
+                freq_offset_hz,
+                nco,
                 decim,
                 ..
             } => {
-                let target_samplerate = (*baudrate as f64 * 16.0).max(1.0);
-                let factor = (state.samplerate / target_samplerate) as u32;
+                nco.set_freq(*freq_offset_hz, state.samplerate as f32);
 
-                let cutoff = *shift as f64 / (2.0 * state.samplerate);
+                let factor = baudot_decimation(state.samplerate, *baudrate, *forced_decim);
+
+                // both tones are at shift/2 from the center, leave them some room within the passband
+                let cutoff_hz = if *cutoff_hz > 0.0 { *cutoff_hz } else { *shift };
+                let cutoff = cutoff_hz as f64 / state.samplerate;
                 let (filter, factor) = MultistageFir::new_multistage_decim_precise(
                     factor,
                     WindowKind::BlackmanHaris,
                     &mut worker.decimation_fir_cache,
                     cutoff,
-                    0.1,
+                    *transition_width as f64,
+                );
+
+                install_filter(worker, filter);
+                *decim = factor;
+
+                let _ = worker
+                    .sender
+                    .send(GuiBoundEvent::DecoderConfigured { decimation: factor });
+            }
+            Decoder::WbfmDecoder {
+                deemphasis_us,
+                freq_offset_hz,
+                nco,
+                deemphasis,
+                audio_filter,
+                audio_buffer,
+                decim,
+                audio_decim,
+                ..
+            } => {
+                let samplerate = state.samplerate;
+                nco.set_freq(*freq_offset_hz, samplerate as f32);
+
+                let (factor, _) =
+                    wbfm_decimations(samplerate).ok_or(DecoderError::UnsupportedSamplerate {
+                        samplerate,
+                        required: WBFM_MIN_IF_SAMPLERATE,
+                    })?;
+
+                let (filter, factor) = MultistageFir::new_multistage_decim_precise(
+                    factor,
+                    WindowKind::BlackmanHaris,
+                    &mut worker.decimation_fir_cache,
+                    WBFM_CHANNEL_HZ / samplerate,
+                    WBFM_IF_TRANSITION_WIDTH,
                 );
+                install_filter(worker, filter);
 
-                worker
-                    .working_memory
-                    .resize(worker.mtu + filter.min_buffer_reserve(), Complex::zero());
-                worker.memory_receive_offset = worker
-                    .memory_receive_offset
-                    .max(filter.min_buffer_reserve());
+                // the cached stages don't always hit the planned decimation, the audio follows the one achieved
+                let if_samplerate = samplerate / factor as f64;
+                *audio_decim = ((if_samplerate / WBFM_AUDIO_SAMPLERATE).round() as u32).max(1);
+                *audio_filter = Some(FirFilter::new_lowpass(
+                    1.0,
+                    WBFM_AUDIO_CUTOFF_HZ / if_samplerate,
+                    WBFM_AUDIO_TRANSITION_HZ / if_samplerate,
+                    WindowKind::BlackmanHaris,
+                ));
+                audio_buffer.clear();
 
-                worker.current_fir_filter = Some(filter);
+                *deemphasis = Deemphasis::new(*deemphasis_us as f64 * 1e-6, if_samplerate);
                 *decim = factor;
             }
+            Decoder::ToneMeasure { fft, powers } => {
+                let len = tone_fft_len(worker.receive_size).ok_or(DecoderError::BufferTooSmall)?;
+
+                // the window is only computed again if the buffer size changed
+                if fft
+                    .as_ref()
+                    .map_or(true, |fft| fft.get_input().len() != len)
+                {
+                    let mut new_fft = FftData::new(len);
+                    new_fft.set_window(WindowKind::BlackmanHaris);
+                    *fft = Some(new_fft);
+                }
+                powers.reserve(len);
+            }
         }
         Ok(())
     }
 
+    // the lowest device samplerate the decoder can work with, it only decimates
+    pub fn required_min_samplerate(&self) -> f64 {
+        match self {
+            Decoder::BaudotDecoder { baudrate, .. } => baudot_samplerate(*baudrate),
+            Decoder::WbfmDecoder { .. } => WBFM_MIN_IF_SAMPLERATE,
+            // the resolution depends on the length of the buffer rather than on the samplerate
+            Decoder::ToneMeasure { .. } => 0.0,
+        }
+    }
+    pub fn check_samplerate(&self, samplerate: f64) -> DecoderResult<()> {
+        let required = self.required_min_samplerate();
+
+        if samplerate < required {
+            return Err(DecoderError::UnsupportedSamplerate {
+                samplerate,
+                required,
+            });
+        }
+
+        Ok(())
+    }
+
+    // unlike replacing the decoder, this keeps the state of the decoding and only rebuilds the filter
+    pub fn update_params(
+        &mut self,
+        worker: &mut DeviceWorker,
+        params: DecoderParams,
+    ) -> DecoderResult<()> {
+        if !self.set_params(params) {
+            return Ok(());
+        }
+
+        self.configuration_changed(worker, false)
+    }
+
+    // only changes the fields, false if the parameters are for another kind of decoder
+    pub fn set_params(&mut self, params: DecoderParams) -> bool {
+        match (&mut *self, params) {
+            (
+                Decoder::BaudotDecoder {
+                    baudrate,
+                    stop_bits,
+                    shift,
+                    cutoff_hz,
+                    transition_width,
+                    forced_decim,
+                    freq_offset_hz,
+                    ..
+                },
+                DecoderParams::Baudot {
+                    baudrate: new_baudrate,
+                    stop_bits: new_stop_bits,
+                    shift: new_shift,
+                    cutoff_hz: new_cutoff_hz,
+                    transition_width: new_transition_width,
+                    forced_decim: new_forced_decim,
+                    freq_offset_hz: new_freq_offset_hz,
+                },
+            ) => {
+                *baudrate = new_baudrate;
+                *stop_bits = new_stop_bits;
+                *shift = new_shift;
+                *cutoff_hz = new_cutoff_hz;
+                *transition_width = new_transition_width;
+                *forced_decim = new_forced_decim;
+                *freq_offset_hz = new_freq_offset_hz;
+            }
+            (
+                Decoder::WbfmDecoder {
+                    deemphasis_us,
+                    freq_offset_hz,
+                    ..
+                },
+                DecoderParams::Wbfm {
+                    deemphasis_us: new_deemphasis_us,
+                    freq_offset_hz: new_freq_offset_hz,
+                },
+            ) => {
+                *deemphasis_us = new_deemphasis_us;
+                *freq_offset_hz = new_freq_offset_hz;
+            }
+            // the parameters were meant for the previous decoder, the new one was sent right after them
+            _ => return false,
+        }
+
+        true
+    }
+
+    // a new decoder with the same parameters, without anything the worker set up or decoded
+    pub fn unconfigured_copy(&self) -> Self {
+        match self {
+            Decoder::BaudotDecoder {
+                baudrate,
+                stop_bits,
+                shift,
+                cutoff_hz,
+                transition_width,
+                forced_decim,
+                freq_offset_hz,
+                ..
+            } => Self::new_baudot(
+                *baudrate,
+                *stop_bits,
+                *shift,
+                *cutoff_hz,
+                *transition_width,
+                *forced_decim,
+                *freq_offset_hz,
+            ),
+            Decoder::WbfmDecoder {
+                deemphasis_us,
+                freq_offset_hz,
+                ..
+            } => Self::new_wbfm(*deemphasis_us, *freq_offset_hz),
+            Decoder::ToneMeasure { .. } => Self::new_tone_measure(),
+        }
+    }
+
     pub fn process(&mut self, worker: &mut DeviceWorker) -> DecoderResult<()> {
         match self {
             Decoder::BaudotDecoder {
@@ -110,15 +416,22 @@ impl Decoder {
                 stop_bits,
                 letters,
                 leftover_bits,
+                prev_sample,
+                nco,
+                decim,
                 ..
             } => {
-                let samplerate = worker.receive_state.as_mut().unwrap().samplerate as f32;
+                // the bits are sampled after decimation
+                let samplerate = worker
+                    .receive_state
+                    .as_ref()
+                    .ok_or(DecoderError::NotConfigured)?
+                    .samplerate as f32
+                    / *decim as f32;
 
-                let filter = worker.current_fir_filter.as_mut().unwrap();
-                let (start, count) = filter.apply(
-                    &mut worker.working_memory[..worker.memory_received_count],
-                    worker.memory_receive_offset,
-                );
+                let (start, count) = mix_and_filter(worker, nco)?;
+
+                let mut samples_start = start;
 
                 if leftover_bits.len() * size_of::<bool>() > start * size_of::<Complex<RxFormat>>()
                 {
@@ -140,6 +453,8 @@ impl Decoder {
                         let dst = buf.add(bits_len_as_complex);
                         std::ptr::copy(src, dst, count);
                     }
+
+                    samples_start = bits_len_as_complex;
                 }
 
                 unsafe {
@@ -151,23 +466,120 @@ impl Decoder {
                     );
                 }
 
-                let (string, _, _) = unsafe {
-                    rtty_decode::decode(
-                        worker.working_memory.as_ptr(),
-                        worker.working_memory.len(),
+                let mut trace = Vec::with_capacity(count);
+                let mut raw_bits = None;
+                let chars = unsafe {
+                    let (chars, rest, rest_len) = rtty_decode::decode(
+                        worker.working_memory.as_ptr().add(samples_start),
+                        count,
                         worker.working_memory.as_mut_ptr() as *mut bool,
                         leftover_bits.len(),
-                        *baudrate,
                         *stop_bits,
+                        *baudrate,
                         samplerate,
                         letters,
-                    )
+                        prev_sample,
+                        &mut trace,
+                    );
+
+                    // the new bits are right after the ones left over from the previous call
+                    if worker.raw_bits {
+                        let bits = (worker.working_memory.as_ptr() as *const bool)
+                            .add(leftover_bits.len());
+                        raw_bits = Some(
+                            std::slice::from_raw_parts(bits, count.min(MAX_RAW_BITS)).to_vec(),
+                        );
+                    }
+
+                    // the bits of a character which isn't complete yet are decoded next time
+                    let rest = std::slice::from_raw_parts(rest, rest_len);
+                    leftover_bits.clear();
+                    leftover_bits.extend_from_slice(rest);
+
+                    chars
                 };
 
-                if !string.is_empty() {
+                if !chars.is_empty() {
                     let _ = worker
                         .sender
-                        .send(GuiBoundEvent::DecodedChars { data: string });
+                        .send(GuiBoundEvent::DecodedChars { data: chars });
+                }
+
+                if let Some(bits) = raw_bits {
+                    let _ = worker.sender.send(GuiBoundEvent::RawBits { bits });
+                }
+
+                // shown in the gui so that the shift can be tuned
+                let _ = worker
+                    .sender
+                    .send(GuiBoundEvent::DemodTrace { samples: trace });
+            }
+            Decoder::WbfmDecoder {
+                nco,
+                prev_sample,
+                deemphasis,
+                audio_filter,
+                audio_buffer,
+                decim,
+                audio_decim,
+                ..
+            } => {
+                let samplerate = worker
+                    .receive_state
+                    .as_ref()
+                    .ok_or(DecoderError::NotConfigured)?
+                    .samplerate
+                    / *decim as f64;
+                let audio_filter = audio_filter.as_ref().ok_or(DecoderError::NotConfigured)?;
+
+                let (start, count) = mix_and_filter(worker, nco)?;
+
+                let gain = samplerate / (2.0 * std::f64::consts::PI * WBFM_DEVIATION_HZ);
+                let demod_start = audio_buffer.len();
+                quadrature_demod(
+                    &worker.working_memory[start..(start + count)],
+                    prev_sample,
+                    gain as f32,
+                    audio_buffer,
+                );
+                deemphasis.process(&mut audio_buffer[demod_start..]);
+
+                let (audio_len, leftover) = audio_filter.apply_in_place(audio_buffer, *audio_decim);
+                let samples = audio_buffer[..audio_len].to_vec();
+                let consumed = audio_buffer.len() - leftover;
+                audio_buffer.drain(..consumed);
+
+                if !samples.is_empty() {
+                    let _ = worker.sender.send(GuiBoundEvent::AudioReady {
+                        samples,
+                        samplerate: samplerate / *audio_decim as f64,
+                    });
+                }
+            }
+            Decoder::ToneMeasure { fft, powers } => {
+                let state = worker
+                    .receive_state
+                    .as_ref()
+                    .ok_or(DecoderError::NotConfigured)?;
+                let fft = fft.as_mut().ok_or(DecoderError::NotConfigured)?;
+                let len = fft.get_input().len();
+
+                // a buffer cut short by an error isn't measured
+                if worker.memory_received_count < len {
+                    return Ok(());
+                }
+
+                // the newest samples if the buffer is longer than the fft
+                let end = worker.memory_receive_offset + worker.memory_received_count;
+                fft.set_input(&worker.working_memory[(end - len)..end]);
+                fft.process(state.samplerate);
+
+                powers.clear();
+                powers.extend(fft.get_output().iter().map(|c| c.norm_sqr()));
+
+                if let Some(bin) = interpolate_peak(powers) {
+                    let freq_hz = state.frequency + bin_frequency(bin, len, state.samplerate);
+                    let _ = worker.sender.send(GuiBoundEvent::Measurement { freq_hz });
                 }
             }
         }
@@ -175,14 +587,637 @@ impl Decoder {
         Ok(())
     }
 
-    pub fn new_baudot(baudrate: f32, stop_bits: f32, shift: f32) -> Self {
+    pub fn new_baudot(
+        baudrate: f32,
+        stop_bits: f32,
+        shift: f32,
+        cutoff_hz: f32,
+        transition_width: f32,
+        forced_decim: Option<u32>,
+        freq_offset_hz: f32,
+    ) -> Self {
         Self::BaudotDecoder {
             baudrate,
             stop_bits,
             shift,
+            cutoff_hz,
+            transition_width,
+            forced_decim,
+            freq_offset_hz,
+            // the step is set once the samplerate is known
+            nco: Nco::new(0.0, 0.0),
             letters: true,
             leftover_bits: Vec::new(),
+            prev_sample: Complex::zero(),
             decim: 0,
         }
     }
+
+    pub fn new_wbfm(deemphasis_us: f32, freq_offset_hz: f32) -> Self {
+        Self::WbfmDecoder {
+            deemphasis_us,
+            freq_offset_hz,
+            // the step is set once the samplerate is known
+            nco: Nco::new(0.0, 0.0),
+            prev_sample: Complex::zero(),
+            deemphasis: Deemphasis::new(deemphasis_us as f64 * 1e-6, WBFM_MIN_IF_SAMPLERATE),
+            audio_filter: None,
+            audio_buffer: Vec::new(),
+            decim: 0,
+            audio_decim: 0,
+        }
+    }
+
+    pub fn new_tone_measure() -> Self {
+        Self::ToneMeasure {
+            // created once the buffer size is known
+            fft: None,
+            powers: Vec::new(),
+        }
+    }
+}
+
+// the longest power of two which fits in the buffer, None if even the shortest fft doesn't
+fn tone_fft_len(receive_size: usize) -> Option<usize> {
+    let len = (receive_size.min(TONE_MAX_FFT_LEN) + 1).next_power_of_two() / 2;
+
+    if len < TONE_MIN_FFT_LEN {
+        None
+    } else {
+        Some(len)
+    }
+}
+
+// the running mean of the last measurements of the tone frequency, the older ones are dropped
+#[derive(Clone, Debug)]
+pub struct MeasurementAverage {
+    values: VecDeque<f64>,
+    len: usize,
+}
+
+impl MeasurementAverage {
+    pub fn new(len: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(len),
+            len: len.max(1),
+        }
+    }
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.max(1);
+        while self.values.len() > self.len {
+            self.values.pop_front();
+        }
+    }
+    // returns the mean including the new value
+    pub fn push(&mut self, value: f64) -> f64 {
+        if self.values.len() == self.len {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+
+        self.mean()
+    }
+    pub fn mean(&self) -> f64 {
+        if self.values.is_empty() {
+            return 0.0;
+        }
+        self.values.iter().sum::<f64>() / self.values.len() as f64
+    }
+    // how much the measurements scatter around the mean, 0 with less than two of them
+    pub fn std_dev(&self) -> f64 {
+        let count = self.values.len();
+        if count < 2 {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        let sum: f64 = self.values.iter().map(|v| (v - mean) * (v - mean)).sum();
+        (sum / (count - 1) as f64).sqrt()
+    }
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+// how far the measured frequency is from the known one in parts per million, positive if the device reads too high
+pub fn frequency_error_ppm(measured_hz: f64, reference_hz: f64) -> f64 {
+    (measured_hz - reference_hz) / reference_hz * 1e6
+}
+
+// the bits are sliced at 16 samples per bit after the decimation
+fn baudot_samplerate(baudrate: f32) -> f64 {
+    (baudrate as f64 * 16.0).max(1.0)
+}
+
+// a forced decimation which would leave less than 16 samples per bit or which the filter stages can't hit exactly
+// is replaced by the computed one, the decoder keeps running either way
+fn baudot_decimation(samplerate: f64, baudrate: f32, forced: Option<u32>) -> u32 {
+    let computed = (samplerate / baudot_samplerate(baudrate)) as u32;
+
+    match forced {
+        None => computed,
+        Some(forced) if forced == 0 || forced > computed => {
+            log::warn!(
+                "The forced decimation of {} is outside of 1 to {} at {:.0} Sps and {} Bd, using {}",
+                forced,
+                computed,
+                samplerate,
+                baudrate,
+                computed
+            );
+            computed
+        }
+        Some(forced) if precise_decimation(forced) != forced => {
+            log::warn!(
+                "The forced decimation of {} can't be split into the filter stages, the closest is {}, using {}",
+                forced,
+                precise_decimation(forced),
+                computed
+            );
+            computed
+        }
+        Some(forced) => forced,
+    }
+}
+
+// makes the filter the one the received samples go through, with enough space reserved in front of them for its taps
+fn install_filter(worker: &mut DeviceWorker, filter: MultistageFir<Complex<RxFormat>>) {
+    worker.working_memory.resize(
+        worker.receive_size + filter.min_buffer_reserve(),
+        Complex::zero(),
+    );
+    worker.memory_receive_offset = worker
+        .memory_receive_offset
+        .max(filter.min_buffer_reserve());
+
+    worker.current_fir_filter = Some(filter);
+}
+
+// shifts the fresh samples by the nco and decimates them, returns where the filtered samples are in working_memory and how many
+fn mix_and_filter(worker: &mut DeviceWorker, nco: &mut Nco) -> DecoderResult<(usize, usize)> {
+    let filter = worker
+        .current_fir_filter
+        .as_mut()
+        .ok_or(DecoderError::NotConfigured)?;
+
+    if worker.memory_receive_offset < filter.min_buffer_reserve() {
+        return Err(DecoderError::BufferTooSmall);
+    }
+
+    let received_end = worker.memory_receive_offset + worker.memory_received_count;
+
+    // only the fresh samples, the ones in front of them were already mixed by the previous call
+    // the spectrum gets the samples before this so it still shows the actual receiver frequency
+    if nco.step != 0.0 {
+        nco.mix(&mut worker.working_memory[worker.memory_receive_offset..received_end]);
+    }
+
+    let start = Instant::now();
+    let filtered = filter.apply(
+        &mut worker.working_memory[..received_end],
+        worker.memory_receive_offset,
+    );
+    worker
+        .processing_stats
+        .add(ProcessingStage::Filter, start.elapsed());
+
+    Ok(filtered)
+}
+
+// the decimation before the demodulation and the one of the audio after it
+// the demodulation runs at the lowest samplerate whose audio is within the tolerance of WBFM_AUDIO_SAMPLERATE,
+// if there is none the audio is as close as it gets, returns None if the samplerate is too low for a whole station
+pub fn wbfm_decimations(samplerate: f64) -> Option<(u32, u32)> {
+    let max_decim = (samplerate / WBFM_MIN_IF_SAMPLERATE) as u32;
+
+    // (decimation, audio decimation, relative error of the audio samplerate)
+    let plans: Vec<(u32, u32, f64)> = (1..=max_decim)
+        .map(|decim| {
+            let if_samplerate = samplerate / decim as f64;
+            let audio_decim = ((if_samplerate / WBFM_AUDIO_SAMPLERATE).round() as u32).max(1);
+            let error = (if_samplerate / audio_decim as f64 / WBFM_AUDIO_SAMPLERATE - 1.0).abs();
+            (decim, audio_decim, error)
+        })
+        .collect();
+
+    plans
+        .iter()
+        .rev()
+        .find(|plan| plan.2 <= WBFM_AUDIO_SAMPLERATE_TOLERANCE)
+        // among equally close ones the cheapest
+        .or_else(|| {
+            plans
+                .iter()
+                .rev()
+                .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+        })
+        .map(|&(decim, audio_decim, _)| (decim, audio_decim))
+}
+
+#[cfg(test)]
+use crate::worker::worker::test_worker;
+
+#[test]
+fn unconfigured_decoder() {
+    let (mut worker, _events) = test_worker();
+    let mut decoder = Decoder::new_baudot(45.45, 1.5, 170.0, 0.0, 0.1, None, 0.0);
+
+    assert!(decoder.init(&mut worker, None).is_ok());
+    assert_eq!(
+        decoder
+            .configuration_changed(&mut worker, true)
+            .unwrap_err(),
+        DecoderError::NotConfigured
+    );
+    assert_eq!(
+        decoder.process(&mut worker).unwrap_err(),
+        DecoderError::NotConfigured
+    );
+}
+
+#[test]
+fn too_low_samplerate() {
+    use crate::worker::worker_manager::ReceiverState;
+
+    let (mut worker, _events) = test_worker();
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(500.0)
+            .frequency(0.0)
+            .build()
+            .unwrap(),
+    );
+    worker.receive_size = 4096;
+
+    // 50 Bd needs 800 Sps
+    let mut decoder = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0);
+    assert_eq!(decoder.required_min_samplerate(), 800.0);
+    decoder.init(&mut worker, None).unwrap();
+    let error = decoder
+        .configuration_changed(&mut worker, true)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        DecoderError::UnsupportedSamplerate {
+            samplerate: 500.0,
+            required: 800.0,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "The samplerate of 500 Sps is below the 800 Sps the decoder needs.\n"
+    );
+    // no degenerate filter was installed
+    assert!(worker.current_fir_filter.is_none());
+
+    // a broadcast station doesn't fit in 100 kSps
+    let decoder = Decoder::new_wbfm(50.0, 0.0);
+    assert_eq!(
+        decoder.check_samplerate(100e3),
+        Err(DecoderError::UnsupportedSamplerate {
+            samplerate: 100e3,
+            required: 200e3,
+        })
+    );
+    assert_eq!(decoder.check_samplerate(240e3), Ok(()));
+    assert_eq!(Decoder::new_tone_measure().check_samplerate(1.0), Ok(()));
+}
+
+#[test]
+fn update_params_keeps_state() {
+    use crate::worker::worker_manager::ReceiverState;
+
+    let (mut worker, _events) = test_worker();
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(48000.0)
+            .frequency(0.0)
+            .build()
+            .unwrap(),
+    );
+    worker.receive_size = 4096;
+
+    let mut decoder = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0);
+    decoder.init(&mut worker, None).unwrap();
+    decoder.configuration_changed(&mut worker, true).unwrap();
+
+    // pretend that a character was cut in half by the end of a buffer while in figures
+    let prev_decim = match &mut decoder {
+        Decoder::BaudotDecoder {
+            letters,
+            leftover_bits,
+            decim,
+            ..
+        } => {
+            *letters = false;
+            leftover_bits.extend_from_slice(&[false, true, true]);
+            *decim
+        }
+        Decoder::WbfmDecoder { .. } | Decoder::ToneMeasure { .. } => unreachable!(),
+    };
+
+    let params = DecoderParams::Baudot {
+        baudrate: 45.45,
+        stop_bits: 2.0,
+        shift: 170.0,
+        cutoff_hz: 300.0,
+        transition_width: 0.05,
+        forced_decim: None,
+        freq_offset_hz: -1200.0,
+    };
+    decoder.update_params(&mut worker, params).unwrap();
+
+    match decoder {
+        Decoder::WbfmDecoder { .. } | Decoder::ToneMeasure { .. } => unreachable!(),
+        Decoder::BaudotDecoder {
+            baudrate,
+            stop_bits,
+            shift,
+            cutoff_hz,
+            transition_width,
+            freq_offset_hz,
+            nco,
+            letters,
+            leftover_bits,
+            decim,
+            ..
+        } => {
+            assert_eq!((baudrate, stop_bits, shift), (45.45, 2.0, 170.0));
+            assert_eq!(cutoff_hz, 300.0);
+            assert_eq!(transition_width, 0.05);
+            assert_eq!(freq_offset_hz, -1200.0);
+            assert_eq!(nco.step, -2.0 * std::f32::consts::PI * 1200.0 / 48000.0);
+            assert!(!letters);
+            assert_eq!(leftover_bits, [false, true, true]);
+            // the lower baudrate needs less samples
+            assert!(decim > prev_decim);
+        }
+    }
+}
+
+#[test]
+fn forced_decimation() {
+    use crate::worker::worker_manager::ReceiverState;
+
+    // 50 Bd at 48 kSps leaves room for a decimation of 60
+    assert_eq!(baudot_decimation(48000.0, 50.0, None), 60);
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(48)), 48);
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(60)), 60);
+    // too few samples per bit and no decimation at all
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(61)), 60);
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(0)), 60);
+    // 30 is split into 15 and 2, no stage can decimate by the prime 17, the closest the stages get is 16
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(30)), 30);
+    assert_eq!(baudot_decimation(48000.0, 50.0, Some(17)), 60);
+
+    let (mut worker, events) = test_worker();
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(48000.0)
+            .frequency(0.0)
+            .build()
+            .unwrap(),
+    );
+    worker.receive_size = 4096;
+
+    let reported = || {
+        events
+            .try_iter()
+            .filter_map(|event| match event {
+                GuiBoundEvent::DecoderConfigured { decimation } => Some(decimation),
+                _ => None,
+            })
+            .last()
+    };
+    let decim = |decoder: &Decoder| match decoder {
+        Decoder::BaudotDecoder { decim, .. } => *decim,
+        Decoder::WbfmDecoder { .. } | Decoder::ToneMeasure { .. } => unreachable!(),
+    };
+
+    let mut decoder = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, Some(48), 0.0);
+    decoder.init(&mut worker, None).unwrap();
+    decoder.configuration_changed(&mut worker, true).unwrap();
+    assert_eq!(decim(&decoder), 48);
+    assert_eq!(reported(), Some(48));
+
+    // 17 can't be split into the filter stages, the computed decimation is used instead and reported
+    let params = DecoderParams::Baudot {
+        baudrate: 50.0,
+        stop_bits: 1.5,
+        shift: 425.0,
+        cutoff_hz: 0.0,
+        transition_width: 0.1,
+        forced_decim: Some(17),
+        freq_offset_hz: 0.0,
+    };
+    decoder.update_params(&mut worker, params).unwrap();
+    assert_eq!(decim(&decoder), precise_decimation(60));
+    assert_eq!(reported(), Some(60));
+}
+
+#[test]
+fn raw_bits_match_the_demodulation() {
+    use crate::{generator::SignalGenerator, worker::worker_manager::ReceiverState};
+
+    let samplerate = 48000.0;
+    let (mut worker, events) = test_worker();
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(samplerate)
+            .frequency(0.0)
+            .build()
+            .unwrap(),
+    );
+    worker.receive_size = 4096;
+
+    let mut decoder = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0);
+    decoder.init(&mut worker, None).unwrap();
+    decoder.configuration_changed(&mut worker, true).unwrap();
+
+    // the mark and space tones alternate every bit, 50 Bd at 48 kSps
+    let samples = SignalGenerator::new_fsk(
+        samplerate,
+        &[(true, 1.0), (false, 1.0)].repeat(9),
+        50.0,
+        425.0,
+        0.0,
+    )
+    .samples();
+
+    let mut process = |worker: &mut DeviceWorker, chunk: &[Complex<RxFormat>]| {
+        worker
+            .receive_buffer(|dst| -> Result<usize, ()> {
+                dst[..chunk.len()].copy_from_slice(chunk);
+                Ok(chunk.len())
+            })
+            .unwrap();
+        decoder.process(worker).unwrap();
+        worker.memory_received_count = 0;
+
+        let (mut bits, mut trace) = (None, None);
+        for event in events.try_iter() {
+            match event {
+                GuiBoundEvent::RawBits { bits: b } => bits = Some(b),
+                GuiBoundEvent::DemodTrace { samples } => trace = Some(samples),
+                _ => {}
+            }
+        }
+        (bits, trace.unwrap())
+    };
+
+    worker.raw_bits = true;
+    let mut all_bits = Vec::new();
+    for chunk in samples[..(4096 * 3)].chunks(4096) {
+        let (bits, trace) = process(&mut worker, chunk);
+        let bits = bits.unwrap();
+
+        // every new sample is sliced by the sign of its demodulated angle
+        assert_eq!(bits.len(), trace.len());
+        for (&bit, &angle) in bits.iter().zip(trace.iter()) {
+            assert_eq!(bit, angle.is_sign_positive());
+        }
+        all_bits.extend(bits);
+    }
+    assert!(all_bits.contains(&true) && all_bits.contains(&false));
+
+    // off by default
+    worker.raw_bits = false;
+    let (bits, _) = process(&mut worker, &samples[(4096 * 3)..(4096 * 4)]);
+    assert_eq!(bits, None);
+}
+
+#[test]
+fn wbfm_decimation_factors() {
+    // 2.4 MSps is demodulated at 240 kSps and decimated to exactly 48 kSps
+    assert_eq!(wbfm_decimations(2.4e6), Some((10, 5)));
+
+    for &samplerate in &[250e3, 1.024e6, 2.048e6, 2.4e6, 3.2e6, 8e6, 10e6, 20e6] {
+        let (decim, audio_decim) = wbfm_decimations(samplerate).unwrap();
+        let if_samplerate = samplerate / decim as f64;
+        let audio_samplerate = if_samplerate / audio_decim as f64;
+
+        // the whole station fits and the demodulation isn't done at a needlessly high samplerate
+        assert!(if_samplerate >= WBFM_MIN_IF_SAMPLERATE, "{}", samplerate);
+        assert!(
+            if_samplerate < 4.0 * WBFM_MIN_IF_SAMPLERATE,
+            "{}",
+            samplerate
+        );
+        assert!(
+            (audio_samplerate / WBFM_AUDIO_SAMPLERATE - 1.0).abs() < 0.1,
+            "{} {}",
+            samplerate,
+            audio_samplerate
+        );
+    }
+
+    // the usual RTL-SDR samplerate can't hit 48 kSps exactly but gets within the tolerance
+    let (decim, audio_decim) = wbfm_decimations(1.024e6).unwrap();
+    assert_eq!((decim, audio_decim), (3, 7));
+    let audio_samplerate = 1.024e6 / (decim * audio_decim) as f64;
+    assert!(
+        (audio_samplerate / WBFM_AUDIO_SAMPLERATE - 1.0).abs() <= WBFM_AUDIO_SAMPLERATE_TOLERANCE
+    );
+
+    // too narrow for a broadcast station
+    assert_eq!(wbfm_decimations(192e3), None);
+}
+
+#[test]
+fn tone_measurement() {
+    use crate::{generator::SignalGenerator, worker::worker_manager::ReceiverState};
+
+    let samplerate = 48000.0;
+    let frequency = 434.5e6;
+    let (mut worker, events) = test_worker();
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(samplerate)
+            .frequency(frequency)
+            .build()
+            .unwrap(),
+    );
+    worker.receive_size = 6000;
+
+    let mut decoder = Decoder::new_tone_measure();
+    decoder.init(&mut worker, None).unwrap();
+    decoder.configuration_changed(&mut worker, true).unwrap();
+
+    // the fft fits in the buffer, one of its bins is 48000 / 4096 = 11.7 Hz wide
+    let len = match &decoder {
+        Decoder::ToneMeasure { fft, .. } => fft.as_ref().unwrap().get_input().len(),
+        _ => unreachable!(),
+    };
+    assert_eq!(len, 4096);
+    let bin_hz = samplerate / len as f64;
+
+    // tones between the bins on both sides of the center, with some noise
+    for &offset in &[1234.56, -7890.12, 5.0 * bin_hz + 0.37 * bin_hz] {
+        let samples = SignalGenerator::new_tone(samplerate, offset, 6000)
+            .noise(0.005)
+            .samples();
+
+        worker
+            .receive_buffer(|dst| -> Result<usize, ()> {
+                dst[..samples.len()].copy_from_slice(&samples);
+                Ok(samples.len())
+            })
+            .unwrap();
+        decoder.process(&mut worker).unwrap();
+        worker.memory_received_count = 0;
+
+        let measured = events
+            .try_iter()
+            .find_map(|event| match event {
+                GuiBoundEvent::Measurement { freq_hz } => Some(freq_hz),
+                _ => None,
+            })
+            .unwrap();
+
+        // the peak bin alone could be half a bin off
+        let error = (measured - (frequency + offset)).abs();
+        assert!(error < 0.05 * bin_hz, "{} Hz off at {} Hz", error, offset);
+    }
+
+    // a partial buffer is skipped
+    worker
+        .receive_buffer(|dst| -> Result<usize, ()> {
+            dst[..100]
+                .iter_mut()
+                .for_each(|s| *s = Complex::new(0.5, 0.0));
+            Ok(100)
+        })
+        .unwrap();
+    decoder.process(&mut worker).unwrap();
+    assert!(events.try_iter().next().is_none());
+
+    assert_eq!(tone_fft_len(100), None);
+    assert_eq!(tone_fft_len(256), Some(256));
+    assert_eq!(tone_fft_len(1 << 20), Some(TONE_MAX_FFT_LEN));
+}
+
+#[test]
+fn measurement_average() {
+    let mut average = MeasurementAverage::new(3);
+    assert_eq!(average.mean(), 0.0);
+    assert_eq!(average.push(10.0), 10.0);
+    assert_eq!(average.std_dev(), 0.0);
+    assert_eq!(average.push(20.0), 15.0);
+    assert_eq!(average.push(30.0), 20.0);
+    assert_eq!(average.std_dev(), 10.0);
+
+    // the oldest one is dropped
+    assert_eq!(average.push(40.0), 30.0);
+    assert_eq!(average.count(), 3);
+
+    average.set_len(1);
+    assert_eq!(average.mean(), 40.0);
+    average.clear();
+    assert_eq!(average.count(), 0);
+
+    assert!((frequency_error_ppm(100_000_050.0, 100e6) - 0.5).abs() < 1e-9);
+    assert!((frequency_error_ppm(434_499_000.0, 434.5e6) + 2.3015).abs() < 1e-3);
 }
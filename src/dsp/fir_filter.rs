@@ -2,6 +2,7 @@ use num_traits::{Num, NumOps};
 
 use super::window_functions::WindowKind;
 
+#[derive(Debug)]
 pub struct FirFilter {
     taps: Box<[f32]>,
 }
@@ -121,17 +122,79 @@ impl FirFilter {
 
         end.offset_from(src).max(0) as usize
     }
+    /// Filters `input` into `output` without any state, the caller doesn't need to reserve or alias any memory.
+    ///
+    /// Only the outputs whose taps are fully covered by `input` are computed, at most `output.len()` of them.
+    /// Returns the number of elements written to `output`.
+    pub fn filter<T: Num + NumOps<f32> + Copy>(
+        &self,
+        input: &[T],
+        output: &mut [T],
+        decimation: u32,
+    ) -> usize {
+        assert!(decimation > 0, "decimation must not be zero");
+
+        let taps = self.len();
+        if input.len() < taps {
+            return 0;
+        }
+
+        let count = ((input.len() - taps) / decimation as usize + 1).min(output.len());
+        if count == 0 {
+            return 0;
+        }
+
+        // the input is cut so that apply() doesn't produce more than count elements
+        let len = (count - 1) * decimation as usize + taps;
+        unsafe {
+            self.apply(input.as_ptr(), output.as_mut_ptr(), len, decimation);
+        }
+
+        count
+    }
+    /// Safe version of `apply` which filters `buffer` in place, the output is written to its beginning.
+    ///
+    /// Returns `(output_len, leftover_len)`, the last `leftover_len` elements of `buffer` weren't consumed
+    /// and have to be put in front of the next buffer.
+    pub fn apply_in_place<T: Num + NumOps<f32> + Copy>(
+        &self,
+        buffer: &mut [T],
+        decimation: u32,
+    ) -> (usize, usize) {
+        assert!(decimation > 0, "decimation must not be zero");
+
+        if buffer.len() < self.len() {
+            return (0, buffer.len());
+        }
+
+        // every output element is written only after all of the input elements it depends on were read
+        // so src and dst can be the same, MultistageFir does the same
+        let ptr = buffer.as_mut_ptr();
+        let leftover = unsafe { self.apply(ptr, ptr, buffer.len(), decimation) };
+
+        ((buffer.len() - leftover) / decimation as usize, leftover)
+    }
     pub fn len(&self) -> usize {
         self.taps.len()
     }
 }
 
+// a single tap would just scale the input, wide transitions still get a filter that attenuates something
+const MIN_TAP_COUNT: usize = 3;
+
 // also taken from gnuradio
 fn min_tap_count(normalized_transition_width: f64, window_kind: WindowKind) -> usize {
+    // a zero width would need an infinitely long filter, the cast would saturate and the allocation fail somewhere far away
+    assert!(
+        normalized_transition_width > 0.0,
+        "the transition width of a filter must be positive, got {}",
+        normalized_transition_width
+    );
+
     let a = window_kind.max_attenuation();
 
     // Fred Harris' rule-of-thumb for estimating filter order
-    let mut ntaps = (a / (22.0 * normalized_transition_width)) as usize;
+    let mut ntaps = ((a / (22.0 * normalized_transition_width)) as usize).max(MIN_TAP_COUNT);
 
     // ensure ntaps is odd
     if ntaps % 2 == 0 {
@@ -140,3 +203,77 @@ fn min_tap_count(normalized_transition_width: f64, window_kind: WindowKind) -> u
 
     ntaps
 }
+
+// the dot product of the taps with every window of the input, written as plainly as possible
+#[cfg(test)]
+fn reference_convolution(fir: &FirFilter, input: &[f32], decimation: usize) -> Vec<f32> {
+    let taps = fir.len();
+    let mut output = Vec::new();
+
+    let mut start = 0;
+    while start + taps <= input.len() {
+        let window = &input[start..start + taps];
+        output.push(window.iter().zip(fir.taps.iter()).map(|(x, t)| x * t).sum());
+        start += decimation;
+    }
+
+    output
+}
+
+// the sizes are kept small so that the unsafe paths can also be checked with Miri, together with those of MultistageFir
+// the Miri job of .github/workflows/miri.yml runs them with
+// cargo +nightly miri test --no-default-features --lib -- dsp::fir_filter dsp::multistage_fir
+#[test]
+fn fir_filter_matches_reference() {
+    let fir = FirFilter::new_lowpass(1.0, 0.2, 0.3, WindowKind::BlackmanHaris);
+    let input: Vec<f32> = (0..64).map(|i| ((i * 37) % 11) as f32 - 5.0).collect();
+
+    for decimation in 1..5 {
+        let expected = reference_convolution(&fir, &input, decimation);
+
+        let mut output = vec![0.0; expected.len() + 3];
+        let count = fir.filter(&input, &mut output, decimation as u32);
+        assert_eq!(count, expected.len());
+        for (a, b) in output.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+
+        // a short output is filled and nothing is written past it
+        let mut short = vec![0.0; 2];
+        assert_eq!(fir.filter(&input, &mut short, decimation as u32), 2);
+        assert_eq!(short, expected[..2]);
+
+        // the in place path has to produce the same result even though it aliases
+        let mut buffer = input.clone();
+        let (count, _) = fir.apply_in_place(&mut buffer, decimation as u32);
+        assert_eq!(count, expected.len());
+        for (a, b) in buffer.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    assert_eq!(fir.filter(&input[..fir.len() - 1], &mut [0.0; 4], 1), 0);
+    assert_eq!(fir.filter(&input, &mut [], 1), 0);
+}
+
+#[test]
+fn degenerate_transition_widths() {
+    let window = WindowKind::BlackmanHaris;
+
+    // wider than the whole band, the rule of thumb gives less than a tap
+    assert_eq!(min_tap_count(10.0, window), MIN_TAP_COUNT);
+    let fir = FirFilter::new_lowpass(1.0, 0.25, 1000.0, window);
+    assert_eq!(fir.len(), MIN_TAP_COUNT);
+    // the gain is still normalized
+    assert!((fir.taps.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+
+    // tiny widths give long filters which are still odd
+    let ntaps = min_tap_count(1e-4, window);
+    assert!(ntaps > 10_000);
+    assert_eq!(ntaps % 2, 1);
+
+    for width in [0.0, -0.1, f64::NAN].iter() {
+        let result = std::panic::catch_unwind(|| min_tap_count(*width, window));
+        assert!(result.is_err(), "width {} was accepted", width);
+    }
+}
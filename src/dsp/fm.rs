@@ -0,0 +1,102 @@
+use rustfft::num_complex::Complex;
+
+// the phase change between every two consecutive samples times the gain, this is the same demodulation
+// rtty_decode does, the last sample is kept so that the first output of the next call isn't garbage
+pub fn quadrature_demod(
+    input: &[Complex<f32>],
+    prev: &mut Complex<f32>,
+    gain: f32,
+    output: &mut Vec<f32>,
+) {
+    output.extend(input.iter().map(|&sample| {
+        let angle = (sample * prev.conj()).arg();
+        *prev = sample;
+        angle * gain
+    }));
+}
+
+// a single pole lowpass which undoes the pre-emphasis of broadcast FM, the corner frequency is 1 / (2 pi tau)
+// tau is 50 us in most of the world and 75 us in the Americas and South Korea
+#[derive(Clone, Debug)]
+pub struct Deemphasis {
+    alpha: f32,
+    state: f32,
+}
+
+impl Deemphasis {
+    pub fn new(tau_s: f64, samplerate: f64) -> Self {
+        // matches the time constant of the analog RC filter, the response is close to it well below the nyquist frequency
+        let alpha = 1.0 - (-1.0 / (tau_s * samplerate)).exp();
+
+        Self {
+            alpha: alpha as f32,
+            state: 0.0,
+        }
+    }
+    pub fn process(&mut self, buf: &mut [f32]) {
+        for x in buf {
+            self.state += self.alpha * (*x - self.state);
+            *x = self.state;
+        }
+    }
+}
+
+// the amplitude of a tone after the filter settled, relative to the input
+#[cfg(test)]
+fn deemphasis_gain(tau_s: f64, samplerate: f64, freq: f64) -> f64 {
+    let mut filter = Deemphasis::new(tau_s, samplerate);
+    let mut buf: Vec<f32> = (0..(samplerate as usize / 10))
+        .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / samplerate).cos() as f32)
+        .collect();
+    filter.process(&mut buf);
+
+    // the first half is left for the filter to settle
+    let peak = buf[(buf.len() / 2)..]
+        .iter()
+        .fold(0.0f32, |peak, x| peak.max(x.abs()));
+    peak as f64
+}
+
+#[test]
+fn deemphasis_response() {
+    let samplerate = 240e3;
+
+    for &tau in &[50e-6, 75e-6] {
+        let corner = 1.0 / (2.0 * std::f64::consts::PI * tau);
+        let db = |freq: f64| 20.0 * deemphasis_gain(tau, samplerate, freq).log10();
+        // the response of the analog RC filter
+        let analog_db = |freq: f64| -10.0 * (1.0 + (freq / corner).powi(2)).log10();
+
+        assert!(db(0.0).abs() < 0.01);
+        assert!((db(corner) + 3.01).abs() < 0.2, "{}", db(corner));
+        for &freq in &[300.0, 1000.0, 5000.0, 10000.0, 15000.0] {
+            assert!((db(freq) - analog_db(freq)).abs() < 0.5, "{} {}", tau, freq);
+        }
+    }
+
+    // the american filter takes away more of the treble
+    let treble = |tau| deemphasis_gain(tau, samplerate, 10e3);
+    assert!(treble(75e-6) < treble(50e-6));
+}
+
+#[test]
+fn demod_follows_frequency() {
+    let samplerate = 48000.0;
+    let mut prev = Complex::new(1.0, 0.0);
+    let mut output = Vec::new();
+
+    // a tone 1200 Hz above the center advances by this much every sample
+    let step = 2.0 * std::f32::consts::PI * 1200.0 / samplerate;
+    let samples: Vec<Complex<f32>> = (1..=100)
+        .map(|i| Complex::from_polar(0.3, step * i as f32))
+        .collect();
+
+    // split in two so that the kept sample is used
+    quadrature_demod(&samples[..37], &mut prev, 1.0 / step, &mut output);
+    quadrature_demod(&samples[37..], &mut prev, 1.0 / step, &mut output);
+
+    assert_eq!(output.len(), 100);
+    for x in output {
+        assert!((x - 1.0).abs() < 1e-3, "{}", x);
+    }
+}
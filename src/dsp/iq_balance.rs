@@ -0,0 +1,129 @@
+use rustfft::num_complex::Complex;
+
+// how much of the statistics of every buffer goes into the running estimate
+const ADAPT_RATE: f32 = 0.1;
+
+// corrects the gain and phase mismatch between the I and Q branches of a receiver, it puts a mirror image
+// of every strong signal on the opposite side of the center
+// the imbalance is estimated blindly from the signal itself, a received signal is on average uncorrelated
+// between I and Q and has the same power in both of them so any difference comes from the receiver
+#[derive(Clone, Debug, Default)]
+pub struct IqCorrector {
+    // running averages of I^2, Q^2 and I*Q
+    ii: f32,
+    qq: f32,
+    iq: f32,
+    estimated: bool,
+}
+
+impl IqCorrector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // with Q = g * sin(x + phi) for I = cos(x), I*Q / I^2 estimates g * sin(phi) and Q^2 / I^2 estimates g^2
+    // Q' = (Q - g * sin(phi) * I) / (g * cos(phi)) is then in quadrature with I and has the same amplitude
+    pub fn process(&mut self, buf: &mut [Complex<f32>]) {
+        if buf.is_empty() {
+            return;
+        }
+
+        let (mut ii, mut qq, mut iq) = (0.0f64, 0.0f64, 0.0f64);
+        for sample in buf.iter() {
+            let (i, q) = (sample.re as f64, sample.im as f64);
+            ii += i * i;
+            qq += q * q;
+            iq += i * q;
+        }
+        let len = buf.len() as f64;
+        let (ii, qq, iq) = ((ii / len) as f32, (qq / len) as f32, (iq / len) as f32);
+
+        if self.estimated {
+            self.ii += ADAPT_RATE * (ii - self.ii);
+            self.qq += ADAPT_RATE * (qq - self.qq);
+            self.iq += ADAPT_RATE * (iq - self.iq);
+        } else {
+            self.ii = ii;
+            self.qq = qq;
+            self.iq = iq;
+            self.estimated = true;
+        }
+
+        // nothing but zeroes was received so far
+        if self.ii <= 0.0 {
+            return;
+        }
+
+        let phase = self.iq / self.ii;
+        let gain_squared = self.qq / self.ii;
+        let quadrature_squared = gain_squared - phase * phase;
+        if quadrature_squared <= 0.0 {
+            return;
+        }
+
+        let scale = 1.0 / quadrature_squared.sqrt();
+        for sample in buf {
+            sample.im = (sample.im - phase * sample.re) * scale;
+        }
+    }
+}
+
+#[test]
+fn image_is_suppressed() {
+    use std::f32::consts::PI;
+
+    let samplerate = 48000.0;
+    let freq = 5000.0;
+    let len = 48000;
+
+    // a tone at +5 kHz with 10 % more gain and 5 degrees more phase in Q than in I
+    let (gain, phase) = (1.1f32, 5.0f32.to_radians());
+    let mut samples: Vec<Complex<f32>> = (0..len)
+        .map(|i| {
+            let x = 2.0 * PI * freq * i as f32 / samplerate;
+            Complex::new(x.cos(), gain * (x + phase).sin())
+        })
+        .collect();
+
+    // the amplitude of the tone at `freq` in the second half of the samples, after the estimate settled
+    let amplitude = |samples: &[Complex<f32>], freq: f32| {
+        let settled = &samples[(len / 2)..];
+        let sum: Complex<f32> = settled
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let x = 2.0 * PI * freq * (i + len / 2) as f32 / samplerate;
+                s * Complex::from_polar(1.0, -x)
+            })
+            .sum();
+        sum.norm() / settled.len() as f32
+    };
+    let image_db = |samples: &[Complex<f32>]| {
+        20.0 * (amplitude(samples, -freq) / amplitude(samples, freq)).log10()
+    };
+
+    let before = image_db(&samples);
+    assert!(before > -30.0, "{}", before);
+
+    let mut corrector = IqCorrector::new();
+    for chunk in samples.chunks_mut(1000) {
+        corrector.process(chunk);
+    }
+
+    let after = image_db(&samples);
+    assert!(after < -60.0, "{} {}", before, after);
+
+    // a balanced signal is left as it is
+    let mut balanced: Vec<Complex<f32>> = (0..1000)
+        .map(|i| Complex::from_polar(0.5, 2.0 * PI * freq * i as f32 / samplerate))
+        .collect();
+    let original = balanced.clone();
+    IqCorrector::new().process(&mut balanced);
+    for (a, b) in balanced.iter().zip(&original) {
+        assert!((a - b).norm() < 1e-3);
+    }
+
+    // zeroes don't turn into NaN
+    let mut zeroes = vec![Complex::new(0.0, 0.0); 100];
+    IqCorrector::new().process(&mut zeroes);
+    assert!(zeroes.iter().all(|s| s.re == 0.0 && s.im == 0.0));
+}
@@ -1,4 +1,9 @@
 pub mod fir_filter;
+pub mod fm;
+pub mod iq_balance;
 pub mod multistage_fir;
+pub mod nco;
+pub mod resample;
+pub mod ring_buffer;
 pub mod rtty_decode;
 pub mod window_functions;
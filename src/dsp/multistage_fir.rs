@@ -1,4 +1,3 @@
-use std::ops::Range;
 use std::rc::Rc;
 
 use num_traits::{Num, NumOps};
@@ -16,6 +15,10 @@ pub struct MultistageFir<T: Num + NumOps<f32> + Copy> {
 
 const LOWPASS_TRANSITION_WIDTH: f64 = 0.05;
 
+// every stage decimates at most by this much, the taps of a stage only depend on the transition width
+// so fewer larger stages are cheaper, but the fixed transition width of the cached ones gets too wide for larger factors
+const MAX_STAGE_DECIMATION: u32 = 16;
+
 impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
     pub fn new() -> Self {
         Self {
@@ -81,6 +84,9 @@ impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
 
         (s, current_factor)
     }
+    // splits the closest factor the stages can hit into the cached stages and the last stage, so the achieved factor
+    // follows the requested one instead of jumping between powers of two
+    // the last stage is always added because it sets the cutoff
     pub fn new_multistage_decim_precise(
         decimation_factor: u32,
         window_kind: WindowKind,
@@ -88,29 +94,32 @@ impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
         normalized_cutoff_freq: f64,
         normalized_transition_width: f64,
     ) -> (Self, u32) {
-        let (mut filter, achieved_decimation) =
-            Self::new_multistage_decim_imprecise_cached(decimation_factor, window_kind, cache);
-
-        let remaining_decimation = decimation_factor / achieved_decimation;
-
-        if remaining_decimation > 0 || normalized_cutoff_freq < 0.5 {
-            // because transition_width is relative to the samplerate, keeping it constant would make it progressively tighter and tighter
-            // to counteract this, transition_width is multiplied with the current decimation factor
-
-            // while keeping it constant wouldn't be incorrect, it would lead to the same number of taps at every stage
-            // this way, less numbers have to be multiplied
-
-            // this is not done with the cached stages because it would make each one unique
-            let fir = Rc::new(FirFilter::new_lowpass(
-                1.0,
-                normalized_cutoff_freq,
-                normalized_transition_width,
-                window_kind,
-            ));
-            filter.add_stage(fir, remaining_decimation.max(1));
+        let (cached_factors, last_decimation) = precise_decimation_plan(decimation_factor);
+
+        let mut filter = Self::new();
+        let mut achieved_decimation = 1;
+        for &factor in &cached_factors {
+            filter.add_stage(cached_lowpass(factor, window_kind, cache), factor);
+            achieved_decimation *= factor;
         }
 
-        (filter, achieved_decimation * remaining_decimation)
+        // because transition_width is relative to the samplerate, keeping it constant would make it progressively tighter and tighter
+        // to counteract this, transition_width is multiplied with the current decimation factor
+
+        // while keeping it constant wouldn't be incorrect, it would lead to the same number of taps at every stage
+        // this way, less numbers have to be multiplied
+
+        // this is not done with the cached stages because it would make each one unique
+        // the cutoff is relative to the samplerate before any decimation, the last stage however runs after the cached ones
+        let fir = Rc::new(FirFilter::new_lowpass(
+            1.0,
+            normalized_cutoff_freq * achieved_decimation as f64,
+            normalized_transition_width,
+            window_kind,
+        ));
+        filter.add_stage(fir, last_decimation);
+
+        (filter, achieved_decimation * last_decimation)
     }
     pub fn add_stage(&mut self, fir: Rc<FirFilter>, decimation: u32) {
         self.min_buffer_reserve = self.min_buffer_reserve.max(fir.len() - 1);
@@ -129,7 +138,7 @@ impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
         // rust doesn't really document how valid _pointer_ aliasing is in relation to references
         // here we take a mutable reference so noone else can reference this
         // from here the original reference will not be used so that the compiler doesn't get any funny ideas
-        // TODO run this through the miri validator (I think it validates pointers?)
+        // the pointers are checked by running the tests of this module under Miri in CI, see the tests of FirFilter
         let buf_start = buffer.as_mut_ptr();
         let buf_end = unsafe { buf_start.add(buffer.len()) };
 
@@ -194,7 +203,8 @@ impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
                 *prev_elements_ref = left as u32;
 
                 // for each n=decimation samples, a new one is written out, pretty much just integer division
-                elements_count = elements_count / decimation as usize;
+                // the leftover samples didn't produce any output, they are only processed in the next call
+                elements_count = (elements_count - left) / decimation as usize;
 
                 // shift the prev_buf to the next filter's frame at which the relevant data starts
                 prev_buf = prev_buf.add(max_leftover);
@@ -208,9 +218,154 @@ impl<T: Num + NumOps<f32> + Copy> MultistageFir<T> {
     pub fn min_buffer_reserve(&self) -> usize {
         self.min_buffer_reserve
     }
+    // the total count of taps over all stages, roughly proportional to the work done per sample
+    pub fn taps(&self) -> usize {
+        self.stages.iter().map(|s| s.2.len()).sum()
+    }
     fn resize_prev_buffer(&mut self) {
         let prev_buffer_len = self.stages.iter().map(|s| s.2.len() - 1).sum();
         self.prev_buffer.resize(prev_buffer_len, T::zero());
         self.prev_buffer_needs_resize = false;
     }
 }
+
+// the decimation of every stage, largest first, None if the factor has a prime factor no stage can decimate by
+fn stage_factors(decimation_factor: u32) -> Option<Vec<u32>> {
+    let mut primes = Vec::new();
+    let mut rest = decimation_factor;
+    let mut prime = 2;
+    while rest > 1 {
+        if prime > MAX_STAGE_DECIMATION {
+            return None;
+        }
+        while rest % prime == 0 {
+            primes.push(prime);
+            rest /= prime;
+        }
+        prime += 1;
+    }
+
+    // the largest primes are placed first, each one into the first stage it still fits
+    let mut stages: Vec<u32> = Vec::new();
+    for &prime in primes.iter().rev() {
+        match stages
+            .iter_mut()
+            .find(|stage| **stage * prime <= MAX_STAGE_DECIMATION)
+        {
+            Some(stage) => *stage *= prime,
+            None => stages.push(prime),
+        }
+    }
+    stages.sort_unstable_by(|a, b| b.cmp(a));
+
+    Some(stages)
+}
+
+// returns the cached stage factors and the decimation of the last stage, the smallest one
+// the closest factor the stages can hit is used, the lower one of two equally close ones
+fn precise_decimation_plan(decimation_factor: u32) -> (Vec<u32>, u32) {
+    let decimation_factor = decimation_factor.max(1);
+
+    // the factors without a prime above MAX_STAGE_DECIMATION are dense, this ends within a few steps
+    for distance in 0.. {
+        let below = decimation_factor
+            .checked_sub(distance)
+            .filter(|&factor| factor > 0);
+        let above = decimation_factor.checked_add(distance);
+
+        for candidate in below.into_iter().chain(above) {
+            if let Some(mut stages) = stage_factors(candidate) {
+                let last = stages.pop().unwrap_or(1);
+                return (stages, last);
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+// the decimation new_multistage_decim_precise achieves for the requested factor
+pub fn precise_decimation(decimation_factor: u32) -> u32 {
+    let (cached_factors, last_decimation) = precise_decimation_plan(decimation_factor);
+    cached_factors.iter().product::<u32>() * last_decimation
+}
+
+fn cached_lowpass(
+    factor: u32,
+    window_kind: WindowKind,
+    cache: &mut Vec<(u32, Rc<FirFilter>)>,
+) -> Rc<FirFilter> {
+    if let Some((_, fir)) = cache.iter().find(|(cached, _)| *cached == factor) {
+        return fir.clone();
+    }
+
+    let fir = Rc::new(FirFilter::new_lowpass(
+        1.0,
+        1.0 / factor as f64,
+        LOWPASS_TRANSITION_WIDTH,
+        window_kind,
+    ));
+    cache.push((factor, fir.clone()));
+
+    fir
+}
+
+#[test]
+fn narrow_transition_needs_more_taps() {
+    let mut cache = Vec::new();
+
+    let mut taps = |transition_width| {
+        let (filter, factor) = MultistageFir::<f32>::new_multistage_decim_precise(
+            100,
+            WindowKind::BlackmanHaris,
+            &mut cache,
+            0.002,
+            transition_width,
+        );
+        assert_eq!(factor, 100);
+
+        filter.taps()
+    };
+
+    let wide = taps(0.1);
+    let narrow = taps(0.02);
+    assert!(narrow > wide);
+    // the cached stages are shared so only the last one grows
+    assert!(taps(0.01) > narrow);
+}
+
+#[test]
+fn precise_decimation_accuracy() {
+    let mut cache = Vec::new();
+
+    // small factors are matched exactly, by the last stage alone
+    for factor in 1..=MAX_STAGE_DECIMATION {
+        assert_eq!(precise_decimation_plan(factor), (Vec::new(), factor));
+    }
+    // 17 is a prime no stage can hit, the lower one of 16 and 18 is used
+    assert_eq!(precise_decimation_plan(17), (Vec::new(), 16));
+
+    let mut worst = 0.0f64;
+    for factor in (128..20_000).step_by(7) {
+        let (_, achieved) = MultistageFir::<f32>::new_multistage_decim_precise(
+            factor,
+            WindowKind::BlackmanHaris,
+            &mut cache,
+            0.001,
+            0.1,
+        );
+        worst = worst.max((achieved as f64 / factor as f64 - 1.0).abs());
+    }
+    assert!(worst < 0.02, "worst relative error {}", worst);
+
+    // the stages are reused
+    assert!(cache.len() < MAX_STAGE_DECIMATION as usize);
+
+    assert_eq!(precise_decimation_plan(100), (vec![10], 10));
+    assert_eq!(precise_decimation_plan(3000), (vec![15, 10, 10], 2));
+    assert_eq!(precise_decimation(3000), 3000);
+    assert_eq!(precise_decimation(48), 48);
+    // 50 Bd and 45.45 Bd at 48 kSps, the factor follows the baudrate
+    assert_eq!(precise_decimation(60), 60);
+    assert_eq!(precise_decimation(66), 66);
+}
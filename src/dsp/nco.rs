@@ -0,0 +1,75 @@
+use std::f32::consts::PI;
+
+use rustfft::num_complex::Complex;
+
+// a numerically controlled oscillator which shifts the complex baseband down by its frequency
+// the phase is kept between calls so that the shifted signal stays continuous across buffers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Nco {
+    // radians
+    pub phase: f32,
+    // radians per sample
+    pub step: f32,
+}
+
+impl Nco {
+    pub fn new(freq: f32, samplerate: f32) -> Self {
+        let mut nco = Nco {
+            phase: 0.0,
+            step: 0.0,
+        };
+        nco.set_freq(freq, samplerate);
+        nco
+    }
+    // the phase is kept, only the rate it rotates at changes
+    pub fn set_freq(&mut self, freq: f32, samplerate: f32) {
+        self.step = if samplerate > 0.0 {
+            2.0 * PI * freq / samplerate
+        } else {
+            0.0
+        };
+    }
+    // multiplies every sample by e^(-j*phase), a signal at the oscillator frequency ends up at DC
+    pub fn mix(&mut self, buf: &mut [Complex<f32>]) {
+        for sample in buf {
+            *sample = *sample * Complex::from_polar(1.0, -self.phase);
+            // wrapped so that the precision doesn't degrade over a long stream, the step can be negative
+            self.phase = (self.phase + self.step).rem_euclid(2.0 * PI);
+        }
+    }
+}
+
+#[test]
+fn nco_moves_tone_to_dc() {
+    let samplerate = 48000.0;
+    let tone = |freq: f32, len: usize| -> Vec<Complex<f32>> {
+        (0..len)
+            .map(|i| Complex::from_polar(1.0, 2.0 * PI * freq * i as f32 / samplerate))
+            .collect()
+    };
+
+    for &freq in &[1000.0, -2500.0, 12345.0] {
+        let mut samples = tone(freq, 10_000);
+
+        // mixed in uneven chunks, the phase has to carry over between them
+        let mut nco = Nco::new(freq, samplerate);
+        for chunk in samples.chunks_mut(777) {
+            nco.mix(chunk);
+        }
+
+        // a tone at DC doesn't rotate anymore, it stays where the first sample was
+        for sample in &samples {
+            assert!(
+                (sample - samples[0]).norm() < 1e-2,
+                "{} Hz: {}",
+                freq,
+                sample
+            );
+        }
+    }
+
+    // no frequency leaves the samples untouched
+    let mut samples = tone(300.0, 100);
+    Nco::new(0.0, samplerate).mix(&mut samples);
+    assert_eq!(samples, tone(300.0, 100));
+}
@@ -0,0 +1,59 @@
+// changes the samplerate of a stream by interpolating linearly between neighbouring samples
+// there is no filtering so it is only meant for rates close to each other, like 48 kSps audio played at 44.1 kSps
+pub struct LinearResampler {
+    // input samples per output sample
+    step: f64,
+    // where the next output sample is, 0 is the last sample of the previous call and 1 the first new one
+    position: f64,
+    last: f32,
+}
+
+impl LinearResampler {
+    pub fn new(input_samplerate: f64, output_samplerate: f64) -> Self {
+        Self {
+            step: input_samplerate / output_samplerate,
+            position: 0.0,
+            last: 0.0,
+        }
+    }
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        if input.is_empty() {
+            return;
+        }
+
+        let last = self.last;
+        let sample = |i: usize| if i == 0 { last } else { input[i - 1] };
+
+        while self.position < input.len() as f64 {
+            let i = self.position as usize;
+            let t = (self.position - i as f64) as f32;
+            output.push(sample(i) + (sample(i + 1) - sample(i)) * t);
+
+            self.position += self.step;
+        }
+
+        self.position -= input.len() as f64;
+        self.last = input[input.len() - 1];
+    }
+}
+
+#[test]
+fn resampled_length_and_shape() {
+    let input: Vec<f32> = (0..48000).map(|i| i as f32 / 48000.0).collect();
+
+    let mut resampler = LinearResampler::new(48000.0, 44100.0);
+    let mut output = Vec::new();
+    // uneven chunks like the bursts of audio from the worker
+    for chunk in input.chunks(1234) {
+        resampler.process(chunk, &mut output);
+    }
+
+    // a second of audio stays a second long
+    assert!((output.len() as i64 - 44100).abs() <= 1, "{}", output.len());
+
+    // a ramp stays a ramp, the output lags by the one sample kept from the previous call
+    for (i, &x) in output.iter().enumerate().skip(1) {
+        let expected = (i as f64 * 48000.0 / 44100.0 - 1.0) / 48000.0;
+        assert!((x as f64 - expected).abs() < 1e-5, "{} {}", i, x);
+    }
+}
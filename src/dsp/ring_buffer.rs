@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+// a bounded fifo of samples which bridges the stream reads (always mtu samples) and the consumers which want windows of some other length
+// if the consumers don't keep up, the oldest samples are dropped so that the memory doesn't grow without limit
+pub struct RingBuffer<T: Copy> {
+    samples: VecDeque<T>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.drop_overflow();
+    }
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+    // the number of samples that were thrown away because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+    // all of the samples from the oldest one, the second slice continues the first one
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        self.samples.as_slices()
+    }
+    pub fn push(&mut self, samples: &[T]) {
+        self.samples.extend(samples.iter().copied());
+        self.drop_overflow();
+    }
+    // copies the oldest `dst.len()` samples into dst and then consumes `advance` of them
+    // returns false without touching anything if there aren't enough samples yet
+    pub fn read(&mut self, dst: &mut [T], advance: usize) -> bool {
+        assert!(advance <= dst.len(), "cannot advance past the read window");
+
+        if self.samples.len() < dst.len() {
+            return false;
+        }
+
+        let (front, back) = self.samples.as_slices();
+
+        if front.len() >= dst.len() {
+            dst.copy_from_slice(&front[..dst.len()]);
+        } else {
+            let (dst_front, dst_back) = dst.split_at_mut(front.len());
+            dst_front.copy_from_slice(front);
+            dst_back.copy_from_slice(&back[..dst_back.len()]);
+        }
+
+        self.samples.drain(..advance);
+
+        true
+    }
+    fn drop_overflow(&mut self) {
+        if self.samples.len() > self.capacity {
+            let overflow = self.samples.len() - self.capacity;
+            self.samples.drain(..overflow);
+            self.dropped += overflow as u64;
+        }
+    }
+}
+
+// how far the read position moves for a window of `len` samples so that consecutive windows overlap by the `overlap` fraction
+pub fn overlap_advance(len: usize, overlap: f32) -> usize {
+    let overlap = overlap.max(0.0).min(1.0);
+    // always advance at least one sample, otherwise the same window would be returned forever
+    ((len as f32 * (1.0 - overlap)).round() as usize)
+        .max(1)
+        .min(len)
+}
+
+#[test]
+fn contiguous_windows() {
+    let mut ring = RingBuffer::new(4096);
+    let mut window = [0u32; 100];
+
+    let mut next_pushed = 0;
+    let mut next_expected = 0;
+
+    // the "mtu" is deliberately not a multiple of the window length so that windows span reads
+    for _ in 0..50 {
+        let chunk = (next_pushed..(next_pushed + 77)).collect::<Vec<_>>();
+        ring.push(&chunk);
+        next_pushed += 77;
+
+        while ring.read(&mut window, 100) {
+            for &sample in &window {
+                assert_eq!(sample, next_expected);
+                next_expected += 1;
+            }
+        }
+    }
+
+    assert_eq!(ring.dropped(), 0);
+    // everything except the last partial window was read exactly once
+    assert_eq!(next_expected + ring.len() as u32, next_pushed);
+}
+
+#[test]
+fn overflow_drops_oldest() {
+    let mut ring = RingBuffer::new(10);
+    ring.push(&(0..15).collect::<Vec<u32>>());
+
+    assert_eq!(ring.len(), 10);
+    assert_eq!(ring.dropped(), 5);
+
+    let mut window = [0u32; 10];
+    assert!(ring.read(&mut window, 10));
+    assert_eq!(window, [5, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+
+    assert!(!ring.read(&mut window, 10));
+}
+
+#[test]
+fn overlapping_windows() {
+    assert_eq!(overlap_advance(512, 0.0), 512);
+    assert_eq!(overlap_advance(512, 0.5), 256);
+    assert_eq!(overlap_advance(512, 0.75), 128);
+    assert_eq!(overlap_advance(512, 1.0), 1);
+    assert_eq!(overlap_advance(512, -1.0), 512);
+
+    let mut ring = RingBuffer::new(64);
+    ring.push(&(0..16).collect::<Vec<u32>>());
+
+    let mut first = [0u32; 8];
+    let mut second = [0u32; 8];
+    let advance = overlap_advance(8, 0.75);
+
+    assert!(ring.read(&mut first, advance));
+    assert!(ring.read(&mut second, advance));
+
+    // the second window starts 2 samples later and shares the other 6 with the first one
+    assert_eq!(first, [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(second, [2, 3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(first[advance..], second[..(8 - advance)]);
+}
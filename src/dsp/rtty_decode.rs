@@ -1,6 +1,23 @@
-use num_traits::{Float, Num, One};
+use num_traits::{Float, Num};
 use rustfft::num_complex::Complex;
 
+// the baudot shift state a character was decoded in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shift {
+    Letters,
+    Figures,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodedChar {
+    pub ch: char,
+    pub shift: Shift,
+    // where the start bit was found, counted from the first of the bits left over from the previous call
+    pub sample_index: usize,
+    // part of the stop bit had the wrong value, the character may be garbage
+    pub suspect: bool,
+}
+
 pub unsafe fn decode<T: Num + Float + Copy>(
     // the  two pointers can alias yadi yadi yada
     samples: *const Complex<T>,
@@ -11,7 +28,11 @@ pub unsafe fn decode<T: Num + Float + Copy>(
     baudrate: f32,
     samplerate: f32,
     letters: &mut bool,
-) -> (String, *const bool, usize)
+    // the last sample of the previous call so that the first bit isn't garbage
+    prev: &mut Complex<T>,
+    // the demodulated angle of every new sample is appended here
+    trace: &mut Vec<f32>,
+) -> (Vec<DecodedChar>, *const bool, usize)
 where
     Complex<T>: Num,
 {
@@ -31,13 +52,13 @@ where
 
     // return noutput_items;
 
-    let mut prev = Complex::<T>::one();
     let new_bits = bits.add(bits_offset);
 
     for i in 0..samples_len {
         let cur = *samples.add(i);
 
         let angle = (prev.conj() * cur).arg();
+        trace.push(angle.to_f32().unwrap_or(0.0));
 
         *new_bits.add(i) = if angle.is_sign_positive() {
             true
@@ -45,7 +66,7 @@ where
             false
         };
 
-        prev = cur;
+        *prev = cur;
     }
 
     let samples_per_symbol_f = samplerate / baudrate;
@@ -54,10 +75,11 @@ where
     let half_samples_per_symbol = (samples_per_symbol_f / 2.0) as usize;
     let samples_per_stop_bit = (samples_per_symbol_f * stop_bits) as usize;
 
-    let mut string = String::new();
+    let mut chars = Vec::new();
 
     let mut cursor = bits;
-    let bits_end = bits.add(samples_len);
+    // the bits left over from the previous call are in front of the new ones
+    let bits_end = new_bits.add(samples_len);
 
     // the loop may exit when
     //  - bits run out while looking for a start of the char -> this is fine because calling this next time will resume exactly there
@@ -80,6 +102,8 @@ where
             cursor = cursor.add(1);
         }
 
+        let start = cursor;
+
         let mut stopbit = cursor.add(6 * samples_per_symbol);
         if stopbit.add(samples_per_stop_bit) > bits_end {
             break 'decode_chars;
@@ -87,13 +111,16 @@ where
 
         // 1 xxxxx 0..
         // ^ - found rising edge, now look at the stop bit if it really is zero, otherwise try again
-        let mut stopbit_wrong = false;
+        let mut stopbit_errors = 0;
         for _ in 0..samples_per_stop_bit {
-            stopbit_wrong |= *stopbit == true;
+            if *stopbit == true {
+                stopbit_errors += 1;
+            }
             stopbit = stopbit.add(1);
         }
 
-        if stopbit_wrong {
+        // a few wrong samples are likely just noise, the character is kept but marked as suspect
+        if stopbit_errors * 2 > samples_per_stop_bit {
             cursor = cursor.add(6 * samples_per_symbol + samples_per_stop_bit);
             continue 'decode_chars;
         }
@@ -135,28 +162,43 @@ where
             cursor = cursor.add(samples_per_symbol);
         }
 
+        // continue after the stop bit, otherwise a wrong sample in it would be taken for the next start bit
+        cursor = stopbit;
+
         // TODO put this ontop the bit memory and then put it into a string all at once
         // since size_of bool == size_of u8 == 1 on any platforms that I care about
-        if let Some(char) = decode_baudot(char, letters) {
-            string.push(char);
+        if let Some(ch) = decode_baudot(char, letters) {
+            chars.push(DecodedChar {
+                ch,
+                shift: if *letters {
+                    Shift::Letters
+                } else {
+                    Shift::Figures
+                },
+                sample_index: start.offset_from(bits) as usize,
+                suspect: stopbit_errors > 0,
+            });
         }
     }
 
-    (string, cursor, bits_end.offset_from(cursor) as usize)
+    (chars, cursor, bits_end.offset_from(cursor) as usize)
 }
 
-fn decode_baudot(bits: u8, letters: &mut bool) -> Option<char> {
-    const ITA2: (&'static [u8], &'static [u8]) = (
-        b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0",
-        b"\03\n- \x0787\r\x054',!:(5\")2#6019?&\0./;\0",
-    );
+// the (letters, figures) characters of every 5 bit code, 0 marks the codes without one
+const ITA2: (&'static [u8], &'static [u8]) = (
+    b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0",
+    b"\03\n- \x0787\r\x054',!:(5\")2#6019?&\0./;\0",
+);
+const LETTERS_CODE: u8 = 0b11111;
+const FIGURES_CODE: u8 = 0b11011;
 
+fn decode_baudot(bits: u8, letters: &mut bool) -> Option<char> {
     match (bits, *letters) {
-        (0b11111u8, _) => {
+        (LETTERS_CODE, _) => {
             *letters = true;
             None
         }
-        (0b11011u8, _) => {
+        (FIGURES_CODE, _) => {
             *letters = false;
             None
         }
@@ -164,3 +206,209 @@ fn decode_baudot(bits: u8, letters: &mut bool) -> Option<char> {
         (figure, false) => Some(ITA2.1[figure as usize] as char),
     }
 }
+
+// the 5 bit codes of the text starting with a letters shift, the shifts are inserted only where the text changes
+// between letters and figures, lowercase is sent as uppercase and characters baudot doesn't have are skipped
+pub fn encode_baudot(text: &str) -> Vec<u8> {
+    let find = |table: &[u8], c: u8| table.iter().position(|&t| t == c && c != 0);
+
+    let mut codes = vec![LETTERS_CODE];
+    let mut letters = true;
+
+    for c in text.chars().filter(char::is_ascii) {
+        let c = c.to_ascii_uppercase() as u8;
+
+        let code = match (find(ITA2.0, c), find(ITA2.1, c)) {
+            // space and the line breaks are in both
+            (Some(code), Some(_)) => code,
+            (Some(code), None) => {
+                if !letters {
+                    codes.push(LETTERS_CODE);
+                    letters = true;
+                }
+                code
+            }
+            (None, Some(code)) => {
+                if letters {
+                    codes.push(FIGURES_CODE);
+                    letters = false;
+                }
+                code
+            }
+            (None, None) => continue,
+        };
+
+        codes.push(code as u8);
+    }
+
+    codes
+}
+
+// modulates the (code, wrong stop bit samples) frames at 100 Bd and 1000 Sps so that a bit is 10 samples long,
+// every frame is 80 samples long and the first one starts at sample 20
+#[cfg(test)]
+fn baudot_samples(frames: &[(u8, usize)]) -> Vec<Complex<f32>> {
+    use crate::generator::SignalGenerator;
+
+    // the phase turns by 0.1 every sample, forward for the space tone which comes out of the demodulation as true
+    let shift = 100.0 / std::f64::consts::PI;
+
+    // (mark, length in bits)
+    let mut bits = vec![(true, 2.0)];
+    for &(code, errors) in frames {
+        bits.push((false, 1.0));
+        bits.extend((0..5).map(|i| ((code >> i) & 1 == 1, 1.0)));
+        bits.push((false, errors as f64 / 10.0));
+        bits.push((true, (20 - errors) as f64 / 10.0));
+    }
+    bits.push((true, 2.0));
+
+    SignalGenerator::new_fsk(1000.0, &bits, 100.0, shift, 0.0).samples()
+}
+
+#[cfg(test)]
+fn decode_samples(samples: &[Complex<f32>], letters: &mut bool) -> Vec<DecodedChar> {
+    let mut memory = vec![false; samples.len()];
+    // the phase baudot_samples starts from
+    let mut prev = Complex::new(1.0, 0.0);
+
+    let (chars, _, _) = unsafe {
+        decode(
+            samples.as_ptr(),
+            samples.len(),
+            memory.as_mut_ptr(),
+            0,
+            1.5,
+            100.0,
+            1000.0,
+            letters,
+            &mut prev,
+            &mut Vec::new(),
+        )
+    };
+
+    chars
+}
+
+#[test]
+fn decoded_char_metadata() {
+    let samples = baudot_samples(&[
+        (10, 0),
+        (21, 0),
+        (0b11011, 0),
+        (1, 3),
+        (0b11111, 0),
+        (10, 9),
+    ]);
+
+    let mut letters = true;
+    let chars = decode_samples(&samples, &mut letters);
+
+    let char = |ch, shift, sample_index, suspect| DecodedChar {
+        ch,
+        shift,
+        sample_index,
+        suspect,
+    };
+    assert_eq!(
+        chars,
+        vec![
+            char('R', Shift::Letters, 20, false),
+            char('Y', Shift::Letters, 100, false),
+            // a few wrong samples in the stop bit
+            char('3', Shift::Figures, 260, true),
+            // the last character has most of its stop bit wrong and is dropped
+        ]
+    );
+    assert!(letters);
+}
+
+#[test]
+fn sample_indices_and_stop_bits() {
+    // every third character has a slightly damaged stop bit
+    let frames: Vec<(u8, usize)> = (0..30)
+        .map(|i| {
+            (
+                [10, 21, 1, 3][i % 4],
+                if i % 3 == 2 { 1 + i % 7 } else { 0 },
+            )
+        })
+        .collect();
+
+    let chars = decode_samples(&baudot_samples(&frames), &mut true);
+    assert_eq!(chars.len(), frames.len());
+
+    for pair in chars.windows(2) {
+        assert!(pair[0].sample_index < pair[1].sample_index);
+    }
+    for (i, (c, &(_, errors))) in chars.iter().zip(frames.iter()).enumerate() {
+        assert_eq!(c.sample_index, 20 + 80 * i);
+        assert_eq!(c.suspect, errors > 0);
+    }
+}
+
+#[test]
+fn decode_across_calls() {
+    let frames: Vec<(u8, usize)> = [10, 21, 1, 3, 10, 21].iter().map(|&c| (c, 0)).collect();
+    let samples = baudot_samples(&frames);
+    let whole: Vec<char> = decode_samples(&samples, &mut true)
+        .iter()
+        .map(|c| c.ch)
+        .collect();
+    assert_eq!(whole.len(), frames.len());
+
+    // the samples arrive in chunks which split the characters, like the buffers of the device stream
+    for &chunk in &[7, 45, 130, 333] {
+        let mut memory = vec![false; samples.len() + chunk];
+        let mut prev = Complex::new(1.0, 0.0);
+        let mut letters = true;
+        let mut leftover = 0;
+        let mut chars = Vec::new();
+
+        for samples in samples.chunks(chunk) {
+            let (decoded, rest, rest_len) = unsafe {
+                decode(
+                    samples.as_ptr(),
+                    samples.len(),
+                    memory.as_mut_ptr(),
+                    leftover,
+                    1.5,
+                    100.0,
+                    1000.0,
+                    &mut letters,
+                    &mut prev,
+                    &mut Vec::new(),
+                )
+            };
+            chars.extend(decoded.iter().map(|c| c.ch));
+
+            // the bits of an unfinished character go in front of the next ones
+            let rest = unsafe { std::slice::from_raw_parts(rest, rest_len) }.to_vec();
+            memory[..rest_len].copy_from_slice(&rest);
+            leftover = rest_len;
+        }
+
+        assert_eq!(chars, whole, "chunks of {}", chunk);
+    }
+}
+
+#[test]
+fn baudot_encoding_round_trip() {
+    let codes = encode_baudot("cq de RT-1 ~ 73 k\r\n");
+    assert_eq!(&codes[..3], &[LETTERS_CODE, 14, 23]);
+
+    let mut letters = false;
+    let text: String = codes
+        .iter()
+        .filter_map(|&code| decode_baudot(code, &mut letters))
+        .collect();
+    // the tilde doesn't exist in baudot
+    assert_eq!(text, "CQ DE RT-1  73 K\r\n");
+
+    // one shift to figures and back, the spaces around the numbers don't need any
+    let shifts = codes
+        .iter()
+        .filter(|&&c| c == LETTERS_CODE || c == FIGURES_CODE)
+        .count();
+    assert_eq!(shifts, 3);
+}
@@ -1,19 +1,51 @@
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum WindowKind {
+    Rectangular,
     BlackmanHaris,
 }
 
+pub const WINDOW_KINDS: &[WindowKind] = &[WindowKind::Rectangular, WindowKind::BlackmanHaris];
+
 impl WindowKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowKind::Rectangular => "Rectangular",
+            WindowKind::BlackmanHaris => "BlackmanHaris",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        WINDOW_KINDS.iter().copied().find(|w| w.name() == name)
+    }
     pub fn max_attenuation(&self) -> f64 {
         match *self {
+            WindowKind::Rectangular => 21.0,
             WindowKind::BlackmanHaris => 92.0,
         }
     }
     pub fn coefficients(&self, buf: &mut [f32]) {
         match *self {
+            WindowKind::Rectangular => buf.iter_mut().for_each(|c| *c = 1.0),
             WindowKind::BlackmanHaris => blackman_haris(buf),
         }
     }
+    // the mean of the window, this is how much the amplitude of a tone in the middle of a bin is scaled
+    pub fn coherent_gain(&self, len: usize) -> f64 {
+        let (sum, _) = self.sums(len);
+        sum / len as f64
+    }
+    // equivalent noise bandwidth in bins, the width of a rectangular filter which lets through the same noise power
+    pub fn enbw(&self, len: usize) -> f64 {
+        let (sum, sum_squares) = self.sums(len);
+        len as f64 * sum_squares / (sum * sum)
+    }
+    fn sums(&self, len: usize) -> (f64, f64) {
+        let mut buf = vec![0f32; len];
+        self.coefficients(&mut buf);
+
+        buf.iter().fold((0.0, 0.0), |(sum, sum_squares), &c| {
+            (sum + c as f64, sum_squares + c as f64 * c as f64)
+        })
+    }
 }
 
 use std::f32::consts::PI;
@@ -37,3 +69,15 @@ fn cos(buf: &mut [f32], c0: f32, c1: f32, c2: f32, c3: f32) {
 fn blackman_haris(buf: &mut [f32]) {
     cos(buf, 0.35874, 0.48829, 0.14128, 0.01168);
 }
+
+#[test]
+fn window_bandwidth() {
+    let rectangular = WindowKind::Rectangular;
+    assert!((rectangular.coherent_gain(1024) - 1.0).abs() < 1e-9);
+    assert!((rectangular.enbw(1024) - 1.0).abs() < 1e-9);
+
+    // the published values for the 4-term Blackman-Harris window
+    let blackman_harris = WindowKind::BlackmanHaris;
+    assert!((blackman_harris.coherent_gain(4096) - 0.35875).abs() < 1e-3);
+    assert!((blackman_harris.enbw(4096) - 2.0044).abs() < 1e-2);
+}
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+
+use crate::{settings::Field, units};
+
+// a frequency saved to be tuned to again with one click, kept as [frequency, samplerate, "mode"] in the settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct Favorite {
+    // MHz and MSps like the frequency and samplerate in AppSettings
+    pub frequency: f64,
+    pub samplerate: f64,
+    // the name of the decoding mode like AppSettings::decoder
+    pub mode: String,
+}
+
+// favorites closer than this are at the same frequency, it's below the 1 Hz resolution of the frequency spinbox
+const SAME_FREQUENCY_MHZ: f64 = 0.5e-6;
+
+impl Favorite {
+    pub fn label(&self) -> String {
+        format!("{} MHz", self.frequency)
+    }
+    pub fn description(&self) -> String {
+        format!(
+            "{} MHz, {} MSps, {}",
+            self.frequency, self.samplerate, self.mode
+        )
+    }
+    fn is_at(&self, frequency: f64) -> bool {
+        (self.frequency - frequency).abs() < SAME_FREQUENCY_MHZ
+    }
+}
+
+impl From<Favorite> for Field {
+    fn from(favorite: Favorite) -> Self {
+        Field::List(vec![
+            favorite.frequency.into(),
+            favorite.samplerate.into(),
+            favorite.mode.into(),
+        ])
+    }
+}
+
+impl TryFrom<Field> for Favorite {
+    type Error = ();
+
+    fn try_from(field: Field) -> Result<Favorite, Self::Error> {
+        let mut items = Vec::<Field>::try_from(field)?.into_iter();
+
+        let favorite = Favorite {
+            frequency: f64::try_from(items.next().ok_or(())?.in_unit(units::to_mega))?,
+            samplerate: f64::try_from(items.next().ok_or(())?.in_unit(units::to_mega))?,
+            mode: String::try_from(items.next().ok_or(())?)?,
+        };
+
+        match items.next() {
+            Some(_) => Err(()),
+            None => Ok(favorite),
+        }
+    }
+}
+
+// the list is kept ordered by frequency so the buttons are too, a favorite at the same frequency is replaced
+pub fn add_favorite(favorites: &mut Vec<Favorite>, favorite: Favorite) {
+    favorites.retain(|f| !f.is_at(favorite.frequency));
+
+    let index = favorites
+        .iter()
+        .position(|f| f.frequency > favorite.frequency)
+        .unwrap_or(favorites.len());
+    favorites.insert(index, favorite);
+}
+
+// None if there isn't a favorite at the frequency
+pub fn remove_favorite(favorites: &mut Vec<Favorite>, frequency: f64) -> Option<Favorite> {
+    let index = favorites.iter().position(|f| f.is_at(frequency))?;
+    Some(favorites.remove(index))
+}
+
+// the favorite to tune to, None if it was removed in the meantime
+pub fn recall_favorite(favorites: &[Favorite], frequency: f64) -> Option<&Favorite> {
+    favorites.iter().find(|f| f.is_at(frequency))
+}
+
+#[test]
+fn add_remove_recall() {
+    let favorite = |frequency: f64, mode: &str| Favorite {
+        frequency,
+        samplerate: 2.048,
+        mode: mode.to_owned(),
+    };
+
+    let mut favorites = Vec::new();
+    add_favorite(&mut favorites, favorite(434.5, "Baudot"));
+    add_favorite(&mut favorites, favorite(144.8, "None"));
+    add_favorite(&mut favorites, favorite(433.92, "Baudot"));
+
+    let frequencies: Vec<f64> = favorites.iter().map(|f| f.frequency).collect();
+    assert_eq!(frequencies, [144.8, 433.92, 434.5]);
+
+    // adding the same frequency again updates it
+    add_favorite(&mut favorites, favorite(433.920_000_1, "WBFM"));
+    assert_eq!(favorites.len(), 3);
+    assert_eq!(favorites[1].mode, "WBFM");
+
+    assert_eq!(recall_favorite(&favorites, 144.8), Some(&favorites[0]));
+    assert_eq!(recall_favorite(&favorites, 144.801), None);
+
+    assert_eq!(
+        remove_favorite(&mut favorites, 434.5).unwrap().mode,
+        "Baudot"
+    );
+    assert_eq!(remove_favorite(&mut favorites, 434.5), None);
+    assert_eq!(favorites.len(), 2);
+    assert_eq!(recall_favorite(&favorites, 434.5), None);
+}
+
+#[test]
+fn favorite_fields() {
+    let favorite = Favorite {
+        frequency: 433.92,
+        samplerate: 2.4,
+        mode: "Baudot".to_owned(),
+    };
+
+    let field = Field::from(favorite.clone());
+    assert_eq!(field.to_string(), "[433.92, 2.4, \"Baudot\"]");
+    assert_eq!(Favorite::try_from(field), Ok(favorite));
+
+    let list = |items: Vec<Field>| Field::List(items);
+    // missing, extra or mistyped items
+    assert!(Favorite::try_from(list(vec![433.92.into(), 2.4.into()])).is_err());
+    assert!(Favorite::try_from(list(vec![
+        433.92.into(),
+        2.4.into(),
+        "Baudot".to_owned().into(),
+        1.0.into()
+    ]))
+    .is_err());
+    assert!(Favorite::try_from(list(vec![
+        "Baudot".to_owned().into(),
+        2.4.into(),
+        433.92.into()
+    ]))
+    .is_err());
+    assert!(Favorite::try_from(Field::Number(433.92)).is_err());
+}
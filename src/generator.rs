@@ -0,0 +1,189 @@
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex;
+
+use crate::{dsp::rtty_decode::encode_baudot, iq_source::SampleSource, worker::worker::RxFormat};
+
+// how long the generator idles in mark before and after the text so that the filters settle, in bits
+const IDLE_BITS: f64 = 20.0;
+// any nonzero seed works for xorshift, a fixed one makes the noise the same on every run
+const NOISE_SEED: u64 = 0x2545F4914F6CDD1D;
+
+// xorshift white noise, uniformly distributed in -1..1 with a variance of 1/3
+pub struct Noise {
+    state: u64,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self { state: NOISE_SEED }
+    }
+}
+
+impl Noise {
+    pub fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+    }
+    pub fn complex(&mut self) -> Complex<RxFormat> {
+        Complex::new(self.uniform(), self.uniform())
+    }
+}
+
+// a synthetic signal for testing the receive chain without any hardware or recordings
+// the tones are phase continuous, the samples run out at the end of the signal like at the end of a recording
+pub struct SignalGenerator {
+    samplerate: f64,
+    amplitude: f64,
+    // (frequency, index of the sample after the last one at that frequency)
+    segments: Vec<(f64, usize)>,
+    segment: usize,
+    position: usize,
+    phase: f64,
+    // (the amplitude of the noise in each of I and Q)
+    noise: Option<(f64, Noise)>,
+}
+
+impl SignalGenerator {
+    // the text as baudot FSK centered at the offset with the space tone above the mark tone, which is what
+    // the decoder expects, every character has a start bit, 5 data bits least significant first and the stop bits
+    pub fn new_baudot(
+        samplerate: f64,
+        text: &str,
+        baudrate: f64,
+        stop_bits: f64,
+        shift: f64,
+        offset: f64,
+    ) -> Self {
+        // (mark, length in bits)
+        let mut bits = vec![(true, IDLE_BITS)];
+        for code in encode_baudot(text) {
+            bits.push((false, 1.0));
+            bits.extend((0..5).map(|i| ((code >> i) & 1 == 1, 1.0)));
+            bits.push((true, stop_bits));
+        }
+        bits.push((true, IDLE_BITS));
+
+        Self::new_fsk(samplerate, &bits, baudrate, shift, offset)
+    }
+    // any (mark, length in bits) sequence with the same tones as new_baudot, for bits no encoder would produce
+    pub fn new_fsk(
+        samplerate: f64,
+        bits: &[(bool, f64)],
+        baudrate: f64,
+        shift: f64,
+        offset: f64,
+    ) -> Self {
+        // the ends are rounded from the total length so that fractional stop bits don't add up to a drift
+        let samples_per_bit = samplerate / baudrate;
+        let mut length = 0.0;
+        let segments = bits
+            .iter()
+            .map(|&(mark, bits)| {
+                length += bits;
+                let frequency = if mark { -shift / 2.0 } else { shift / 2.0 };
+                (
+                    offset + frequency,
+                    (length * samples_per_bit).round() as usize,
+                )
+            })
+            .collect();
+
+        Self::new(samplerate, segments)
+    }
+    // a single tone at the offset from the center
+    pub fn new_tone(samplerate: f64, offset: f64, sample_count: usize) -> Self {
+        Self::new(samplerate, vec![(offset, sample_count)])
+    }
+    fn new(samplerate: f64, segments: Vec<(f64, usize)>) -> Self {
+        Self {
+            samplerate,
+            // leaves some headroom like a real receiver would
+            amplitude: 0.5,
+            segments,
+            segment: 0,
+            position: 0,
+            phase: 0.0,
+            noise: None,
+        }
+    }
+    pub fn amplitude(mut self, amplitude: f64) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+    // white noise is added to every sample, the same noise for every generator
+    pub fn noise(mut self, amplitude: f64) -> Self {
+        self.noise = Some((amplitude, Noise::default()));
+        self
+    }
+    pub fn sample_count(&self) -> usize {
+        self.segments.last().map_or(0, |&(_, end)| end)
+    }
+    // the rest of the signal at once
+    pub fn samples(&mut self) -> Vec<Complex<RxFormat>> {
+        let mut samples = vec![Complex::new(0.0, 0.0); self.sample_count() - self.position];
+        let count = self.read_samples(&mut samples).unwrap();
+        samples.truncate(count);
+
+        samples
+    }
+}
+
+impl SampleSource for SignalGenerator {
+    fn read_samples(&mut self, dst: &mut [Complex<RxFormat>]) -> std::io::Result<usize> {
+        let mut count = 0;
+
+        while count < dst.len() {
+            let (frequency, end) = match self.segments.get(self.segment) {
+                Some(&segment) => segment,
+                None => break,
+            };
+            if self.position >= end {
+                self.segment += 1;
+                continue;
+            }
+
+            self.phase = (self.phase + 2.0 * PI * frequency / self.samplerate) % (2.0 * PI);
+            dst[count] = Complex::new(
+                (self.amplitude * self.phase.cos()) as RxFormat,
+                (self.amplitude * self.phase.sin()) as RxFormat,
+            );
+            if let Some((amplitude, noise)) = &mut self.noise {
+                dst[count] += noise.complex() * *amplitude as RxFormat;
+            }
+
+            self.position += 1;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[test]
+fn tone_with_noise() {
+    let clean = SignalGenerator::new_tone(48000.0, 1000.0, 480).samples();
+    assert_eq!(clean.len(), 480);
+    // 48 samples per period, the phase advances before the first sample
+    assert!((clean[47] - Complex::new(0.5, 0.0)).norm() < 1e-6);
+
+    let mut generator = SignalGenerator::new_tone(48000.0, 1000.0, 480)
+        .amplitude(0.25)
+        .noise(0.01);
+    let noisy = generator.samples();
+    assert!(generator.samples().is_empty());
+
+    for (clean, noisy) in clean.iter().zip(&noisy) {
+        let noise = *noisy - *clean * 0.5;
+        assert!(noise.re.abs() < 0.0101 && noise.im.abs() < 0.0101);
+    }
+
+    // the same noise every time
+    let again = SignalGenerator::new_tone(48000.0, 1000.0, 480)
+        .amplitude(0.25)
+        .noise(0.01)
+        .samples();
+    assert_eq!(noisy, again);
+}
@@ -3,22 +3,28 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::app_settings::AppSettings;
-use crate::decoder::Decoder;
+use crate::decoder::{frequency_error_ppm, Decoder, DecoderParams, MeasurementAverage};
+use crate::units;
 use crate::worker::worker::{DeviceBoundCommand, GuiBoundEvent};
 use crate::worker::worker_manager::DeviceManager;
 
-use qt_charts::qt_core::{SlotNoArgs, SlotOfInt};
+use qt_charts::qt_core::{SlotNoArgs, SlotOfBool, SlotOfDouble, SlotOfInt};
 use qt_widgets::{
     cpp_core::Ptr,
     q_form_layout::FieldGrowthPolicy,
     qt_core::{qs, QBox},
-    QComboBox, QDoubleSpinBox, QFormLayout, QFrame, QGroupBox, QSpinBox,
+    QAbstractSpinBox, QCheckBox, QComboBox, QDoubleSpinBox, QFormLayout, QFrame, QGroupBox, QLabel,
+    QSpinBox,
 };
 use qt_widgets::{QPushButton, QVBoxLayout, QWidget};
 
-use super::handle_send_result;
+use super::{handle_send_result, send_change, ApplyMode, Change};
 
-const MODES: &[&str] = &["None", "Baudot"];
+const MODES: &[&str] = &["None", "Baudot", "WBFM", "Tone"];
+// the de-emphasis time constants used by FM broadcasting, in us
+const DEEMPHASIS_US: &[u32] = &[50, 75];
+// how many of the last tone measurements are averaged by default
+const DEFAULT_MEASUREMENT_AVERAGE: usize = 20;
 
 enum ModeConfig {
     None,
@@ -28,6 +34,26 @@ enum ModeConfig {
         baudrate: QBox<QDoubleSpinBox>,
         stop_bits: QBox<QDoubleSpinBox>,
         freq_shift: QBox<QDoubleSpinBox>,
+        cutoff: QBox<QDoubleSpinBox>,
+        transition_width: QBox<QDoubleSpinBox>,
+        // 0 leaves the decimation to the decoder
+        decimation: QBox<QSpinBox>,
+        // the decimation the worker achieved, it differs from the forced one if that one couldn't be used
+        achieved: QBox<QLabel>,
+        freq_offset: QBox<QDoubleSpinBox>,
+    },
+    Wbfm {
+        form: QBox<QFormLayout>,
+        deemphasis: QBox<QComboBox>,
+        freq_offset: QBox<QDoubleSpinBox>,
+    },
+    Tone {
+        form: QBox<QFormLayout>,
+        averaging: QBox<QSpinBox>,
+        // the known frequency of the measured tone in MHz, the error of the device is shown relative to it
+        reference: QBox<QDoubleSpinBox>,
+        readout: QBox<QLabel>,
+        average: RefCell<MeasurementAverage>,
     },
 }
 
@@ -49,24 +75,144 @@ impl ModeConfig {
                 let baudrate = QDoubleSpinBox::new_0a();
                 baudrate.set_suffix(&qs(" Bd"));
                 baudrate.set_range(0.0, 1000.0);
-                // settings.baudratebaudrate.set_value()
+                baudrate.set_value(settings.baudrate as f64);
                 form.add_row_q_string_q_widget(&qs("Baudrate"), &baudrate);
 
                 let stop_bits = QDoubleSpinBox::new_0a();
                 stop_bits.set_suffix(&qs(" Bits"));
+                stop_bits.set_value(settings.stop_bits as f64);
                 form.add_row_q_string_q_widget(&qs("Stop bits"), &stop_bits);
 
                 let freq_shift = QDoubleSpinBox::new_0a();
                 freq_shift.set_suffix(&qs(" Hz"));
                 freq_shift.set_range(0.0, 1000.0);
+                freq_shift.set_value(settings.freq_shift as f64);
                 form.add_row_q_string_q_widget(&qs("Frequency shift"), &freq_shift);
 
+                let cutoff = QDoubleSpinBox::new_0a();
+                cutoff.set_suffix(&qs(" Hz"));
+                cutoff.set_range(0.0, 100_000.0);
+                cutoff.set_single_step(10.0);
+                cutoff.set_special_value_text(&qs("Follow shift"));
+                cutoff.set_value(settings.cutoff_hz as f64);
+                cutoff.set_tool_tip(&qs(
+                    "How far from the signal the low-pass filter reaches, selecting a band in the spectrum sets it to half of its width",
+                ));
+                form.add_row_q_string_q_widget(&qs("Filter cutoff"), &cutoff);
+
+                let transition_width = QDoubleSpinBox::new_0a();
+                transition_width.set_decimals(3);
+                transition_width.set_range(0.005, 0.5);
+                transition_width.set_single_step(0.01);
+                transition_width.set_value(settings.transition_width as f64);
+                transition_width.set_tool_tip(&qs(
+                    "Transition width of the low-pass filter relative to its samplerate, a narrower one separates small shifts better but needs more CPU",
+                ));
+                form.add_row_q_string_q_widget(&qs("Transition width"), &transition_width);
+
+                let decimation = QSpinBox::new_0a();
+                decimation.set_range(0, 4096);
+                decimation.set_special_value_text(&qs("Automatic"));
+                decimation.set_value(settings.decimation as i32);
+                decimation.set_tool_tip(&qs(
+                    "Decimation of the low-pass filter, a lower one needs more CPU but keeps the aliases further from the signal. One the filter stages can't hit exactly or which leaves less than 16 samples per bit is replaced by the automatic one",
+                ));
+                form.add_row_q_string_q_widget(&qs("Decimation"), &decimation);
+
+                let achieved = QLabel::new();
+                form.add_row_q_string_q_widget(&qs("Achieved decimation"), &achieved);
+
+                let freq_offset = QDoubleSpinBox::new_0a();
+                freq_offset.set_suffix(&qs(" Hz"));
+                freq_offset.set_range(-100_000.0, 100_000.0);
+                freq_offset.set_single_step(10.0);
+                freq_offset.set_value(settings.freq_offset as f64);
+                freq_offset.set_tool_tip(&qs(
+                    "Where the signal is relative to the receiver frequency, it is shifted to the center before decoding without retuning the device",
+                ));
+                form.add_row_q_string_q_widget(&qs("Frequency offset"), &freq_offset);
+
                 let s = Self::Baudot {
                     form,
                     // frame,
                     baudrate,
                     stop_bits,
                     freq_shift,
+                    cutoff,
+                    transition_width,
+                    decimation,
+                    achieved,
+                    freq_offset,
+                };
+
+                (s, widget)
+            }
+            2 => {
+                let widget = QWidget::new_0a();
+                let form = QFormLayout::new_0a();
+                widget.set_layout(&form);
+
+                let deemphasis = QComboBox::new_0a();
+                for us in DEEMPHASIS_US {
+                    deemphasis.add_item_q_string(&qs(format!("{} µs", us)));
+                }
+                deemphasis.set_current_index(deemphasis_index(settings.deemphasis_us) as i32);
+                deemphasis.set_tool_tip(&qs(
+                    "50 µs in most of the world, 75 µs in the Americas and South Korea",
+                ));
+                form.add_row_q_string_q_widget(&qs("De-emphasis"), &deemphasis);
+
+                let freq_offset = QDoubleSpinBox::new_0a();
+                freq_offset.set_suffix(&qs(" Hz"));
+                freq_offset.set_range(-10_000_000.0, 10_000_000.0);
+                freq_offset.set_single_step(1000.0);
+                freq_offset.set_value(settings.freq_offset as f64);
+                freq_offset.set_tool_tip(&qs(
+                    "Where the station is relative to the receiver frequency, it is shifted to the center before demodulating",
+                ));
+                form.add_row_q_string_q_widget(&qs("Frequency offset"), &freq_offset);
+
+                let s = Self::Wbfm {
+                    form,
+                    deemphasis,
+                    freq_offset,
+                };
+
+                (s, widget)
+            }
+            3 => {
+                let widget = QWidget::new_0a();
+                let form = QFormLayout::new_0a();
+                widget.set_layout(&form);
+
+                let averaging = QSpinBox::new_0a();
+                averaging.set_suffix(&qs(" measurements"));
+                averaging.set_range(1, 1000);
+                averaging.set_value(DEFAULT_MEASUREMENT_AVERAGE as i32);
+                averaging.set_tool_tip(&qs(
+                    "How many of the last measurements are averaged, one is made every buffer",
+                ));
+                form.add_row_q_string_q_widget(&qs("Average"), &averaging);
+
+                let reference = QDoubleSpinBox::new_0a();
+                reference.set_suffix(&qs(" MHz"));
+                reference.set_decimals(6);
+                reference.set_range(0.0, 10_000.0);
+                reference.set_special_value_text(&qs("None"));
+                reference.set_tool_tip(&qs(
+                    "The known frequency of the measured signal, like a beacon or a broadcast carrier, to show the error of the device in ppm",
+                ));
+                form.add_row_q_string_q_widget(&qs("Reference"), &reference);
+
+                let readout = QLabel::new();
+                form.add_row_q_string_q_widget(&qs("Measured"), &readout);
+
+                let s = Self::Tone {
+                    form,
+                    averaging,
+                    reference,
+                    readout,
+                    average: RefCell::new(MeasurementAverage::new(DEFAULT_MEASUREMENT_AVERAGE)),
                 };
 
                 (s, widget)
@@ -81,16 +227,188 @@ impl ModeConfig {
                 baudrate,
                 stop_bits,
                 freq_shift,
+                cutoff,
+                transition_width,
+                decimation,
+                freq_offset,
                 ..
             } => Some(Decoder::new_baudot(
                 baudrate.value() as f32,
                 stop_bits.value() as f32,
                 freq_shift.value() as f32,
+                cutoff.value() as f32,
+                transition_width.value() as f32,
+                forced_decim(decimation.value()),
+                freq_offset.value() as f32,
+            )),
+            ModeConfig::Wbfm {
+                deemphasis,
+                freq_offset,
+                ..
+            } => Some(Decoder::new_wbfm(
+                DEEMPHASIS_US[deemphasis.current_index() as usize] as f32,
+                freq_offset.value() as f32,
             )),
+            ModeConfig::Tone { .. } => Some(Decoder::new_tone_measure()),
         }
     }
-    fn populate_settings(&self, settings: &mut AppSettings) {
-
+    unsafe fn get_params(&self) -> Option<DecoderParams> {
+        match self {
+            ModeConfig::None => None,
+            ModeConfig::Baudot {
+                baudrate,
+                stop_bits,
+                freq_shift,
+                cutoff,
+                transition_width,
+                decimation,
+                freq_offset,
+                ..
+            } => Some(DecoderParams::Baudot {
+                baudrate: baudrate.value() as f32,
+                stop_bits: stop_bits.value() as f32,
+                shift: freq_shift.value() as f32,
+                cutoff_hz: cutoff.value() as f32,
+                transition_width: transition_width.value() as f32,
+                forced_decim: forced_decim(decimation.value()),
+                freq_offset_hz: freq_offset.value() as f32,
+            }),
+            ModeConfig::Wbfm {
+                deemphasis,
+                freq_offset,
+                ..
+            } => Some(DecoderParams::Wbfm {
+                deemphasis_us: DEEMPHASIS_US[deemphasis.current_index() as usize] as f32,
+                freq_offset_hz: freq_offset.value() as f32,
+            }),
+            ModeConfig::Tone { .. } => None,
+        }
+    }
+    // the spinboxes whose changes can be sent to a running decoder
+    unsafe fn tunable_spinboxes(&self) -> Vec<Ptr<QAbstractSpinBox>> {
+        match self {
+            ModeConfig::None => Vec::new(),
+            ModeConfig::Baudot {
+                baudrate,
+                stop_bits,
+                freq_shift,
+                cutoff,
+                transition_width,
+                decimation,
+                freq_offset,
+                ..
+            } => vec![
+                baudrate.static_upcast(),
+                stop_bits.static_upcast(),
+                freq_shift.static_upcast(),
+                cutoff.static_upcast(),
+                transition_width.static_upcast(),
+                decimation.static_upcast(),
+                freq_offset.static_upcast(),
+            ],
+            ModeConfig::Wbfm { freq_offset, .. } => vec![freq_offset.static_upcast()],
+            ModeConfig::Tone { .. } => Vec::new(),
+        }
+    }
+    // the offset applies to every mode, only the Baudot filter can be narrowed to the width of the channel
+    unsafe fn select_channel(&self, offset_hz: f64, width_hz: f64) {
+        match self {
+            ModeConfig::None => {}
+            ModeConfig::Baudot {
+                cutoff,
+                freq_offset,
+                ..
+            } => {
+                freq_offset.set_value(offset_hz);
+                cutoff.set_value(width_hz / 2.0);
+            }
+            ModeConfig::Wbfm { freq_offset, .. } => freq_offset.set_value(offset_hz),
+            // the strongest tone of the whole band is measured
+            ModeConfig::Tone { .. } => {}
+        }
+    }
+    unsafe fn populate_settings(&self, settings: &mut AppSettings) {
+        match self {
+            ModeConfig::None => {}
+            ModeConfig::Baudot {
+                baudrate,
+                stop_bits,
+                freq_shift,
+                cutoff,
+                transition_width,
+                decimation,
+                freq_offset,
+                ..
+            } => {
+                settings.baudrate = baudrate.value() as f32;
+                settings.stop_bits = stop_bits.value() as f32;
+                settings.freq_shift = freq_shift.value() as f32;
+                settings.cutoff_hz = cutoff.value() as f32;
+                settings.transition_width = transition_width.value() as f32;
+                settings.decimation = decimation.value() as u32;
+                settings.freq_offset = freq_offset.value() as f32;
+            }
+            ModeConfig::Wbfm {
+                deemphasis,
+                freq_offset,
+                ..
+            } => {
+                settings.deemphasis_us = DEEMPHASIS_US[deemphasis.current_index() as usize];
+                settings.freq_offset = freq_offset.value() as f32;
+            }
+            ModeConfig::Tone { .. } => {}
+        }
+    }
+    // adds the measurement to the running average of the tone mode, the other modes don't measure anything
+    unsafe fn show_measurement(&self, freq_hz: f64) {
+        if let ModeConfig::Tone {
+            averaging,
+            reference,
+            readout,
+            average,
+            ..
+        } = self
+        {
+            let mut average = average.borrow_mut();
+            average.set_len(averaging.value() as usize);
+            let mean = average.push(freq_hz);
+
+            let mut text = format!(
+                "{:.1} Hz ± {:.1} Hz ({} measurements)",
+                mean,
+                average.std_dev(),
+                average.count()
+            );
+            if reference.value() > 0.0 {
+                let error = frequency_error_ppm(mean, units::from_mega(reference.value()));
+                text += &format!("\n{:+.2} ppm", error);
+            }
+            readout.set_text(&qs(text));
+        }
+    }
+    // None clears it, the resulting samplerate is shown too if the receiver is known
+    unsafe fn show_decimation(&self, decimation: Option<u32>, samplerate: Option<f64>) {
+        if let ModeConfig::Baudot { achieved, .. } = self {
+            match (decimation, samplerate) {
+                (Some(decimation), Some(samplerate)) => achieved.set_text(&qs(format!(
+                    "{} ({:.0} Sps)",
+                    decimation,
+                    samplerate / decimation as f64
+                ))),
+                (Some(decimation), None) => achieved.set_text(&qs(decimation.to_string())),
+                (None, _) => achieved.clear(),
+            }
+        }
+    }
+    // the measurements of a previous device or receiver configuration would skew the average
+    unsafe fn clear_measurements(&self) {
+        if let ModeConfig::Tone {
+            readout, average, ..
+        } = self
+        {
+            average.borrow_mut().clear();
+            readout.clear();
+        }
     }
 }
 
@@ -103,15 +421,24 @@ pub struct DecodeGroup {
     mode_widget: RefCell<QBox<QWidget>>,
     mode_config: RefCell<ModeConfig>,
     apply_btn: QBox<QPushButton>,
+    squelch: QBox<QDoubleSpinBox>,
+    level: QBox<QLabel>,
+    raw_bits: QBox<QCheckBox>,
+    // why the decoder was refused or stopped, hidden again once one is configured
+    warning: QBox<QLabel>,
 
     device: Rc<DeviceManager>,
-    settings: Rc<AppSettings>,
+    // replaced when the settings are applied from the outside, like when restoring the defaults
+    settings: RefCell<Rc<AppSettings>>,
+    // with the automatic mode the mode is sent when it is picked and the parameters as they are edited
+    apply_mode: Rc<ApplyMode>,
 }
 
 impl DecodeGroup {
     pub unsafe fn new(
         device: Rc<DeviceManager>,
         settings: Rc<AppSettings>,
+        apply_mode: Rc<ApplyMode>,
     ) -> (Rc<Self>, Ptr<QGroupBox>) {
         let group = QGroupBox::new();
         group.set_title(&qs("Decode"));
@@ -130,12 +457,36 @@ impl DecodeGroup {
 
         form.add_row_q_string_q_widget(&qs("Mode"), &mode_select);
 
-        let index = MODES.iter().position(|name| *name == settings.decoder.as_str()).unwrap_or(0);
+        let squelch = QDoubleSpinBox::new_0a();
+        squelch.set_suffix(&qs(" dB"));
+        squelch.set_range(0.0, 100.0);
+        squelch.set_special_value_text(&qs("Off"));
+        squelch.set_value(settings.squelch_threshold as f64);
+        form.add_row_q_string_q_widget(&qs("Squelch"), &squelch);
+
+        let level = QLabel::new();
+        form.add_row_q_string_q_widget(&qs("SNR"), &level);
+
+        let raw_bits = QCheckBox::new();
+        raw_bits.set_checked(settings.raw_bits);
+        raw_bits.set_tool_tip(&qs(
+            "Log the bits sliced from the demodulated signal at debug level, to tell apart demodulation and framing errors",
+        ));
+        form.add_row_q_string_q_widget(&qs("Log raw bits"), &raw_bits);
+
+        let index = mode_index(&settings.decoder);
+        // this is done before the signals are connected in init() so the mode isn't constructed twice
+        mode_select.set_current_index(index as i32);
 
-        let (mode_config, mode_widget) = ModeConfig::new_from_index(index);
+        let (mode_config, mode_widget) = ModeConfig::new_from_index(index, &settings);
 
         v_layout.add_widget(&mode_widget);
 
+        let warning = QLabel::new();
+        warning.set_word_wrap(true);
+        warning.set_visible(false);
+        v_layout.add_widget(&warning);
+
         let apply = QPushButton::from_q_string(&qs("Apply"));
 
         v_layout.add_widget(&apply);
@@ -144,12 +495,17 @@ impl DecodeGroup {
         let s = Rc::new(Self {
             group,
             device,
-            settings,
+            settings: RefCell::new(settings),
+            apply_mode,
             v_layout,
             mode_select,
             mode_config: RefCell::new(mode_config),
             mode_widget: RefCell::new(mode_widget),
             apply_btn: apply,
+            squelch,
+            level,
+            raw_bits,
+            warning,
         });
         
         s.apply_btn.set_enabled(false);
@@ -162,15 +518,51 @@ impl DecodeGroup {
         match event.as_ref().unwrap() {
             GuiBoundEvent::DeviceCreated { .. } => {
                 if let Some(decoder) = self.mode_config.borrow().get_decoder() {
-                    let command = DeviceBoundCommand::SetDecoder { decoder };
-
-                    handle_send_result(self.device.send_command(command));
-
+                    self.send_decoder(decoder);
                 }
+                self.send_squelch(Change::Applied);
+                self.send_raw_bits(Change::Applied);
                 self.apply_btn.set_enabled(true);
             }
             GuiBoundEvent::DeviceDestroyed | GuiBoundEvent::WorkerReset => {
                 self.apply_btn.set_enabled(false);
+                self.level.clear();
+                self.mode_config.borrow().clear_measurements();
+                self.mode_config.borrow().show_decimation(None, None);
+            }
+            GuiBoundEvent::ReceiverConfigured { .. } => {
+                self.mode_config.borrow().clear_measurements();
+            }
+            GuiBoundEvent::DecoderConfigured { decimation } => {
+                self.show_warning(None);
+                let samplerate = self
+                    .device
+                    .get_receiver_state()
+                    .map(|state| state.samplerate);
+                self.mode_config
+                    .borrow()
+                    .show_decimation(Some(*decimation), samplerate);
+            }
+            GuiBoundEvent::Measurement { freq_hz } => {
+                self.mode_config.borrow().show_measurement(*freq_hz);
+            }
+            GuiBoundEvent::SignalLevel { snr, squelched } => {
+                let text = if *squelched {
+                    format!("{:.1} dB (squelched)", snr)
+                } else {
+                    format!("{:.1} dB", snr)
+                };
+                self.level.set_text(&qs(text));
+            }
+            GuiBoundEvent::RawBits { bits } => {
+                let bits: String = bits.iter().map(|&b| if b { '1' } else { '0' }).collect();
+                log::debug!("Raw bits: {}", bits);
+            }
+            GuiBoundEvent::DecoderFailed { kind } => {
+                self.show_warning(Some(&format!("The decoder was stopped: {}", kind)));
+                self.mode_config.borrow().show_decimation(None, None);
+                // the worker dropped the decoder, let the user fix the configuration and apply it again
+                self.apply_btn.set_enabled(self.device.get_device_valid());
             }
             _ => {}
         };
@@ -180,30 +572,230 @@ impl DecodeGroup {
             group,
             apply_btn: apply,
             mode_select,
+            squelch,
+            raw_bits,
             ..
         } = &*self.borrow();
 
         let s = self.clone();
         mode_select
             .current_index_changed()
-            .connect(&SlotOfInt::new(group, move |i| {
-                let (mode_config, mode_widget) = ModeConfig::new_from_index(i as usize);
-                s.v_layout
-                    .replace_widget_2a(&*s.mode_widget.borrow(), &mode_widget);
-                s.mode_widget.replace(mode_widget);
-                s.mode_config.replace(mode_config);
-            }));
+            .connect(&SlotOfInt::new(group, move |i| s.set_mode(i as usize)));
 
         let s = self.clone();
         apply.clicked().connect(&SlotNoArgs::new(group, move || {
-            if let Some(decoder) = s.mode_config.borrow().get_decoder() {
-                let command = DeviceBoundCommand::SetDecoder { decoder };
-
-                handle_send_result(s.device.send_command(command));
+            match s.mode_config.borrow().get_decoder() {
+                Some(decoder) => s.send_decoder(decoder),
+                None => handle_send_result(s.device.send_command(DeviceBoundCommand::ClearDecoder)),
             }
+            s.send_squelch(Change::Applied);
+            s.send_raw_bits(Change::Applied);
         }));
+
+        self.connect_params_edited();
+
+        let s = self.clone();
+        squelch
+            .value_changed()
+            .connect(&SlotOfDouble::new(group, move |_| {
+                s.send_squelch(Change::Edited)
+            }));
+
+        let s = self.clone();
+        raw_bits
+            .toggled()
+            .connect(&SlotOfBool::new(group, move |_| {
+                s.send_raw_bits(Change::Edited)
+            }));
+    }
+    unsafe fn set_mode(self: &Rc<Self>, index: usize) {
+        self.rebuild_mode(index);
+
+        // the None mode has nothing to apply, stop the running decoder right away
+        if let ModeConfig::None = &*self.mode_config.borrow() {
+            if self.device.get_decoder_valid() {
+                handle_send_result(self.device.send_command(DeviceBoundCommand::ClearDecoder));
+            }
+            return;
+        }
+
+        if self.apply_mode.sends(Change::Edited) && self.device.get_device_valid() {
+            if let Some(decoder) = self.mode_config.borrow().get_decoder() {
+                self.send_decoder(decoder);
+            }
+        }
+    }
+    // replaces the mode widget with a new one loaded from the current settings
+    unsafe fn rebuild_mode(self: &Rc<Self>, index: usize) {
+        let (mode_config, mode_widget) = ModeConfig::new_from_index(index, &self.settings.borrow());
+        self.v_layout
+            .replace_widget_2a(&*self.mode_widget.borrow(), &mode_widget);
+        self.mode_widget.replace(mode_widget);
+        self.mode_config.replace(mode_config);
+        self.connect_params_edited();
+    }
+    // the slots are owned by the mode widget so they are dropped together with the spinboxes when the mode changes
+    unsafe fn connect_params_edited(self: &Rc<Self>) {
+        let mode_widget = self.mode_widget.borrow();
+
+        for spinbox in self.mode_config.borrow().tunable_spinboxes() {
+            let s = self.clone();
+            spinbox
+                .editing_finished()
+                .connect(&SlotNoArgs::new(&*mode_widget, move || {
+                    if !(s.apply_mode.sends(Change::Edited) && s.device.get_decoder_valid()) {
+                        return;
+                    }
+
+                    if let Some(params) = s.mode_config.borrow().get_params() {
+                        let command = DeviceBoundCommand::UpdateDecoderParams { params };
+                        handle_send_result(s.device.send_command(command));
+                    }
+                }));
+        }
+    }
+    // a decoder which can't work at the samplerate of the receiver isn't sent, the worker would only refuse it
+    // the receiver may not be configured yet, then the worker checks it once it is
+    unsafe fn send_decoder(&self, decoder: Decoder) {
+        let samplerate = self
+            .device
+            .get_receiver_state()
+            .map(|state| state.samplerate);
+
+        if let Some(Err(e)) = samplerate.map(|samplerate| decoder.check_samplerate(samplerate)) {
+            self.show_warning(Some(&format!("The decoder was not started: {}", e)));
+            return;
+        }
+
+        handle_send_result(
+            self.device
+                .send_command(DeviceBoundCommand::SetDecoder { decoder }),
+        );
+    }
+    unsafe fn show_warning(&self, text: Option<&str>) {
+        match text {
+            Some(text) => {
+                log::warn!("{}", text);
+                self.warning.set_text(&qs(text));
+                self.warning.set_visible(true);
+            }
+            None => {
+                self.warning.clear();
+                self.warning.set_visible(false);
+            }
+        }
+    }
+    unsafe fn send_squelch(&self, change: Change) {
+        let command = DeviceBoundCommand::SetSquelch {
+            threshold: self.squelch.value() as f32,
+        };
+
+        send_change(&self.device, &self.apply_mode, change, command);
+    }
+    unsafe fn send_raw_bits(&self, change: Change) {
+        let command = DeviceBoundCommand::SetRawBits {
+            enabled: self.raw_bits.is_checked(),
+        };
+
+        send_change(&self.device, &self.apply_mode, change, command);
+    }
+    // decodes only the channel selected in the spectrum, the running decoder is updated without retuning the device
+    pub unsafe fn select_channel(&self, offset_hz: f64, width_hz: f64) {
+        let mode_config = self.mode_config.borrow();
+        mode_config.select_channel(offset_hz, width_hz);
+
+        if !self.device.get_decoder_valid() {
+            return;
+        }
+
+        if let Some(params) = mode_config.get_params() {
+            let command = DeviceBoundCommand::UpdateDecoderParams { params };
+            handle_send_result(self.device.send_command(command));
+        }
+    }
+    pub unsafe fn mode_name(&self) -> &'static str {
+        MODES[self.mode_select.current_index() as usize]
+    }
+    // like picking the mode in the combobox, the decoder is sent with Apply as usual
+    pub unsafe fn select_mode(&self, name: &str) {
+        self.mode_select.set_current_index(mode_index(name) as i32);
     }
     pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
+        settings.decoder = MODES[self.mode_select.current_index() as usize].to_owned();
+
+        // keep the loaded values so that they aren't lost when a mode without these parameters is selected
+        let loaded = self.settings.borrow();
+        settings.baudrate = loaded.baudrate;
+        settings.stop_bits = loaded.stop_bits;
+        settings.freq_shift = loaded.freq_shift;
+        settings.cutoff_hz = loaded.cutoff_hz;
+        settings.transition_width = loaded.transition_width;
+        settings.decimation = loaded.decimation;
+        settings.freq_offset = loaded.freq_offset;
+        settings.deemphasis_us = loaded.deemphasis_us;
+        settings.squelch_threshold = self.squelch.value() as f32;
+        settings.raw_bits = self.raw_bits.is_checked();
+
         self.mode_config.borrow().populate_settings(settings);
     }
+    // the signals are blocked so that nothing is sent to the device, the decoder is sent with Apply as usual
+    pub unsafe fn apply_settings(self: &Rc<Self>, settings: &AppSettings) {
+        self.settings.replace(Rc::new(settings.clone()));
+
+        // the mode is rebuilt even if the index stays the same so that the parameters are reloaded
+        let index = mode_index(&settings.decoder);
+        self.mode_select.block_signals(true);
+        self.mode_select.set_current_index(index as i32);
+        self.mode_select.block_signals(false);
+        self.rebuild_mode(index);
+
+        self.squelch.block_signals(true);
+        self.squelch.set_value(settings.squelch_threshold as f64);
+        self.squelch.block_signals(false);
+
+        self.raw_bits.block_signals(true);
+        self.raw_bits.set_checked(settings.raw_bits);
+        self.raw_bits.block_signals(false);
+    }
+}
+
+// an unknown decoder name selects None
+fn mode_index(name: &str) -> usize {
+    MODES.iter().position(|mode| *mode == name).unwrap_or(0)
+}
+
+// the special value of the spinbox leaves the decimation to the decoder
+fn forced_decim(value: i32) -> Option<u32> {
+    if value > 0 {
+        Some(value as u32)
+    } else {
+        None
+    }
+}
+
+// a time constant that isn't used anywhere selects the first one
+fn deemphasis_index(us: u32) -> usize {
+    DEEMPHASIS_US.iter().position(|&d| d == us).unwrap_or(0)
+}
+
+#[test]
+fn default_mode_round_trip() {
+    use crate::app_settings::DEFAULT_SETTINGS;
+
+    // the empty decoder of the defaults is saved as None, after that the name stays the same
+    let index = mode_index(&DEFAULT_SETTINGS.decoder);
+    assert_eq!(MODES[index], "None");
+    assert_eq!(mode_index(MODES[index]), index);
+
+    for (i, mode) in MODES.iter().enumerate() {
+        assert_eq!(mode_index(mode), i);
+    }
+    assert_eq!(mode_index("Morse"), 0);
+
+    assert_eq!(
+        DEEMPHASIS_US[deemphasis_index(DEFAULT_SETTINGS.deemphasis_us)],
+        DEFAULT_SETTINGS.deemphasis_us
+    );
+    assert_eq!(DEEMPHASIS_US[deemphasis_index(75)], 75);
+    assert_eq!(deemphasis_index(60), 0);
 }
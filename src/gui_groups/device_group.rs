@@ -1,16 +1,18 @@
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::rc::Rc;
 
 use qt_charts::qt_core::{SlotNoArgs, SlotOfBool};
 use qt_widgets::cpp_core::Ptr;
 use qt_widgets::q_layout::SizeConstraint;
-use qt_widgets::qt_core::{qs, QBox};
+use qt_widgets::qt_core::{qs, QBox, TextInteractionFlag};
 use qt_widgets::{
-    QCheckBox, QComboBox, QGroupBox, QHBoxLayout, QLineEdit, QPushButton, QVBoxLayout, QWidget,
+    QCheckBox, QComboBox, QGroupBox, QHBoxLayout, QLabel, QLineEdit, QPushButton, QVBoxLayout,
+    QWidget,
 };
 
 use crate::app_settings::AppSettings;
-use crate::worker::worker::{DeviceBoundCommand, GuiBoundEvent};
+use crate::worker::worker::{DeviceBoundCommand, GuiBoundEvent, LongCommand};
 use crate::worker::worker_manager::DeviceManager;
 
 use super::handle_send_result;
@@ -25,9 +27,14 @@ pub struct DeviceGroup {
     b1: QBox<QPushButton>,
     b2: QBox<QPushButton>,
     b3: QBox<QPushButton>,
+    // read-only driver/hardware info of the created device, useful when filing bug reports
+    details: QBox<QLabel>,
+    // the serial of the device created last, saved so that auto select finds the same device again
+    device_serial: RefCell<String>,
 
     device: Rc<DeviceManager>,
-    settings: Rc<AppSettings>,
+    // replaced when the settings are applied from the outside, like when restoring the defaults
+    settings: RefCell<Rc<AppSettings>>,
 }
 
 const DEVICES_REFRESH_INTERVAL_MS: u64 = 1000;
@@ -70,6 +77,11 @@ impl DeviceGroup {
         layout.add_widget(&combo_box);
         layout.add_widget(&row_widget);
 
+        let details = QLabel::new();
+        details.set_text_interaction_flags(TextInteractionFlag::TextSelectableByMouse.into());
+        details.set_visible(false);
+        layout.add_widget(&details);
+
         layout.set_size_constraint(SizeConstraint::SetFixedSize);
 
         let ptr = group.as_ptr();
@@ -87,9 +99,11 @@ impl DeviceGroup {
             b1,
             b2,
             b3,
+            details,
             auto_select,
+            device_serial: RefCell::new(settings.device_serial.clone()),
 
-            settings,
+            settings: RefCell::new(settings),
             device,
         });
 
@@ -167,15 +181,44 @@ impl DeviceGroup {
     }
     pub unsafe fn handle_event(&self, event: &mut Option<GuiBoundEvent>) {
         match event.as_ref().unwrap() {
+            GuiBoundEvent::DeviceCreated {
+                device_info,
+                channels_info,
+            } => {
+                let text = device_info.describe(channels_info);
+                self.details.set_text(&qs(text.trim_end()));
+                self.details.set_visible(true);
+
+                // a device without a serial keeps the previous one, it may come back
+                if !device_info.serial.is_empty() {
+                    self.device_serial.replace(device_info.serial.clone());
+                }
+            }
+            GuiBoundEvent::DeviceDestroyed => {
+                self.details.clear();
+                self.details.set_visible(false);
+            }
+            GuiBoundEvent::DeviceNotCreated { message } => {
+                self.details.set_text(&qs(format!(
+                    "The device could not be created:\n{}",
+                    message
+                )));
+                self.details.set_visible(true);
+            }
             GuiBoundEvent::WorkerReset => {
+                self.details.clear();
+                self.details.set_visible(false);
+
                 // self.combo_box.clear(); // it's not very ergonomic to make me click refresh every time the worker crashes
                 self.b2.set_enabled(false);
                 self.b3.set_enabled(false);
+                // the command the old worker was running won't be acknowledged
+                self.set_busy(None);
 
                 // force refresh the devices because the worker thread lost it's list of them
                 self.b1.click();
             }
-            GuiBoundEvent::RefreshedDevices { list } => {
+            GuiBoundEvent::RefreshedDevices { list, serials } => {
                 self.combo_box.clear();
 
                 for name in list {
@@ -189,41 +232,69 @@ impl DeviceGroup {
                 self.b2.set_enabled(!list.is_empty());
 
                 if self.auto_select.is_checked() {
-                    if list.is_empty() && !self.device.get_refreshing_devices() {
-                        let filter = self.filter.text().to_std_string();
-                        self.device.schedule_command(
-                            DeviceBoundCommand::RefreshDevices { args: filter },
-                            DEVICES_REFRESH_INTERVAL_MS,
-                        );
-                        return;
-                    }
-
-                    // try to find the exact device as was selected previously
-                    if !self.settings.device.is_empty() {
-                        if let Some((i, _)) = list
-                            .iter()
-                            .enumerate()
-                            .find(|(_, s)| **s == self.settings.device)
-                        {
-                            self.combo_box.set_current_index(i as i32);
+                    // try to find the exact device as was selected previously, the ones other sessions opened are skipped
+                    let taken = self.device.opened_elsewhere(list, serials);
+                    let index = auto_select_index(
+                        list,
+                        serials,
+                        &self.settings.borrow().device,
+                        &self.device_serial.borrow(),
+                        &taken,
+                    );
+
+                    match index {
+                        Some(index) => {
+                            self.combo_box.set_current_index(index as i32);
                             self.b2.click();
-                            return;
+                        }
+                        // nothing is listed or every device is in use, look again later
+                        None => {
+                            if !self.device.get_refreshing_devices() {
+                                let filter = self.filter.text().to_std_string();
+                                self.device.schedule_command(
+                                    DeviceBoundCommand::RefreshDevices { args: filter },
+                                    DEVICES_REFRESH_INTERVAL_MS,
+                                );
+                            }
                         }
                     }
-
-                    // just select the first device
-                    self.combo_box.set_current_index(0);
-                    self.b2.click();
+                }
+            }
+            GuiBoundEvent::CommandStarted { command } => self.set_busy(Some(*command)),
+            GuiBoundEvent::CommandAck { command } => {
+                self.set_busy(None);
+
+                if *command == LongCommand::CreateDevice {
+                    let valid = self.device.get_device_valid();
+                    self.b2.set_enabled(!valid && self.combo_box.count() != 0);
+                    self.b3.set_enabled(valid);
                 }
             }
             _ => (),
         };
     }
+    // the buttons which would send another command are disabled until the worker acknowledges the running one
+    unsafe fn set_busy(&self, command: Option<LongCommand>) {
+        let (refresh, start) = match command {
+            Some(LongCommand::RefreshDevices) => ("Refreshing...", "Start"),
+            Some(LongCommand::CreateDevice) => ("Refresh", "Starting..."),
+            None => ("Refresh", "Start"),
+        };
+        self.b1.set_text(&qs(refresh));
+        self.b2.set_text(&qs(start));
+
+        self.b1.set_enabled(command.is_none());
+        if command.is_some() {
+            self.b2.set_enabled(false);
+            self.b3.set_enabled(false);
+        }
+    }
     pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
         let AppSettings {
             auto_device: auto_select_device,
             device_filter,
             device,
+            device_serial,
             ..
         } = settings;
 
@@ -231,9 +302,109 @@ impl DeviceGroup {
 
         *device_filter = self.filter.text().to_std_string();
 
+        // without any devices listed keep the loaded one so that it is selected again once it is plugged in
         *device = match self.combo_box.count() {
-            0 => "".to_string(),
+            0 => self.settings.borrow().device.clone(),
             _ => self.combo_box.current_text().to_std_string(),
-        }
+        };
+
+        *device_serial = self.device_serial.borrow().clone();
+    }
+    // no commands are sent, the settings only take effect the next time the devices are refreshed
+    pub unsafe fn apply_settings(&self, settings: &AppSettings) {
+        self.settings.replace(Rc::new(settings.clone()));
+        self.device_serial.replace(settings.device_serial.clone());
+
+        self.filter.set_text(&qs(&settings.device_filter));
+
+        // set_checked doesn't emit clicked() so the widgets have to be updated here as well
+        self.auto_select.set_checked(settings.auto_device);
+        self.filter.set_enabled(!settings.auto_device);
+        self.combo_box.set_enabled(!settings.auto_device);
     }
 }
+
+// the device to create automatically, the one with the saved serial, then the one with the saved label, otherwise the first one
+// an empty serial or label never matches, plenty of devices don't have a serial
+// the `taken` devices are open in another session and are never selected, None if no device is left
+fn auto_select_index(
+    labels: &[String],
+    serials: &[String],
+    label: &str,
+    serial: &str,
+    taken: &[bool],
+) -> Option<usize> {
+    let free = |i: &usize| !taken.get(*i).copied().unwrap_or(false);
+
+    let by_serial = serials
+        .iter()
+        .position(|s| !serial.is_empty() && s == serial)
+        .filter(free);
+    let by_label = labels
+        .iter()
+        .position(|l| !label.is_empty() && l == label)
+        .filter(free);
+
+    by_serial
+        .or(by_label)
+        .or_else(|| (0..labels.len()).find(free))
+}
+
+#[test]
+fn auto_select_priority() {
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let labels = strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0002", "HackRF One"]);
+    let serials = strings(&["0001", "0002", ""]);
+
+    // the serial wins even if the label points elsewhere, the label could have been reassigned
+    assert_eq!(
+        auto_select_index(&labels, &serials, "RTL-SDR :: 0001", "0002", &[]),
+        Some(1)
+    );
+    // an unknown serial falls back to the label
+    assert_eq!(
+        auto_select_index(&labels, &serials, "HackRF One", "ffff", &[]),
+        Some(2)
+    );
+    assert_eq!(
+        auto_select_index(&labels, &serials, "HackRF One", "", &[]),
+        Some(2)
+    );
+    // nothing matches
+    assert_eq!(
+        auto_select_index(&labels, &serials, "Airspy", "ffff", &[]),
+        Some(0)
+    );
+    // the empty serial of the HackRF isn't matched by an empty saved one
+    assert_eq!(auto_select_index(&labels, &serials, "", "", &[]), Some(0));
+    assert_eq!(auto_select_index(&[], &[], "HackRF One", "0001", &[]), None);
+}
+
+#[test]
+fn auto_select_skips_taken_devices() {
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let labels = strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0002", "HackRF One"]);
+    let serials = strings(&["0001", "0002", ""]);
+
+    // another session has the saved device open, the label and then the first free one are tried
+    let taken = [false, true, false];
+    assert_eq!(
+        auto_select_index(&labels, &serials, "HackRF One", "0002", &taken),
+        Some(2)
+    );
+    assert_eq!(
+        auto_select_index(&labels, &serials, "", "0002", &taken),
+        Some(0)
+    );
+
+    // two sessions with the same settings don't pick the same first device
+    assert_eq!(
+        auto_select_index(&labels, &serials, "", "", &[true]),
+        Some(1)
+    );
+
+    assert_eq!(
+        auto_select_index(&labels, &serials, "", "0001", &[true, true, true]),
+        None
+    );
+}
@@ -1,28 +1,43 @@
+use std::cell::RefCell;
+use std::path::Path;
 use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::app_settings::AppSettings;
+use crate::decode_log::{log_decode, DecodeRecord, DecodeSource};
+use crate::gui_groups::{ApplyMode, Change};
+use crate::habhub::{parse_sentence, CsvLogger, LineBuffer};
 use crate::worker::worker::GuiBoundEvent;
 use crate::worker::worker_manager::DeviceManager;
 
 use qt_widgets::{
     cpp_core::Ptr,
     q_size_policy::Policy,
-    qt_core::{qs, QBox},
-    QCheckBox, QFormLayout, QGroupBox, QLineEdit,
+    qt_core::{qs, QBox, SlotNoArgs},
+    QCheckBox, QFormLayout, QGroupBox, QLineEdit, QPushButton,
 };
 
 #[allow(unused)]
 pub struct HabhubGroup {
     group: QBox<QGroupBox>,
+    telemetry_log: QBox<QLineEdit>,
+    apply_btn: QBox<QPushButton>,
+
+    // the decoded text of every session is split into lines by the name of its worker, every line is tried as a UKHAS sentence
+    lines: RefCell<Vec<(String, LineBuffer)>>,
+    // None if the logging is disabled or the file couldn't be opened
+    logger: RefCell<Option<CsvLogger>>,
+    // the path the logger was last opened with, the same file isn't reopened
+    logger_path: RefCell<Option<String>>,
 
-    device: Rc<DeviceManager>,
     settings: Rc<AppSettings>,
+    apply_mode: Rc<ApplyMode>,
 }
 
 impl HabhubGroup {
     pub unsafe fn new(
-        device: Rc<DeviceManager>,
         settings: Rc<AppSettings>,
+        apply_mode: Rc<ApplyMode>,
     ) -> (Rc<Self>, Ptr<QGroupBox>) {
         let group = QGroupBox::new();
         group.set_size_policy_2a(Policy::Fixed, Policy::Fixed);
@@ -39,20 +54,133 @@ impl HabhubGroup {
 
         form.add_row_q_string_q_widget(&qs("Habhub send"), &habhub_send);
 
+        let telemetry_log = QLineEdit::new();
+        telemetry_log.set_text(&qs(&settings.telemetry_log));
+        telemetry_log.set_placeholder_text(&qs("Disabled"));
+        telemetry_log.set_tool_tip(&qs(
+            "The CSV file the received position reports are appended to",
+        ));
+        form.add_row_q_string_q_widget(&qs("Telemetry log"), &telemetry_log);
+
+        let apply_btn = QPushButton::from_q_string(&qs("Apply"));
+        form.add_row_q_widget(&apply_btn);
+
         let ptr = group.as_ptr();
         let s = Rc::new(Self {
             group,
-            device,
+            telemetry_log,
+            apply_btn,
+            lines: RefCell::new(Vec::new()),
+            logger: RefCell::new(None),
+            logger_path: RefCell::new(None),
             settings,
+            apply_mode,
         });
 
+        s.init();
+        s.open_logger(Change::Applied);
+
         (s, ptr)
     }
-    unsafe fn init(self: &Rc<Self>) {}
-    pub unsafe fn handle_event(&self, event: &mut Option<GuiBoundEvent>) {
+    unsafe fn init(self: &Rc<Self>) {
+        let s = self.clone();
+        self.telemetry_log
+            .editing_finished()
+            .connect(&SlotNoArgs::new(&self.group, move || {
+                s.open_logger(Change::Edited);
+            }));
+
+        let s = self.clone();
+        self.apply_btn
+            .clicked()
+            .connect(&SlotNoArgs::new(&self.group, move || {
+                s.open_logger(Change::Applied);
+            }));
+    }
+    // the previous file is closed even if the new one fails to open
+    unsafe fn open_logger(&self, change: Change) {
+        let path = self.telemetry_log.text().to_std_string();
+
+        if !self.apply_mode.sends(change) || self.logger_path.borrow().as_ref() == Some(&path) {
+            return;
+        }
+
+        let logger = if path.is_empty() {
+            None
+        } else {
+            match CsvLogger::open(Path::new(&path)) {
+                Ok(logger) => {
+                    log::info!("Logging telemetry to '{}'", path);
+                    Some(logger)
+                }
+                Err(e) => {
+                    log::error!("Failed to open the telemetry log '{}': {}", path, e);
+                    None
+                }
+            }
+        };
+
+        // a file that failed to open is tried again on the next change
+        let opened = path.is_empty() || logger.is_some();
+        self.logger_path
+            .replace(if opened { Some(path) } else { None });
+        self.logger.replace(logger);
+    }
+    // every session feeds it the events of its `device`
+    pub unsafe fn handle_event(&self, device: &DeviceManager, event: &mut Option<GuiBoundEvent>) {
         match event.as_ref().unwrap() {
+            GuiBoundEvent::DecodedChars { data } => {
+                let text: String = data.iter().map(|c| c.ch).collect();
+
+                let name = device.get_name();
+                let lines = {
+                    let mut lines = self.lines.borrow_mut();
+                    let i = match lines.iter().position(|(worker, _)| *worker == name) {
+                        Some(i) => i,
+                        None => {
+                            lines.push((name, LineBuffer::default()));
+                            lines.len() - 1
+                        }
+                    };
+                    lines[i].1.push(&text)
+                };
+
+                for line in lines {
+                    let fix = match parse_sentence(&line) {
+                        Some(fix) => fix,
+                        None => continue,
+                    };
+
+                    if let Some(logger) = self.logger.borrow_mut().as_mut() {
+                        if let Err(e) = logger.log(&fix) {
+                            log::error!("Failed to write the telemetry log: {}", e);
+                        }
+                    }
+
+                    // only sentences which parsed get here, the checksum if any matched
+                    log_decode(&DecodeRecord {
+                        unix_time: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |time| time.as_secs()),
+                        level: log::Level::Info,
+                        source: DecodeSource::Telemetry,
+                        frequency: device
+                            .get_receiver_state()
+                            .map_or(0.0, |state| state.frequency),
+                        mode: "UKHAS",
+                        text: &fix.sentence,
+                    });
+                }
+            }
             _ => (),
         };
     }
-    pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {}
+    pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
+        settings.telemetry_log = self.telemetry_log.text().to_std_string();
+    }
+    pub unsafe fn apply_settings(&self, settings: &AppSettings) {
+        // set_text doesn't emit editing_finished, the file is reopened here
+        self.telemetry_log.set_text(&qs(&settings.telemetry_log));
+        self.open_logger(Change::Applied);
+    }
 }
@@ -3,8 +3,14 @@ pub mod device_group;
 pub mod habhub_group;
 pub mod output_group;
 pub mod receive_group;
+pub mod scan_group;
 
-use crate::worker::worker_manager::DeviceError;
+use std::cell::Cell;
+
+use crate::worker::{
+    worker::DeviceBoundCommand,
+    worker_manager::{DeviceError, DeviceManager},
+};
 
 // crash on BadState, ignore WorkerPoisoned because it will be handled in the next iteration
 // previously the code was just unwrapping the result which enabled a race condition when the worker thread has just closed
@@ -17,3 +23,131 @@ pub fn handle_send_result(result: Result<(), DeviceError>) {
         Err(DeviceError::WorkerPoisoned) | Ok(()) => {}
     }
 }
+
+// what made a group want to send its configuration to the device
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Change {
+    // a value was edited
+    Edited,
+    // the Apply button was pressed
+    Applied,
+}
+
+// whether the groups send their changes right away or only with Apply, one is shared by all the groups of all the devices
+// the groups still skip sending something that is the same as what the device already has
+pub struct ApplyMode {
+    automatic: Cell<bool>,
+}
+
+impl ApplyMode {
+    pub fn new(automatic: bool) -> Self {
+        Self {
+            automatic: Cell::new(automatic),
+        }
+    }
+    pub fn is_automatic(&self) -> bool {
+        self.automatic.get()
+    }
+    pub fn set_automatic(&self, automatic: bool) {
+        self.automatic.set(automatic);
+    }
+    pub fn sends(&self, change: Change) -> bool {
+        match change {
+            Change::Edited => self.automatic.get(),
+            Change::Applied => true,
+        }
+    }
+}
+
+// the command of a change is sent only if the mode lets it through, without a device there is nothing to send it to
+pub fn send_change(
+    device: &DeviceManager,
+    apply_mode: &ApplyMode,
+    change: Change,
+    command: DeviceBoundCommand,
+) {
+    if apply_mode.sends(change) && device.get_device_valid() {
+        handle_send_result(device.send_command(command));
+    }
+}
+
+// Qt only lets the widgets be used on the thread of the application, so the tests which need them are run one after
+// another on a thread which keeps the application for the whole test binary, the offscreen platform needs no display
+#[cfg(test)]
+pub(crate) fn run_on_gui_thread(test: impl FnOnce() + Send + 'static) {
+    use std::{panic, sync::Mutex, thread};
+
+    use crossbeam_channel::Sender;
+    use qt_widgets::QApplication;
+
+    type Test = (Box<dyn FnOnce() + Send>, Sender<thread::Result<()>>);
+    static GUI_THREAD: Mutex<Option<Sender<Test>>> = Mutex::new(None);
+
+    let sender = GUI_THREAD
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            let (sender, receiver) = crossbeam_channel::unbounded::<Test>();
+            thread::spawn(move || {
+                std::env::set_var("QT_QPA_PLATFORM", "offscreen");
+                QApplication::init(move |_| {
+                    for (test, result) in receiver {
+                        let _ = result.send(panic::catch_unwind(panic::AssertUnwindSafe(test)));
+                    }
+                    0
+                });
+            });
+            sender
+        })
+        .clone();
+
+    let (result_sender, result) = crossbeam_channel::bounded(1);
+    sender.send((Box::new(test), result_sender)).unwrap();
+    if let Err(panic) = result.recv().unwrap() {
+        panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+fn apply_mode_gates_edits() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let device = DeviceManager::new_named("Worker thread apply mode");
+    let squelch = |threshold| DeviceBoundCommand::SetSquelch { threshold };
+
+    // nothing is sent before there is a device, not even with Apply
+    let mode = ApplyMode::new(false);
+    send_change(&device, &mode, Change::Applied, squelch(1.0));
+
+    device
+        .send_command(DeviceBoundCommand::CreateDevice { index: 0 })
+        .unwrap();
+
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let s = sent.clone();
+    device.set_command_tap(Some(Box::new(move |tapped, command| {
+        s.borrow_mut().push(format!("{:?} {:?}", tapped, command));
+    })));
+
+    // the edits are held until Apply
+    send_change(&device, &mode, Change::Edited, squelch(2.0));
+    send_change(&device, &mode, Change::Edited, squelch(3.0));
+    assert!(sent.borrow().is_empty());
+    send_change(&device, &mode, Change::Applied, squelch(3.0));
+
+    // switching the mode doesn't send anything by itself, then every edit is sent right away
+    mode.set_automatic(true);
+    assert!(mode.is_automatic());
+    assert_eq!(sent.borrow().len(), 1);
+    send_change(&device, &mode, Change::Edited, squelch(4.0));
+    send_change(&device, &mode, Change::Applied, squelch(4.0));
+
+    assert_eq!(
+        *sent.borrow(),
+        [
+            "Sent SetSquelch { threshold: 3.0 }",
+            "Sent SetSquelch { threshold: 4.0 }",
+            "Sent SetSquelch { threshold: 4.0 }",
+        ]
+    );
+}
@@ -1,24 +1,42 @@
 use std::borrow::Borrow;
 use std::cell::{Cell, RefCell};
+use std::f64::consts::PI;
 use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{ops::Range, rc::Rc};
 
-use crate::gui_groups::handle_send_result;
-use crate::worker::worker::{DeviceBoundCommand, GuiBoundEvent};
+use crate::app_settings::{parse_fft_window, AppSettings};
+use crate::audio::AudioSink;
+use crate::band_plan::{load_band_plan, visible_bands, Band};
+use crate::decode_log::{log_decode, DecodeRecord, DecodeSource, DecodedLines};
+use crate::dsp::rtty_decode::{DecodedChar, Shift};
+use crate::dsp::window_functions::WindowKind;
+use crate::gui_groups::{decode_group::DecodeGroup, handle_send_result};
+use crate::iq_source::IqFormat;
+use crate::snapshot::SpectrumSnapshot;
+use crate::worker::worker::{DeviceBoundCommand, GuiBoundEvent, ProcessingTimes};
 use crate::worker::worker_manager::DeviceManager;
-use crate::{FftData, DATA_REQUESTS_IN_FLIGHT, SAMPLE_COUNT};
+use crate::{fft_shift, SpectrumData, MAX_DATA_REQUESTS_IN_FLIGHT, SAMPLE_COUNT};
 
 use qt_charts::{
-    qt_core::{AlignmentFlag, QVectorOfQPointF, SlotNoArgs},
-    qt_gui::{q_font_database::SystemFont, q_painter::RenderHint, QFontDatabase},
+    qt_core::{
+        AlignmentFlag, ContextMenuPolicy, GlobalColor, MouseButton, Orientation, PenStyle, QPointF,
+        QRectF, QTimer, QVectorOfQPointF, SlotNoArgs, SlotOfBool, SlotOfDouble, SlotOfInt,
+    },
+    qt_gui::{
+        q_font_database::SystemFont, q_painter::RenderHint, q_palette::ColorRole, QBrush, QColor,
+        QCursor, QFontDatabase, QGuiApplication, QPainter, QPen, QPixmap,
+    },
     QChart, QChartView, QLineSeries, QValueAxis,
 };
 use qt_widgets::{
-    cpp_core::Ptr,
+    cpp_core::{CppBox, Ptr},
     q_size_policy::Policy,
     q_style::StandardPixmap,
     qt_core::{qs, QBox},
-    QApplication, QGridLayout, QGroupBox, QPushButton, QTextEdit,
+    QAction, QApplication, QCheckBox, QComboBox, QDoubleSpinBox, QFileDialog, QGraphicsItem,
+    QGraphicsRectItem, QGraphicsSimpleTextItem, QGridLayout, QGroupBox, QLabel, QPushButton,
+    QSlider, QSpinBox, QTextEdit,
 };
 use rustfft::num_complex::Complex32;
 
@@ -27,6 +45,8 @@ struct SingleSeriesGraph {
     chart: QBox<QChart>,
     view: QBox<QChartView>,
     series: QBox<QLineSeries>,
+    // hidden unless the imaginary parts are plotted as well, it shares the axes with the first series
+    second_series: QBox<QLineSeries>,
 
     x_axis: QBox<QValueAxis>,
     y_axis: QBox<QValueAxis>,
@@ -38,6 +58,54 @@ struct SingleSeriesGraph {
 
 const REQUEST_DATA_INTERVAL_MS: u64 = 20;
 
+// of the 0..100 volume slider
+const DEFAULT_VOLUME: i32 = 50;
+
+// the resolution of the demodulated angle histogram
+const TUNING_BINS: usize = 64;
+
+// (label, overlap fraction) of consecutive spectrum windows
+const OVERLAPS: &[(&str, f32)] = &[
+    ("No overlap", 0.0),
+    ("50% overlap", 0.5),
+    ("75% overlap", 0.75),
+];
+
+// what of the complex samples the signal graph shows
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SignalDisplay {
+    Real,
+    RealImag,
+    Magnitude,
+    Phase,
+}
+
+const SIGNAL_DISPLAYS: &[(&str, SignalDisplay)] = &[
+    ("I", SignalDisplay::Real),
+    ("I and Q", SignalDisplay::RealImag),
+    ("Magnitude", SignalDisplay::Magnitude),
+    ("Phase", SignalDisplay::Phase),
+];
+
+impl SignalDisplay {
+    // the value of the first series and of the second one if it is shown
+    fn values(self, sample: Complex32) -> (f32, Option<f32>) {
+        match self {
+            SignalDisplay::Real => (sample.re, None),
+            SignalDisplay::RealImag => (sample.re, Some(sample.im)),
+            SignalDisplay::Magnitude => (sample.norm(), None),
+            SignalDisplay::Phase => (sample.arg(), None),
+        }
+    }
+    // the samples are rewritten so that update_series plots the values, the second one goes into the imaginary part
+    fn apply(self, samples: &mut [Complex32]) {
+        for sample in samples {
+            let (first, second) = self.values(*sample);
+            *sample = Complex32::new(first, second.unwrap_or(0.0));
+        }
+    }
+}
+
 impl SingleSeriesGraph {
     unsafe fn new(
         x: Range<f64>,
@@ -79,9 +147,15 @@ impl SingleSeriesGraph {
 
         let series = QLineSeries::new_0a();
         chart.add_series(&series);
-        // no x-axis is set and the series is empty so it seems that the series defaults to range 0..1
+        series.attach_axis(&x_axis);
         series.attach_axis(&y_axis);
 
+        let second_series = QLineSeries::new_0a();
+        chart.add_series(&second_series);
+        second_series.attach_axis(&x_axis);
+        second_series.attach_axis(&y_axis);
+        second_series.set_visible(false);
+
         if !y_axis_show_labels {
             y_axis.set_label_format(&qs(" "));
         }
@@ -104,6 +178,7 @@ impl SingleSeriesGraph {
             chart,
             view,
             series,
+            second_series,
 
             x_axis,
             y_axis,
@@ -114,15 +189,20 @@ impl SingleSeriesGraph {
         }
     }
 
+    pub unsafe fn set_second_series_visible(&self, visible: bool) {
+        self.second_series.set_visible(visible);
+    }
+
     // fill the QLineSeries in the graph with the entirety of y_samples
-    //  x is always scaled from 0..1
-    //  the imaginary part is discarded
+    //  x is spread evenly over x_range which also becomes the range of the x axis
+    //  the imaginary part is discarded unless the second series is visible, then it is plotted there
 
-    // the safety of this is dubious at best but should work
+    // the points are written through fill_points, see there for the memory layout it relies on
     #[rustfmt::skip]
     pub unsafe fn update_series(
         &self,
         y_samples: &[Complex32],
+        x_range: Range<f64>,
         fit_y: bool,
         y_symmetric: bool,
         smoothing_factor: f32,
@@ -137,9 +217,14 @@ impl SingleSeriesGraph {
         if fit_y {
             let mut min = 0.0f32;
             let mut max = 0.0f32;
+            let second = self.second_series.is_visible();
             for s in y_samples {
                 min = min.min(s.re);
                 max = max.max(s.re);
+                if second {
+                    min = min.min(s.im);
+                    max = max.max(s.im);
+                }
             }
 
             if y_symmetric {
@@ -180,60 +265,109 @@ impl SingleSeriesGraph {
             }
         }
 
-        // QVector, like most Qt containers, is implicitly shared which allows us to update all the data at once in this roundabout way
-        let vector = self.series.points_vector();
-        {
-            let empty = QVectorOfQPointF::new_0a();
-            // remove the shared reference held by the series, otherwise resize() and more importantly data() would reallocate even though the size is the same and the old data is discarded
-            // look at the beautiful code here https://code.woboq.org/qt5/include/qt/QtCore/qvector.h.html#_ZN7QVector4dataEv
-            self.series.replace_q_vector_of_q_point_f(&empty);
-        }
+        self.x_axis.set_range(x_range.start, x_range.end);
 
-        // qt reallocates vectors even thought the previous size is larger than the requested size
-        // (this has now been changed but not backported)
-        if y_samples.len() as i32 > vector.size() {
-            vector.resize(y_samples.len() as i32);
-        }
+        let d_x = (x_range.end - x_range.start) / (y_samples.len() as f64);
+        let x = move |i: usize| x_range.start + i as f64 * d_x;
 
-        // PointF source is here https://code.woboq.org/qt5/qtbase/src/corelib/tools/qpoint.h.html#QPointF::xp
-        // this is horrible hacking to be able to write the vector memory without calling qt functions which cannot be inlined
-        // due to cpp not having a stable abi, it is impossible to soundly bind field access so the field offsets are computed here
+        replace_points(&self.series, y_samples.iter().enumerate().map(|(i, c)| (x(i), c.re as f64)));
+        if self.second_series.is_visible() {
+            replace_points(&self.second_series, y_samples.iter().enumerate().map(|(i, c)| (x(i), c.im as f64)));
+        }
 
-        // with common sense, PointF should always have a stride of 16 bytes (2 doubles)
-        // but such speculation on memory layout is so horribly evil and not guaranteed
-        let pointf_stride = {
-            let ptr0 = vector.at(0).as_raw_ptr() as *const u8;
-            let ptr1 = vector.at(1).as_raw_ptr() as *const u8;
+        self.view.set_updates_enabled(true);
+    }
+}
 
-            ptr1.offset_from(ptr0)
-        };
+// replaces all points of the series at once
+unsafe fn replace_points(
+    series: &QBox<QLineSeries>,
+    points: impl ExactSizeIterator<Item = (f64, f64)>,
+) {
+    // QVector, like most Qt containers, is implicitly shared which allows us to update all the data at once in this roundabout way
+    let vector = series.points_vector();
+    {
+        let empty = QVectorOfQPointF::new_0a();
+        // remove the shared reference held by the series, otherwise resize() and more importantly data() would reallocate even though the size is the same and the old data is discarded
+        // look at the beautiful code here https://code.woboq.org/qt5/include/qt/QtCore/qvector.h.html#_ZN7QVector4dataEv
+        series.replace_q_vector_of_q_point_f(&empty);
+    }
 
-        let data_ptr = vector.data().as_mut_raw_ptr();
+    // qt reallocates vectors even thought the previous size is larger than the requested size
+    // (this has now been changed but not backported)
+    if points.len() as i32 > vector.size() {
+        vector.resize(points.len() as i32);
+    }
 
-        // most likely offset from the base pointer by 0 bytes
-        let x0 = (*data_ptr).rx() as *mut u8;
-        // most likely offset from the base pointer by 8 bytes
-        let y0 = (*data_ptr).ry() as *mut u8;
+    fill_points(&vector, points);
 
-        // dbg!(pointf_stride);
-        // dbg!(x0.offset_from(data_ptr as *const u8));
-        // dbg!(y0.offset_from(data_ptr as *const u8));
+    series.replace_q_vector_of_q_point_f(&vector);
+}
 
-        let d_x = 1.0 / (y_samples.len() as f64);
-        let mut x = 0.0;
+// the layout of QPointF the points are written with directly, two doubles with x first
+// https://code.woboq.org/qt5/qtbase/src/corelib/tools/qpoint.h.html#QPointF::xp
+const POINTF_STRIDE: isize = 16;
+const POINTF_Y_OFFSET: isize = 8;
+
+// writes the points to the start of the vector, which has to be at least as long
+// the qt functions can't be inlined because c++ doesn't have a stable abi, calling them for every point of every graph
+// every frame is slow, so the doubles are written straight into the memory of the vector instead
+// a field access can't be bound soundly either, the layout is measured on the vector itself and if it isn't the
+// expected one the points are set with QVector::replace, which is slower but doesn't assume anything
+// the vector must not be shared, otherwise data() would copy it and the points would end up in the copy
+unsafe fn fill_points(
+    vector: &CppBox<QVectorOfQPointF>,
+    points: impl ExactSizeIterator<Item = (f64, f64)>,
+) {
+    assert!(
+        points.len() <= vector.size() as usize,
+        "the vector is too short for the points"
+    );
+
+    // two points are needed to measure the stride
+    if vector.size() < 2 {
+        return fill_points_slow(vector, points);
+    }
 
-        for (i, c) in y_samples.iter().enumerate() {
-            let y = c.re as f64;
+    let stride = {
+        let ptr0 = vector.at(0).as_raw_ptr() as *const u8;
+        let ptr1 = vector.at(1).as_raw_ptr() as *const u8;
 
-            (x0.offset(i as isize * pointf_stride) as *mut ::std::os::raw::c_double).write(x);
-            (y0.offset(i as isize * pointf_stride) as *mut ::std::os::raw::c_double).write(y);
+        ptr1.offset_from(ptr0)
+    };
 
-            x += d_x;
-        }
+    let data_ptr = vector.data().as_mut_raw_ptr();
+    let x0 = (*data_ptr).rx() as *mut u8;
+    let y0 = (*data_ptr).ry() as *mut u8;
+
+    let packed = stride == POINTF_STRIDE
+        && x0 == data_ptr as *mut u8
+        && y0.offset_from(x0) == POINTF_Y_OFFSET;
+
+    if !packed {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| {
+            log::warn!(
+                "QPointF has an unexpected layout (stride {} bytes), the graphs are updated the slow way",
+                stride
+            )
+        });
+        return fill_points_slow(vector, points);
+    }
 
-        self.view.set_updates_enabled(true);
+    for (i, (x, y)) in points.enumerate() {
+        (x0.offset(i as isize * stride) as *mut f64).write(x);
+        (y0.offset(i as isize * stride) as *mut f64).write(y);
+    }
+}
 
-        self.series.replace_q_vector_of_q_point_f(&vector);
+// the fallback of fill_points which only goes through qt
+unsafe fn fill_points_slow(
+    vector: &CppBox<QVectorOfQPointF>,
+    points: impl Iterator<Item = (f64, f64)>,
+) {
+    for (i, (x, y)) in points.enumerate() {
+        vector.replace(i as i32, &QPointF::new_2a(x, y));
     }
 }
 
@@ -242,18 +376,73 @@ pub struct OutputGroup {
     group: QBox<QGroupBox>,
     run: QBox<QPushButton>,
     run_state: Cell<bool>,
+    // start receiving once a device is created even if run wasn't pressed
+    auto_run: QBox<QCheckBox>,
+    overlap: QBox<QComboBox>,
+    double_precision: QBox<QCheckBox>,
+    centered: QBox<QCheckBox>,
+    psd_label: QBox<QLabel>,
+    save_image: QBox<QPushButton>,
     grid: QBox<QGridLayout>,
     signal: SingleSeriesGraph,
+    signal_display: QBox<QComboBox>,
     spectrum: SingleSeriesGraph,
+    // in the context menu of the spectrum
+    copy_csv: QBox<QAction>,
+    // the last spectrum received, it is what gets copied
+    last_spectrum: RefCell<Option<SpectrumSnapshot>>,
+    // histogram of the demodulated angles, the mark and space peaks are symmetric about zero when the decoder is tuned correctly
+    tuning: SingleSeriesGraph,
     text_edit: QBox<QTextEdit>,
+    color_text: QBox<QCheckBox>,
+    burst_gap: QBox<QSpinBox>,
+    // checks whether the current burst of text has ended even if nothing is received
+    burst_timer: QBox<QTimer>,
+    bursts: RefCell<BurstDetector>,
+    // the decoded text is written to the decode log a line at a time
+    decoded_lines: RefCell<DecodedLines>,
+    // how many spectra are kept requested from the worker, more than one hide the round trip to the worker
+    requests_in_flight: QBox<QSpinBox>,
+    // how many stream reads overflowed, helps to find a samplerate the computer can keep up with
+    stream_stats: QBox<QLabel>,
+    // the share of the time the worker spends in every stage of the processing, to see what the cpu goes to
+    worker_stats: QBox<QLabel>,
+    // checked while the IQ is recorded, the worker unchecks it when the recording stops on its own
+    record: QBox<QPushButton>,
+    pre_trigger: QBox<QDoubleSpinBox>,
+    mute: QBox<QCheckBox>,
+    volume: QBox<QSlider>,
+    // opened on the first audio from the decoder and closed with the device
+    audio: RefCell<Option<AudioSink>>,
+    // a missing audio device is only reported once
+    audio_failed: Cell<bool>,
+    // every spectrum requested from the worker is computed with this window
+    fft_window: Cell<WindowKind>,
+    show_band_plan: QBox<QCheckBox>,
+    // the file the bands were loaded from, empty for the default plan
+    band_plan_path: RefCell<String>,
+    band_plan: RefCell<Vec<Band>>,
+    // a rectangle and a label for every band that was visible so far, they are owned by the spectrum chart
+    band_items: RefCell<Vec<(Ptr<QGraphicsRectItem>, Ptr<QGraphicsSimpleTextItem>)>>,
 
     smoothed_spectrum: RefCell<[f32; SAMPLE_COUNT]>,
+    // the data converted to single precision for plotting
+    display_signal: RefCell<Vec<Complex32>>,
+    display_spectrum: RefCell<Vec<Complex32>>,
+    display_tuning: RefCell<Vec<Complex32>>,
+    // where the left button was pressed in the spectrum, in chart coordinates, while a band is being selected
+    selection_start: Cell<Option<f64>>,
+    // the selected band is decoded without retuning the device
+    decode_group: RefCell<Option<Rc<DecodeGroup>>>,
 
     device: Rc<DeviceManager>,
 }
 
 impl OutputGroup {
-    pub unsafe fn new(device: Rc<DeviceManager>) -> (Rc<Self>, Ptr<QGroupBox>) {
+    pub unsafe fn new(
+        device: Rc<DeviceManager>,
+        settings: Rc<AppSettings>,
+    ) -> (Rc<Self>, Ptr<QGroupBox>) {
         let group = QGroupBox::new();
         let grid = QGridLayout::new_0a();
 
@@ -269,9 +458,21 @@ impl OutputGroup {
             SingleSeriesGraph::new(0.0..1.0, 0.1, "MHz", "", "Spectrum", true, false, true);
         grid.add_widget_3a(&spectrum.view, 0, 1);
 
+        let copy_csv = QAction::from_q_string_q_object(&qs("Copy as CSV"), &spectrum.view);
+        copy_csv.set_tool_tip(&qs(
+            "Copy the frequency and the power spectral density of every bin to the clipboard",
+        ));
+        spectrum.view.add_action(&copy_csv);
+        spectrum
+            .view
+            .set_context_menu_policy(ContextMenuPolicy::ActionsContextMenu);
+
         let text_edit = QTextEdit::new();
         text_edit.set_read_only(true);
-        grid.add_widget_5a(&text_edit, 1, 0, 1, 2);
+        grid.add_widget_3a(&text_edit, 1, 0);
+
+        let tuning = SingleSeriesGraph::new(-PI..PI, 0.1, "rad", "", "Tuning", false, false, true);
+        grid.add_widget_3a(&tuning.view, 1, 1);
 
         let run = QPushButton::new();
         set_run_button_icon(&run, false);
@@ -281,17 +482,200 @@ impl OutputGroup {
 
         grid.add_widget_6a(&run, 2, 0, 1, 2, AlignmentFlag::AlignCenter.into());
 
+        let overlap = QComboBox::new_0a();
+        for (label, _) in OVERLAPS {
+            overlap.add_item_q_string(&qs(*label));
+        }
+        grid.add_widget_6a(&overlap, 3, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let double_precision = QCheckBox::new();
+        double_precision.set_text(&qs("f64 FFT"));
+        double_precision.set_tool_tip(&qs("Compute the spectrum in double precision"));
+        grid.add_widget_6a(
+            &double_precision,
+            3,
+            0,
+            1,
+            1,
+            AlignmentFlag::AlignLeft.into(),
+        );
+
+        let centered = QCheckBox::new();
+        centered.set_text(&qs("Center DC"));
+        centered.set_tool_tip(&qs(
+            "Show the negative frequencies left of the center frequency instead of after the positive ones",
+        ));
+        centered.set_checked(true);
+        grid.add_widget_6a(&centered, 4, 0, 1, 1, AlignmentFlag::AlignLeft.into());
+
+        // the power spectral density under the mouse cursor
+        let psd_label = QLabel::new();
+        psd_label.set_font(&QFontDatabase::system_font(SystemFont::FixedFont));
+        grid.add_widget_6a(&psd_label, 4, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let save_image = QPushButton::from_q_string(&qs("Save image"));
+        save_image.set_tool_tip(&qs("Save the signal and the spectrum as a PNG"));
+        save_image.set_enabled(false);
+        grid.add_widget_6a(&save_image, 5, 0, 1, 2, AlignmentFlag::AlignCenter.into());
+
+        let color_text = QCheckBox::new();
+        color_text.set_text(&qs("Color text"));
+        color_text.set_tool_tip(&qs(
+            "Show figures and characters with a damaged stop bit in a different color",
+        ));
+        grid.add_widget_6a(&color_text, 6, 0, 1, 1, AlignmentFlag::AlignLeft.into());
+
+        let burst_gap = QSpinBox::new_0a();
+        burst_gap.set_prefix(&qs("Line break after "));
+        burst_gap.set_suffix(&qs(" ms"));
+        burst_gap.set_range(0, 60_000);
+        burst_gap.set_single_step(100);
+        burst_gap.set_special_value_text(&qs("No line breaks"));
+        burst_gap.set_value(settings.burst_gap_ms as i32);
+        burst_gap.set_tool_tip(&qs(
+            "End the line once nothing was decoded for this long, every line starts with the time it was received at",
+        ));
+        grid.add_widget_6a(&burst_gap, 6, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let requests_in_flight = QSpinBox::new_0a();
+        requests_in_flight.set_prefix(&qs("FFTs in flight: "));
+        requests_in_flight.set_range(1, MAX_DATA_REQUESTS_IN_FLIGHT as i32);
+        requests_in_flight.set_value(settings.data_requests_in_flight as i32);
+        requests_in_flight.set_tool_tip(&qs(
+            "How many spectra are requested from the worker at once, more update the graphs faster when the computer is slow to respond",
+        ));
+        grid.add_widget_6a(
+            &requests_in_flight,
+            10,
+            0,
+            1,
+            1,
+            AlignmentFlag::AlignLeft.into(),
+        );
+
+        let show_band_plan = QCheckBox::new();
+        show_band_plan.set_text(&qs("Band plan"));
+        show_band_plan.set_tool_tip(&qs("Mark the bands of the band plan behind the spectrum"));
+        show_band_plan.set_checked(settings.show_band_plan);
+        grid.add_widget_6a(
+            &show_band_plan,
+            10,
+            1,
+            1,
+            1,
+            AlignmentFlag::AlignRight.into(),
+        );
+
+        let stream_stats = QLabel::new();
+        stream_stats.set_tool_tip(&qs(
+            "The samples are dropped when the computer can't keep up, try a lower samplerate if this keeps growing",
+        ));
+        grid.add_widget_6a(&stream_stats, 7, 0, 1, 2, AlignmentFlag::AlignLeft.into());
+
+        let worker_stats = QLabel::new();
+        worker_stats.set_tool_tip(&qs(
+            "How much of the time the worker spends reading the stream, computing the spectra and decoding, the decoding includes the filtering",
+        ));
+        grid.add_widget_6a(&worker_stats, 11, 0, 1, 2, AlignmentFlag::AlignLeft.into());
+
+        let record = QPushButton::from_q_string(&qs("Record IQ"));
+        record.set_checkable(true);
+        record.set_enabled(false);
+        record.set_tool_tip(&qs(
+            "Write the received samples to a cf32 file, starting with the pre-trigger",
+        ));
+        grid.add_widget_6a(&record, 12, 0, 1, 1, AlignmentFlag::AlignLeft.into());
+
+        let pre_trigger = QDoubleSpinBox::new_0a();
+        pre_trigger.set_prefix(&qs("Pre-trigger "));
+        pre_trigger.set_suffix(&qs(" s"));
+        pre_trigger.set_range(0.0, 60.0);
+        pre_trigger.set_single_step(0.5);
+        pre_trigger.set_special_value_text(&qs("No pre-trigger"));
+        pre_trigger.set_value(settings.pre_trigger_s as f64);
+        pre_trigger.set_tool_tip(&qs(
+            "How much of what was received before Record was pressed the recording starts with, it is kept in memory, a second of 10 MSps takes 80 MB",
+        ));
+        grid.add_widget_6a(&pre_trigger, 12, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let signal_display = QComboBox::new_0a();
+        for (label, _) in SIGNAL_DISPLAYS {
+            signal_display.add_item_q_string(&qs(*label));
+        }
+        signal_display.set_tool_tip(&qs("What of the complex samples the signal graph shows"));
+        grid.add_widget_6a(&signal_display, 8, 0, 1, 1, AlignmentFlag::AlignLeft.into());
+
+        let mute = QCheckBox::new();
+        mute.set_text(&qs("Mute"));
+        mute.set_tool_tip(&qs("Silence the audio of the demodulator"));
+        grid.add_widget_6a(&mute, 9, 0, 1, 1, AlignmentFlag::AlignLeft.into());
+
+        let volume = QSlider::new();
+        volume.set_orientation(Orientation::Horizontal);
+        volume.set_range(0, 100);
+        volume.set_value(DEFAULT_VOLUME);
+        volume.set_tool_tip(&qs("Volume"));
+        grid.add_widget_6a(&volume, 9, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let auto_run = QCheckBox::new();
+        auto_run.set_text(&qs("Run on connect"));
+        auto_run.set_tool_tip(&qs(
+            "Start receiving as soon as a device is created, together with automatic device selection nothing has to be clicked",
+        ));
+        auto_run.set_checked(settings.auto_run);
+        grid.add_widget_6a(&auto_run, 8, 1, 1, 1, AlignmentFlag::AlignRight.into());
+
+        let burst_timer = QTimer::new_1a(&group);
+        burst_timer.set_interval(BURST_POLL_INTERVAL_MS);
+        burst_timer.set_single_shot(false);
+
         let ptr = group.as_ptr();
         let s = Rc::new(Self {
             group,
             run,
             run_state: Cell::new(false),
+            auto_run,
+            overlap,
+            double_precision,
+            centered,
+            psd_label,
+            save_image,
             grid,
             signal,
+            signal_display,
             spectrum,
+            copy_csv,
+            last_spectrum: RefCell::new(None),
+            tuning,
             text_edit,
+            color_text,
+            burst_gap,
+            burst_timer,
+            bursts: RefCell::new(BurstDetector::new(Duration::from_millis(
+                settings.burst_gap_ms as u64,
+            ))),
+            decoded_lines: RefCell::new(DecodedLines::default()),
+            requests_in_flight,
+            stream_stats,
+            worker_stats,
+            record,
+            pre_trigger,
+            mute,
+            volume,
+            audio: RefCell::new(None),
+            audio_failed: Cell::new(false),
+            fft_window: Cell::new(parse_fft_window(&settings.fft_window)),
+            show_band_plan,
+            band_plan_path: RefCell::new(settings.band_plan.clone()),
+            band_plan: RefCell::new(load_band_plan(&settings.band_plan)),
+            band_items: RefCell::new(Vec::new()),
 
             smoothed_spectrum: RefCell::new([0.0; SAMPLE_COUNT]),
+            display_signal: RefCell::new(Vec::with_capacity(SAMPLE_COUNT)),
+            display_spectrum: RefCell::new(Vec::with_capacity(SAMPLE_COUNT)),
+            display_tuning: RefCell::new(vec![Complex32::new(0.0, 0.0); TUNING_BINS]),
+            selection_start: Cell::new(None),
+            decode_group: RefCell::new(None),
 
             device,
         });
@@ -301,34 +685,134 @@ impl OutputGroup {
         (s, ptr)
     }
     unsafe fn init(self: &Rc<Self>) {
-        let Self { group, run, .. } = self.borrow();
+        let Self {
+            group,
+            run,
+            save_image,
+            ..
+        } = self.borrow();
 
         let s = self.clone();
-        // FIXME deduplicate this from handle_event
-        run.clicked().connect(&SlotNoArgs::new(group, move || {
-            let run = !s.run_state.get();
-            s.run_state.set(run);
+        save_image
+            .clicked()
+            .connect(&SlotNoArgs::new(group, move || s.save_image()));
 
-            let enabled =
-                run == true && s.device.get_device_valid() && s.device.get_receiver_valid();
+        let s = self.clone();
+        self.copy_csv
+            .triggered()
+            .connect(&SlotNoArgs::new(group, move || s.copy_spectrum_csv()));
 
-            set_run_button_icon(&s.run, enabled);
+        let s = self.clone();
+        self.burst_gap
+            .value_changed()
+            .connect(&SlotOfInt::new(group, move |gap| {
+                s.bursts.borrow_mut().gap = Duration::from_millis(gap.max(0) as u64);
+            }));
 
-            if enabled {
-                s.device.set_receive_enabled(true);
+        let s = self.clone();
+        self.burst_timer
+            .timeout()
+            .connect(&SlotNoArgs::new(group, move || {
+                if s.bursts.borrow_mut().poll(Instant::now()) {
+                    s.end_burst();
+                }
+            }));
+        self.burst_timer.start_0a();
 
-                for _ in 0..(DATA_REQUESTS_IN_FLIGHT
-                    .saturating_sub(s.device.get_data_requests_in_flight()))
+        // more requests are sent right away, the surplus ones are dropped as they come back
+        let s = self.clone();
+        self.requests_in_flight
+            .value_changed()
+            .connect(&SlotOfInt::new(group, move |_| {
+                if s.run_state.get() && s.device.get_device_valid() && s.device.get_receiver_valid()
                 {
-                    let command = DeviceBoundCommand::RequestData {
-                        data: FftData::new(SAMPLE_COUNT),
-                    };
+                    s.start_receiving();
+                }
+            }));
 
-                    handle_send_result(s.device.send_command(command));
+        let s = self.clone();
+        self.record
+            .toggled()
+            .connect(&SlotOfBool::new(group, move |checked| {
+                s.set_recording(checked)
+            }));
+
+        let s = self.clone();
+        self.pre_trigger
+            .value_changed()
+            .connect(&SlotOfDouble::new(group, move |_| {
+                if s.device.get_device_valid() {
+                    s.send_pre_trigger();
                 }
-            }
+            }));
+
+        let s = self.clone();
+        self.signal_display
+            .current_index_changed()
+            .connect(&SlotOfInt::new(group, move |_| {
+                let display = s.get_signal_display();
+                s.signal
+                    .set_second_series_visible(display == SignalDisplay::RealImag);
+            }));
+
+        let s = self.clone();
+        self.show_band_plan
+            .toggled()
+            .connect(&SlotOfBool::new(group, move |_| s.update_band_plan()));
+
+        let s = self.clone();
+        self.mute
+            .toggled()
+            .connect(&SlotOfBool::new(group, move |muted| {
+                if let Some(audio) = s.audio.borrow().as_ref() {
+                    audio.set_muted(muted);
+                }
+            }));
+
+        let s = self.clone();
+        self.volume
+            .value_changed()
+            .connect(&SlotOfInt::new(group, move |_| {
+                if let Some(audio) = s.audio.borrow().as_ref() {
+                    audio.set_volume(s.get_volume());
+                }
+            }));
+
+        let s = self.clone();
+        run.clicked().connect(&SlotNoArgs::new(group, move || {
+            let run = !s.run_state.get();
+            s.run_state.set(run);
+
+            s.set_receiving(run && s.device.get_device_valid() && s.device.get_receiver_valid());
         }));
     }
+    // the run button shows whether it is receiving, the same as the run state once there is a receiver
+    unsafe fn set_receiving(&self, receive: bool) {
+        set_run_button_icon(&self.run, receive);
+
+        if receive {
+            self.start_receiving();
+        }
+    }
+    // enables the receiving and tops the data requests up to the number kept in flight
+    unsafe fn start_receiving(&self) {
+        self.device.set_receive_enabled(true);
+
+        let wanted = self.requests_in_flight.value() as usize;
+        for _ in 0..wanted.saturating_sub(self.device.get_data_requests_pending()) {
+            let command = DeviceBoundCommand::RequestData {
+                data: self.new_spectrum(self.double_precision.is_checked()),
+                overlap: self.get_overlap(),
+            };
+
+            handle_send_result(self.device.send_command(command));
+        }
+    }
+    fn new_spectrum(&self, double_precision: bool) -> SpectrumData {
+        let mut data = SpectrumData::new(SAMPLE_COUNT, double_precision);
+        data.set_window(self.fft_window.get());
+        data
+    }
     pub unsafe fn handle_event(&self, event: &mut Option<GuiBoundEvent>) {
         match event.as_mut().unwrap() {
             GuiBoundEvent::DeviceCreated { .. } => {
@@ -336,42 +820,87 @@ impl OutputGroup {
                 // self.spectrum.clear();
                 // self.text_edit.clear();
 
-                if self.run_state.get() && self.device.get_receiver_valid() {
-                    self.device.set_receive_enabled(true);
+                self.save_image.set_enabled(true);
+                self.record.set_enabled(true);
+                self.send_pre_trigger();
+
+                let (run, receive) = run_after_device_created(
+                    self.run_state.get(),
+                    self.auto_run.is_checked(),
+                    self.device.get_receiver_valid(),
+                );
+                self.run_state.set(run);
+                self.set_receiving(receive);
+            }
+            GuiBoundEvent::DecodedChars { data } => {
+                let text: String = data.iter().map(|c| c.ch).collect();
 
-                    for _ in 0..(DATA_REQUESTS_IN_FLIGHT
-                        .saturating_sub(self.device.get_data_requests_in_flight()))
-                    {
-                        let command = DeviceBoundCommand::RequestData {
-                            data: FftData::new(SAMPLE_COUNT),
-                        };
+                let now = Instant::now();
+                // a burst which ended between the timer ticks is closed before the next one starts
+                if self.bursts.borrow_mut().poll(now) {
+                    self.end_burst();
+                }
+                if self.bursts.borrow_mut().chars_decoded(now) {
+                    let unix_time = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |time| time.as_secs());
+
+                    self.text_edit
+                        .set_text_color(self.text_edit.palette().color_1a(ColorRole::Text));
+                    self.text_edit
+                        .insert_plain_text(&qs(burst_timestamp(unix_time)));
+                }
+
+                let stdout = std::io::stdout();
+                let mut lock = stdout.lock();
+
+                lock.write_all(text.as_bytes()).unwrap();
 
-                        handle_send_result(self.device.send_command(command));
+                if self.color_text.is_checked() {
+                    for (kind, run) in text_runs(data) {
+                        let color = match kind {
+                            TextKind::Letters => {
+                                QColor::new_copy(self.text_edit.palette().color_1a(ColorRole::Text))
+                            }
+                            TextKind::Figures => QColor::from_global_color(GlobalColor::DarkCyan),
+                            TextKind::Suspect => QColor::from_global_color(GlobalColor::Red),
+                        };
+                        self.text_edit.set_text_color(&color);
+                        self.text_edit.insert_plain_text(&qs(run));
                     }
+                } else {
+                    self.text_edit
+                        .set_text_color(self.text_edit.palette().color_1a(ColorRole::Text));
+                    self.text_edit.insert_plain_text(&qs(text));
                 }
+
+                self.log_decoded_lines(data);
             }
-            GuiBoundEvent::DecodedChars { data } => {
-                let stdout = std::io::stdout();
-                let mut lock = stdout.lock();
+            GuiBoundEvent::DemodTrace { samples } => {
+                let tuning = &mut *self.display_tuning.borrow_mut();
 
-                lock.write_all(data.as_bytes()).unwrap();
+                let mut bins = [0.0; TUNING_BINS];
+                histogram(samples, -PI as f32..PI as f32, &mut bins);
+                for (dst, &bin) in tuning.iter_mut().zip(bins.iter()) {
+                    dst.re = bin;
+                }
 
-                self.text_edit.insert_plain_text(&qs(data.as_str()));
+                self.tuning
+                    .update_series(tuning, -PI..PI, true, false, 0.9, 0.1);
             }
             GuiBoundEvent::DataReady { data } => {
                 if !(self.device.get_receiver_valid() && self.run_state.get()) {
                     return;
                 }
 
-                let spectrum = data.get_output_mut();
-                
-                let half = spectrum.len() / 2;
-                // the output of fft is not actually continuous, it is swapped around 0
-                // [0ppppppp|nnnnnnnn]
-                //  DC     N/2
-                // |positive|negative|
-                let (positive, negative) = spectrum.split_at_mut(half);
-                positive.swap_with_slice(negative);
+                let signal = &mut *self.display_signal.borrow_mut();
+                let spectrum = &mut *self.display_spectrum.borrow_mut();
+                data.copy_to_f32(signal, spectrum);
+
+                let centered = self.centered.is_checked();
+                if centered {
+                    fft_shift(spectrum);
+                }
 
                 let smoothed = &mut*self.smoothed_spectrum.borrow_mut();
 
@@ -380,44 +909,457 @@ impl OutputGroup {
                     smoothed[i] = 0.1 * spectrum[i].re + 0.9 * smoothed[i];
                 }
                 
-                let signal = data.get_input();
-                let spectrum = data.get_output();
-
-                self.signal.update_series(signal, true, true, 0.9, 0.2);
-                self.spectrum.update_series(spectrum, true, false, 0.9, 0.2);
-
-                if let Some(state) = self.device.get_receiver_state() {
-                    // todo decimate the signal first and take that into account
-                    let samplerate = data.get_samplerate();
-                    let n = signal.len() as f64;
-
-                    // ms
-                    self.signal
-                        .x_axis
-                        .set_range(0.0, samplerate.recip() * n * 1000.0);
-
-                    // MHz
-                    let offset = state.frequency / 1000_000.0;
-                    let samplerate = samplerate / 1000_000.0;
-                    self.spectrum
-                        .x_axis
-                        .set_range(offset - samplerate / 2.0, offset + samplerate / 2.0);
+                // todo decimate the signal first and take that into account
+                let samplerate = data.get_samplerate();
+                let center = self
+                    .device
+                    .get_receiver_state()
+                    .map_or(0.0, |state| state.frequency);
+
+                // the spectrum is displayed in MHz
+                let frequencies = spectrum_axis_range(center, samplerate, centered);
+                let frequencies = (frequencies.start / 1000_000.0)..(frequencies.end / 1000_000.0);
+
+                let times = signal_axis_range(signal.len(), samplerate);
+
+                self.get_signal_display().apply(signal);
+
+                self.signal
+                    .update_series(signal, times, true, true, 0.9, 0.2);
+                self.spectrum
+                    .update_series(spectrum, frequencies, true, false, 0.9, 0.2);
+
+                self.update_psd_readout(data);
+                if let Some(receiver) = self.device.get_receiver_state() {
+                    self.last_spectrum
+                        .replace(Some(SpectrumSnapshot::from_spectrum(data, &receiver)));
+                }
+                self.update_band_selection();
+                self.update_band_plan();
+
+                // fewer requests are wanted than before
+                if self.device.get_data_requests_pending()
+                    >= self.requests_in_flight.value() as usize
+                {
+                    return;
                 }
 
                 match event.take().unwrap() {
-                    GuiBoundEvent::DataReady { data } => self.device.schedule_command(
-                        DeviceBoundCommand::RequestData { data },
-                        REQUEST_DATA_INTERVAL_MS,
-                    ),
+                    GuiBoundEvent::DataReady { mut data } => {
+                        // the precision was changed since the request was sent
+                        let double_precision = self.double_precision.is_checked();
+                        if data.is_double_precision() != double_precision {
+                            data = self.new_spectrum(double_precision);
+                        }
+
+                        self.device.schedule_command(
+                            DeviceBoundCommand::RequestData {
+                                data,
+                                overlap: self.get_overlap(),
+                            },
+                            REQUEST_DATA_INTERVAL_MS,
+                        )
+                    }
                     _ => unreachable!(),
                 };
             }
+            GuiBoundEvent::StreamStats {
+                overflows,
+                read_count,
+            } => {
+                self.stream_stats
+                    .set_text(&qs(stream_stats_text(*overflows, *read_count)));
+            }
+            GuiBoundEvent::WorkerStats { times, elapsed } => {
+                self.worker_stats
+                    .set_text(&qs(worker_stats_text(times, *elapsed)));
+            }
+            GuiBoundEvent::RecordingStarted {
+                pre_trigger_samples,
+            } => {
+                log::info!(
+                    "Recording started with {} pre-trigger samples",
+                    pre_trigger_samples
+                );
+            }
+            GuiBoundEvent::RecordingStopped { samples, error } => {
+                match error {
+                    Some(e) => log::warn!("Recording stopped after {} samples: {}", samples, e),
+                    None => log::info!("Recorded {} samples", samples),
+                }
+                self.record.block_signals(true);
+                self.record.set_checked(false);
+                self.record.block_signals(false);
+            }
+            GuiBoundEvent::AudioReady {
+                samples,
+                samplerate,
+            } => {
+                self.play_audio(samples, *samplerate);
+            }
             GuiBoundEvent::DeviceDestroyed | GuiBoundEvent::WorkerReset => {
                 self.set_run(false);
+                self.save_image.set_enabled(false);
+                // the worker stopped the recording together with the device
+                self.record.block_signals(true);
+                self.record.set_checked(false);
+                self.record.block_signals(false);
+                self.record.set_enabled(false);
+                self.stream_stats.clear();
+                self.worker_stats.clear();
+                self.audio.replace(None);
+                self.audio_failed.set(false);
             }
             _ => (),
         }
     }
+    pub fn set_decode_group(&self, decode_group: Rc<DecodeGroup>) {
+        self.decode_group.replace(Some(decode_group));
+    }
+    // the mouse cursor and the plot area of the spectrum, both in chart coordinates
+    unsafe fn spectrum_cursor(&self) -> (CppBox<QPointF>, CppBox<QRectF>) {
+        let graph = &self.spectrum;
+
+        let view_pos = graph.view.map_from_global(&QCursor::pos_0a());
+        let pos = graph
+            .chart
+            .map_from_scene_q_point_f(&graph.view.map_to_scene_q_point(&view_pos));
+
+        (pos, graph.chart.plot_area())
+    }
+    unsafe fn update_psd_readout(&self, data: &SpectrumData) {
+        let graph = &self.spectrum;
+        let (pos, area) = self.spectrum_cursor();
+
+        // the fraction of the plot area, 0..1 from left to right
+        let x = (pos.x() - area.left()) / area.width();
+        let y = (pos.y() - area.top()) / area.height();
+
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            self.psd_label.clear();
+            return;
+        }
+
+        let len = data.len();
+        let mut bin = (x * len as f64) as usize;
+        // undo the fft_shift() of the displayed spectrum
+        if self.centered.is_checked() {
+            bin = (bin + len - len / 2) % len;
+        }
+
+        let (min, max) = (graph.x_axis.min(), graph.x_axis.max());
+        let frequency = min + x * (max - min);
+
+        self.psd_label.set_text(&qs(format!(
+            "{:.6} MHz  {:.1} dBFS/Hz",
+            frequency,
+            data.psd_db(bin)
+        )));
+    }
+    // a band is selected by dragging over the spectrum with the left button, like the psd readout the mouse is polled
+    // with every spectrum so the selection only works while receiving
+    unsafe fn update_band_selection(&self) {
+        let (pos, area) = self.spectrum_cursor();
+        let pressed = QGuiApplication::mouse_buttons().test_flag(MouseButton::LeftButton);
+
+        let band = |start: f64| {
+            let axis = &self.spectrum.x_axis;
+            // the axis is in MHz
+            let frequencies = (axis.min() * 1000_000.0)..(axis.max() * 1000_000.0);
+            let center = self
+                .device
+                .get_receiver_state()
+                .map_or(0.0, |state| state.frequency);
+
+            selected_band(
+                start,
+                pos.x(),
+                area.left()..area.right(),
+                frequencies,
+                center,
+            )
+        };
+
+        match (pressed, self.selection_start.get()) {
+            // the drag has to start in the plot, otherwise it belongs to some other widget
+            (true, None) => {
+                if area.contains_q_point_f(&pos) {
+                    self.selection_start.set(Some(pos.x()));
+                }
+            }
+            (true, Some(start)) => {
+                if let Some((offset, width)) = band(start) {
+                    self.psd_label.set_text(&qs(format!(
+                        "Selected {:+.0} Hz, {:.0} Hz wide",
+                        offset, width
+                    )));
+                }
+            }
+            (false, Some(start)) => {
+                self.selection_start.set(None);
+
+                if let Some((offset, width)) = band(start) {
+                    log::debug!("Selected a {:.0} Hz wide band at {:+.0} Hz", width, offset);
+
+                    if let Some(decode_group) = &*self.decode_group.borrow() {
+                        decode_group.select_channel(offset, width);
+                    }
+                }
+            }
+            (false, None) => (),
+        }
+    }
+    // the bands overlapping the visible span are drawn over the plot area, like the selection they only follow the axis
+    // when a spectrum arrives
+    unsafe fn update_band_plan(&self) {
+        let graph = &self.spectrum;
+        let bands = self.band_plan.borrow();
+        let mut items = self.band_items.borrow_mut();
+
+        for (rect, label) in items.iter() {
+            rect.set_visible(false);
+            label.set_visible(false);
+        }
+
+        if !self.show_band_plan.is_checked() {
+            return;
+        }
+
+        // the axis is in MHz
+        let axis = (graph.x_axis.min() * 1000_000.0)..(graph.x_axis.max() * 1000_000.0);
+        let area = graph.chart.plot_area();
+
+        let mut previous = None;
+        for (i, visible) in band_plan_regions(&bands, axis, self.centered.is_checked()) {
+            // the second part of a band split by the uncentered spectrum has its own item in the same color
+            let item = 2 * i + (previous == Some(i)) as usize;
+            previous = Some(i);
+
+            while items.len() <= item {
+                items.push(new_band_item(&graph.chart, items.len() / 2));
+            }
+            let (rect, label) = items[item];
+
+            let left = area.left() + visible.start * area.width();
+            let width = (visible.end - visible.start) * area.width();
+            rect.set_rect_4a(left, area.top(), width, area.height());
+            rect.set_visible(true);
+
+            // a label that doesn't fit would run into the neighbouring bands
+            label.set_text(&qs(&bands[i].name));
+            label.set_pos_2a(left + 2.0, area.top() + 2.0);
+            label.set_visible(label.bounding_rect().width() + 4.0 < width);
+        }
+    }
+    // asks for the file when checked, the button is unchecked again if the dialog is cancelled
+    unsafe fn set_recording(&self, checked: bool) {
+        if !checked {
+            handle_send_result(self.device.send_command(DeviceBoundCommand::StopRecording));
+            return;
+        }
+
+        // the worker can only record once the receiver is configured
+        let path = match self.device.get_receiver_state() {
+            Some(state) if self.device.get_receiver_valid() => {
+                let unix_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs());
+
+                QFileDialog::get_save_file_name_4a(
+                    &self.group,
+                    &qs("Record IQ"),
+                    &qs(capture_file_name(state.frequency, unix_time, "cf32")),
+                    &qs("Complex float32 IQ (*.cf32)"),
+                )
+                .to_std_string()
+            }
+            _ => String::new(),
+        };
+
+        if path.is_empty() {
+            self.record.block_signals(true);
+            self.record.set_checked(false);
+            self.record.block_signals(false);
+            return;
+        }
+
+        let command = DeviceBoundCommand::StartRecording {
+            path: path.into(),
+            format: IqFormat::Cf32,
+        };
+        handle_send_result(self.device.send_command(command));
+    }
+    unsafe fn send_pre_trigger(&self) {
+        let command = DeviceBoundCommand::SetPreTrigger {
+            seconds: self.pre_trigger.value() as f32,
+        };
+
+        handle_send_result(self.device.send_command(command));
+    }
+    // the graphs are drawn next to each other with the receiver configuration written below them
+    unsafe fn save_image(&self) {
+        let state = match self.device.get_receiver_state() {
+            Some(state) => state,
+            None => return,
+        };
+
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+
+        let path = QFileDialog::get_save_file_name_4a(
+            &self.group,
+            &qs("Save image"),
+            &qs(capture_file_name(state.frequency, unix_time, "png")),
+            &qs("PNG image (*.png)"),
+        )
+        .to_std_string();
+
+        if path.is_empty() {
+            return;
+        }
+
+        let signal = self.signal.view.grab_0a();
+        let spectrum = self.spectrum.view.grab_0a();
+
+        let caption_height = QFontDatabase::system_font(SystemFont::FixedFont).point_size() * 3;
+        let image = QPixmap::from_2_int(
+            signal.width() + spectrum.width(),
+            signal.height().max(spectrum.height()) + caption_height,
+        );
+        image.fill_1a(&QColor::from_global_color(GlobalColor::White));
+
+        let painter = QPainter::new_1a(&image);
+        painter.draw_pixmap_2_int_q_pixmap(0, 0, &signal);
+        painter.draw_pixmap_2_int_q_pixmap(signal.width(), 0, &spectrum);
+        painter.set_font(&QFontDatabase::system_font(SystemFont::FixedFont));
+        painter.draw_text_2_int_q_string(
+            caption_height / 3,
+            image.height() - caption_height / 3,
+            &qs(capture_caption(state.frequency, state.samplerate)),
+        );
+        painter.end();
+
+        if image.save_q_string(&qs(&path)) {
+            log::info!("Saved image to '{}'", path);
+        } else {
+            log::error!("Failed to save image to '{}'", path);
+        }
+    }
+    unsafe fn copy_spectrum_csv(&self) {
+        match &*self.last_spectrum.borrow() {
+            Some(spectrum) => {
+                QGuiApplication::clipboard().set_text_1a(&qs(spectrum.to_csv()));
+                log::info!("Copied {} bins of the spectrum", spectrum.psd_db.len());
+            }
+            None => log::warn!("There is no spectrum to copy yet, start receiving first"),
+        }
+    }
+    unsafe fn get_overlap(&self) -> f32 {
+        OVERLAPS
+            .get(self.overlap.current_index() as usize)
+            .map(|(_, overlap)| *overlap)
+            .unwrap_or(0.0)
+    }
+    unsafe fn get_signal_display(&self) -> SignalDisplay {
+        SIGNAL_DISPLAYS
+            .get(self.signal_display.current_index() as usize)
+            .map(|(_, display)| *display)
+            .unwrap_or(SignalDisplay::Real)
+    }
+    unsafe fn play_audio(&self, samples: &[f32], samplerate: f64) {
+        let mut audio = self.audio.borrow_mut();
+
+        if audio.is_none() && !self.audio_failed.get() {
+            match AudioSink::new(samplerate) {
+                Ok(sink) => {
+                    sink.set_volume(self.get_volume());
+                    sink.set_muted(self.mute.is_checked());
+                    *audio = Some(sink);
+                }
+                Err(e) => {
+                    log::warn!("Failed to open the audio output: {}", e);
+                    self.audio_failed.set(true);
+                }
+            }
+        }
+
+        if let Some(audio) = audio.as_mut() {
+            audio.push(samples, samplerate);
+        }
+    }
+    unsafe fn get_volume(&self) -> f32 {
+        self.volume.value() as f32 / 100.0
+    }
+    // the lines with a damaged character are logged as warnings, the mode is the one selected when the line ended
+    unsafe fn log_decoded_lines(&self, chars: &[DecodedChar]) {
+        let lines = self.decoded_lines.borrow_mut().push(chars);
+        if lines.is_empty() {
+            return;
+        }
+
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let frequency = self
+            .device
+            .get_receiver_state()
+            .map_or(0.0, |state| state.frequency);
+        let mode = match &*self.decode_group.borrow() {
+            Some(decode_group) => decode_group.mode_name(),
+            None => "",
+        };
+
+        for (line, suspect) in lines {
+            log_decode(&DecodeRecord {
+                unix_time,
+                level: if suspect {
+                    log::Level::Warn
+                } else {
+                    log::Level::Info
+                },
+                source: DecodeSource::Decoder,
+                frequency,
+                mode,
+                text: &line,
+            });
+        }
+    }
+    unsafe fn end_burst(&self) {
+        self.text_edit.insert_plain_text(&qs("\n"));
+    }
+    pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
+        settings.burst_gap_ms = self.burst_gap.value() as u32;
+        settings.auto_run = self.auto_run.is_checked();
+        settings.data_requests_in_flight = self.requests_in_flight.value() as u32;
+        settings.fft_window = self.fft_window.get().name().to_owned();
+        settings.show_band_plan = self.show_band_plan.is_checked();
+        settings.band_plan = self.band_plan_path.borrow().clone();
+        settings.pre_trigger_s = self.pre_trigger.value() as f32;
+    }
+    // the value_changed slot updates the detector
+    pub unsafe fn apply_settings(&self, settings: &AppSettings) {
+        self.burst_gap.set_value(settings.burst_gap_ms as i32);
+        self.auto_run.set_checked(settings.auto_run);
+        // the spinbox clamps it to the supported range
+        self.requests_in_flight
+            .set_value(settings.data_requests_in_flight as i32);
+        self.fft_window.set(parse_fft_window(&settings.fft_window));
+
+        // the file is only read again when it changed
+        if *self.band_plan_path.borrow() != settings.band_plan {
+            self.band_plan_path.replace(settings.band_plan.clone());
+            self.band_plan.replace(load_band_plan(&settings.band_plan));
+        }
+        // the toggled slot redraws the bands
+        self.show_band_plan.set_checked(settings.show_band_plan);
+        // the value_changed slot sends it to the worker
+        self.pre_trigger.set_value(settings.pre_trigger_s as f64);
+    }
+    // the same as clicking the run button
+    pub unsafe fn toggle_run(&self) {
+        self.run.click();
+    }
     pub unsafe fn set_run(&self, run: bool) {
         self.run.set_checked(run);
         set_run_button_icon(&self.run, run);
@@ -425,6 +1367,231 @@ impl OutputGroup {
     }
 }
 
+// a translucent rectangle with a label for the band at `index` of the plan, every band keeps its color
+// the z values are between those of the plot area background and the grid, see ChartPresenter::ZValues in QtCharts
+unsafe fn new_band_item(
+    chart: &QBox<QChart>,
+    index: usize,
+) -> (Ptr<QGraphicsRectItem>, Ptr<QGraphicsSimpleTextItem>) {
+    let parent = chart.as_ptr().static_upcast::<QGraphicsItem>();
+    // spread around the color wheel by the golden angle so neighbouring bands differ
+    let hue = (index * 137 % 360) as i32;
+
+    let rect = QGraphicsRectItem::from_q_graphics_item(parent).into_ptr();
+    rect.set_brush(&QBrush::from_q_color(&QColor::from_hsv_4a(
+        hue, 160, 230, 48,
+    )));
+    rect.set_pen(&QPen::from_pen_style(PenStyle::NoPen));
+    rect.set_z_value(0.5);
+    rect.set_visible(false);
+
+    let label = QGraphicsSimpleTextItem::from_q_graphics_item(parent).into_ptr();
+    label.set_brush(&QBrush::from_q_color(&QColor::from_hsv_3a(hue, 200, 140)));
+    label.set_z_value(0.5);
+    label.set_visible(false);
+
+    (rect, label)
+}
+
+// (the run state, whether to start receiving) once a device is created
+// run may have been pressed before there was a device, auto_run starts it regardless
+fn run_after_device_created(run_state: bool, auto_run: bool, receiver_valid: bool) -> (bool, bool) {
+    let run = run_state || auto_run;
+    (run, run && receiver_valid)
+}
+
+// how often the end of a burst is checked for
+const BURST_POLL_INTERVAL_MS: i32 = 100;
+
+// splits the decoded text into bursts, a burst ends once nothing was decoded for the gap
+// a gap of zero disables the splitting
+struct BurstDetector {
+    gap: Duration,
+    // when the last characters of the current burst were decoded, None between bursts
+    last: Option<Instant>,
+}
+
+impl BurstDetector {
+    fn new(gap: Duration) -> Self {
+        Self { gap, last: None }
+    }
+    // returns true if the characters start a new burst
+    // poll() should be called first so that a burst which has already ended isn't continued
+    fn chars_decoded(&mut self, now: Instant) -> bool {
+        let starts = self.last.is_none() && self.gap > Duration::from_secs(0);
+        self.last = Some(now);
+        starts
+    }
+    // returns true once the current burst has ended
+    fn poll(&mut self, now: Instant) -> bool {
+        match self.last {
+            Some(last) if self.gap > Duration::from_secs(0) && now - last >= self.gap => {
+                self.last = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// the UTC time of day a burst starts with
+fn burst_timestamp(unix_time: u64) -> String {
+    let seconds = unix_time % (24 * 60 * 60);
+    format!(
+        "[{:02}:{:02}:{:02}] ",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// the time the samples span in ms
+fn signal_axis_range(len: usize, samplerate: f64) -> Range<f64> {
+    if samplerate <= 0.0 {
+        return 0.0..1.0;
+    }
+
+    0.0..(len as f64 / samplerate * 1000.0)
+}
+
+// the frequencies in Hz the spectrum of a receiver tuned to center covers
+// without the shift the negative frequencies alias above the positive ones so the axis starts at the center
+fn spectrum_axis_range(center: f64, samplerate: f64, centered: bool) -> Range<f64> {
+    if centered {
+        (center - samplerate / 2.0)..(center + samplerate / 2.0)
+    } else {
+        center..(center + samplerate)
+    }
+}
+
+// the parts of the plot covered by the visible bands with their index into bands, as fractions of the plot from its
+// left to its right edge, the plot shows the frequencies of axis
+// without the centering the upper half of the plot shows the negative frequencies like in selected_band, the bands
+// below the receiver frequency are moved there and a band across it is split into a part at each edge of the plot
+fn band_plan_regions(bands: &[Band], axis: Range<f64>, centered: bool) -> Vec<(usize, Range<f64>)> {
+    if centered {
+        return visible_bands(bands, axis);
+    }
+
+    // the axis starts at the receiver frequency
+    let half = (axis.end - axis.start) / 2.0;
+    let span = (axis.start - half)..(axis.start + half);
+
+    let mut regions = Vec::new();
+    for (i, visible) in visible_bands(bands, span) {
+        if visible.start < 0.5 {
+            regions.push((i, (visible.start + 0.5)..(visible.end.min(0.5) + 0.5)));
+        }
+        if visible.end > 0.5 {
+            regions.push((i, (visible.start.max(0.5) - 0.5)..(visible.end - 0.5)));
+        }
+    }
+    regions
+}
+
+// the offset of the center of the band selected between two x positions in the plot from the receiver frequency
+// and the width of the band, both in Hz, the positions are clamped to the plot which shows the frequencies of axis
+// None if nothing was selected
+fn selected_band(
+    start: f64,
+    end: f64,
+    plot: Range<f64>,
+    axis: Range<f64>,
+    center: f64,
+) -> Option<(f64, f64)> {
+    let plot_width = plot.end - plot.start;
+    if !(plot_width > 0.0) {
+        return None;
+    }
+
+    let fraction = |x: f64| ((x - plot.start) / plot_width).max(0.0).min(1.0);
+    let (start, end) = (fraction(start), fraction(end));
+
+    let span = axis.end - axis.start;
+    let width = (end - start).abs() * span;
+    if !(width > 0.0) {
+        return None;
+    }
+
+    let mut offset = axis.start + (start + end) / 2.0 * span - center;
+    // without the centering the upper half of the spectrum shows the negative frequencies
+    if offset >= span / 2.0 {
+        offset -= span;
+    }
+
+    Some((offset, width))
+}
+
+// the receiver configuration written on the saved images
+fn capture_caption(frequency: f64, samplerate: f64) -> String {
+    format!(
+        "{:.6} MHz  {:.3} MSps",
+        frequency / 1000_000.0,
+        samplerate / 1000_000.0
+    )
+}
+
+// the name suggested for a saved image or recording, the time keeps the captures of the same frequency apart
+fn capture_file_name(frequency: f64, unix_time: u64, extension: &str) -> String {
+    format!(
+        "radiothing_{:.6}MHz_{}.{}",
+        frequency / 1000_000.0,
+        unix_time,
+        extension
+    )
+}
+
+// sorts the samples into evenly spaced bins over range, the bins hold the fraction of the samples which fell into them
+// samples outside of the range are counted only in the total
+fn histogram(samples: &[f32], range: Range<f32>, bins: &mut [f32]) {
+    bins.iter_mut().for_each(|b| *b = 0.0);
+
+    if samples.is_empty() {
+        return;
+    }
+
+    let bin_width = (range.end - range.start) / bins.len() as f32;
+    let weight = 1.0 / samples.len() as f32;
+
+    for &s in samples {
+        if s < range.start || s > range.end {
+            continue;
+        }
+
+        // the end of the range belongs to the last bin
+        let i = (((s - range.start) / bin_width) as usize).min(bins.len() - 1);
+        bins[i] += weight;
+    }
+}
+
+// how a decoded character is colored, a damaged stop bit takes precedence over the shift
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TextKind {
+    Letters,
+    Figures,
+    Suspect,
+}
+
+// groups consecutive characters of the same kind so that the color is only changed when needed
+fn text_runs(chars: &[DecodedChar]) -> Vec<(TextKind, String)> {
+    let mut runs: Vec<(TextKind, String)> = Vec::new();
+
+    for c in chars {
+        let kind = match (c.suspect, c.shift) {
+            (true, _) => TextKind::Suspect,
+            (false, Shift::Letters) => TextKind::Letters,
+            (false, Shift::Figures) => TextKind::Figures,
+        };
+
+        match runs.last_mut() {
+            Some((last, run)) if *last == kind => run.push(c.ch),
+            _ => runs.push((kind, c.ch.to_string())),
+        }
+    }
+
+    runs
+}
+
 unsafe fn set_run_button_icon(button: &QPushButton, state: bool) {
     let icon = match state {
         true => QApplication::style().standard_icon_1a(StandardPixmap::SPMediaPause),
@@ -433,3 +1600,336 @@ unsafe fn set_run_button_icon(button: &QPushButton, state: bool) {
 
     button.set_icon(&icon);
 }
+
+fn stream_stats_text(overflows: u64, read_count: u64) -> String {
+    if overflows == 0 {
+        format!("No overflows in {} reads", read_count)
+    } else {
+        format!(
+            "{} overflows in {} reads ({:.1}%)",
+            overflows,
+            read_count,
+            overflows as f64 * 100.0 / read_count as f64
+        )
+    }
+}
+
+// the reading is mostly waiting for the device, a high share of it means that the worker keeps up
+fn worker_stats_text(times: &ProcessingTimes, elapsed: Duration) -> String {
+    let percent = |time: Duration| time.as_secs_f64() * 100.0 / elapsed.as_secs_f64().max(1e-9);
+
+    format!(
+        "Read {:.1}%, FFT {:.1}%, decoder {:.1}% (filter {:.1}%)",
+        percent(times.read),
+        percent(times.fft),
+        percent(times.decoder),
+        percent(times.filter)
+    )
+}
+
+#[test]
+fn axis_ranges() {
+    // 512 samples at 2.048 MSps take 0.25 ms
+    assert_eq!(signal_axis_range(512, 2_048_000.0), 0.0..0.25);
+    assert_eq!(signal_axis_range(4800, 48_000.0), 0.0..100.0);
+    // before the first data arrives
+    assert_eq!(signal_axis_range(512, 0.0), 0.0..1.0);
+
+    assert_eq!(
+        spectrum_axis_range(0.0, 2_048_000.0, true),
+        -1_024_000.0..1_024_000.0
+    );
+    assert_eq!(
+        spectrum_axis_range(433_920_000.0, 250_000.0, true),
+        433_795_000.0..434_045_000.0
+    );
+    assert_eq!(
+        spectrum_axis_range(433_920_000.0, 250_000.0, false),
+        433_920_000.0..434_170_000.0
+    );
+}
+
+#[test]
+fn band_selection() {
+    // a 400 px wide plot starting at 50 px showing 2 MHz around 100 MHz
+    let plot = 50.0..450.0;
+    let axis = 99e6..101e6;
+    let center = 100e6;
+    let band = |start, end| selected_band(start, end, plot.clone(), axis.clone(), center);
+
+    // a quarter of the plot right of the center
+    assert_eq!(band(250.0, 350.0), Some((250e3, 500e3)));
+    // dragged from right to left
+    assert_eq!(band(350.0, 250.0), Some((250e3, 500e3)));
+    assert_eq!(band(50.0, 150.0), Some((-750e3, 500e3)));
+    // the part outside the plot is left out
+    assert_eq!(band(0.0, 150.0), Some((-750e3, 500e3)));
+    assert_eq!(band(400.0, 1000.0), Some((875e3, 250e3)));
+
+    // a click doesn't select anything, neither does a drag outside the plot
+    assert_eq!(band(200.0, 200.0), None);
+    assert_eq!(band(460.0, 600.0), None);
+    assert_eq!(
+        selected_band(10.0, 20.0, 0.0..0.0, axis.clone(), center),
+        None
+    );
+
+    // without the centering the upper half shows the negative frequencies
+    let axis = spectrum_axis_range(center, 2e6, false);
+    assert_eq!(
+        selected_band(350.0, 450.0, plot.clone(), axis.clone(), center),
+        Some((-250e3, 500e3))
+    );
+    assert_eq!(
+        selected_band(50.0, 150.0, plot, axis, center),
+        Some((250e3, 500e3))
+    );
+}
+
+#[test]
+fn band_plan_placement() {
+    let band = |start_hz, end_hz| Band {
+        name: String::new(),
+        start_hz,
+        end_hz,
+    };
+    let bands = [
+        // across the receiver frequency
+        band(99.5e6, 100.5e6),
+        band(100.25e6, 100.75e6),
+        band(99.25e6, 99.5e6),
+        // where a linear axis would put it without the centering, it isn't received
+        band(101.5e6, 102e6),
+    ];
+    let center = 100e6;
+
+    // centered the plot is linear
+    let axis = spectrum_axis_range(center, 2e6, true);
+    assert_eq!(
+        band_plan_regions(&bands, axis, true),
+        [(0, 0.25..0.75), (1, 0.625..0.875), (2, 0.125..0.25)]
+    );
+
+    // the left half shows the frequencies above the center, the right half those below it
+    let axis = spectrum_axis_range(center, 2e6, false);
+    assert_eq!(
+        band_plan_regions(&bands, axis, false),
+        [
+            (0, 0.75..1.0),
+            (0, 0.0..0.25),
+            (1, 0.125..0.375),
+            (2, 0.625..0.75)
+        ]
+    );
+}
+
+#[test]
+fn capture_labels() {
+    assert_eq!(
+        capture_caption(433_920_000.0, 2_048_000.0),
+        "433.920000 MHz  2.048 MSps"
+    );
+    assert_eq!(
+        capture_file_name(144_800_000.0, 1_600_000_000, "png"),
+        "radiothing_144.800000MHz_1600000000.png"
+    );
+    assert_eq!(
+        capture_file_name(434_500_000.0, 1_600_000_000, "cf32"),
+        "radiothing_434.500000MHz_1600000000.cf32"
+    );
+}
+
+#[test]
+fn worker_stats_percentages() {
+    let ms = Duration::from_millis;
+    let times = ProcessingTimes {
+        read: ms(800),
+        fft: ms(50),
+        filter: ms(25),
+        decoder: ms(100),
+    };
+    assert_eq!(
+        worker_stats_text(&times, ms(1000)),
+        "Read 80.0%, FFT 5.0%, decoder 10.0% (filter 2.5%)"
+    );
+    assert_eq!(
+        worker_stats_text(&ProcessingTimes::default(), ms(0)),
+        "Read 0.0%, FFT 0.0%, decoder 0.0% (filter 0.0%)"
+    );
+}
+
+#[test]
+fn decoded_text_runs() {
+    let char = |ch, shift, suspect| DecodedChar {
+        ch,
+        shift,
+        sample_index: 0,
+        suspect,
+    };
+    let chars = [
+        char('R', Shift::Letters, false),
+        char('Y', Shift::Letters, false),
+        char('3', Shift::Figures, false),
+        char('4', Shift::Figures, true),
+        char('5', Shift::Figures, false),
+    ];
+
+    assert_eq!(
+        text_runs(&chars),
+        vec![
+            (TextKind::Letters, "RY".to_owned()),
+            (TextKind::Figures, "3".to_owned()),
+            (TextKind::Suspect, "4".to_owned()),
+            (TextKind::Figures, "5".to_owned()),
+        ]
+    );
+    assert!(text_runs(&[]).is_empty());
+}
+
+#[test]
+fn histogram_binning() {
+    let mut bins = [0.0; 4];
+
+    histogram(
+        &[-1.0, -0.6, -0.4, 0.0, 0.9, 1.0, 1.5, -2.0],
+        -1.0..1.0,
+        &mut bins,
+    );
+    // two of the samples are outside of the range
+    assert_eq!(bins, [0.25, 0.125, 0.125, 0.25]);
+
+    histogram(&[], -1.0..1.0, &mut bins);
+    assert_eq!(bins, [0.0; 4]);
+}
+
+#[test]
+fn burst_finalization() {
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+
+    let mut bursts = BurstDetector::new(Duration::from_millis(500));
+    assert!(!bursts.poll(at(0)));
+
+    // characters arriving closer than the gap are one burst
+    assert!(bursts.chars_decoded(at(0)));
+    assert!(!bursts.chars_decoded(at(300)));
+    assert!(!bursts.poll(at(700)));
+    assert!(!bursts.chars_decoded(at(750)));
+
+    // ended only once
+    assert!(!bursts.poll(at(1249)));
+    assert!(bursts.poll(at(1250)));
+    assert!(!bursts.poll(at(1400)));
+    assert!(bursts.chars_decoded(at(2000)));
+
+    // the timer didn't run in time, the burst is ended by the next characters
+    assert!(bursts.poll(at(3000)));
+    assert!(bursts.chars_decoded(at(3000)));
+
+    // a zero gap never splits the text
+    let mut bursts = BurstDetector::new(Duration::from_millis(0));
+    assert!(!bursts.chars_decoded(at(0)));
+    assert!(!bursts.poll(at(60_000)));
+    assert!(!bursts.chars_decoded(at(60_000)));
+
+    assert_eq!(burst_timestamp(0), "[00:00:00] ");
+    assert_eq!(burst_timestamp(1_700_000_000), "[22:13:20] ");
+}
+
+#[test]
+fn signal_display_values() {
+    use std::f32::consts::FRAC_PI_2;
+
+    let samples = [
+        Complex32::new(3.0, 4.0),
+        Complex32::new(-1.0, 0.0),
+        Complex32::new(0.0, -2.0),
+    ];
+    let values = |display: SignalDisplay| {
+        samples
+            .iter()
+            .map(|&sample| display.values(sample))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        values(SignalDisplay::Real),
+        [(3.0, None), (-1.0, None), (0.0, None)]
+    );
+    assert_eq!(
+        values(SignalDisplay::RealImag),
+        [(3.0, Some(4.0)), (-1.0, Some(0.0)), (0.0, Some(-2.0))]
+    );
+    assert_eq!(
+        values(SignalDisplay::Magnitude),
+        [(5.0, None), (1.0, None), (2.0, None)]
+    );
+    assert_eq!(
+        values(SignalDisplay::Phase),
+        [
+            ((4.0f32).atan2(3.0), None),
+            (PI as f32, None),
+            (-FRAC_PI_2, None)
+        ]
+    );
+
+    // the second value ends up in the imaginary part, which isn't plotted otherwise
+    let mut applied = samples;
+    SignalDisplay::Magnitude.apply(&mut applied);
+    assert_eq!(applied[0], Complex32::new(5.0, 0.0));
+    let mut applied = samples;
+    SignalDisplay::RealImag.apply(&mut applied);
+    assert_eq!(applied, samples);
+}
+
+#[test]
+fn point_filling() {
+    let points = |len: usize| (0..len).map(|i| (i as f64 * 0.5, -(i as f64)));
+
+    unsafe {
+        let check = |vector: &CppBox<QVectorOfQPointF>, len: usize| {
+            for (i, (x, y)) in points(len).enumerate() {
+                let point = vector.at(i as i32);
+                assert_eq!((point.x(), point.y()), (x, y), "point {}", i);
+            }
+        };
+
+        // the direct write has to give the same points as qt itself
+        let vector = QVectorOfQPointF::new_0a();
+        vector.resize(100);
+        fill_points(&vector, points(100));
+        check(&vector, 100);
+
+        let vector = QVectorOfQPointF::new_0a();
+        vector.resize(100);
+        fill_points_slow(&vector, points(100));
+        check(&vector, 100);
+
+        // the points after the written ones are left alone
+        let vector = QVectorOfQPointF::new_0a();
+        vector.resize(10);
+        fill_points(&vector, points(4));
+        check(&vector, 4);
+        assert_eq!((vector.at(4).x(), vector.at(9).y()), (0.0, 0.0));
+
+        // a single point can't be measured, it goes the slow way
+        let vector = QVectorOfQPointF::new_0a();
+        vector.resize(1);
+        fill_points(&vector, points(1));
+        check(&vector, 1);
+    }
+}
+
+#[test]
+fn auto_run_on_device_created() {
+    // without auto_run the receiving starts only if run was pressed before
+    assert_eq!(run_after_device_created(false, false, true), (false, false));
+    assert_eq!(run_after_device_created(true, false, true), (true, true));
+
+    assert_eq!(run_after_device_created(false, true, true), (true, true));
+    assert_eq!(run_after_device_created(true, true, true), (true, true));
+
+    // an invalid receiver configuration keeps the run state for when it is fixed
+    assert_eq!(run_after_device_created(false, true, false), (true, false));
+    assert_eq!(run_after_device_created(true, false, false), (true, false));
+}
@@ -6,13 +6,17 @@ use std::{
 
 use qt_charts::qt_core::{qs, CheckState, QBox, SlotNoArgs, SlotOfInt};
 use qt_widgets::{
-    cpp_core::Ptr, q_form_layout::FieldGrowthPolicy, QCheckBox, QComboBox, QDoubleSpinBox,
-    QFormLayout, QGroupBox, QPushButton, QVBoxLayout,
+    cpp_core::Ptr,
+    q_form_layout::{FieldGrowthPolicy, ItemRole},
+    QCheckBox, QComboBox, QDoubleSpinBox, QFormLayout, QGroupBox, QHBoxLayout, QLabel, QPushButton,
+    QVBoxLayout, QWidget,
 };
 
 use crate::{
     app_settings::AppSettings,
-    gui_groups::handle_send_result,
+    favorites::{self, Favorite},
+    gui_groups::{decode_group::DecodeGroup, handle_send_result, ApplyMode, Change},
+    units::{self, FrequencyUnit, FREQUENCY_UNITS},
     worker::{
         worker::{DeviceBoundCommand, GuiBoundEvent},
         worker_manager::{DeviceManager, ReceiverState, ValueRanges},
@@ -24,69 +28,140 @@ enum Samplerate {
     Values(QBox<QComboBox>),
 }
 
+// where the samplerate row is put in the form, right below the frequency
+const SAMPLERATE_ROW: i32 = 1;
+
 pub struct ReceiveGroup {
-    automatic_update: QBox<QCheckBox>,
     frequency: QBox<QDoubleSpinBox>,
+    frequency_unit_select: QBox<QComboBox>,
+    // the unit the frequency spinbox currently displays, the value ranges are always kept in MHz
+    frequency_unit: Cell<FrequencyUnit>,
     // most devices provide only a set of valid values for samplerate
     // some are able to cover a range though, :(
     samplerate: RefCell<Samplerate>,
     gain: QBox<QDoubleSpinBox>,
+    gain_unit_select: QBox<QComboBox>,
+    // the gain is entered in percent of the gain range instead of dB, ReceiverState always uses dB
+    gain_percent: Cell<bool>,
     // some devices, for example RTL-SDR, do not allow setting bandwidth
     // currently it set if the valid bandwith range returned by the device is empty
     bandwidth_available: Cell<bool>,
     automatic_gain: QBox<QCheckBox>,
     automatic_dc_offset: QBox<QCheckBox>,
+    iq_correction: QBox<QCheckBox>,
+    // the first item leaves the clock to the driver, the row is only shown for devices which report some sources
+    clock_source: QBox<QComboBox>,
+    clock_source_label: QBox<QLabel>,
+    carrier_offset_label: QBox<QLabel>,
+    center_btn: QBox<QPushButton>,
+    // the bandwidth the device actually uses, it isn't entered but follows the samplerate
+    bandwidth_label: QBox<QLabel>,
+    // the last offset of the strongest signal measured by the worker in Hz
+    carrier_offset: Cell<Option<f64>>,
     apply_btn: QBox<QPushButton>,
+    // why the receiver was not configured, hidden again once it is
+    warning: QBox<QLabel>,
+    // one button for every favorite, the widget is rebuilt whenever the list changes
+    favorites: RefCell<Vec<Favorite>>,
+    favorites_widget: RefCell<QBox<QWidget>>,
+    add_favorite_btn: QBox<QPushButton>,
+    remove_favorite_btn: QBox<QPushButton>,
+    // the favorites store and recall the decoding mode too
+    decode_group: RefCell<Option<Rc<DecodeGroup>>>,
 
     group: QBox<QGroupBox>,
+    v_layout: QBox<QVBoxLayout>,
     form_layout: QBox<QFormLayout>,
 
     value_ranges: RefCell<Option<ValueRanges>>,
     device: Rc<DeviceManager>,
-    settings: Rc<AppSettings>,
+    // replaced when the settings are applied from the outside, like when restoring the defaults
+    settings: RefCell<Rc<AppSettings>>,
+    apply_mode: Rc<ApplyMode>,
 }
 
 impl ReceiveGroup {
     pub unsafe fn new(
         device: Rc<DeviceManager>,
         settings: Rc<AppSettings>,
+        apply_mode: Rc<ApplyMode>,
     ) -> (Rc<Self>, Ptr<QGroupBox>) {
         let group = QGroupBox::new();
         group.set_title(&qs("Receive"));
 
         let v = QVBoxLayout::new_0a();
 
-        let automatic_update = QCheckBox::new();
-        automatic_update.set_checked(settings.auto_update);
-        automatic_update.set_text(&qs("Automatic update"));
-        v.add_widget(&automatic_update);
-
         let form = QFormLayout::new_0a();
         // form.set_label_alignment(AlignmentFlag::AlignLeft.into());
         form.set_field_growth_policy(FieldGrowthPolicy::AllNonFixedFieldsGrow);
         v.add_layout_1a(&form);
         group.set_layout(&v);
 
+        let unit = settings_unit(&settings.frequency_unit);
+
         let frequency = QDoubleSpinBox::new_0a();
-        frequency.set_suffix(&qs(" MHz"));
         // start with practically unlimited range so that the following set_value isn't accidentally rounded
         // the correct range is later set when the actual Device is created and queried for ranges
         // TODO maybe leave the range uncapped this way and rely only on the clamp_value function
-        frequency.set_range(0.0, 10000.0);
-        frequency.set_value(settings.frequency);
-        form.add_row_q_string_q_widget(&qs("Frequency"), &frequency);
+        set_frequency_unit(
+            &frequency,
+            unit,
+            0.0,
+            FrequencyUnit::MHz.rescale(10000.0, unit),
+        );
+        // the frequency is always saved in MHz
+        frequency.set_value(FrequencyUnit::MHz.rescale(settings.frequency, unit));
+
+        let frequency_unit_select = QComboBox::new_0a();
+        for unit in FREQUENCY_UNITS {
+            frequency_unit_select.add_item_q_string(&qs(unit.name()));
+        }
+        frequency_unit_select
+            .set_current_index(FREQUENCY_UNITS.iter().position(|u| *u == unit).unwrap() as i32);
+
+        let frequency_row = QHBoxLayout::new_0a();
+        frequency_row.add_widget(&frequency);
+        frequency_row.add_widget(&frequency_unit_select);
+        form.add_row_q_string_q_layout(&qs("Frequency"), &frequency_row);
 
         let samplerate = QDoubleSpinBox::new_0a();
         samplerate.set_suffix(&qs(" MSps"));
+        // 1 kSps resolution, common rates like 2.048 MSps would be rounded with the default of two decimals
+        samplerate.set_decimals(3);
         samplerate.set_range(0.0, 10000.0);
         samplerate.set_value(settings.samplerate);
         form.add_row_q_string_q_widget(&qs("Samplerate"), &samplerate);
 
+        let carrier_offset_label = QLabel::new();
+        carrier_offset_label.set_text(&qs(carrier_offset_text(None)));
+        let center_btn = QPushButton::new();
+        center_btn.set_text(&qs("Center on peak"));
+        center_btn.set_enabled(false);
+
+        let carrier_offset_row = QHBoxLayout::new_0a();
+        carrier_offset_row.add_widget(&carrier_offset_label);
+        carrier_offset_row.add_widget(&center_btn);
+        form.add_row_q_string_q_layout(&qs("Carrier offset"), &carrier_offset_row);
+
         let gain = QDoubleSpinBox::new_0a();
         gain.set_suffix(&qs(" dB"));
         gain.set_range(0.0, 10000.0);
         gain.set_value(settings.gain);
-        form.add_row_q_string_q_widget(&qs("Gain"), &gain);
+
+        let gain_unit_select = QComboBox::new_0a();
+        gain_unit_select.add_item_q_string(&qs("dB"));
+        gain_unit_select.add_item_q_string(&qs("%"));
+        // the percentage needs the gain range of the device
+        gain_unit_select.set_enabled(false);
+
+        let gain_row = QHBoxLayout::new_0a();
+        gain_row.add_widget(&gain);
+        gain_row.add_widget(&gain_unit_select);
+        form.add_row_q_string_q_layout(&qs("Gain"), &gain_row);
+
+        let bandwidth_label = QLabel::new();
+        bandwidth_label.set_text(&qs(bandwidth_text(None)));
+        form.add_row_q_string_q_widget(&qs("Bandwidth"), &bandwidth_label);
 
         let automatic_gain = QCheckBox::new();
         automatic_gain.set_checked(settings.automatic_gain);
@@ -96,26 +171,81 @@ impl ReceiveGroup {
         automatic_dc_offset.set_checked(settings.automatic_dc_offset);
         form.add_row_q_string_q_widget(&qs("Automatic DC offset"), &automatic_dc_offset);
 
+        let iq_correction = QCheckBox::new();
+        iq_correction.set_checked(settings.iq_correction);
+        iq_correction.set_tool_tip(&qs(
+            "Correct the IQ imbalance which puts a mirror image of strong signals on the other side of the center",
+        ));
+        form.add_row_q_string_q_widget(&qs("IQ correction"), &iq_correction);
+
+        let clock_source_label = QLabel::from_q_string(&qs("Clock source"));
+        let clock_source = QComboBox::new_0a();
+        clock_source.set_tool_tip(&qs(
+            "The reference the device is clocked from, like an external or a GPS disciplined oscillator",
+        ));
+        form.add_row_q_widget_q_widget(&clock_source_label, &clock_source);
+        clock_source_label.set_visible(false);
+        clock_source.set_visible(false);
+
+        // the buttons are added in init() once they can call back into the group
+        let favorites_widget = QWidget::new_0a();
+        v.add_widget(&favorites_widget);
+
+        let add_favorite_btn = QPushButton::from_q_string(&qs("Add favorite"));
+        add_favorite_btn.set_tool_tip(&qs(
+            "Save the current frequency, samplerate and decoding mode, a favorite at the same frequency is replaced",
+        ));
+        let remove_favorite_btn = QPushButton::from_q_string(&qs("Remove favorite"));
+        remove_favorite_btn.set_tool_tip(&qs("Remove the favorite at the current frequency"));
+
+        let favorites_row = QHBoxLayout::new_0a();
+        favorites_row.add_widget(&add_favorite_btn);
+        favorites_row.add_widget(&remove_favorite_btn);
+        v.add_layout_1a(&favorites_row);
+
+        let warning = QLabel::new();
+        warning.set_word_wrap(true);
+        warning.set_visible(false);
+        v.add_widget(&warning);
+
         let apply_btn = QPushButton::new();
         apply_btn.set_text(&qs("Apply"));
         v.add_widget(&apply_btn);
 
         let ptr = group.as_ptr();
         let s = Rc::new(Self {
-            automatic_update,
             samplerate: RefCell::new(Samplerate::Ranges(samplerate)),
             frequency,
+            frequency_unit_select,
+            frequency_unit: Cell::new(unit),
             bandwidth_available: Cell::new(true),
             gain,
+            gain_unit_select,
+            gain_percent: Cell::new(false),
             automatic_gain,
             automatic_dc_offset,
+            iq_correction,
+            clock_source,
+            clock_source_label,
+            carrier_offset_label,
+            center_btn,
+            bandwidth_label,
+            carrier_offset: Cell::new(None),
             apply_btn,
+            warning,
+            favorites: RefCell::new(settings.favorites.clone()),
+            favorites_widget: RefCell::new(favorites_widget),
+            add_favorite_btn,
+            remove_favorite_btn,
+            decode_group: RefCell::new(None),
             group,
+            v_layout: v,
             form_layout: form,
 
             value_ranges: RefCell::new(None),
             device,
-            settings,
+            settings: RefCell::new(settings),
+            apply_mode,
         });
 
         s.apply_btn.set_enabled(false);
@@ -124,37 +254,78 @@ impl ReceiveGroup {
         (s, ptr)
     }
     unsafe fn update_receiver_configuration(&self, force: bool) {
-        // everything is in megahertz or megasamples/second
-        const MIL: f64 = 1_000_000.0;
+        // the samplerate and bandwidth ranges are in megahertz or megasamples/second
+        let value_ranges = self.value_ranges.borrow();
+        // the checkboxes can be toggled before there is a device to configure
+        let ranges = match value_ranges.as_ref() {
+            Some(ranges) => ranges,
+            None => return,
+        };
 
         let samplerate = match &*self.samplerate.borrow() {
-            Samplerate::Ranges(spinbox) => spinbox.value(),
+            Samplerate::Ranges(spinbox) => clamp_to_ranges(spinbox.value(), &ranges.samplerate),
             // in the case of only discreet values being available, minimum==maximum
             // simply get it from the Range minimum
-            Samplerate::Values(combox) => {
-                self.value_ranges.borrow().as_ref().unwrap().samplerate
-                    [combox.current_index() as usize]
-                    .minimum
+            Samplerate::Values(combox) => ranges
+                .samplerate
+                .get(combox.current_index() as usize)
+                .map(|r| r.minimum),
+        };
+
+        let samplerate = match samplerate {
+            Some(samplerate) => samplerate,
+            None => {
+                self.show_warning(Some(
+                    "The device reports no valid samplerate, not configuring the receiver",
+                ));
+                return;
             }
         };
 
-        let state = ReceiverState {
+        // set bandwidth to 75% of samplerate, seems to work fine for OsmoSDR
+        // https://github.com/osmocom/gr-osmosdr/blob/e5bee0820f493d2ff048ba4ed18be4d0c7976a87/lib/soapy/soapy_sink_c.cc#L297
+        // it is clamped here so that the driver isn't asked for something it would error on
+        let bandwidth = if self.bandwidth_available.get() {
+            match clamp_to_ranges(samplerate * 0.75, &ranges.bandwidth) {
+                Some(bandwidth) => bandwidth,
+                None => {
+                    self.show_warning(Some(
+                        "The device reports no valid bandwidth, not configuring the receiver",
+                    ));
+                    return;
+                }
+            }
+        } else {
+            0.0
+        };
+
+        drop(value_ranges);
+
+        let state = ReceiverState::builder()
             // TODO channel is hardcoded for now, it seems it is not too useful to be able to specify it, at least on my device
-            channel: 0,
-            samplerate: samplerate * MIL,
-            frequency: self.frequency.value() * MIL,
-            // set bandwidth to 75% of samplerate, seems to work fine for OsmoSDR
-            // https://github.com/osmocom/gr-osmosdr/blob/e5bee0820f493d2ff048ba4ed18be4d0c7976a87/lib/soapy/soapy_sink_c.cc#L297
-            // hopefully the driver is fine with rounding it to an available value, it is possible to be more smart about it
-            bandwidth: if self.bandwidth_available.get() {
-                samplerate * MIL * 0.75
-            } else {
-                0.0
-            },
-            gain: self.gain.value(),
-            automatic_gain: self.automatic_gain.is_checked(),
-            automatic_dc_offset: self.automatic_dc_offset.is_checked(),
+            .channel(0)
+            .samplerate(units::from_mega(samplerate))
+            .frequency(self.frequency_unit.get().to_hz(self.frequency.value()))
+            .bandwidth(units::from_mega(bandwidth))
+            .gain_db(self.gain_db())
+            .automatic_gain(self.automatic_gain.is_checked())
+            .automatic_dc_offset(self.automatic_dc_offset.is_checked())
+            .iq_correction(self.iq_correction.is_checked())
+            .clock_source(&self.clock_source_name())
+            .buffer_samples(self.settings.borrow().buffer_samples as usize)
+            .settle_us(self.settings.borrow().settle_us as u64)
+            .receive_timeout_us(self.settings.borrow().receive_timeout_us as u64)
+            .event_budget_us(self.settings.borrow().worker_event_budget_us as u64)
+            .build();
+
+        let state = match state {
+            Ok(state) => state,
+            Err(e) => {
+                self.show_warning(Some(&format!("Not configuring the receiver: {}", e)));
+                return;
+            }
         };
+        self.show_warning(None);
 
         // nothing changed, this is possible because this function is called on editing_finished signal from qt
         // this signal gets sent if for example you click into the value field of a spinbox and then focus something else
@@ -168,64 +339,224 @@ impl ReceiveGroup {
                 .send_command(DeviceBoundCommand::SetReceiver(state)),
         );
     }
+    unsafe fn show_warning(&self, text: Option<&str>) {
+        match text {
+            Some(text) => {
+                log::warn!("{}", text);
+                self.warning.set_text(&qs(text));
+                self.warning.set_visible(true);
+            }
+            None => {
+                self.warning.clear();
+                self.warning.set_visible(false);
+            }
+        }
+    }
+    // the frequency is saved in MHz regardless of the unit it is displayed in
+    unsafe fn frequency_mhz(&self) -> f64 {
+        self.frequency_unit
+            .get()
+            .rescale(self.frequency.value(), FrequencyUnit::MHz)
+    }
+    unsafe fn samplerate_mhz(&self) -> f64 {
+        match &*self.samplerate.borrow() {
+            Samplerate::Ranges(spinbox) => spinbox.value(),
+            // in the case of only discreet values being available, minimum==maximum
+            // simply get it from the Range minimum
+            Samplerate::Values(combox) => {
+                self.value_ranges.borrow().as_ref().unwrap().samplerate
+                    [combox.current_index() as usize]
+                    .minimum
+            }
+        }
+    }
+    // the signals of the combobox are blocked so that nothing is sent to the device
+    unsafe fn set_samplerate_mhz(&self, samplerate: f64) {
+        match &*self.samplerate.borrow() {
+            Samplerate::Ranges(spinbox) => spinbox.set_value(samplerate),
+            // keep the current samplerate if the device doesn't offer this one
+            Samplerate::Values(combox) => {
+                let index = self
+                    .value_ranges
+                    .borrow()
+                    .as_ref()
+                    .and_then(|ranges| samplerate_index(&ranges.samplerate, samplerate));
+
+                if let Some(index) = index {
+                    combox.block_signals(true);
+                    combox.set_current_index(index as i32);
+                    combox.block_signals(false);
+                }
+            }
+        }
+    }
+    // the form row of the current samplerate widget, -1 if it isn't in the form
+    unsafe fn samplerate_row(&self) -> i32 {
+        let mut row = -1;
+        let mut role = ItemRole::FieldRole;
+
+        match &*self.samplerate.borrow() {
+            Samplerate::Ranges(spinbox) => self
+                .form_layout
+                .get_widget_position(spinbox, &mut row, &mut role),
+            Samplerate::Values(combox) => self
+                .form_layout
+                .get_widget_position(combox, &mut row, &mut role),
+        }
+
+        row
+    }
+    // fills the clock sources of a new device and selects the saved one, the signals are blocked so that nothing is sent yet
+    unsafe fn set_clock_sources(&self, sources: &[String]) {
+        self.clock_source.block_signals(true);
+        self.clock_source.clear();
+        self.clock_source.add_item_q_string(&qs("Device default"));
+        for source in sources {
+            self.clock_source.add_item_q_string(&qs(source));
+        }
+        self.select_clock_source(&self.settings.borrow().clock_source);
+        self.clock_source.block_signals(false);
+
+        let visible = !sources.is_empty();
+        self.clock_source_label.set_visible(visible);
+        self.clock_source.set_visible(visible);
+    }
+    // a source the device doesn't have falls back to the default
+    unsafe fn select_clock_source(&self, name: &str) {
+        let index = match name {
+            "" => 0,
+            name => self.clock_source.find_text_1a(&qs(name)).max(0),
+        };
+        self.clock_source.set_current_index(index);
+    }
+    // empty for the default
+    unsafe fn clock_source_name(&self) -> String {
+        if self.clock_source.current_index() > 0 {
+            self.clock_source.current_text().to_std_string()
+        } else {
+            String::new()
+        }
+    }
+    // the gain in dB regardless of the unit it is entered in
+    unsafe fn gain_db(&self) -> f64 {
+        match (self.gain_percent.get(), &*self.value_ranges.borrow()) {
+            (true, Some(ranges)) => gain_percent_to_db(self.gain.value(), &ranges.gain),
+            _ => self.gain.value(),
+        }
+    }
+    unsafe fn set_gain_db(&self, db: f64) {
+        let value = match (self.gain_percent.get(), &*self.value_ranges.borrow()) {
+            (true, Some(ranges)) => gain_db_to_percent(db, &ranges.gain),
+            _ => db,
+        };
+
+        self.gain.set_value(value);
+    }
+    unsafe fn set_gain_unit(&self, percent: bool) {
+        // the same gain is displayed in the new unit
+        let db = self.gain_db();
+        self.gain_percent.set(percent);
+
+        if percent {
+            self.gain.set_suffix(&qs(" %"));
+            self.gain.set_range(0.0, 100.0);
+        } else {
+            self.gain.set_suffix(&qs(" dB"));
+            if let Some(ranges) = &*self.value_ranges.borrow() {
+                self.gain
+                    .set_range(ranges.gain.minimum, ranges.gain.maximum);
+            }
+        }
+
+        self.set_gain_db(db);
+    }
     unsafe fn init(self: &Rc<Self>) {
         let Self {
-            automatic_update,
             frequency,
+            frequency_unit_select,
             gain,
+            gain_unit_select,
             automatic_gain,
             automatic_dc_offset,
+            iq_correction,
+            clock_source,
+            center_btn,
             apply_btn,
+            add_favorite_btn,
+            remove_favorite_btn,
             group,
             ..
         } = self.borrow();
 
-        // let s = self.clone();
-        // automatic_update
-        //     .state_changed()
-        //     .connect(&SlotOfInt::new(group, move |state| {
-        //         let enabled = state == CheckState::Unchecked.into() && s.device.get_device_valid();
-        //         s.apply_btn.set_enabled(enabled);
-
-        //         if state == CheckState::Checked.into() {
-        //             s.update_receiver_configuration(false);
-        //         }
-        //     }));
-
-        // another extremely bikeshedded (bikeshad?) macro
-        macro_rules! setup_values_changed {
-            ($name:ident, $iter:path) => {
-                let s = self.clone();
-                $name
-                    .editing_finished()
-                    .connect(&SlotNoArgs::new(group, move || {
-                        let ranges = s.value_ranges.borrow_mut();
-                        let r = &ranges.as_ref().unwrap().$name;
-
-                        clamp_value(&s.$name, &mut $iter(r));
-
-                        drop(ranges);
-
-                        if s.automatic_update.is_checked() && s.device.get_device_valid() {
-                            s.update_receiver_configuration(false);
-                        }
-                    }));
-            };
-        }
+        let s = self.clone();
+        gain.editing_finished()
+            .connect(&SlotNoArgs::new(group, move || {
+                // a percentage is already limited by the range of the spinbox, the step of the gain range is in dB
+                if !s.gain_percent.get() {
+                    let ranges = s.value_ranges.borrow_mut();
+                    clamp_value(
+                        &s.gain,
+                        std::slice::from_ref(&ranges.as_ref().unwrap().gain),
+                    );
+                }
+
+                if s.apply_mode.sends(Change::Edited) && s.device.get_device_valid() {
+                    s.update_receiver_configuration(false);
+                }
+            }));
+
+        let s = self.clone();
+        gain_unit_select
+            .current_index_changed()
+            .connect(&SlotOfInt::new(group, move |i| s.set_gain_unit(i == 1)));
+
+        let s = self.clone();
+        frequency
+            .editing_finished()
+            .connect(&SlotNoArgs::new(group, move || s.frequency_edited()));
 
-        // setup_values_changed! {samplerate, std::iter::IntoIterator::into_iter};
-        setup_values_changed! {frequency, std::iter::IntoIterator::into_iter};
-        setup_values_changed! {gain, std::iter::once};
+        let s = self.clone();
+        frequency_unit_select
+            .current_index_changed()
+            .connect(&SlotOfInt::new(group, move |i| {
+                let previous = s.frequency_unit.get();
+                let unit = FREQUENCY_UNITS[i as usize];
+
+                // keep the absolute frequency the same, only the way it is displayed changes
+                let value = previous.rescale(s.frequency.value(), unit);
+                let min = previous.rescale(s.frequency.minimum(), unit);
+                let max = previous.rescale(s.frequency.maximum(), unit);
+
+                set_frequency_unit(&s.frequency, unit, min, max);
+                s.frequency.set_value(value);
+                s.frequency_unit.set(unit);
+            }));
 
         let s = self.clone();
         let checkbox_slot = SlotNoArgs::new(group, move || {
-            if s.automatic_update.is_checked() {
+            if s.apply_mode.sends(Change::Edited) {
                 s.update_receiver_configuration(false);
             }
         });
 
         automatic_gain.state_changed().connect(&checkbox_slot);
         automatic_dc_offset.state_changed().connect(&checkbox_slot);
+        iq_correction.state_changed().connect(&checkbox_slot);
+        clock_source.current_index_changed().connect(&checkbox_slot);
+
+        // retunes by the measured offset so that the strongest signal ends up at the center
+        let s = self.clone();
+        center_btn
+            .clicked()
+            .connect(&SlotNoArgs::new(group, move || {
+                if let Some(offset) = s.carrier_offset.get() {
+                    let unit = s.frequency_unit.get();
+                    let frequency = unit.to_hz(s.frequency.value()) + offset;
+                    // the spinbox keeps the frequency in the range of the device
+                    s.frequency.set_value(unit.from_hz(frequency));
+                    s.update_receiver_configuration(false);
+                }
+            }));
 
         let s = self.clone();
         apply_btn
@@ -233,22 +564,157 @@ impl ReceiveGroup {
             .connect(&SlotNoArgs::new(group, move || {
                 s.update_receiver_configuration(false);
             }));
+
+        let s = self.clone();
+        add_favorite_btn
+            .clicked()
+            .connect(&SlotNoArgs::new(group, move || {
+                let mode = match &*s.decode_group.borrow() {
+                    Some(decode_group) => decode_group.mode_name().to_owned(),
+                    None => s.settings.borrow().decoder.clone(),
+                };
+                let favorite = Favorite {
+                    frequency: s.frequency_mhz(),
+                    samplerate: s.samplerate_mhz(),
+                    mode,
+                };
+
+                favorites::add_favorite(&mut s.favorites.borrow_mut(), favorite);
+                s.rebuild_favorites();
+            }));
+
+        let s = self.clone();
+        remove_favorite_btn
+            .clicked()
+            .connect(&SlotNoArgs::new(group, move || {
+                let removed =
+                    favorites::remove_favorite(&mut s.favorites.borrow_mut(), s.frequency_mhz());
+
+                if removed.is_some() {
+                    s.rebuild_favorites();
+                }
+            }));
+
+        self.rebuild_favorites();
+    }
+    pub fn set_decode_group(&self, decode_group: Rc<DecodeGroup>) {
+        self.decode_group.replace(Some(decode_group));
+    }
+    // replaces the favorite buttons with ones for the current list
+    unsafe fn rebuild_favorites(self: &Rc<Self>) {
+        let favorites_widget = QWidget::new_0a();
+        let layout = QHBoxLayout::new_1a(&favorites_widget);
+        layout.set_contents_margins_4a(0, 0, 0, 0);
+
+        for favorite in self.favorites.borrow().iter() {
+            let button = QPushButton::from_q_string(&qs(favorite.label()));
+            button.set_tool_tip(&qs(favorite.description()));
+
+            // the slots are owned by the widget so they are dropped together with the buttons
+            let s = self.clone();
+            let frequency = favorite.frequency;
+            button
+                .clicked()
+                .connect(&SlotNoArgs::new(&favorites_widget, move || {
+                    s.recall_favorite(frequency)
+                }));
+
+            layout.add_widget(&button);
+        }
+
+        self.v_layout
+            .replace_widget_2a(&*self.favorites_widget.borrow(), &favorites_widget);
+        // the replaced widget is still a child of the group, it has to be deleted explicitly
+        self.favorites_widget
+            .replace(favorites_widget)
+            .delete_later();
+    }
+    // tunes to the favorite right away, without waiting for Apply even if the changes aren't applied automatically
+    unsafe fn recall_favorite(&self, frequency: f64) {
+        let favorite = match favorites::recall_favorite(&self.favorites.borrow(), frequency) {
+            Some(favorite) => favorite.clone(),
+            None => return,
+        };
+
+        self.frequency
+            .set_value(FrequencyUnit::MHz.rescale(favorite.frequency, self.frequency_unit.get()));
+        clamp_value(&self.frequency, &self.frequency_ranges());
+        self.set_samplerate_mhz(favorite.samplerate);
+
+        if let Some(decode_group) = &*self.decode_group.borrow() {
+            decode_group.select_mode(&favorite.mode);
+        }
+
+        if self.device.get_device_valid() {
+            self.update_receiver_configuration(false);
+        }
+    }
+    // the frequency ranges of the device rescaled to the displayed unit, empty without a device
+    unsafe fn frequency_ranges(&self) -> Vec<soapysdr::Range> {
+        let unit = self.frequency_unit.get();
+
+        self.value_ranges
+            .borrow()
+            .as_ref()
+            .map_or(Vec::new(), |ranges| {
+                FrequencyUnit::MHz.rescale_ranges(&ranges.frequency, unit)
+            })
+    }
+    unsafe fn frequency_edited(&self) {
+        clamp_value(&self.frequency, &self.frequency_ranges());
+
+        if self.apply_mode.sends(Change::Edited) && self.device.get_device_valid() {
+            self.update_receiver_configuration(false);
+        }
+    }
+    // tunes by the fine or the coarse step from the settings, up if direction is positive
+    // the new frequency is handled like one typed into the spinbox
+    pub unsafe fn step_frequency(&self, direction: f64, coarse: bool) {
+        if !self.frequency.is_enabled() {
+            return;
+        }
+
+        let step_hz = {
+            let settings = self.settings.borrow();
+            if coarse {
+                settings.coarse_step_hz
+            } else {
+                settings.fine_step_hz
+            }
+        };
+        let step = self
+            .frequency_unit
+            .get()
+            .from_hz(direction.signum() * step_hz);
+
+        self.frequency.set_value(stepped_frequency(
+            self.frequency.value(),
+            step,
+            &self.frequency_ranges(),
+        ));
+        self.frequency_edited();
     }
     pub unsafe fn handle_event(self: &Rc<Self>, event: &mut Option<GuiBoundEvent>) {
         match event.as_ref().unwrap() {
             // it is incredibly ugly to be doing this replacement here and everytime the device changes
             // but this wouldn't be a gui project without bad code
-            GuiBoundEvent::DeviceCreated { channels_info } => {
+            GuiBoundEvent::DeviceCreated {
+                device_info,
+                channels_info,
+            } => {
                 let mut ranges = channels_info[0].ranges.clone();
 
-                // everything is in megahertz or megasamples/second
-                const MIL: f64 = 1_000_000.0;
+                self.set_clock_sources(&device_info.clock_sources);
 
-                // remove the samplerate widget, it will be replaced later
-                self.form_layout.remove_row_int(1);
+                // remove the samplerate widget of the previous device, it will be replaced later
+                // the row is looked up instead of assumed so that repeated DeviceCreated events can't remove anything else
+                let (remove, samplerate_row) = samplerate_rows(self.samplerate_row());
+                if let Some(row) = remove {
+                    self.form_layout.remove_row_int(row);
+                }
 
                 // the device supports only discreet samplerates
-                if ranges.samplerate[0].minimum == ranges.samplerate[0].maximum {
+                if is_discrete(&ranges.samplerate) {
                     let combox = QComboBox::new_0a();
 
                     // the index of the samplerate loaded from AppSettings
@@ -256,10 +722,10 @@ impl ReceiveGroup {
                     let mut set_samplerate_index = 0;
 
                     for (i, range) in ranges.samplerate.iter().enumerate() {
-                        let label = format!("{} MSps", range.minimum / MIL);
+                        let label = format!("{} MSps", units::to_mega(range.minimum));
                         combox.add_item_q_string(&qs(label));
 
-                        if range.minimum / MIL == self.settings.samplerate {
+                        if units::to_mega(range.minimum) == self.settings.borrow().samplerate {
                             // index is found
                             set_samplerate_index = i;
                         }
@@ -271,36 +737,35 @@ impl ReceiveGroup {
                     combox
                         .current_index_changed()
                         .connect(&SlotNoArgs::new(&combox, move || {
-                            if s.automatic_update.is_checked() {
+                            if s.apply_mode.sends(Change::Edited) {
                                 s.update_receiver_configuration(false);
                             }
                         }));
 
                     self.form_layout.insert_row_int_q_string_q_widget(
-                        1,
+                        samplerate_row,
                         &qs("Samplerate"),
                         &combox,
                     );
                     self.samplerate.replace(Samplerate::Values(combox));
                 } else {
-                    // NaN fun
-                    let min = ranges
-                        .samplerate
-                        .iter()
-                        .map(|r| r.minimum)
-                        .min_by(|a, b| a.partial_cmp(b).unwrap())
-                        .unwrap();
-                    let max = ranges
-                        .samplerate
-                        .iter()
-                        .map(|r| r.maximum)
-                        .max_by(|a, b| a.partial_cmp(b).unwrap())
-                        .unwrap();
-
                     let spinbox = QDoubleSpinBox::new_0a();
-                    spinbox.set_range(min / MIL, max / MIL);
+                    spinbox.set_suffix(&qs(" MSps"));
+                    spinbox.set_decimals(3);
+
+                    match ranges_bounds(&ranges.samplerate) {
+                        Some((min, max)) => {
+                            spinbox.set_range(units::to_mega(min), units::to_mega(max))
+                        }
+                        // nothing can be configured anyway, keep the wide range from ReceiveGroup::new
+                        None => {
+                            log::warn!("The device reports no samplerate range, disabling the samplerate control");
+                            spinbox.set_range(0.0, 10000.0);
+                            spinbox.set_enabled(false);
+                        }
+                    }
 
-                    spinbox.set_value(self.settings.samplerate);
+                    spinbox.set_value(self.settings.borrow().samplerate);
 
                     let s = self.clone();
                     spinbox
@@ -310,57 +775,56 @@ impl ReceiveGroup {
                             let r = &ranges.as_ref().unwrap().samplerate;
 
                             match &*s.samplerate.borrow() {
-                                Samplerate::Ranges(spinbox) => clamp_value(&spinbox, &mut r.iter()),
+                                Samplerate::Ranges(spinbox) => clamp_value(&spinbox, r),
                                 Samplerate::Values(_) => unreachable!(),
                             }
 
                             drop(ranges);
 
-                            if s.automatic_update.is_checked() {
+                            if s.apply_mode.sends(Change::Edited) {
                                 s.update_receiver_configuration(false);
                             }
                         }));
 
                     self.form_layout.insert_row_int_q_string_q_widget(
-                        1,
+                        samplerate_row,
                         &qs("Samplerate"),
                         &spinbox,
                     );
                     self.samplerate.replace(Samplerate::Ranges(spinbox));
                 }
 
-                let min = ranges
-                    .frequency
-                    .iter()
-                    .map(|r| r.minimum)
-                    .min_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap();
-                let max = ranges
-                    .frequency
-                    .iter()
-                    .map(|r| r.maximum)
-                    .max_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap();
-                self.frequency.set_range(min / MIL, max / MIL);
+                // the frequency spinbox outlives the device, a previous one may have disabled it
+                match ranges_bounds(&ranges.frequency) {
+                    Some((min, max)) => {
+                        let unit = self.frequency_unit.get();
+                        self.frequency
+                            .set_range(unit.from_hz(min), unit.from_hz(max));
+                        self.frequency.set_enabled(true);
+                    }
+                    None => {
+                        log::warn!("The device reports no frequency range, disabling the frequency control");
+                        self.frequency.set_enabled(false);
+                    }
+                }
 
                 self.bandwidth_available.set(!ranges.bandwidth.is_empty());
 
-                let min = ranges.gain.minimum;
-                let max = ranges.gain.maximum;
-                self.gain.set_range(min, max);
-
-                fn scale_to_mega(ranges: &mut Vec<soapysdr::Range>) {
-                    ranges.iter_mut().for_each(|s| {
-                        s.minimum /= MIL;
-                        s.maximum /= MIL;
-                        s.step /= MIL
-                    });
+                // a percentage stays the same, it's applied to the new range
+                if !self.gain_percent.get() {
+                    self.gain
+                        .set_range(ranges.gain.minimum, ranges.gain.maximum);
                 }
+                // a driver without a gain range or with a fixed gain leaves nothing to set
+                let gain_adjustable = ranges.gain.minimum < ranges.gain.maximum;
+                self.gain.set_enabled(gain_adjustable);
+                self.gain_unit_select.set_enabled(gain_adjustable);
 
                 // scale the ranges so that they match the displayed units
-                scale_to_mega(&mut ranges.samplerate);
-                scale_to_mega(&mut ranges.frequency);
-                scale_to_mega(&mut ranges.bandwidth);
+                let (hz, mhz) = (FrequencyUnit::Hz, FrequencyUnit::MHz);
+                ranges.samplerate = hz.rescale_ranges(&ranges.samplerate, mhz);
+                ranges.frequency = hz.rescale_ranges(&ranges.frequency, mhz);
+                ranges.bandwidth = hz.rescale_ranges(&ranges.bandwidth, mhz);
 
                 log::debug!("Receiver value ranges: {:#?}", ranges);
 
@@ -371,76 +835,697 @@ impl ReceiveGroup {
 
                 self.apply_btn.set_enabled(true);
             }
+            // show what the device actually uses, none of the setters here trigger the editing_finished slots
+            GuiBoundEvent::ReceiverConfigured { actual } => {
+                self.frequency
+                    .set_value(self.frequency_unit.get().from_hz(actual.frequency));
+                self.set_gain_db(actual.gain);
+                self.bandwidth_label
+                    .set_text(&qs(bandwidth_text(Some(actual.bandwidth))));
+
+                match &*self.samplerate.borrow() {
+                    Samplerate::Ranges(spinbox) => {
+                        spinbox.set_value(units::to_mega(actual.samplerate))
+                    }
+                    Samplerate::Values(combox) => {
+                        let value_ranges = self.value_ranges.borrow();
+                        let closest = value_ranges.as_ref().and_then(|ranges| {
+                            (0..ranges.samplerate.len()).min_by(|&a, &b| {
+                                let distance = |i: usize| {
+                                    (units::from_mega(ranges.samplerate[i].minimum)
+                                        - actual.samplerate)
+                                        .abs()
+                                };
+                                distance(a).partial_cmp(&distance(b)).unwrap()
+                            })
+                        });
+
+                        if let Some(index) = closest {
+                            // unlike the spinboxes this would send the configuration again
+                            combox.block_signals(true);
+                            combox.set_current_index(index as i32);
+                            combox.block_signals(false);
+                        }
+                    }
+                }
+            }
+            GuiBoundEvent::CarrierOffset { offset } => {
+                self.carrier_offset.set(Some(*offset));
+                self.carrier_offset_label
+                    .set_text(&qs(carrier_offset_text(Some(*offset))));
+                self.center_btn.set_enabled(self.device.get_device_valid());
+            }
             GuiBoundEvent::DeviceDestroyed | GuiBoundEvent::WorkerReset => {
                 self.apply_btn.set_enabled(false);
+
+                self.carrier_offset.set(None);
+                self.carrier_offset_label
+                    .set_text(&qs(carrier_offset_text(None)));
+                self.center_btn.set_enabled(false);
+
+                self.bandwidth_label.set_text(&qs(bandwidth_text(None)));
+
+                self.clock_source_label.set_visible(false);
+                self.clock_source.set_visible(false);
             }
             _ => (),
         }
     }
+    // the range the gain is entered relative to, None while it is entered in dB or nothing is known about the device
+    fn gain_percent_range<'a>(
+        &self,
+        ranges: &'a Option<ValueRanges>,
+    ) -> Option<&'a soapysdr::Range> {
+        ranges
+            .as_ref()
+            .filter(|_| self.gain_percent.get())
+            .map(|ranges| &ranges.gain)
+    }
     pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
-        let AppSettings {
-            auto_update,
-            frequency,
-            samplerate,
-            gain,
-            automatic_gain,
-            automatic_dc_offset,
-            ..
-        } = settings;
+        let loaded = self.settings.borrow();
+        let values = ReceiveValues {
+            frequency: self.frequency.value(),
+            frequency_unit: self.frequency_unit.get(),
+            samplerate: self.samplerate_mhz(),
+            gain: self.gain.value(),
+            automatic_gain: self.automatic_gain.is_checked(),
+            automatic_dc_offset: self.automatic_dc_offset.is_checked(),
+            iq_correction: self.iq_correction.is_checked(),
+            // without a device that has some sources there is nothing to choose from, keep the loaded value
+            clock_source: if self.clock_source.count() > 1 {
+                self.clock_source_name()
+            } else {
+                loaded.clock_source.clone()
+            },
+            favorites: self.favorites.borrow().clone(),
+        };
 
-        // TODO deduplicate this from values_changed()
-        *auto_update = self.automatic_update.is_checked();
+        let ranges = self.value_ranges.borrow();
+        values.populate(settings, &loaded, self.gain_percent_range(&ranges));
+    }
+    // the signals are blocked so that nothing is sent to the device, the configuration is sent with Apply as usual
+    pub unsafe fn apply_settings(self: &Rc<Self>, settings: &AppSettings) {
+        self.settings.replace(Rc::new(settings.clone()));
 
-        *frequency = self.frequency.value();
-        *samplerate = match &*self.samplerate.borrow() {
-            Samplerate::Ranges(spinbox) => spinbox.value(),
-            // in the case of only discreet values being available, minimum==maximum
-            // simply get it from the Range minimum
-            Samplerate::Values(combox) => {
-                self.value_ranges.borrow().as_ref().unwrap().samplerate
-                    [combox.current_index() as usize]
-                    .minimum
-            }
+        let values = {
+            let ranges = self.value_ranges.borrow();
+            ReceiveValues::from_settings(settings, self.gain_percent_range(&ranges))
         };
-        *gain = self.gain.value();
-        *automatic_gain = self.automatic_gain.is_checked();
-        *automatic_dc_offset = self.automatic_dc_offset.is_checked();
+
+        // the ranges are rescaled the same way the unit select does, the frequency is set afterwards
+        let previous = self.frequency_unit.get();
+        let unit = values.frequency_unit;
+        let min = previous.rescale(self.frequency.minimum(), unit);
+        let max = previous.rescale(self.frequency.maximum(), unit);
+
+        self.frequency_unit_select.block_signals(true);
+        self.frequency_unit_select
+            .set_current_index(FREQUENCY_UNITS.iter().position(|u| *u == unit).unwrap() as i32);
+        self.frequency_unit_select.block_signals(false);
+
+        set_frequency_unit(&self.frequency, unit, min, max);
+        self.frequency_unit.set(unit);
+        self.frequency.set_value(values.frequency);
+
+        self.set_samplerate_mhz(values.samplerate);
+
+        self.gain.set_value(values.gain);
+
+        for (checkbox, checked) in [
+            (&self.automatic_gain, values.automatic_gain),
+            (&self.automatic_dc_offset, values.automatic_dc_offset),
+            (&self.iq_correction, values.iq_correction),
+        ] {
+            checkbox.block_signals(true);
+            checkbox.set_checked(checked);
+            checkbox.block_signals(false);
+        }
+
+        self.clock_source.block_signals(true);
+        self.select_clock_source(&values.clock_source);
+        self.clock_source.block_signals(false);
+
+        self.favorites.replace(values.favorites);
+        self.rebuild_favorites();
     }
 }
 
-// a helper function for ReceiveGroup to clamp the configured parameters to valid ranges
-unsafe fn clamp_value(
+// what the widgets of the group show for some settings, apply_settings sets them and populate_settings reads them back
+// the frequency and gain are in the displayed unit
+#[derive(Clone, Debug, PartialEq)]
+struct ReceiveValues {
+    frequency: f64,
+    frequency_unit: FrequencyUnit,
+    samplerate: f64,
+    gain: f64,
+    automatic_gain: bool,
+    automatic_dc_offset: bool,
+    iq_correction: bool,
+    clock_source: String,
+    favorites: Vec<Favorite>,
+}
+
+impl ReceiveValues {
+    // gain_percent is the gain range if the gain is entered in percent
+    fn from_settings(settings: &AppSettings, gain_percent: Option<&soapysdr::Range>) -> Self {
+        let frequency_unit = settings_unit(&settings.frequency_unit);
+
+        Self {
+            frequency: FrequencyUnit::MHz.rescale(settings.frequency, frequency_unit),
+            frequency_unit,
+            samplerate: settings.samplerate,
+            gain: match gain_percent {
+                Some(range) => gain_db_to_percent(settings.gain, range),
+                None => settings.gain,
+            },
+            automatic_gain: settings.automatic_gain,
+            automatic_dc_offset: settings.automatic_dc_offset,
+            iq_correction: settings.iq_correction,
+            clock_source: settings.clock_source.clone(),
+            favorites: settings.favorites.clone(),
+        }
+    }
+    // the settings not editable in the gui are kept from the loaded ones
+    fn populate(
+        self,
+        settings: &mut AppSettings,
+        loaded: &AppSettings,
+        gain_percent: Option<&soapysdr::Range>,
+    ) {
+        // the frequency is always saved in MHz
+        settings.frequency = self
+            .frequency_unit
+            .rescale(self.frequency, FrequencyUnit::MHz);
+        settings.frequency_unit = self.frequency_unit.name().to_owned();
+        settings.samplerate = self.samplerate;
+        settings.gain = match gain_percent {
+            Some(range) => gain_percent_to_db(self.gain, range),
+            None => self.gain,
+        };
+        settings.automatic_gain = self.automatic_gain;
+        settings.automatic_dc_offset = self.automatic_dc_offset;
+        settings.iq_correction = self.iq_correction;
+        settings.clock_source = self.clock_source;
+        settings.favorites = self.favorites;
+
+        settings.buffer_samples = loaded.buffer_samples;
+        settings.settle_us = loaded.settle_us;
+        settings.receive_timeout_us = loaded.receive_timeout_us;
+        settings.worker_event_budget_us = loaded.worker_event_budget_us;
+        settings.fine_step_hz = loaded.fine_step_hz;
+        settings.coarse_step_hz = loaded.coarse_step_hz;
+    }
+}
+
+// the sign shows which way the signal is off, nothing is known before the first buffer is received
+fn carrier_offset_text(offset: Option<f64>) -> String {
+    match offset {
+        Some(offset) => format!("{:+.0} Hz", offset),
+        None => "-".to_owned(),
+    }
+}
+
+// 0 is what the bandwidth is left at on devices that don't allow setting it, nothing is known before the receiver is configured
+fn bandwidth_text(bandwidth: Option<f64>) -> String {
+    match bandwidth {
+        Some(bandwidth) if bandwidth > 0.0 => format!("{} MHz", units::to_mega(bandwidth)),
+        Some(_) => "Set by the device".to_owned(),
+        None => "-".to_owned(),
+    }
+}
+
+// the lowest minimum and the highest maximum of the ranges, None if there are no ranges at all
+// NaN bounds are skipped instead of panicking in a comparison
+fn ranges_bounds(ranges: &[soapysdr::Range]) -> Option<(f64, f64)> {
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let min = ranges
+        .iter()
+        .map(|r| r.minimum)
+        .fold(f64::INFINITY, f64::min);
+    let max = ranges
+        .iter()
+        .map(|r| r.maximum)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    Some((min, max))
+}
+
+// devices with only a set of samplerates report every one of them as a range with minimum==maximum
+// an empty list is treated as a range so that it ends up in a (disabled) spinbox
+fn is_discrete(ranges: &[soapysdr::Range]) -> bool {
+    ranges.first().map_or(false, |r| r.minimum == r.maximum)
+}
+
+// the row to remove and the row to insert the new samplerate widget at, given the row of the current one
+// if the current widget is somehow not in the form nothing is removed, it is only inserted at its usual place
+fn samplerate_rows(current: i32) -> (Option<i32>, i32) {
+    if current < 0 {
+        (None, SAMPLERATE_ROW)
+    } else {
+        (Some(current), current)
+    }
+}
+
+// the index of a discrete samplerate, both are in MSps
+fn samplerate_index(ranges: &[soapysdr::Range], samplerate: f64) -> Option<usize> {
+    ranges.iter().position(|r| r.minimum == samplerate)
+}
+
+// the frequency unit stored in AppSettings, an unknown or missing one falls back to MHz
+fn settings_unit(name: &str) -> FrequencyUnit {
+    FrequencyUnit::from_name(name).unwrap_or(FrequencyUnit::MHz)
+}
+
+// maps 0..100 % linearly onto the gain range, a range without any width is always at its minimum
+fn gain_percent_to_db(percent: f64, range: &soapysdr::Range) -> f64 {
+    let percent = percent.max(0.0).min(100.0);
+    range.minimum + (range.maximum - range.minimum) * percent / 100.0
+}
+
+// the inverse of gain_percent_to_db, a gain outside of the range is clamped to it
+fn gain_db_to_percent(db: f64, range: &soapysdr::Range) -> f64 {
+    let width = range.maximum - range.minimum;
+    if width <= 0.0 {
+        return 0.0;
+    }
+
+    ((db - range.minimum) / width * 100.0).max(0.0).min(100.0)
+}
+
+// the decimals need to be set before the range and value, otherwise qt rounds them to the previous precision
+unsafe fn set_frequency_unit(
     widget: &QBox<QDoubleSpinBox>,
-    ranges: &mut dyn Iterator<Item = &soapysdr::Range>,
+    unit: FrequencyUnit,
+    min: f64,
+    max: f64,
 ) {
-    let mut val = widget.value();
+    widget.set_decimals(unit.decimals());
+    widget.set_single_step(unit.step());
+    widget.set_suffix(&qs(format!(" {}", unit.name())));
+    widget.set_range(min, max);
+}
+
+// a helper function for ReceiveGroup to clamp the configured parameters to valid ranges
+unsafe fn clamp_value(widget: &QBox<QDoubleSpinBox>, ranges: &[soapysdr::Range]) {
+    if let Some(val) = clamp_to_ranges(widget.value(), ranges) {
+        widget.set_value(val);
+    }
+}
+
+// the ranges are expected to be sorted and non-overlapping, as reported by SoapySDR
+// returns None if there are no ranges to clamp to
+fn clamp_to_ranges(val: f64, ranges: &[soapysdr::Range]) -> Option<f64> {
+    let mut previous: Option<&soapysdr::Range> = None;
 
-    // this seems pretty robust however it is quite spaghetti so it is very much possible there is an off-by-one error
-    let mut previous_edge = 0.0;
-    let mut first = true;
     for range in ranges {
-        // make sure to check against the start of the first range
-        if first {
-            val = val.max(range.minimum);
-            first = false;
+        if val < range.minimum {
+            // the value is in the gap between two ranges (or before the first one), snap it to the closer edge
+            // on a tie the lower value is used
+            return Some(match previous {
+                Some(previous) if val - previous.maximum <= range.minimum - val => previous.maximum,
+                _ => range.minimum,
+            });
         }
 
-        // if the value is inside the valid range, snap it a multiple of step
-        if (range.minimum..=range.maximum).contains(&val) {
-            // if the step is 0 or some small value, this doesn't work
-            // let step = range.step.min(0.0001);
-            // val = (val / step).trunc() * step;
-
-            // it seems that the device round is itself which I assume is better
+        if val <= range.maximum {
+            return Some(snap_to_step(val, range));
         }
-        // if the value is between the previous maximum and the current minimum it is not a valid range, therefore we snap it to the previous maximum
-        else if (previous_edge..=(range.minimum)).contains(&val) {
-            val = previous_edge;
+
+        previous = Some(range);
+    }
+
+    // past the end of the last range
+    previous.map(|last| last.maximum)
+}
+
+// the frequency one step away, kept inside the ranges
+// a step into the gap between two ranges continues on the far side of it instead of snapping back to where it started
+fn stepped_frequency(value: f64, step: f64, ranges: &[soapysdr::Range]) -> f64 {
+    let target = value + step;
+    let clamped = match clamp_to_ranges(target, ranges) {
+        Some(clamped) => clamped,
+        None => return target,
+    };
+
+    if ranges
+        .iter()
+        .any(|r| r.minimum <= target && target <= r.maximum)
+    {
+        return clamped;
+    }
+
+    if step > 0.0 && clamped <= value {
+        ranges
+            .iter()
+            .map(|r| r.minimum)
+            .find(|&minimum| minimum > value)
+            .unwrap_or(clamped)
+    } else if step < 0.0 && clamped >= value {
+        ranges
+            .iter()
+            .rev()
+            .map(|r| r.maximum)
+            .find(|&maximum| maximum < value)
+            .unwrap_or(clamped)
+    } else {
+        clamped
+    }
+}
+
+// snaps the value to a multiple of step counted from the start of the range, a step of 0 means the range is continuous
+fn snap_to_step(val: f64, range: &soapysdr::Range) -> f64 {
+    if range.step <= 0.0 {
+        return val;
+    }
+
+    let steps = ((val - range.minimum) / range.step).round();
+    // rounding up could leave the range if the maximum isn't a multiple of step
+    let max_steps = ((range.maximum - range.minimum) / range.step).floor();
+
+    range.minimum + steps.min(max_steps) * range.step
+}
+
+#[cfg(test)]
+fn range(minimum: f64, maximum: f64) -> soapysdr::Range {
+    soapysdr::Range {
+        minimum,
+        maximum,
+        step: 0.0,
+    }
+}
+
+#[test]
+fn clamp_discrete_samplerates() {
+    // RTL-SDR style list of discrete samplerates, in MSps
+    let ranges = [0.25, 1.024, 1.536, 2.048, 2.4]
+        .iter()
+        .map(|&v| range(v, v))
+        .collect::<Vec<_>>();
+
+    assert_eq!(clamp_to_ranges(0.0, &ranges), Some(0.25));
+    assert_eq!(clamp_to_ranges(1.536, &ranges), Some(1.536));
+    // between two samplerates the closer one is picked
+    assert_eq!(clamp_to_ranges(1.7, &ranges), Some(1.536));
+    assert_eq!(clamp_to_ranges(1.9, &ranges), Some(2.048));
+    assert_eq!(clamp_to_ranges(10.0, &ranges), Some(2.4));
+    assert_eq!(clamp_to_ranges(1.0, &[]), None);
+}
+
+#[test]
+fn clamp_continuous_ranges() {
+    let ranges = [range(0.225, 0.3), range(0.9, 3.2)];
+
+    assert_eq!(clamp_to_ranges(0.1, &ranges), Some(0.225));
+    assert_eq!(clamp_to_ranges(0.25, &ranges), Some(0.25));
+    assert_eq!(clamp_to_ranges(0.5, &ranges), Some(0.3));
+    assert_eq!(clamp_to_ranges(0.7, &ranges), Some(0.9));
+    assert_eq!(clamp_to_ranges(2.0, &ranges), Some(2.0));
+    assert_eq!(clamp_to_ranges(5.0, &ranges), Some(3.2));
+
+    // bandwidth computed as 75% of the samplerate
+    assert_eq!(clamp_to_ranges(3.2 * 0.75, &ranges), Some(3.2 * 0.75));
+}
+
+#[test]
+fn clamp_to_step() {
+    let ranges = [soapysdr::Range {
+        minimum: 0.5,
+        maximum: 3.2,
+        step: 0.25,
+    }];
+
+    assert_eq!(clamp_to_ranges(1.3, &ranges), Some(1.25));
+    assert_eq!(clamp_to_ranges(1.4, &ranges), Some(1.5));
+    assert_eq!(clamp_to_ranges(0.5, &ranges), Some(0.5));
+    // the nearest step would be 3.25 which is past the maximum
+    assert_eq!(clamp_to_ranges(3.15, &ranges), Some(3.0));
+    assert_eq!(clamp_to_ranges(5.0, &ranges), Some(3.2));
+}
+
+#[test]
+fn apply_populate_round_trip() {
+    use crate::app_settings::DEFAULT_SETTINGS;
+
+    let mut settings = DEFAULT_SETTINGS;
+    settings.frequency = 145.8;
+    settings.samplerate = 1.024;
+    settings.gain = 19.7;
+    settings.automatic_gain = true;
+    settings.iq_correction = true;
+    settings.clock_source = "external".to_owned();
+    settings.buffer_samples = 4096;
+    settings.fine_step_hz = 50.0;
+    settings.favorites = vec![Favorite {
+        frequency: 434.0,
+        samplerate: 2.048,
+        mode: "RTTY".to_owned(),
+    }];
+
+    let gain = range(0.0, 49.6);
+
+    for &unit in FREQUENCY_UNITS {
+        for gain_percent in [None, Some(&gain)] {
+            settings.frequency_unit = unit.name().to_owned();
+
+            // what apply_settings puts into the widgets is read back by populate_settings
+            let values = ReceiveValues::from_settings(&settings, gain_percent);
+            assert_eq!(values.frequency_unit, unit);
+
+            let mut populated = DEFAULT_SETTINGS;
+            values
+                .clone()
+                .populate(&mut populated, &settings, gain_percent);
+
+            assert!((populated.frequency - settings.frequency).abs() < 1e-9);
+            assert!((populated.gain - settings.gain).abs() < 1e-9);
+            populated.frequency = settings.frequency;
+            populated.gain = settings.gain;
+            // the fields of the other groups are left at the same defaults
+            assert_eq!(populated, settings);
+
+            // a second pass doesn't change anything
+            assert_eq!(
+                ReceiveValues::from_settings(&populated, gain_percent),
+                values
+            );
         }
-        previous_edge = range.maximum;
     }
-    // make sure to also check the end of the last range
-    val = val.min(previous_edge);
 
-    widget.set_value(val);
+    // an unknown unit is saved as MHz
+    settings.frequency_unit = "furlongs".to_owned();
+    let mut populated = DEFAULT_SETTINGS;
+    ReceiveValues::from_settings(&settings, None).populate(&mut populated, &settings, None);
+    assert_eq!(populated.frequency_unit, "MHz");
+}
+
+#[test]
+fn gain_percent_mapping() {
+    // RTL-SDR with the R820T tuner
+    let r820t = range(0.0, 49.6);
+    assert_eq!(gain_percent_to_db(0.0, &r820t), 0.0);
+    assert_eq!(gain_percent_to_db(50.0, &r820t), 24.8);
+    assert_eq!(gain_percent_to_db(100.0, &r820t), 49.6);
+    assert_eq!(gain_db_to_percent(24.8, &r820t), 50.0);
+
+    // a range starting below zero
+    let attenuator = range(-20.0, 10.0);
+    assert_eq!(gain_percent_to_db(0.0, &attenuator), -20.0);
+    assert_eq!(gain_db_to_percent(-5.0, &attenuator), 50.0);
+
+    for percent in &[0.0, 12.5, 33.3, 100.0] {
+        let db = gain_percent_to_db(*percent, &attenuator);
+        assert!((gain_db_to_percent(db, &attenuator) - percent).abs() < 1e-9);
+    }
+
+    // outside of the range
+    assert_eq!(gain_percent_to_db(150.0, &r820t), 49.6);
+    assert_eq!(gain_db_to_percent(-3.0, &r820t), 0.0);
+    assert_eq!(gain_db_to_percent(60.0, &r820t), 100.0);
+
+    // a driver which reports a fixed gain
+    let fixed = range(15.0, 15.0);
+    assert_eq!(gain_percent_to_db(70.0, &fixed), 15.0);
+    assert_eq!(gain_db_to_percent(15.0, &fixed), 0.0);
+}
+
+// a group whose manager has sent CreateDevice, so that it can configure the receiver of the devices it is given
+// the worker refuses the configurations as it has no such device, the test never receives its answers
+#[cfg(test)]
+unsafe fn test_group(name: &str) -> Rc<ReceiveGroup> {
+    use crate::app_settings::DEFAULT_SETTINGS;
+
+    let device = Rc::new(DeviceManager::new_named(name));
+    handle_send_result(device.send_command(DeviceBoundCommand::CreateDevice { index: 0 }));
+
+    let (group, _) = ReceiveGroup::new(
+        device,
+        Rc::new(DEFAULT_SETTINGS),
+        Rc::new(ApplyMode::new(false)),
+    );
+    group
+}
+
+// what the worker sends for a single channel device with these ranges in Hz
+#[cfg(test)]
+fn device_created(
+    samplerate: Vec<soapysdr::Range>,
+    frequency: Vec<soapysdr::Range>,
+    gain: soapysdr::Range,
+) -> Option<GuiBoundEvent> {
+    use crate::worker::worker_manager::{ChannelInfo, DeviceInfo};
+
+    Some(GuiBoundEvent::DeviceCreated {
+        device_info: DeviceInfo {
+            serial: String::new(),
+            driver: "test".to_owned(),
+            hardware: "test".to_owned(),
+            hardware_info: Vec::new(),
+            clock_sources: Vec::new(),
+        },
+        channels_info: vec![ChannelInfo {
+            ranges: ValueRanges {
+                samplerate,
+                frequency,
+                bandwidth: Vec::new(),
+                gain,
+            },
+            info: Vec::new(),
+        }],
+    })
+}
+
+#[test]
+fn empty_device_ranges() {
+    use crate::gui_groups::run_on_gui_thread;
+
+    run_on_gui_thread(|| unsafe {
+        let group = test_group("Worker thread empty ranges");
+        group.handle_event(&mut device_created(Vec::new(), Vec::new(), range(0.0, 0.0)));
+
+        // the controls which can't be set are disabled, the samplerate keeps the wide range of ReceiveGroup::new
+        assert!(!group.frequency.is_enabled());
+        assert!(!group.gain.is_enabled());
+        assert!(!group.gain_unit_select.is_enabled());
+        match &*group.samplerate.borrow() {
+            Samplerate::Ranges(spinbox) => {
+                assert!(!spinbox.is_enabled());
+                assert_eq!(spinbox.maximum(), 10000.0);
+            }
+            Samplerate::Values(_) => panic!("An empty samplerate range isn't discrete"),
+        }
+
+        // and the receiver isn't configured with made up values
+        assert!(!group.warning.is_hidden());
+        assert!(group.device.get_receiver_state().is_none());
+
+        // the next device has ranges again
+        group.handle_event(&mut device_created(
+            vec![range(0.1e6, 20e6)],
+            vec![range(24e6, 1766e6)],
+            range(0.0, 49.6),
+        ));
+        assert!(group.frequency.is_enabled());
+        assert!(group.gain.is_enabled());
+        assert!(group.gain_unit_select.is_enabled());
+        match &*group.samplerate.borrow() {
+            Samplerate::Ranges(spinbox) => assert!(spinbox.is_enabled()),
+            Samplerate::Values(_) => panic!("A continuous samplerate range isn't discrete"),
+        }
+        assert!(group.warning.is_hidden());
+        assert!(group.device.get_receiver_state().is_some());
+    });
+
+    let frequency = [range(24e6, 1000e6), range(1100e6, 1766e6)];
+    assert_eq!(ranges_bounds(&frequency), Some((24e6, 1766e6)));
+    assert_eq!(ranges_bounds(&[]), None);
+}
+
+#[test]
+fn repeated_device_creation() {
+    use crate::gui_groups::run_on_gui_thread;
+    use qt_widgets::QLabel;
+
+    run_on_gui_thread(|| unsafe {
+        let group = test_group("Worker thread repeated creation");
+        let rows = group.form_layout.row_count();
+
+        let discrete = vec![range(0.25e6, 0.25e6), range(2.4e6, 2.4e6)];
+        let continuous = vec![range(0.1e6, 20e6)];
+        let devices = [
+            &discrete,
+            &discrete,
+            &continuous,
+            &Vec::new(),
+            &discrete,
+            &continuous,
+        ];
+
+        for samplerate in devices.iter() {
+            group.handle_event(&mut device_created(
+                samplerate.to_vec(),
+                vec![range(24e6, 1766e6)],
+                range(0.0, 49.6),
+            ));
+
+            // the widget of the previous device was replaced in its row, nothing else was removed or added
+            assert_eq!(group.form_layout.row_count(), rows);
+            assert_eq!(group.samplerate_row(), SAMPLERATE_ROW);
+
+            let label = match &*group.samplerate.borrow() {
+                Samplerate::Values(combox) => {
+                    assert!(is_discrete(samplerate));
+                    assert_eq!(combox.count(), samplerate.len() as i32);
+                    group.form_layout.label_for_field_q_widget(combox)
+                }
+                Samplerate::Ranges(spinbox) => {
+                    assert!(!is_discrete(samplerate));
+                    group.form_layout.label_for_field_q_widget(spinbox)
+                }
+            };
+            assert_eq!(
+                label.dynamic_cast::<QLabel>().text().to_std_string(),
+                "Samplerate"
+            );
+        }
+    });
+
+    // the widget went missing, it is inserted without removing anything else
+    assert_eq!(samplerate_rows(-1), (None, SAMPLERATE_ROW));
+}
+
+#[test]
+fn frequency_steps() {
+    // in kHz, like an E4000 tuner which can't tune between 1100 and 1250 MHz
+    let ranges = [
+        range(52_000.0, 1_100_000.0),
+        range(1_250_000.0, 2_200_000.0),
+    ];
+    let (fine, coarse) = (1.0, 100.0);
+
+    assert_eq!(stepped_frequency(433_920.0, fine, &ranges), 433_921.0);
+    assert_eq!(stepped_frequency(433_920.0, -coarse, &ranges), 433_820.0);
+
+    // the edges of the device can't be stepped past
+    assert_eq!(stepped_frequency(2_199_950.0, coarse, &ranges), 2_200_000.0);
+    assert_eq!(stepped_frequency(2_200_000.0, fine, &ranges), 2_200_000.0);
+    assert_eq!(stepped_frequency(52_000.0, -fine, &ranges), 52_000.0);
+
+    // the gap is crossed in both directions even with the fine step
+    assert_eq!(stepped_frequency(1_100_000.0, fine, &ranges), 1_250_000.0);
+    assert_eq!(stepped_frequency(1_250_000.0, -fine, &ranges), 1_100_000.0);
+    assert_eq!(stepped_frequency(1_099_950.0, coarse, &ranges), 1_100_000.0);
+
+    // a device with a tuning step rounds to it
+    let stepped = [soapysdr::Range {
+        minimum: 0.0,
+        maximum: 1_000.0,
+        step: 25.0,
+    }];
+    assert_eq!(stepped_frequency(500.0, 20.0, &stepped), 525.0);
+
+    // without a device nothing is clamped
+    assert_eq!(stepped_frequency(100.0, coarse, &[]), 200.0);
 }
@@ -0,0 +1,190 @@
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::scan::{scan_frequencies, ScanController, ScanStatus};
+use crate::units::{self, FrequencyUnit};
+use crate::worker::worker::GuiBoundEvent;
+use crate::worker::worker_manager::DeviceManager;
+
+use qt_charts::qt_core::SlotNoArgs;
+use qt_widgets::{
+    cpp_core::Ptr,
+    q_size_policy::Policy,
+    qt_core::{qs, QBox},
+    QDoubleSpinBox, QFormLayout, QGroupBox, QLabel, QPushButton, QSpinBox,
+};
+
+#[allow(unused)]
+pub struct ScanGroup {
+    group: QBox<QGroupBox>,
+
+    start: QBox<QDoubleSpinBox>,
+    stop: QBox<QDoubleSpinBox>,
+    step: QBox<QDoubleSpinBox>,
+    dwell: QBox<QSpinBox>,
+    threshold: QBox<QDoubleSpinBox>,
+    run_btn: QBox<QPushButton>,
+    status: QBox<QLabel>,
+
+    controller: RefCell<ScanController>,
+
+    device: Rc<DeviceManager>,
+}
+
+impl ScanGroup {
+    pub unsafe fn new(device: Rc<DeviceManager>) -> (Rc<Self>, Ptr<QGroupBox>) {
+        let group = QGroupBox::new();
+        group.set_size_policy_2a(Policy::Fixed, Policy::Fixed);
+
+        group.set_title(&qs("Scan"));
+
+        let form = QFormLayout::new_0a();
+        group.set_layout(&form);
+
+        let frequency_spinbox = || {
+            let spinbox = QDoubleSpinBox::new_0a();
+            spinbox.set_suffix(&qs(" MHz"));
+            spinbox.set_decimals(6);
+            spinbox.set_range(0.0, 100_000.0);
+            spinbox
+        };
+
+        let start = frequency_spinbox();
+        form.add_row_q_string_q_widget(&qs("Start"), &start);
+
+        let stop = frequency_spinbox();
+        form.add_row_q_string_q_widget(&qs("Stop"), &stop);
+
+        let step = QDoubleSpinBox::new_0a();
+        step.set_suffix(&qs(" kHz"));
+        step.set_decimals(3);
+        step.set_range(0.0, 100_000.0);
+        step.set_value(25.0);
+        form.add_row_q_string_q_widget(&qs("Step"), &step);
+
+        let dwell = QSpinBox::new_0a();
+        dwell.set_suffix(&qs(" ms"));
+        dwell.set_range(0, 60_000);
+        dwell.set_value(200);
+        form.add_row_q_string_q_widget(&qs("Dwell"), &dwell);
+
+        let threshold = QDoubleSpinBox::new_0a();
+        threshold.set_suffix(&qs(" dBFS/Hz"));
+        threshold.set_range(-200.0, 0.0);
+        threshold.set_value(-60.0);
+        form.add_row_q_string_q_widget(&qs("Threshold"), &threshold);
+
+        let run_btn = QPushButton::from_q_string(&qs("Start scan"));
+        run_btn.set_tool_tip(&qs(
+            "The scan measures the spectrum so it has to be running",
+        ));
+        run_btn.set_enabled(false);
+        form.add_row_q_widget(&run_btn);
+
+        let status = QLabel::new();
+        form.add_row_q_widget(&status);
+
+        let ptr = group.as_ptr();
+        let s = Rc::new(Self {
+            group,
+            start,
+            stop,
+            step,
+            dwell,
+            threshold,
+            run_btn,
+            status,
+            controller: RefCell::new(ScanController::new()),
+            device,
+        });
+
+        s.init();
+
+        (s, ptr)
+    }
+    unsafe fn init(self: &Rc<Self>) {
+        let Self { group, run_btn, .. } = self.borrow();
+
+        let s = self.clone();
+        run_btn.clicked().connect(&SlotNoArgs::new(group, move || {
+            let mut controller = s.controller.borrow_mut();
+
+            if controller.is_active() {
+                controller.stop();
+                s.status.set_text(&qs("Stopped"));
+            } else {
+                let frequencies = match scan_frequencies(
+                    units::from_mega(s.start.value()),
+                    units::from_mega(s.stop.value()),
+                    FrequencyUnit::KHz.to_hz(s.step.value()),
+                ) {
+                    Ok(frequencies) => frequencies,
+                    Err(e) => {
+                        s.status.set_text(&qs(e.to_string()));
+                        return;
+                    }
+                };
+
+                let status = controller.start(
+                    &s.device,
+                    frequencies,
+                    s.dwell.value() as u64,
+                    s.threshold.value(),
+                );
+                s.show_status(status);
+            }
+
+            s.update_run_button(controller.is_active());
+        }));
+    }
+    pub unsafe fn handle_event(&self, event: &mut Option<GuiBoundEvent>) {
+        let event = event.as_ref().unwrap();
+
+        let mut controller = self.controller.borrow_mut();
+        let was_active = controller.is_active();
+        let status = controller.handle_event(&self.device, event);
+
+        self.show_status(status);
+        if was_active != controller.is_active() {
+            self.update_run_button(controller.is_active());
+        }
+
+        match event {
+            GuiBoundEvent::ReceiverConfigured { .. } => self.run_btn.set_enabled(true),
+            GuiBoundEvent::DeviceDestroyed | GuiBoundEvent::WorkerReset => {
+                self.run_btn.set_enabled(false);
+                self.status.clear();
+            }
+            _ => (),
+        }
+    }
+    unsafe fn show_status(&self, status: Option<ScanStatus>) {
+        let text = match status {
+            Some(ScanStatus::Tuning(frequency)) => {
+                format!("Tuning to {:.6} MHz", units::to_mega(frequency))
+            }
+            Some(ScanStatus::Measuring(frequency, level)) => {
+                format!("{:.6} MHz: {:.1} dBFS/Hz", units::to_mega(frequency), level)
+            }
+            Some(ScanStatus::Found(frequency, level)) => format!(
+                "Activity at {:.6} MHz: {:.1} dBFS/Hz",
+                units::to_mega(frequency),
+                level
+            ),
+            Some(ScanStatus::Finished(Some((frequency, level)))) => format!(
+                "Nothing found, best {:.6} MHz: {:.1} dBFS/Hz",
+                units::to_mega(frequency),
+                level
+            ),
+            Some(ScanStatus::Finished(None)) => "Nothing found".to_owned(),
+            None => return,
+        };
+
+        self.status.set_text(&qs(text));
+    }
+    unsafe fn update_run_button(&self, active: bool) {
+        let text = if active { "Stop scan" } else { "Start scan" };
+        self.run_btn.set_text(&qs(text));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+// a position report from a UKHAS telemetry sentence
+// $$CALLSIGN,sentence id,time,latitude,longitude,altitude[,anything else]*CRC16
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    pub time: String,
+    pub lat: f64,
+    pub lon: f64,
+    // m
+    pub alt: f64,
+    // the whole line it was parsed from
+    pub sentence: String,
+}
+
+// returns None for anything which isn't a valid sentence, including a wrong checksum
+// the checksum is optional, some payloads don't send one
+pub fn parse_sentence(line: &str) -> Option<Fix> {
+    let line = line.trim();
+    let body = line.strip_prefix("$$")?;
+
+    let body = match body.rfind('*') {
+        Some(star) => {
+            let (body, checksum) = (&body[..star], &body[(star + 1)..]);
+            if u16::from_str_radix(checksum, 16).ok()? != crc16_ccitt(body.as_bytes()) {
+                return None;
+            }
+            body
+        }
+        None => body,
+    };
+
+    let fields: Vec<&str> = body.split(',').collect();
+    if fields.len() < 6 || fields[0].is_empty() {
+        return None;
+    }
+
+    Some(Fix {
+        time: fields[2].to_owned(),
+        lat: fields[3].parse().ok()?,
+        lon: fields[4].parse().ok()?,
+        alt: fields[5].parse().ok()?,
+        sentence: line.to_owned(),
+    })
+}
+
+// CRC16-CCITT (0x1021, starting at 0xFFFF) over everything between the $$ and the *
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// collects the decoded text and returns the lines as they are completed
+#[derive(Default)]
+pub struct LineBuffer {
+    line: String,
+}
+
+impl LineBuffer {
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        for ch in text.chars() {
+            match ch {
+                '\r' | '\n' => {
+                    if !self.line.is_empty() {
+                        lines.push(std::mem::take(&mut self.line));
+                    }
+                }
+                ch => self.line.push(ch),
+            }
+        }
+        lines
+    }
+}
+
+pub const CSV_HEADER: &str = "time,lat,lon,alt,sentence\n";
+
+// appends a row for every fix, every row is flushed right away so that a crash loses nothing
+pub struct CsvLogger {
+    file: File,
+}
+
+impl CsvLogger {
+    // an existing log is appended to, the header is written only to a new (or empty) file
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(CSV_HEADER.as_bytes())?;
+            file.flush()?;
+        }
+
+        Ok(Self { file })
+    }
+    pub fn log(&mut self, fix: &Fix) -> io::Result<()> {
+        let row = format!(
+            "{},{},{},{},{}\n",
+            csv_field(&fix.time),
+            fix.lat,
+            fix.lon,
+            fix.alt,
+            csv_field(&fix.sentence)
+        );
+
+        self.file.write_all(row.as_bytes())?;
+        self.file.flush()
+    }
+}
+
+// the sentence itself is full of commas, it is quoted the usual way
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[test]
+fn parse_ukhas_sentences() {
+    let fix = parse_sentence("$$RADIO,12,13:37:00,51.12345,-1.5,12034*258C\r").unwrap();
+    assert_eq!(fix.time, "13:37:00");
+    assert_eq!((fix.lat, fix.lon, fix.alt), (51.12345, -1.5, 12034.0));
+    assert_eq!(fix.sentence, "$$RADIO,12,13:37:00,51.12345,-1.5,12034*258C");
+
+    // without a checksum and with extra fields
+    assert!(parse_sentence("$$RADIO,13,13:37:10,51.2,-1.4,12100,5,-12.5").is_some());
+
+    // wrong checksum, missing fields, garbage from noise
+    assert_eq!(
+        parse_sentence("$$RADIO,12,13:37:00,51.12345,-1.5,12034*258D"),
+        None
+    );
+    assert_eq!(parse_sentence("$$RADIO,12,13:37:00,51.12345"), None);
+    assert_eq!(parse_sentence("RYRYRY THE QUICK BROWN FOX"), None);
+    assert_eq!(parse_sentence("$$RADIO,12,13:37:00,5X.1,-1.5,12034"), None);
+
+    let mut buffer = LineBuffer::default();
+    assert!(buffer.push("$$RADIO,1,").is_empty());
+    assert_eq!(
+        buffer.push("00:00:01,1,2,3\r\n$$RA"),
+        vec!["$$RADIO,1,00:00:01,1,2,3".to_owned()]
+    );
+}
+
+#[test]
+fn csv_logger_appends() {
+    let path =
+        std::env::temp_dir().join(format!("radiothing_telemetry_{}.csv", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let first = parse_sentence("$$RADIO,1,10:00:00,51.5,-0.25,100").unwrap();
+    let second = parse_sentence("$$RADIO,2,10:00:10,51.6,-0.2,150").unwrap();
+
+    let mut logger = CsvLogger::open(&path).unwrap();
+    logger.log(&first).unwrap();
+    logger.log(&second).unwrap();
+    drop(logger);
+
+    // the next session continues the same file without another header
+    let mut logger = CsvLogger::open(&path).unwrap();
+    logger.log(&first).unwrap();
+    drop(logger);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let first_row = "10:00:00,51.5,-0.25,100,\"$$RADIO,1,10:00:00,51.5,-0.25,100\"\n";
+    let second_row = "10:00:10,51.6,-0.2,150,\"$$RADIO,2,10:00:10,51.6,-0.2,150\"\n";
+    assert_eq!(
+        contents,
+        format!("{}{}{}{}", CSV_HEADER, first_row, second_row, first_row)
+    );
+}
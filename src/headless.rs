@@ -0,0 +1,179 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Write},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use crossbeam_channel::Receiver;
+
+use crate::{
+    app_settings::{HeadlessInput, HeadlessSettings},
+    decoder::Decoder,
+    generator::SignalGenerator,
+    iq_source::{IqFormat, IqReader, SampleSource},
+    worker::{
+        worker::{DeviceWorker, GuiBoundEvent},
+        worker_manager::ReceiverState,
+    },
+};
+
+// how many samples are read from the input at a time, this stands in for the device mtu
+const FILE_READ_SAMPLES: usize = 4096;
+
+// a DeviceWorker without a device, the samples are pushed through the same decoding pipeline the GUI runs
+// instead of being read from a stream
+pub struct HeadlessDecoder {
+    worker: DeviceWorker,
+    events: Receiver<GuiBoundEvent>,
+}
+
+impl HeadlessDecoder {
+    pub fn new(
+        mut decoder: Decoder,
+        samplerate: f64,
+        squelch_threshold: f32,
+    ) -> Result<Self, Box<dyn Error>> {
+        // nothing is ever sent to the worker, the command channel only exists to construct it
+        let (_, receiver) = crossbeam_channel::unbounded();
+        let (sender, events) = crossbeam_channel::unbounded();
+
+        let mut worker = DeviceWorker::new(receiver, sender, Arc::new(AtomicBool::new(true)));
+        worker.receive_state = Some(
+            ReceiverState::builder()
+                .samplerate(samplerate)
+                .frequency(0.0)
+                .bandwidth(samplerate)
+                .buffer_samples(FILE_READ_SAMPLES)
+                // neither a file nor a pipe has a tuner to settle
+                .settle_us(0)
+                .receive_timeout_us(0)
+                .build()?,
+        );
+        worker.mtu = FILE_READ_SAMPLES;
+        worker.receive_size = FILE_READ_SAMPLES;
+        worker.squelch_threshold = squelch_threshold;
+
+        decoder.init(&mut worker, None)?;
+        decoder.configuration_changed(&mut worker, true)?;
+        worker.decoder = Some(decoder);
+
+        Ok(Self { worker, events })
+    }
+    // decodes the next buffer of samples from the source
+    // returns the text decoded from it and false once the source ran out of samples
+    pub fn decode_next(
+        &mut self,
+        source: &mut dyn SampleSource,
+    ) -> Result<(String, bool), Box<dyn Error>> {
+        let worker = &mut self.worker;
+
+        // the whole buffer is filled by every read except for the last one
+        let full = worker.receive_buffer(|dst| source.read_samples(dst))?;
+
+        if worker.memory_received_count > 0 {
+            worker.process_buffer()?;
+        }
+
+        let mut text = String::new();
+        for event in self.events.try_iter() {
+            if let GuiBoundEvent::DecodedChars { data } = event {
+                text.extend(data.iter().map(|c| c.ch));
+            }
+        }
+
+        Ok((text, full))
+    }
+    // decodes the source until it runs out of samples
+    pub fn decode_all(&mut self, source: &mut dyn SampleSource) -> Result<String, Box<dyn Error>> {
+        let mut text = String::new();
+
+        loop {
+            let (decoded, more) = self.decode_next(source)?;
+            text.push_str(&decoded);
+
+            if !more {
+                return Ok(text);
+            }
+        }
+    }
+}
+
+// runs the decoder on a recording or piped samples the same way DeviceWorker runs it on a device stream
+// the decoded text is printed to stdout as it arrives
+pub fn run(settings: HeadlessSettings) -> Result<(), Box<dyn Error>> {
+    let HeadlessSettings {
+        input,
+        samplerate,
+        mode,
+        baudrate,
+        stop_bits,
+        freq_shift,
+        cutoff_hz,
+        transition_width,
+        forced_decim,
+        freq_offset,
+        squelch_threshold,
+    } = settings;
+
+    let decoder = match mode.to_lowercase().as_str() {
+        "baudot" => Decoder::new_baudot(
+            baudrate,
+            stop_bits,
+            freq_shift,
+            cutoff_hz,
+            transition_width,
+            forced_decim,
+            freq_offset,
+        ),
+        _ => return Err(format!("Unknown decoding mode '{}'", mode).into()),
+    };
+
+    let mut decoder = HeadlessDecoder::new(decoder, samplerate, squelch_threshold)?;
+
+    // recordings are always cf32, stdin can be anything the other tools output
+    // the generator loops the signal back with the same parameters the decoder was given
+    let mut source: Box<dyn SampleSource> = match input {
+        HeadlessInput::File(path) => {
+            log::info!("Decoding '{}'", path.to_string_lossy());
+            Box::new(IqReader::new(
+                BufReader::new(File::open(&path)?),
+                IqFormat::Cf32,
+            ))
+        }
+        HeadlessInput::Stdin(format) => {
+            log::info!("Decoding {} samples from stdin", format.name());
+            Box::new(IqReader::new(std::io::stdin(), format))
+        }
+        HeadlessInput::Generator(text) => {
+            let generator = SignalGenerator::new_baudot(
+                samplerate,
+                &text,
+                baudrate as f64,
+                stop_bits as f64,
+                freq_shift as f64,
+                freq_offset as f64,
+            );
+            log::info!("Decoding {} generated samples", generator.sample_count());
+            Box::new(generator)
+        }
+    };
+
+    let stdout = std::io::stdout();
+
+    loop {
+        let (text, more) = decoder.decode_next(&mut *source)?;
+
+        let mut out = stdout.lock();
+        out.write_all(text.as_bytes())?;
+        out.flush()?;
+
+        if !more {
+            break;
+        }
+    }
+
+    println!();
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::io::{Read, Write};
+
+use rustfft::num_complex::Complex;
+
+use crate::worker::worker::RxFormat;
+
+// the interleaved IQ formats of recordings and of the usual command line tools
+// rtl_sdr writes cu8, hackrf_transfer writes cs8 which isn't supported yet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IqFormat {
+    Cu8,
+    Cs16,
+    Cf32,
+}
+
+pub const IQ_FORMATS: &[IqFormat] = &[IqFormat::Cu8, IqFormat::Cs16, IqFormat::Cf32];
+
+impl IqFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IqFormat::Cu8 => "cu8",
+            IqFormat::Cs16 => "cs16",
+            IqFormat::Cf32 => "cf32",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        IQ_FORMATS.iter().copied().find(|f| f.name() == name)
+    }
+    // the size of a whole IQ pair in bytes
+    pub fn sample_size(&self) -> usize {
+        match self {
+            IqFormat::Cu8 => 2,
+            IqFormat::Cs16 => 4,
+            IqFormat::Cf32 => 8,
+        }
+    }
+    // the integer formats are scaled to -1..1, unsigned 8 bit samples are centered at 127.5
+    fn convert(&self, bytes: &[u8]) -> Complex<RxFormat> {
+        match self {
+            IqFormat::Cu8 => Complex::new(
+                (bytes[0] as RxFormat - 127.5) / 127.5,
+                (bytes[1] as RxFormat - 127.5) / 127.5,
+            ),
+            IqFormat::Cs16 => Complex::new(
+                i16::from_le_bytes([bytes[0], bytes[1]]) as RxFormat / 32768.0,
+                i16::from_le_bytes([bytes[2], bytes[3]]) as RxFormat / 32768.0,
+            ),
+            IqFormat::Cf32 => Complex::new(
+                RxFormat::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                RxFormat::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            ),
+        }
+    }
+    // the inverse of convert, the integer formats are clipped to their range
+    fn write(&self, sample: Complex<RxFormat>, bytes: &mut Vec<u8>) {
+        match self {
+            IqFormat::Cu8 => {
+                let to_u8 = |x: RxFormat| (x * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8;
+                bytes.extend_from_slice(&[to_u8(sample.re), to_u8(sample.im)]);
+            }
+            IqFormat::Cs16 => {
+                let to_i16 = |x: RxFormat| (x * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+                bytes.extend_from_slice(&to_i16(sample.re).to_le_bytes());
+                bytes.extend_from_slice(&to_i16(sample.im).to_le_bytes());
+            }
+            IqFormat::Cf32 => {
+                bytes.extend_from_slice(&sample.re.to_le_bytes());
+                bytes.extend_from_slice(&sample.im.to_le_bytes());
+            }
+        }
+    }
+}
+
+// anything samples can be read from outside of a SoapySDR device, like a recording or a pipe
+pub trait SampleSource {
+    // returns the number of samples read which is less than dst.len() only at the end of the input
+    fn read_samples(&mut self, dst: &mut [Complex<RxFormat>]) -> std::io::Result<usize>;
+}
+
+pub struct IqReader<R: Read> {
+    reader: R,
+    format: IqFormat,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> IqReader<R> {
+    pub fn new(reader: R, format: IqFormat) -> Self {
+        Self {
+            reader,
+            format,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> SampleSource for IqReader<R> {
+    fn read_samples(&mut self, dst: &mut [Complex<RxFormat>]) -> std::io::Result<usize> {
+        let size = self.format.sample_size();
+        self.bytes.resize(dst.len() * size, 0);
+
+        // a pipe returns whatever is available, keep reading until the buffer is full or the input ends
+        let mut filled = 0;
+        while filled < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        // a trailing incomplete sample is ignored
+        let count = filled / size;
+        for (sample, chunk) in dst
+            .iter_mut()
+            .zip(self.bytes[..(count * size)].chunks_exact(size))
+        {
+            *sample = self.format.convert(chunk);
+        }
+
+        Ok(count)
+    }
+}
+
+// writes samples in the same formats IqReader reads, for recording what the device receives
+pub struct IqWriter<W: Write> {
+    writer: W,
+    format: IqFormat,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write> IqWriter<W> {
+    pub fn new(writer: W, format: IqFormat) -> Self {
+        Self {
+            writer,
+            format,
+            bytes: Vec::new(),
+        }
+    }
+    pub fn write_samples(&mut self, samples: &[Complex<RxFormat>]) -> std::io::Result<()> {
+        self.bytes.clear();
+        for &sample in samples {
+            self.format.write(sample, &mut self.bytes);
+        }
+
+        self.writer.write_all(&self.bytes)
+    }
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// samples already in memory, every read consumes them from the front
+impl SampleSource for &[Complex<RxFormat>] {
+    fn read_samples(&mut self, dst: &mut [Complex<RxFormat>]) -> std::io::Result<usize> {
+        let count = dst.len().min(self.len());
+        let (read, rest) = self.split_at(count);
+
+        dst[..count].copy_from_slice(read);
+        *self = rest;
+
+        Ok(count)
+    }
+}
+
+#[test]
+fn iq_format_conversion() {
+    let read = |format, bytes: &[u8]| {
+        let mut dst = vec![Complex::new(0.0, 0.0); 4];
+        let count = IqReader::new(bytes, format).read_samples(&mut dst).unwrap();
+        dst.truncate(count);
+        dst
+    };
+
+    assert_eq!(
+        read(IqFormat::Cu8, &[0, 255, 127, 128, 64]),
+        vec![
+            Complex::new(-1.0, 1.0),
+            Complex::new(-0.5 / 127.5, 0.5 / 127.5),
+        ]
+    );
+
+    let mut cs16 = Vec::new();
+    for value in [i16::MIN, 16384, -1, 0] {
+        cs16.extend_from_slice(&value.to_le_bytes());
+    }
+    assert_eq!(
+        read(IqFormat::Cs16, &cs16),
+        vec![Complex::new(-1.0, 0.5), Complex::new(-1.0 / 32768.0, 0.0)]
+    );
+
+    let mut cf32 = Vec::new();
+    for value in [0.25f32, -0.75, 1.5, 0.0] {
+        cf32.extend_from_slice(&value.to_le_bytes());
+    }
+    // the trailing 3 bytes are not a whole sample
+    cf32.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(
+        read(IqFormat::Cf32, &cf32),
+        vec![Complex::new(0.25, -0.75), Complex::new(1.5, 0.0)]
+    );
+
+    assert_eq!(IqFormat::from_name("cs16"), Some(IqFormat::Cs16));
+    assert_eq!(IqFormat::from_name("cs8"), None);
+}
+
+#[test]
+fn iq_format_round_trip() {
+    let samples = [
+        Complex::new(0.5, -0.25),
+        Complex::new(-1.0, 0.0),
+        Complex::new(0.125, 0.75),
+    ];
+
+    for &format in IQ_FORMATS {
+        let mut writer = IqWriter::new(Vec::new(), format);
+        writer.write_samples(&samples).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(bytes.len(), samples.len() * format.sample_size());
+
+        let mut read = vec![Complex::new(0.0, 0.0); samples.len()];
+        let count = IqReader::new(&bytes[..], format)
+            .read_samples(&mut read)
+            .unwrap();
+        assert_eq!(count, samples.len());
+
+        // the 8 bit samples are only as exact as their step
+        for (a, b) in samples.iter().zip(&read) {
+            assert!((a - b).norm() < 0.01, "{:?} {:?} {:?}", format, a, b);
+        }
+    }
+
+    // out of range samples are clipped instead of wrapping around
+    let mut writer = IqWriter::new(Vec::new(), IqFormat::Cs16);
+    writer.write_samples(&[Complex::new(2.0, -2.0)]).unwrap();
+    let bytes = writer.into_inner();
+    assert_eq!(i16::from_le_bytes([bytes[0], bytes[1]]), i16::MAX);
+    assert_eq!(i16::from_le_bytes([bytes[2], bytes[3]]), i16::MIN);
+}
@@ -0,0 +1,320 @@
+// the decoding pipeline of radiothing without the interface, it can be used by other tools as well
+// the Qt interface and the audio output are only built with the gui feature, without it the binary only decodes headless
+pub mod app_settings;
+#[cfg(feature = "gui")]
+pub mod audio;
+pub mod band_plan;
+pub mod decode_log;
+pub mod decoder;
+pub mod dsp;
+pub mod favorites;
+pub mod generator;
+#[cfg(feature = "gui")]
+pub mod gui_groups;
+pub mod habhub;
+pub mod headless;
+pub mod iq_source;
+pub mod recording;
+pub mod scan;
+#[cfg(feature = "gui")]
+pub mod session;
+pub mod settings;
+pub mod snapshot;
+pub mod units;
+pub mod worker;
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use dsp::window_functions::WindowKind;
+use rustfft::{num_complex::Complex, num_traits::Zero, Fft, FftNum, FftPlanner};
+
+pub const SAMPLE_COUNT: usize = 512;
+
+// the most spectra the gui keeps requested from the worker at once, beyond a few they only add latency
+pub const MAX_DATA_REQUESTS_IN_FLIGHT: usize = 8;
+
+// TODO the fft can be owned by the worker since the fft length is static
+pub struct FftData<T: FftNum> {
+    fft: Arc<dyn Fft<T>>,
+    input: Box<[Complex<T>]>,
+    output: Box<[Complex<T>]>,
+    scratch: Box<[Complex<T>]>,
+    window: Box<[T]>,
+    window_kind: WindowKind,
+    // the noise power the window lets through, the sum of the squared coefficients
+    window_power: f64,
+
+    meta_samplerate: f64,
+}
+
+impl<T: FftNum> FftData<T> {
+    pub fn new(len: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(len);
+        // let scratch = fft.get_outofplace_scratch_len();
+        let scratch = fft.get_outofplace_scratch_len();
+
+        let input = vec![Complex::zero(); len].into_boxed_slice();
+        let output = vec![Complex::zero(); len].into_boxed_slice();
+        let scratch = vec![Complex::zero(); scratch].into_boxed_slice();
+
+        let mut s = Self {
+            fft,
+            input,
+            output,
+            scratch,
+            window: Box::new([]),
+            window_kind: WindowKind::Rectangular,
+            window_power: 0.0,
+
+            meta_samplerate: 0.0,
+        };
+
+        s.set_window(WindowKind::Rectangular);
+        s
+    }
+    // the window is applied to the samples in set_input()
+    pub fn set_window(&mut self, kind: WindowKind) {
+        let len = self.input.len();
+        let mut coefficients = vec![0f32; len];
+        kind.coefficients(&mut coefficients);
+
+        self.window = coefficients
+            .iter()
+            .map(|&c| T::from_f32(c).unwrap())
+            .collect();
+
+        self.window_kind = kind;
+
+        // sum(w^2) == len * coherent_gain^2 * enbw
+        let gain = kind.coherent_gain(len);
+        self.window_power = len as f64 * gain * gain * kind.enbw(len);
+    }
+    pub fn get_window_kind(&self) -> WindowKind {
+        self.window_kind
+    }
+    pub fn get_input(&self) -> &[Complex<T>] {
+        &self.input
+    }
+    pub fn get_input_mut(&mut self) -> &mut [Complex<T>] {
+        &mut self.input
+    }
+    pub fn get_output(&self) -> &[Complex<T>] {
+        &self.output
+    }
+    pub fn get_output_mut(&mut self) -> &mut [Complex<T>] {
+        &mut self.output
+    }
+    pub fn get_samplerate(&self) -> f64 {
+        self.meta_samplerate
+    }
+    // copies the windowed samples into the input, converting them to the precision of the fft
+    pub fn set_input(&mut self, samples: &[Complex<f32>]) {
+        for ((dst, src), &w) in self.input.iter_mut().zip(samples).zip(self.window.iter()) {
+            *dst = Complex::new(T::from_f32(src.re).unwrap(), T::from_f32(src.im).unwrap()) * w;
+        }
+    }
+
+    pub fn process(&mut self, samplerate: f64) {
+        self.fft.process_outofplace_with_scratch(
+            &mut self.input,
+            &mut self.output,
+            &mut self.scratch,
+        );
+        self.meta_samplerate = samplerate;
+    }
+}
+
+// moves the negative frequencies in front of the positive ones so that DC ends up in the middle
+// the output of fft is not actually continuous, it is swapped around 0
+// [0ppppppp|nnnnnnnn] -> [nnnnnnnn|0ppppppp]
+//  DC     N/2
+pub fn fft_shift<T>(buf: &mut [T]) {
+    let len = buf.len();
+    buf.rotate_right(len / 2);
+}
+
+impl<T: FftNum + Into<f64>> FftData<T> {
+    // power spectral density of a bin relative to full scale per Hz
+    // the window gain and its noise bandwidth are divided out so that the noise floor doesn't depend on the window
+    pub fn psd(&self, bin: usize) -> f64 {
+        let power: f64 = self.output[bin].norm_sqr().into();
+        power / (self.window_power * self.meta_samplerate)
+    }
+}
+
+impl<T: FftNum> Clone for FftData<T> {
+    fn clone(&self) -> Self {
+        let input = vec![Complex::zero(); self.input.len()].into_boxed_slice();
+        let output = vec![Complex::zero(); self.output.len()].into_boxed_slice();
+        let scratch = vec![Complex::zero(); self.scratch.len()].into_boxed_slice();
+
+        Self {
+            fft: self.fft.clone(),
+            input,
+            output,
+            scratch,
+            window: self.window.clone(),
+            window_kind: self.window_kind,
+            window_power: self.window_power,
+            meta_samplerate: self.meta_samplerate,
+        }
+    }
+}
+
+// the spectrum is computed in single precision unless more dynamic range is needed
+#[derive(Clone, Debug)]
+pub enum SpectrumData {
+    Single(FftData<f32>),
+    Double(FftData<f64>),
+}
+
+impl SpectrumData {
+    pub fn new(len: usize, double_precision: bool) -> Self {
+        if double_precision {
+            SpectrumData::Double(FftData::new(len))
+        } else {
+            SpectrumData::Single(FftData::new(len))
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            SpectrumData::Single(data) => data.get_input().len(),
+            SpectrumData::Double(data) => data.get_input().len(),
+        }
+    }
+    pub fn is_double_precision(&self) -> bool {
+        matches!(self, SpectrumData::Double(_))
+    }
+    pub fn get_samplerate(&self) -> f64 {
+        match self {
+            SpectrumData::Single(data) => data.get_samplerate(),
+            SpectrumData::Double(data) => data.get_samplerate(),
+        }
+    }
+    pub fn process(&mut self, samples: &[Complex<f32>], samplerate: f64) {
+        match self {
+            SpectrumData::Single(data) => {
+                data.set_input(samples);
+                data.process(samplerate);
+            }
+            SpectrumData::Double(data) => {
+                data.set_input(samples);
+                data.process(samplerate);
+            }
+        }
+    }
+    pub fn set_window(&mut self, kind: WindowKind) {
+        match self {
+            SpectrumData::Single(data) => data.set_window(kind),
+            SpectrumData::Double(data) => data.set_window(kind),
+        }
+    }
+    // in dBFS/Hz
+    pub fn psd_db(&self, bin: usize) -> f64 {
+        let psd = match self {
+            SpectrumData::Single(data) => data.psd(bin),
+            SpectrumData::Double(data) => data.psd(bin),
+        };
+        10.0 * psd.log10()
+    }
+    // the plotting is always done in single precision
+    pub fn copy_to_f32(&self, input: &mut Vec<Complex<f32>>, output: &mut Vec<Complex<f32>>) {
+        input.clear();
+        output.clear();
+
+        match self {
+            SpectrumData::Single(data) => {
+                input.extend_from_slice(data.get_input());
+                output.extend_from_slice(data.get_output());
+            }
+            SpectrumData::Double(data) => {
+                let to_f32 = |c: &Complex<f64>| Complex::new(c.re as f32, c.im as f32);
+                input.extend(data.get_input().iter().map(to_f32));
+                output.extend(data.get_output().iter().map(to_f32));
+            }
+        }
+    }
+}
+
+impl<T: FftNum> Debug for FftData<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // it is useless to print many-thousand-long arrays
+        f.debug_struct("FftData").finish()
+    }
+}
+
+#[test]
+fn double_precision_spectrum() {
+    let len = 512;
+    // a tone in the middle of bin 37 and a bit off the center of bin 100
+    let samples = (0..len)
+        .map(|i| {
+            let t = i as f32 / len as f32;
+            let phase = 2.0 * std::f32::consts::PI * t;
+            Complex::from_polar(1.0, phase * 37.0) + Complex::from_polar(0.001, phase * 100.3)
+        })
+        .collect::<Vec<_>>();
+
+    let mut single = SpectrumData::new(len, false);
+    let mut double = SpectrumData::new(len, true);
+    single.process(&samples, 1.0);
+    double.process(&samples, 1.0);
+    assert!(double.is_double_precision());
+
+    let (mut single_output, mut double_output) = (Vec::new(), Vec::new());
+    single.copy_to_f32(&mut Vec::new(), &mut single_output);
+    double.copy_to_f32(&mut Vec::new(), &mut double_output);
+
+    for (s, d) in single_output.iter().zip(&double_output) {
+        assert!((s.norm() - d.norm()).abs() < 1e-3 * len as f32);
+    }
+    assert!((double_output[37].norm() / len as f32 - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn white_noise_psd() {
+    let len = 4096;
+    let samplerate = 48000.0;
+
+    // uniform noise in -1..1 has a variance of 1/3 per component
+    let mut noise = generator::Noise::default();
+    let samples = (0..len).map(|_| noise.complex()).collect::<Vec<_>>();
+
+    let mut data = FftData::<f64>::new(len);
+    data.set_window(WindowKind::BlackmanHaris);
+    data.set_input(&samples);
+    data.process(samplerate);
+
+    // the average over all bins is the total power spread over the samplerate, regardless of the window
+    let mean = (0..len).map(|bin| data.psd(bin)).sum::<f64>() / len as f64;
+    let expected = (2.0 / 3.0) / samplerate;
+    assert!((mean / expected - 1.0).abs() < 0.1);
+}
+
+#[test]
+fn shifted_tone_right_of_center() {
+    let len = 512;
+    // a tone 20 bins above DC
+    let phase = 2.0 * std::f32::consts::PI * 20.0 / len as f32;
+    let samples = (0..len)
+        .map(|i| Complex::from_polar(1.0, phase * i as f32))
+        .collect::<Vec<_>>();
+
+    let mut data = FftData::<f32>::new(len);
+    data.set_input(&samples);
+    data.process(1.0);
+
+    let mut spectrum = data.get_output().to_vec();
+    fft_shift(&mut spectrum);
+
+    let peak = (0..len)
+        .max_by(|&a, &b| spectrum[a].norm().partial_cmp(&spectrum[b].norm()).unwrap())
+        .unwrap();
+    assert_eq!(peak, len / 2 + 20);
+
+    // DC is in the middle, also for odd lengths
+    let mut odd = [0, 1, 2, -2, -1];
+    fft_shift(&mut odd);
+    assert_eq!(odd, [-2, -1, 0, 1, 2]);
+}
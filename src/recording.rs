@@ -0,0 +1,155 @@
+use std::io::{self, Write};
+
+use rustfft::num_complex::Complex;
+
+use crate::{dsp::ring_buffer::RingBuffer, iq_source::IqWriter, worker::worker::RxFormat};
+
+// writes the received samples with an IqWriter while recording
+// in between the last samples are kept in the pre-trigger ring so that a recording starts with what was received
+// just before it was started, like the beginning of a transmission the user only reacted to
+pub struct Recorder<W: Write> {
+    pre_trigger: RingBuffer<Complex<RxFormat>>,
+    writer: Option<IqWriter<W>>,
+    // of the current or the last recording, including the pre-trigger samples
+    written: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    // 0 samples disables the pre-trigger
+    pub fn new(pre_trigger_len: usize) -> Self {
+        Self {
+            pre_trigger: RingBuffer::new(pre_trigger_len),
+            writer: None,
+            written: 0,
+        }
+    }
+    // a shorter ring keeps the newest samples
+    pub fn set_pre_trigger_len(&mut self, len: usize) {
+        self.pre_trigger.set_capacity(len);
+    }
+    pub fn pre_trigger_len(&self) -> usize {
+        self.pre_trigger.capacity()
+    }
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+    // the samples of a previous receiver configuration don't belong in front of a recording of the new one
+    pub fn clear_pre_trigger(&mut self) {
+        self.pre_trigger.clear();
+    }
+    // the buffered samples are written first, oldest to newest, returns how many there were
+    // a recording which is already running is replaced without writing anything to the new one
+    pub fn start(&mut self, mut writer: IqWriter<W>) -> io::Result<usize> {
+        let (front, back) = self.pre_trigger.as_slices();
+        writer.write_samples(front)?;
+        writer.write_samples(back)?;
+
+        let len = self.pre_trigger.len();
+        self.pre_trigger.clear();
+        self.writer = Some(writer);
+        self.written = len as u64;
+
+        Ok(len)
+    }
+    // returns the writer once everything was flushed to it, None if nothing was recorded
+    pub fn stop(&mut self) -> Option<io::Result<IqWriter<W>>> {
+        let mut writer = self.writer.take()?;
+        Some(writer.flush().map(|_| writer))
+    }
+    // writes the samples if recording, otherwise they go to the pre-trigger ring
+    // the recording is stopped if the write fails, what was written so far stays in the file
+    pub fn push(&mut self, samples: &[Complex<RxFormat>]) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => {
+                if let Err(e) = writer.write_samples(samples) {
+                    self.writer = None;
+                    return Err(e);
+                }
+                self.written += samples.len() as u64;
+            }
+            None if self.pre_trigger.capacity() > 0 => self.pre_trigger.push(samples),
+            None => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn pre_trigger_flush() {
+    use crate::iq_source::{IqFormat, IqReader, SampleSource};
+
+    let sample = |i: usize| Complex::new(i as RxFormat, -(i as RxFormat));
+    let samples: Vec<_> = (0..1000).map(sample).collect();
+
+    // the ring keeps the last 300 of the 1000 samples, pushed in chunks which don't divide it
+    let mut recorder = Recorder::new(300);
+    for chunk in samples.chunks(77) {
+        recorder.push(chunk).unwrap();
+    }
+    assert!(!recorder.is_recording());
+
+    let flushed = recorder
+        .start(IqWriter::new(Vec::new(), IqFormat::Cf32))
+        .unwrap();
+    assert_eq!(flushed, 300);
+    assert!(recorder.is_recording());
+
+    // the recording goes on live after the history
+    recorder.push(&[sample(1000), sample(1001)]).unwrap();
+    assert_eq!(recorder.written(), 302);
+
+    let bytes = recorder.stop().unwrap().unwrap().into_inner();
+    let mut recorded = vec![Complex::new(0.0, 0.0); 400];
+    let count = IqReader::new(&bytes[..], IqFormat::Cf32)
+        .read_samples(&mut recorded)
+        .unwrap();
+    assert_eq!(count, 302);
+    for (i, s) in recorded[..count].iter().enumerate() {
+        assert_eq!(*s, sample(700 + i));
+    }
+
+    // the ring starts over after a recording and nothing is recorded without one
+    assert!(recorder.stop().is_none());
+    recorder.push(&samples[..10]).unwrap();
+    let bytes = recorder
+        .start(IqWriter::new(Vec::new(), IqFormat::Cf32))
+        .map(|flushed| assert_eq!(flushed, 10))
+        .and_then(|_| recorder.stop().unwrap())
+        .unwrap()
+        .into_inner();
+    assert_eq!(bytes.len(), 10 * IqFormat::Cf32.sample_size());
+
+    // without a pre-trigger the recording starts empty
+    let mut recorder = Recorder::new(0);
+    recorder.push(&samples).unwrap();
+    assert_eq!(
+        recorder
+            .start(IqWriter::new(Vec::new(), IqFormat::Cf32))
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn failed_write_stops_recording() {
+    use crate::iq_source::IqFormat;
+
+    struct Full;
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut recorder = Recorder::new(0);
+    recorder.start(IqWriter::new(Full, IqFormat::Cu8)).unwrap();
+    assert!(recorder.push(&[Complex::new(0.0, 0.0)]).is_err());
+    assert!(!recorder.is_recording());
+}
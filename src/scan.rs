@@ -0,0 +1,241 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use crate::worker::{
+    worker::{DeviceBoundCommand, GuiBoundEvent},
+    worker_manager::DeviceManager,
+};
+use crate::SpectrumData;
+
+// more steps than anyone would wait for, a tiny step over a wide range would otherwise take all of the memory
+pub const MAX_SCAN_STEPS: usize = 100_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanError {
+    // (the number of steps the range needs)
+    TooManySteps(f64),
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::TooManySteps(steps) => write!(
+                f,
+                "The range needs {:.0} steps, at most {} are allowed. Use a larger step.",
+                steps, MAX_SCAN_STEPS
+            ),
+        }
+    }
+}
+
+// the frequencies visited by a scan, the stop frequency is included only if the steps land on it
+// the scan goes downwards if stop is below start
+pub fn scan_frequencies(start: f64, stop: f64, step: f64) -> Result<Vec<f64>, ScanError> {
+    if step <= 0.0 || !step.is_finite() {
+        return Ok(vec![start]);
+    }
+
+    let step = if stop < start { -step } else { step };
+    // a tiny bit of tolerance so that the rounding of the step doesn't drop the last frequency
+    let count = ((stop - start) / step + 1e-9).floor() + 1.0;
+    if count > MAX_SCAN_STEPS as f64 {
+        return Err(ScanError::TooManySteps(count));
+    }
+
+    // multiplying instead of adding keeps the rounding errors from accumulating
+    Ok((0..count as usize)
+        .map(|i| start + i as f64 * step)
+        .collect())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanStatus {
+    // (frequency)
+    Tuning(f64),
+    // (frequency, the highest power spectral density measured so far in dBFS/Hz)
+    Measuring(f64, f64),
+    // the scan stopped at a frequency with a level above the threshold
+    Found(f64, f64),
+    // the whole range was scanned without finding anything above the threshold, the receiver is tuned to the best frequency
+    Finished(Option<(f64, f64)>),
+}
+
+pub struct ScanController {
+    frequencies: Vec<f64>,
+    index: usize,
+    dwell: Duration,
+    threshold: f64,
+
+    // set once the receiver confirms the current frequency, only the spectra after that are measured
+    dwell_start: Option<Instant>,
+    peak: f64,
+    // (index, level)
+    best: Option<(usize, f64)>,
+    active: bool,
+}
+
+impl ScanController {
+    pub fn new() -> Self {
+        Self {
+            frequencies: Vec::new(),
+            index: 0,
+            dwell: Duration::from_millis(0),
+            threshold: 0.0,
+            dwell_start: None,
+            peak: f64::NEG_INFINITY,
+            best: None,
+            active: false,
+        }
+    }
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+    // the scan needs the spectrum to be running, it only measures the data the output requests
+    pub fn start(
+        &mut self,
+        device: &DeviceManager,
+        frequencies: Vec<f64>,
+        dwell_ms: u64,
+        threshold: f64,
+    ) -> Option<ScanStatus> {
+        if frequencies.is_empty() || !device.get_receiver_valid() {
+            return None;
+        }
+
+        self.frequencies = frequencies;
+        self.index = 0;
+        self.dwell = Duration::from_millis(dwell_ms);
+        self.threshold = threshold;
+        self.best = None;
+        self.active = true;
+
+        self.tune(device, 0)
+    }
+    pub fn stop(&mut self) {
+        self.active = false;
+        self.dwell_start = None;
+    }
+    pub fn handle_event(
+        &mut self,
+        device: &DeviceManager,
+        event: &GuiBoundEvent,
+    ) -> Option<ScanStatus> {
+        if !self.active {
+            return None;
+        }
+
+        match event {
+            GuiBoundEvent::ReceiverConfigured { actual } => {
+                let requested = self.frequencies[self.index];
+
+                // the driver may round the frequency, anything closer than to the neighbouring steps is fine
+                if self.dwell_start.is_none()
+                    && (actual.frequency - requested).abs() <= self.tolerance()
+                {
+                    self.dwell_start = Some(Instant::now());
+                    self.peak = f64::NEG_INFINITY;
+                }
+            }
+            GuiBoundEvent::DataReady { data } => {
+                let dwell_start = self.dwell_start?;
+                let frequency = self.frequencies[self.index];
+
+                self.peak = self.peak.max(peak_level(data));
+
+                if dwell_start.elapsed() < self.dwell {
+                    return Some(ScanStatus::Measuring(frequency, self.peak));
+                }
+
+                if self.best.map_or(true, |(_, level)| self.peak > level) {
+                    self.best = Some((self.index, self.peak));
+                }
+
+                if self.peak >= self.threshold {
+                    self.stop();
+                    return Some(ScanStatus::Found(frequency, self.peak));
+                }
+
+                if self.index + 1 < self.frequencies.len() {
+                    return self.tune(device, self.index + 1);
+                }
+
+                // the best frequency is where the user most likely wants to listen
+                if let Some((index, _)) = self.best {
+                    self.tune(device, index);
+                }
+                self.stop();
+
+                let best = self
+                    .best
+                    .map(|(index, level)| (self.frequencies[index], level));
+                return Some(ScanStatus::Finished(best));
+            }
+            GuiBoundEvent::DeviceDestroyed | GuiBoundEvent::WorkerReset => self.stop(),
+            _ => {}
+        }
+
+        None
+    }
+    fn tune(&mut self, device: &DeviceManager, index: usize) -> Option<ScanStatus> {
+        let mut state = device.get_receiver_state()?;
+        state.frequency = self.frequencies[index];
+
+        self.index = index;
+        self.dwell_start = None;
+
+        device.schedule_command(DeviceBoundCommand::SetReceiver(state), 0);
+
+        Some(ScanStatus::Tuning(self.frequencies[index]))
+    }
+    fn tolerance(&self) -> f64 {
+        match self.frequencies.as_slice() {
+            [first, second, ..] => (second - first).abs() / 2.0,
+            // there is nothing to confuse a single frequency with
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+// the highest power spectral density in dBFS/Hz
+// the dc bin is skipped because the dc offset of many receivers would always win
+fn peak_level(data: &SpectrumData) -> f64 {
+    (1..data.len())
+        .map(|bin| data.psd_db(bin))
+        .fold(f64::NEG_INFINITY, f64::max)
+}
+
+#[test]
+fn scan_step_sequence() {
+    assert_eq!(
+        scan_frequencies(144.0e6, 144.1e6, 25.0e3).unwrap(),
+        [144.0e6, 144.025e6, 144.05e6, 144.075e6, 144.1e6]
+    );
+
+    // the last step would overshoot
+    assert_eq!(
+        scan_frequencies(100.0e6, 100.07e6, 25.0e3).unwrap(),
+        [100.0e6, 100.025e6, 100.05e6]
+    );
+
+    // downwards
+    assert_eq!(
+        scan_frequencies(433.1e6, 433.0e6, 50.0e3).unwrap(),
+        [433.1e6, 433.05e6, 433.0e6]
+    );
+
+    assert_eq!(scan_frequencies(7.0e6, 8.0e6, 0.0), Ok(vec![7.0e6]));
+    assert_eq!(scan_frequencies(7.0e6, 7.0e6, 1.0e3), Ok(vec![7.0e6]));
+
+    // the whole range of the spinboxes with the smallest step they allow
+    assert_eq!(
+        scan_frequencies(0.0, 100.0e9, 1.0),
+        Err(ScanError::TooManySteps(100.0e9 + 1.0))
+    );
+    assert_eq!(
+        scan_frequencies(0.0, 99_999.0, 1.0).unwrap().len(),
+        MAX_SCAN_STEPS
+    );
+    assert!(scan_frequencies(0.0, 100_000.0, 1.0).is_err());
+}
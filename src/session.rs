@@ -0,0 +1,554 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use qt_charts::qt_core::{QTimer, SlotNoArgs};
+use qt_widgets::{
+    qt_core::{QBox, QListOfInt},
+    QHBoxLayout, QSplitter, QVBoxLayout, QWidget,
+};
+
+use crate::app_settings::AppSettings;
+use crate::gui_groups::{
+    decode_group::DecodeGroup, device_group::DeviceGroup, habhub_group::HabhubGroup,
+    handle_send_result, output_group::OutputGroup, receive_group::ReceiveGroup,
+    scan_group::ScanGroup, ApplyMode,
+};
+use crate::worker::worker::{DeviceBoundCommand, DeviceErrorKind, GuiBoundEvent};
+use crate::worker::worker_manager::{drain_events, DeviceManager, WorkerPoisoned};
+
+const MAX_CONSECUTIVE_TIMEOUT_COUNT: u32 = 8;
+
+// what happens to the device after the worker reports an error
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ErrorAction {
+    // the device is unusable, stop receiving and destroy it
+    Destroy,
+    // stop receiving but keep the device so the user can try again
+    Stop,
+    // the device may still recover
+    Ignore,
+}
+
+// what a session does with an event of its worker
+#[derive(Debug)]
+enum Reaction {
+    // the groups handle it
+    Handle(GuiBoundEvent),
+    // stop receiving, with `destroy` also destroy the device
+    Stop { destroy: bool },
+    // the worker panicked, it is replaced with a new one and the receiving is stopped
+    Reset,
+    Ignore,
+}
+
+// the state a session keeps about the events of its own worker, without the widgets
+#[derive(Default)]
+struct SessionEvents {
+    consecutive_timeout_count: u32,
+}
+
+impl SessionEvents {
+    fn react(&mut self, event: Result<GuiBoundEvent, WorkerPoisoned>) -> Reaction {
+        match event {
+            Ok(GuiBoundEvent::Error { kind, error }) => {
+                match error_action(kind, &error, &mut self.consecutive_timeout_count) {
+                    ErrorAction::Destroy => Reaction::Stop { destroy: true },
+                    ErrorAction::Stop => Reaction::Stop { destroy: false },
+                    ErrorAction::Ignore => Reaction::Ignore,
+                }
+            }
+            Ok(GuiBoundEvent::DeviceNotCreated { message }) => {
+                log::error!("The device could not be created: {}", message);
+                Reaction::Handle(GuiBoundEvent::DeviceNotCreated { message })
+            }
+            Ok(GuiBoundEvent::WorkerError { message }) => {
+                log::error!("The device refused the configuration: {}", message);
+                Reaction::Stop { destroy: false }
+            }
+            Ok(event) => {
+                self.consecutive_timeout_count = 0;
+                Reaction::Handle(event)
+            }
+            Err(_) => {
+                log::error!("The receiver worker thread has panicked, resetting the worker and restoring its configuration");
+                Reaction::Reset
+            }
+        }
+    }
+}
+
+// one device with its own worker thread and the groups controlling it, every session is shown in its own tab
+#[allow(unused)]
+pub struct DeviceSession {
+    page: QBox<QWidget>,
+    // holds the left, middle and right columns so the user can resize them
+    splitter: QBox<QSplitter>,
+    device_group: Rc<DeviceGroup>,
+    receive_group: Rc<ReceiveGroup>,
+    decode_group: Rc<DecodeGroup>,
+    output_group: Rc<OutputGroup>,
+    scan_group: Rc<ScanGroup>,
+    // shared by all the sessions, every one feeds it its decoded text
+    habhub_group: RefCell<Option<Rc<HabhubGroup>>>,
+
+    device: Rc<DeviceManager>,
+    events: RefCell<SessionEvents>,
+}
+
+impl DeviceSession {
+    pub unsafe fn new(
+        device: DeviceManager,
+        settings: Rc<AppSettings>,
+        apply_mode: Rc<ApplyMode>,
+    ) -> Self {
+        let device = Rc::new(device);
+
+        let page = QWidget::new_0a();
+
+        // this timer runs the scheduled device command
+        // these exist because it is very useful to ensure that a command is sent at some point but not eventually
+        // at this time only to limit the rate at which the commands are exchanged - the automatic device starting would spam the requests a lot otherwise
+        // and rate of RequestData sending would depend on other events being sent through the channel
+        let timer = QTimer::new_1a(&page);
+        timer.set_interval(5);
+        timer.set_single_shot(false);
+        let timer_ptr = timer.as_ptr();
+        let d = device.clone();
+        timer
+            .timeout()
+            .connect(&SlotNoArgs::new(timer_ptr, move || {
+                let next = d.poll_scheduled_commands();
+                // the timer gets the how long it will take for the next earliest command to be "ready"
+                // and then sets it as its interval
+                timer.set_interval(next as i32);
+            }));
+        timer_ptr.start_0a();
+
+        let h_layout = QHBoxLayout::new_1a(&page);
+
+        let splitter = QSplitter::new();
+        splitter.set_children_collapsible(false);
+        h_layout.add_widget(&splitter);
+
+        // LEFT
+        let left = QWidget::new_0a();
+        let v_layout_left = QVBoxLayout::new_1a(&left);
+        splitter.add_widget(&left);
+
+        let (device_group, group) = DeviceGroup::new(device.clone(), settings.clone());
+        v_layout_left.add_widget(group);
+
+        let (receive_group, group) =
+            ReceiveGroup::new(device.clone(), settings.clone(), apply_mode.clone());
+        v_layout_left.add_widget(group);
+
+        let (decode_group, group) = DecodeGroup::new(device.clone(), settings.clone(), apply_mode);
+        v_layout_left.add_widget(group);
+
+        receive_group.set_decode_group(decode_group.clone());
+
+        v_layout_left.add_stretch_0a();
+
+        // MIDDLE
+        let (output_group, group) = OutputGroup::new(device.clone(), settings.clone());
+        splitter.add_widget(group);
+
+        output_group.set_decode_group(decode_group.clone());
+
+        // RIGHT
+        let right = QWidget::new_0a();
+        let v_layout_right = QVBoxLayout::new_1a(&right);
+        splitter.add_widget(&right);
+
+        let (scan_group, group) = ScanGroup::new(device.clone());
+        v_layout_right.add_widget(group);
+
+        v_layout_right.add_stretch_0a();
+
+        Self {
+            page,
+            splitter,
+            device_group,
+            receive_group,
+            decode_group,
+            output_group,
+            scan_group,
+            habhub_group: RefCell::new(None),
+
+            device,
+            events: RefCell::new(SessionEvents::default()),
+        }
+    }
+    pub fn page(&self) -> &QBox<QWidget> {
+        &self.page
+    }
+    pub fn device(&self) -> Rc<DeviceManager> {
+        self.device.clone()
+    }
+    pub fn set_habhub_group(&self, habhub_group: Rc<HabhubGroup>) {
+        self.habhub_group.replace(Some(habhub_group));
+    }
+    // the widths of the left, middle and right columns
+    pub unsafe fn splitter_sizes(&self) -> [i32; 3] {
+        let list = self.splitter.sizes();
+
+        let mut sizes = [0; 3];
+        for (i, size) in sizes.iter_mut().enumerate() {
+            *size = *list.at(i as i32);
+        }
+
+        sizes
+    }
+    pub unsafe fn set_splitter_sizes(&self, sizes: [i32; 3]) {
+        let list = QListOfInt::new();
+        for size in &sizes {
+            list.append_int(size);
+        }
+
+        self.splitter.set_sizes(&list);
+    }
+    unsafe fn handle_event(&self, event: GuiBoundEvent) {
+        let mut event = Some(event);
+
+        macro_rules! chain_handle_events {
+            ($event:ident, $($handler:expr),+) => {
+                $(
+                    if $event.is_some() {
+                        $handler.handle_event(&mut $event);
+                    } else { return }
+                )+
+            }
+        }
+
+        // only looks at the events, it isn't part of the session
+        if let Some(habhub_group) = &*self.habhub_group.borrow() {
+            habhub_group.handle_event(&self.device, &mut event);
+        }
+
+        // the output group takes the DataReady events so the scan group has to see them first
+        chain_handle_events! {event, self.device_group, self.receive_group, self.decode_group, self.scan_group, self.output_group};
+    }
+    unsafe fn reset_worker(&self) {
+        self.device.reset();
+
+        let event = GuiBoundEvent::WorkerReset;
+        self.handle_event(event);
+    }
+    // processes the events queued by the worker, gives up after `budget` so that the other sessions get their turn
+    pub unsafe fn poll_events(&self, budget: Duration) {
+        drain_events(
+            budget,
+            || self.device.try_receive().transpose(),
+            |event| {
+                let reaction = self.events.borrow_mut().react(event);
+
+                match reaction {
+                    Reaction::Handle(event) => self.handle_event(event),
+                    Reaction::Stop { destroy } => {
+                        self.device.set_receive_enabled(false);
+                        self.output_group.set_run(false);
+
+                        if destroy {
+                            handle_send_result(
+                                self.device.send_command(DeviceBoundCommand::DestroyDevice),
+                            );
+                        }
+                    }
+                    Reaction::Reset => {
+                        self.reset_worker();
+                        self.device.set_receive_enabled(false);
+                        self.output_group.set_run(false);
+                    }
+                    Reaction::Ignore => {}
+                }
+            },
+        );
+    }
+    pub unsafe fn step_frequency(&self, direction: f64, coarse: bool) {
+        self.receive_group.step_frequency(direction, coarse);
+    }
+    pub unsafe fn toggle_run(&self) {
+        self.output_group.toggle_run();
+    }
+    pub unsafe fn populate_settings(&self, settings: &mut AppSettings) {
+        self.device_group.populate_settings(settings);
+        self.receive_group.populate_settings(settings);
+        self.decode_group.populate_settings(settings);
+        self.output_group.populate_settings(settings);
+    }
+    pub unsafe fn apply_settings(&self, settings: &AppSettings) {
+        self.device_group.apply_settings(settings);
+        self.receive_group.apply_settings(settings);
+        self.decode_group.apply_settings(settings);
+        self.output_group.apply_settings(settings);
+    }
+}
+
+fn error_action(
+    kind: DeviceErrorKind,
+    e: &soapysdr::Error,
+    consecutive_timeout_count: &mut u32,
+) -> ErrorAction {
+    match kind {
+        DeviceErrorKind::Fatal => {
+            log::error!(
+                "Device encountered a fatal error: {:?}: '{}'",
+                e.code,
+                e.message
+            );
+            *consecutive_timeout_count = 0;
+            ErrorAction::Destroy
+        }
+        DeviceErrorKind::Timeout => {
+            *consecutive_timeout_count += 1;
+
+            if *consecutive_timeout_count == MAX_CONSECUTIVE_TIMEOUT_COUNT {
+                log::error!("Device timed out too many times");
+                *consecutive_timeout_count = 0;
+                ErrorAction::Destroy
+            } else {
+                log::debug!("Timeout");
+                ErrorAction::Ignore
+            }
+        }
+        // the worker already dropped the damaged buffer and keeps receiving
+        DeviceErrorKind::SamplesDropped => {
+            log::warn!("Samples were dropped: {:?}: '{}'", e.code, e.message);
+            ErrorAction::Ignore
+        }
+        // non-fatal error, continue
+        DeviceErrorKind::Other => {
+            log::error!("Device encountered an error: {:?}: '{}'", e.code, e.message);
+            ErrorAction::Stop
+        }
+    }
+}
+
+// the kind is attached by the worker when it sends the error
+#[cfg(test)]
+fn react(e: &soapysdr::Error, consecutive_timeout_count: &mut u32) -> ErrorAction {
+    error_action(DeviceErrorKind::from_error(e), e, consecutive_timeout_count)
+}
+
+#[test]
+fn sessions_handle_events_independently() {
+    use soapysdr::ErrorCode;
+
+    type Event = Result<GuiBoundEvent, WorkerPoisoned>;
+
+    let timeout = || -> Event {
+        Ok(GuiBoundEvent::Error {
+            kind: DeviceErrorKind::Timeout,
+            error: soapysdr::Error {
+                code: ErrorCode::Timeout,
+                message: String::new(),
+            },
+        })
+    };
+
+    // the event queues of two workers, each drained by its own session like in DeviceSession::poll_events
+    let queues = [
+        crossbeam_channel::unbounded::<Event>(),
+        crossbeam_channel::unbounded::<Event>(),
+    ];
+    let mut sessions = [SessionEvents::default(), SessionEvents::default()];
+    let mut reactions = [Vec::new(), Vec::new()];
+    let poll = |sessions: &mut [SessionEvents; 2], reactions: &mut [Vec<String>; 2]| {
+        for (i, (session, reactions)) in sessions.iter_mut().zip(reactions).enumerate() {
+            drain_events(
+                Duration::from_secs(1),
+                || queues[i].1.try_recv().ok(),
+                |event| reactions.push(format!("{:?}", session.react(event))),
+            );
+        }
+    };
+
+    // the first device keeps timing out while the second one receives in between its timeouts
+    let (first, second) = (&queues[0].0, &queues[1].0);
+    for _ in 1..MAX_CONSECUTIVE_TIMEOUT_COUNT {
+        first.send(timeout()).unwrap();
+        second.send(timeout()).unwrap();
+        second.send(Ok(GuiBoundEvent::DeviceDestroyed)).unwrap();
+        poll(&mut sessions, &mut reactions);
+    }
+    first.send(timeout()).unwrap();
+    second.send(timeout()).unwrap();
+    poll(&mut sessions, &mut reactions);
+
+    // only the first one gives up on its device, the second one counts its own timeouts
+    assert_eq!(reactions[0].len(), MAX_CONSECUTIVE_TIMEOUT_COUNT as usize);
+    assert!(reactions[0][..reactions[0].len() - 1]
+        .iter()
+        .all(|reaction| reaction == "Ignore"));
+    assert_eq!(reactions[0].last().unwrap(), "Stop { destroy: true }");
+    assert_eq!(sessions[0].consecutive_timeout_count, 0);
+
+    assert_eq!(reactions[1][..2], ["Ignore", "Handle(DeviceDestroyed)"]);
+    assert_eq!(reactions[1].last().unwrap(), "Ignore");
+    assert_eq!(sessions[1].consecutive_timeout_count, 1);
+
+    // a panicked worker only resets its own session
+    second.send(Err(WorkerPoisoned)).unwrap();
+    first.send(Ok(GuiBoundEvent::DeviceDestroyed)).unwrap();
+    poll(&mut sessions, &mut reactions);
+    assert_eq!(reactions[0].last().unwrap(), "Handle(DeviceDestroyed)");
+    assert_eq!(reactions[1].last().unwrap(), "Reset");
+}
+
+#[test]
+fn error_codes_map_to_reactions() {
+    use soapysdr::ErrorCode;
+    use DeviceErrorKind as K;
+    use ErrorAction as A;
+
+    let cases = [
+        (ErrorCode::Overflow, "", K::SamplesDropped, A::Ignore),
+        (ErrorCode::Corruption, "", K::SamplesDropped, A::Ignore),
+        (ErrorCode::Timeout, "", K::Timeout, A::Ignore),
+        (ErrorCode::Timeout, "Lost", K::Fatal, A::Destroy),
+        (ErrorCode::StreamError, "", K::Fatal, A::Destroy),
+        (ErrorCode::NotSupported, "", K::Other, A::Stop),
+        (ErrorCode::Other, "bad frequency", K::Other, A::Stop),
+    ];
+
+    for &(code, message, kind, action) in &cases {
+        let e = soapysdr::Error {
+            code,
+            message: message.to_owned(),
+        };
+        assert_eq!(DeviceErrorKind::from_error(&e), kind, "{:?}", code);
+
+        let mut count = 0;
+        assert_eq!(react(&e, &mut count), action, "{:?}", code);
+    }
+
+    // dropped samples in between don't reset the count of the timeouts
+    let timeout = soapysdr::Error {
+        code: ErrorCode::Timeout,
+        message: String::new(),
+    };
+    let overflow = soapysdr::Error {
+        code: ErrorCode::Overflow,
+        message: String::new(),
+    };
+    let mut count = 0;
+    react(&timeout, &mut count);
+    react(&overflow, &mut count);
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn restored_defaults_are_stable() {
+    use crate::app_settings::{parse_fft_window, DEFAULT_SETTINGS};
+    use crate::gui_groups::run_on_gui_thread;
+
+    run_on_gui_thread(|| unsafe {
+        let apply_mode = Rc::new(ApplyMode::new(false));
+        let session = DeviceSession::new(
+            DeviceManager::new_named("Worker thread restore defaults"),
+            Rc::new(DEFAULT_SETTINGS),
+            apply_mode.clone(),
+        );
+        let (habhub_group, _) = HabhubGroup::new(Rc::new(DEFAULT_SETTINGS), apply_mode);
+
+        // what the restore defaults button applies and what is saved afterwards
+        let restore = |settings: &AppSettings| {
+            session.apply_settings(settings);
+            habhub_group.apply_settings(settings);
+
+            let mut populated = DEFAULT_SETTINGS;
+            session.populate_settings(&mut populated);
+            habhub_group.populate_settings(&mut populated);
+            populated
+        };
+
+        // the values the defaults leave empty are saved as the ones the groups use for them
+        let restored = restore(&DEFAULT_SETTINGS);
+        assert_eq!(restored.frequency_unit, "MHz");
+        assert_eq!(restored.decoder, "None");
+        assert_eq!(restored.fft_window, parse_fft_window("").name());
+        assert_eq!(
+            AppSettings {
+                frequency_unit: String::new(),
+                decoder: String::new(),
+                fft_window: String::new(),
+                ..restored.clone()
+            },
+            DEFAULT_SETTINGS
+        );
+        assert_eq!(restore(&restored), restored);
+
+        // whatever was edited before is replaced
+        let mut edited = restored.clone();
+        edited.auto_device = true;
+        edited.frequency = 145.8;
+        edited.frequency_unit = "kHz".to_owned();
+        edited.gain = 12.5;
+        edited.decoder = "Baudot".to_owned();
+        edited.squelch_threshold = 10.0;
+        edited.burst_gap_ms = 500;
+        assert_eq!(restore(&edited), edited);
+        assert_eq!(restore(&DEFAULT_SETTINGS), restored);
+    });
+}
+
+#[test]
+fn settings_survive_save_and_load() {
+    use crate::app_settings::{full_settings, parse_settings, DEFAULT_SETTINGS};
+    use crate::gui_groups::run_on_gui_thread;
+
+    run_on_gui_thread(|| unsafe {
+        let telemetry_log = std::env::temp_dir().join(format!(
+            "radiothing_session_telemetry_{}.csv",
+            std::process::id()
+        ));
+        let settings = AppSettings {
+            // the group opens the log as soon as the settings are applied
+            telemetry_log: telemetry_log.to_string_lossy().into_owned(),
+            ..full_settings()
+        };
+
+        // the settings are applied to a new session and collected from it and the habhub group like App does
+        let apply_collect = |settings: &AppSettings, name: &str| {
+            let apply_mode = Rc::new(ApplyMode::new(false));
+            let session = DeviceSession::new(
+                DeviceManager::new_named(name),
+                Rc::new(DEFAULT_SETTINGS),
+                apply_mode.clone(),
+            );
+            let (habhub_group, _) = HabhubGroup::new(Rc::new(DEFAULT_SETTINGS), apply_mode);
+            session.apply_settings(settings);
+            habhub_group.apply_settings(settings);
+
+            let mut collected = DEFAULT_SETTINGS;
+            session.populate_settings(&mut collected);
+            habhub_group.populate_settings(&mut collected);
+            collected
+        };
+
+        // App reads these from its own widgets or keeps the loaded values, every other field belongs to a group
+        let from_groups = AppSettings {
+            auto_update: DEFAULT_SETTINGS.auto_update,
+            event_budget_ms: DEFAULT_SETTINGS.event_budget_ms,
+            autosave_s: DEFAULT_SETTINGS.autosave_s,
+            window_x: DEFAULT_SETTINGS.window_x,
+            window_y: DEFAULT_SETTINGS.window_y,
+            window_width: DEFAULT_SETTINGS.window_width,
+            window_height: DEFAULT_SETTINGS.window_height,
+            left_width: DEFAULT_SETTINGS.left_width,
+            middle_width: DEFAULT_SETTINGS.middle_width,
+            right_width: DEFAULT_SETTINGS.right_width,
+            ..settings.clone()
+        };
+
+        let collected = apply_collect(&settings, "Worker thread save");
+        assert_eq!(collected, from_groups);
+
+        let parsed = parse_settings(&collected.pretty_serialize()).unwrap();
+        assert_eq!(parsed, collected);
+        assert_eq!(apply_collect(&parsed, "Worker thread load"), collected);
+
+        let _ = std::fs::remove_file(&telemetry_log);
+    });
+}
@@ -4,6 +4,9 @@ use std::{collections::HashMap, convert::TryFrom, error::Error, fmt::Display, pa
 pub enum Field {
     String(String),
     Number(f64),
+    // a number written with a unit like 433.92MHz, it is scaled to Hz or Sps
+    Quantity(f64),
+    List(Vec<Field>),
     ParseError,
 }
 
@@ -11,7 +14,17 @@ impl Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Field::String(s) => write!(f, "\"{}\"", s),
-            Field::Number(n) => write!(f, "{}", n),
+            Field::Number(n) | Field::Quantity(n) => write!(f, "{}", n),
+            Field::List(list) => {
+                write!(f, "[")?;
+                for (i, field) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", field)?;
+                }
+                write!(f, "]")
+            }
             Field::ParseError => write!(f, "Parse Error"),
         }
     }
@@ -25,8 +38,8 @@ macro_rules! field_try_into {
             fn try_from(field: Field) -> Result<$type, Self::Error> {
                 match field {
                     Field::String(s) => return { $string }(s),
-                    Field::Number(n) => return { $number }(n),
-                    Field::ParseError => Err(()),
+                    Field::Number(n) | Field::Quantity(n) => return { $number }(n),
+                    Field::List(_) | Field::ParseError => Err(()),
                 }
             }
         }
@@ -38,6 +51,7 @@ field_try_into! {f64, |s: String| s.parse::<f64>().map_err(|_| ()), |n| Ok(n)}
 field_try_into! {f32, |s: String| s.parse::<f32>().map_err(|_| ()), |n| Ok(n as f32)}
 field_try_into! {u64, |s: String| s.parse::<u64>().map_err(|_| ()), |n| Ok(n as u64)}
 field_try_into! {u32, |s: String| s.parse::<u32>().map_err(|_| ()), |n| Ok(n as u32)}
+field_try_into! {i32, |s: String| s.parse::<i32>().map_err(|_| ()), |n| Ok(n as i32)}
 field_try_into! {String, |s| Ok(s), |n: f64| Ok(n.to_string())}
 field_try_into! {bool,
     |s: String| match s.as_str() {
@@ -52,6 +66,32 @@ field_try_into! {bool,
     }
 }
 
+impl Field {
+    // a field kept in another unit than Hz or Sps, like a frequency in MHz, converts a value written with a unit into it
+    // a plain number is already in that unit and is left as it is
+    pub fn in_unit(self, from_hz: fn(f64) -> f64) -> Field {
+        match self {
+            Field::Quantity(n) => Field::Number(from_hz(n)),
+            other => other,
+        }
+    }
+}
+
+// every item has to convert for the list to convert
+impl<T: TryFrom<Field>> TryFrom<Field> for Vec<T> {
+    type Error = ();
+
+    fn try_from(field: Field) -> Result<Vec<T>, Self::Error> {
+        match field {
+            Field::List(list) => list
+                .into_iter()
+                .map(|f| T::try_from(f).map_err(|_| ()))
+                .collect(),
+            _ => Err(()),
+        }
+    }
+}
+
 macro_rules! field_from {
     ($type:ty, $from:stmt) => {
         impl From<$type> for Field {
@@ -66,7 +106,14 @@ field_from! {f64, |v| Field::Number(v)}
 field_from! {f32, |v| Field::Number(v as f64)}
 field_from! {u64, |v| Field::Number(v as f64)}
 field_from! {u32, |v| Field::Number(v as f64)}
+field_from! {i32, |v| Field::Number(v as f64)}
 field_from! {String, |v| Field::String(v)}
+
+impl<T: Into<Field>> From<Vec<T>> for Field {
+    fn from(val: Vec<T>) -> Self {
+        Field::List(val.into_iter().map(Into::into).collect())
+    }
+}
 field_from! {bool, |v| Field::String(
     match v {
         true => "true".to_string(),
@@ -156,6 +203,191 @@ impl<'a> CharCursor<'a> {
             line: self.cur_line,
             col: self.cur_column,
             desc,
+            source_line: String::new(),
+        }
+    }
+}
+
+// the units that can follow a number and what the number is multiplied by
+const UNIT_SUFFIXES: &[(&str, f64)] = &[
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("Hz", 1.0),
+    ("kHz", 1e3),
+    ("MHz", 1e6),
+    ("GHz", 1e9),
+    ("Sps", 1.0),
+    ("kSps", 1e3),
+    ("MSps", 1e6),
+];
+
+// reads the letters after a number, the cursor is left on the character that ends the value
+fn parse_unit_suffix(cursor: &mut CharCursor, end: fn(char) -> bool) -> Result<f64, ParseError> {
+    // an unknown unit is reported at its start
+    let mut unknown = cursor.err(String::new());
+
+    // collected instead of sliced, at the end of the buffer the cursor doesn't point past the last character
+    let mut suffix = String::new();
+    while cursor.current().1.is_alphabetic() {
+        suffix.push(cursor.current().1);
+        cursor.next();
+    }
+
+    let current = cursor.current().1;
+    if !(ends_value(current) || end(current)) {
+        return Err(cursor.err(format!(
+            "Expected the end of the value after the unit '{}', found '{}'.",
+            suffix, current
+        )));
+    }
+
+    match UNIT_SUFFIXES.iter().find(|(name, _)| *name == suffix) {
+        Some((_, multiplier)) => Ok(*multiplier),
+        None => {
+            unknown.desc = format!(
+                "Unknown unit '{}' after a number, expected one of {}.",
+                suffix,
+                UNIT_SUFFIXES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Err(unknown)
+        }
+    }
+}
+
+// whitespace, a comment or the end of the buffer end a number
+fn ends_value(c: char) -> bool {
+    c.is_whitespace() || c == '#' || c == '\0'
+}
+
+// the cursor is on the opening '"', it is left after the closing one
+fn parse_string(cursor: &mut CharCursor) -> Result<String, ParseError> {
+    let mut string = String::new();
+    loop {
+        match cursor.next().1 {
+            '\\' => {
+                let escape = match cursor.next().1 {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    '\'' => '\'',
+                    '\"' => '\"',
+                    other => {
+                        return Err(cursor.err(format!("Unknown escape sequence '\\{}'.", other)));
+                    }
+                };
+                string.push(escape);
+            }
+            '"' => {
+                cursor.next();
+                return Ok(string);
+            }
+            '\n' | '\0' => return Err(cursor.err("Unclosed string.".to_owned())),
+            other => string.push(other),
+        }
+    }
+}
+
+// besides ends_value the number also ends where `end` says, like at the ',' in a list
+// the cursor is left on the character that ended it
+fn parse_number(cursor: &mut CharCursor, end: fn(char) -> bool) -> Result<Field, ParseError> {
+    let mut number = 0.0;
+    let mut decimal: Option<f64> = None;
+    // the weight of the next decimal digit
+    let mut multiplier = 0.1;
+
+    loop {
+        match cursor.current().1 {
+            c if ends_value(c) || end(c) => break,
+            '.' if decimal.is_none() => decimal = Some(0.0),
+            // a unit right after the number like 433.92MHz, the number is scaled by it
+            c if c.is_alphabetic() => {
+                let unit = parse_unit_suffix(cursor, end)?;
+                return Ok(Field::Quantity((number + decimal.unwrap_or(0.0)) * unit));
+            }
+            c if c.is_ascii_digit() => {
+                let digit = c.to_digit(10).unwrap() as f64;
+                match &mut decimal {
+                    Some(decimal) => {
+                        *decimal += digit * multiplier;
+                        multiplier *= 0.1;
+                    }
+                    None => number = number * 10.0 + digit,
+                }
+            }
+            other => {
+                return Err(cursor.err(match decimal {
+                    Some(_) => format!("Expected an ascii digit while parsing the decimal part of a number, found '{}'.", other),
+                    None => format!("Expected an ascii digit or '.' while parsing a number, found '{}'.", other),
+                }))
+            }
+        }
+        cursor.next();
+    }
+
+    Ok(Field::Number(number + decimal.unwrap_or(0.0)))
+}
+
+fn skip_whitespace_and_comments(cursor: &mut CharCursor) {
+    cursor.consume_all(|c| c.is_whitespace());
+    while cursor.current().1 == '#' {
+        cursor.consume_until('\n');
+        cursor.consume_all(|c| c.is_whitespace());
+    }
+}
+
+// the cursor is on the opening '[', it is left after the closing ']'
+// the items are numbers, strings or lists separated by ',', the list can span several lines with comments in between
+fn parse_list(cursor: &mut CharCursor) -> Result<Vec<Field>, ParseError> {
+    // reported at the '[' since the rest of the file was consumed looking for the ']'
+    let unclosed = cursor.err("Unclosed list.".to_owned());
+    let mut list = Vec::new();
+
+    cursor.next();
+    loop {
+        skip_whitespace_and_comments(cursor);
+
+        let item = match cursor.current().1 {
+            // an empty list or a trailing ','
+            ']' => {
+                cursor.next();
+                return Ok(list);
+            }
+            '"' => Field::String(parse_string(cursor)?),
+            '[' => Field::List(parse_list(cursor)?),
+            c if c.is_ascii_digit() || c == '.' => parse_number(cursor, |c| c == ',' || c == ']')?,
+            '\0' => return Err(unclosed),
+            other => {
+                return Err(cursor.err(format!(
+                    "Expected a number, a string or a list as a list item, found '{}'.",
+                    other
+                )))
+            }
+        };
+        list.push(item);
+
+        skip_whitespace_and_comments(cursor);
+        match cursor.current().1 {
+            ',' => {
+                cursor.next();
+            }
+            ']' => {
+                cursor.next();
+                return Ok(list);
+            }
+            '\0' => return Err(unclosed),
+            other => {
+                return Err(cursor.err(format!(
+                    "Expected ',' or ']' after a list item, found '{}'.",
+                    other
+                )))
+            }
         }
     }
 }
@@ -165,6 +397,15 @@ pub struct ParseError {
     line: usize,
     col: usize,
     desc: String,
+    // the whole line the error is on
+    source_line: String,
+}
+
+impl ParseError {
+    // displays the error with its line and a '^' under the offending character, the position is counted from 1
+    pub fn with_source(&self) -> ParseErrorSnippet<'_> {
+        ParseErrorSnippet(self)
+    }
 }
 
 impl Display for ParseError {
@@ -173,6 +414,30 @@ impl Display for ParseError {
     }
 }
 
+pub struct ParseErrorSnippet<'a>(&'a ParseError);
+
+impl Display for ParseErrorSnippet<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let e = self.0;
+        let line = (e.line + 1).to_string();
+        let gutter = " ".repeat(line.len());
+
+        // tabs are kept so that the caret lines up with the line above it
+        let indent: String = e
+            .source_line
+            .chars()
+            .take(e.col)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{}", e.desc)?;
+        writeln!(f, "{}--> {}:{}", gutter, line, e.col + 1)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line, e.source_line)?;
+        writeln!(f, "{} | {}^", gutter, indent)
+    }
+}
+
 impl Error for ParseError {}
 
 #[derive(Clone, Debug)]
@@ -196,6 +461,14 @@ impl Display for SerializeError {
 
 impl Error for SerializeError {}
 
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerializeOptions {
+    // pad the names so that all the '=' are in the same column
+    pub align: bool,
+    // write the fields ordered by name instead of in the random hashmap order, for readable diffs
+    pub sort: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Settings {
     fields: HashMap<String, Field>,
@@ -277,6 +550,7 @@ impl Settings {
                             line: human_name_start.0,
                             col: human_name_start.1,
                             desc: format!("Encountered unexpected character '{}' while matching a name.", c),
+                            source_line: String::new(),
                         });
                         // the name is invalid but we need the parser to be in the correct state after this (assuming the rest of the declaration is correct)
                         skip = true;
@@ -292,103 +566,31 @@ impl Settings {
             cursor.consume_all(|c| c.is_whitespace());
 
             // VALUE
-            let value =
-            // is a loop only so we can early break here in stable rust, see https://github.com/rust-lang/rust/issues/48594
-            'value: loop {
-                match cursor.current().1 {
-                    // value must be a string, go until we find an unescaped '"'
-                    '"' => {
-                        let mut string = String::new();
-                        loop {
-                            match cursor.next().1 {
-                                '\\' => {
-                                    let escape = match cursor.next().1 {
-                                    'n' => '\n',
-                                    'r' => '\r',
-                                    't' => '\t',
-                                    '0' => '\0',
-                                    '\\' => '\\',
-                                    '\'' => '\'',
-                                    '\"' => '\"',
-                                    other => {
-                                        err!("Unknown escape sequence '\\{}'.", other);
-                                        break 'value Field::ParseError;
-                                    }
-                                };
-                                string.push(escape);
-                                },
-                                '"' => {
-                                    cursor.next();
-                                    break;
-                                },
-                                '\n' | '\0' => {
-                                    err!("Unclosed string.");
-                                    break 'value Field::ParseError;
-                                },
-                                other => string.push(other),
-                            }
-                        }
-
-                        break 'value Field::String(string);
-                    },
-                    '\n' | '\0' => {
-                        err!("Expected value assignment to '{}'.", name);
-                        break 'value Field::ParseError;
-                    },
-                    // value must be a number, go and accumulate value until we reach a '.' then accumulate backwards
-                    _ => {
-                        if !cursor.current().1.is_ascii_digit() && cursor.current().1 != '.' {
-                            let start = cursor.current().0;
-                            cursor.consume_until('\n');
-                            let end = cursor.current().0;
-
-                            let slice = &string.as_bytes()[start..end];
-                            err!("Expected a number or a string while parsing a value, found '{}'.", std::str::from_utf8(slice).unwrap().trim_end());
-                            break 'value Field::ParseError;
-                        }
-
-                        let mut number = 0.0;
-                        'number: loop {
-                            let current = cursor.current().1;
-                            match current {
-                                '.' => {
-                                    let mut decimal_number = 0.0;
-                                    let mut multiplier = 0.1;
-                                    loop {
-                                        let next = cursor.next().1;
-                                        match next {
-                                            '\n' | '#' | '\0' | _ if next.is_whitespace() => {
-                                                number += decimal_number;
-                                                break 'number;
-                                            },
-                                            other => {
-                                                if !other.is_ascii_digit() {
-                                                    err!("Expected an ascii digit while parsing the decimal part of a number, found '{}'.", other);
-                                                    break 'value Field::ParseError;
-                                                }
-                                                decimal_number +=
-                                                    other.to_digit(10).unwrap() as f64 * multiplier;
-                                                multiplier *= 0.1;
-                                            }
-                                        }
-                                    }
-                                }
-                                '\n' | '#' | '\0' | _ if current.is_whitespace() => break 'number,
-                                other => {
-                                    if !other.is_ascii_digit() {
-                                        err!("Expected an ascii digit or '.' while parsing a number, found '{}'.", other);
-                                        break 'value Field::ParseError;
-                                    }
-
-                                    number *= 10.0;
-                                    number += other.to_digit(10).unwrap() as f64;
-                                }
-                            }
-                            cursor.next();
-                        }
+            let value = match cursor.current().1 {
+                // value must be a string, go until we find an unescaped '"'
+                '"' => parse_string(&mut cursor).map(Field::String),
+                '[' => parse_list(&mut cursor).map(Field::List),
+                '\n' | '\0' => Err(cursor.err(format!("Expected value assignment to '{}'.", name))),
+                // value must be a number, go and accumulate value until we reach a '.' then accumulate backwards
+                c if c.is_ascii_digit() || c == '.' => parse_number(&mut cursor, |_| false),
+                _ => {
+                    let start = cursor.current().0;
+                    cursor.consume_until('\n');
+                    let end = cursor.current().0;
 
-                        break 'value Field::Number(number);
-                    }
+                    let slice = &string.as_bytes()[start..end];
+                    Err(cursor.err(format!(
+                        "Expected a number, a string or a list while parsing a value, found '{}'.",
+                        std::str::from_utf8(slice).unwrap().trim_end()
+                    )))
+                }
+            };
+
+            let value = match value {
+                Ok(value) => value,
+                Err(e) => {
+                    errors.push(e);
+                    Field::ParseError
                 }
             };
 
@@ -397,6 +599,12 @@ impl Settings {
             }
         }
 
+        // kept so that the errors can be shown with the line they are on
+        let lines: Vec<&str> = string.lines().collect();
+        for e in &mut errors {
+            e.source_line = lines.get(e.line).copied().unwrap_or_default().to_owned();
+        }
+
         (Self { fields }, errors)
     }
     pub fn new_from_file(path: impl AsRef<Path>) -> std::io::Result<(Self, Vec<ParseError>)> {
@@ -411,7 +619,11 @@ impl Settings {
             match value {
                 Field::String(string) => writeln!(&mut output_string, "{} = \"{}\"", name, string)
                     .map_err(|e| SerializeError::FmtError(e))?,
-                Field::Number(number) => writeln!(&mut output_string, "{} = {}", name, number)
+                Field::Number(number) | Field::Quantity(number) => {
+                    writeln!(&mut output_string, "{} = {}", name, number)
+                        .map_err(|e| SerializeError::FmtError(e))?
+                }
+                Field::List(_) => writeln!(&mut output_string, "{} = {}", name, value)
                     .map_err(|e| SerializeError::FmtError(e))?,
                 Field::ParseError => {
                     return Err(SerializeError::InvalidField {
@@ -423,6 +635,37 @@ impl Settings {
 
         Ok(output_string)
     }
+    pub fn pretty_serialize(&self, options: SerializeOptions) -> Result<String, SerializeError> {
+        let mut fields: Vec<(&String, &Field)> = self.fields.iter().collect();
+        if options.sort {
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+        }
+
+        let width = match options.align {
+            true => fields
+                .iter()
+                .map(|(name, _)| name.chars().count())
+                .max()
+                .unwrap_or(0),
+            false => 0,
+        };
+
+        let mut output_string = String::new();
+        for (name, value) in fields {
+            use std::fmt::Write;
+            match value {
+                Field::ParseError => {
+                    return Err(SerializeError::InvalidField {
+                        field_name: name.clone(),
+                    })
+                }
+                value => writeln!(&mut output_string, "{:2$} = {}", name, value, width)
+                    .map_err(|e| SerializeError::FmtError(e))?,
+            }
+        }
+
+        Ok(output_string)
+    }
     pub fn get_hashmap(&self) -> &HashMap<String, Field> {
         &self.fields
     }
@@ -440,6 +683,24 @@ impl Settings {
             .get(name)
             .and_then(|f| TryFrom::try_from(f.clone()).ok())
     }
+    // the parsed value of the field, or the default if it is missing or has the wrong type
+    // the default then replaces the field so that the next serialize writes it out
+    pub fn get_or<T: TryFrom<Field> + Into<Field> + Clone>(&mut self, name: &str, default: T) -> T {
+        match self.fields.get(name) {
+            Some(field) => match T::try_from(field.clone()) {
+                Ok(value) => return value,
+                Err(_) => log::warn!(
+                    "Setting '{}' has the invalid value {}, using the default",
+                    name,
+                    field
+                ),
+            },
+            None => log::debug!("Setting '{}' is missing, using the default", name),
+        }
+
+        self.set(name, default.clone());
+        default
+    }
     pub fn set<T: Into<Field>>(&mut self, name: &str, t: T) {
         // to clone the name only if there is no previous entry we would need the unstable hash_raw_entry
         // https://github.com/rust-lang/rust/issues/56167
@@ -502,3 +763,225 @@ fn error() {
     let (_settings, errors) = Settings::new(s);
     assert!(!errors.is_empty());
 }
+
+#[test]
+fn get_or_default() {
+    let (mut settings, errors) = Settings::new("present = 5\nmalformed = \"five\"\n");
+    assert!(errors.is_empty());
+
+    // a valid field is returned and left as it is
+    assert_eq!(settings.get_or("present", 1u32), 5);
+    assert_eq!(settings.get::<String>("present").unwrap(), "5");
+
+    // a missing or malformed one is replaced by the default
+    assert_eq!(settings.get_or("absent", 2u32), 2);
+    assert_eq!(settings.get_or("malformed", 3u32), 3);
+    assert_eq!(settings.get::<u32>("absent"), Some(2));
+    assert_eq!(settings.get::<u32>("malformed"), Some(3));
+
+    let (settings, errors) = Settings::new(&settings.serialize().unwrap());
+    assert!(errors.is_empty());
+    assert_eq!(settings.get::<u32>("present"), Some(5));
+    assert_eq!(settings.get::<u32>("absent"), Some(2));
+    assert_eq!(settings.get::<u32>("malformed"), Some(3));
+
+    // the default of a list is backfilled as a list
+    let mut settings = settings;
+    assert_eq!(settings.get_or("list", vec![1.5, 2.5]), [1.5, 2.5]);
+    assert_eq!(settings.get::<Vec<f64>>("list").unwrap(), [1.5, 2.5]);
+}
+
+#[test]
+fn unit_suffixes() {
+    let s = "
+    a = 1k
+    b = 2.4M
+    c = 433.92MHz # comment
+    e = 12Hz
+    ";
+    let (settings, errors) = Settings::new(s);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let close = |name: &str, expected: f64| {
+        let value = settings.get::<f64>(name).unwrap();
+        assert!((value - expected).abs() < 1e-6, "{} {}", name, value);
+    };
+    close("a", 1e3);
+    close("b", 2.4e6);
+    close("c", 433_920_000.0);
+    close("e", 12.0);
+    // the unit is remembered so that a field kept in MHz can convert the value into it
+    assert_eq!(
+        settings
+            .get_field("c")
+            .cloned()
+            .unwrap()
+            .in_unit(|hz| hz / 1e6)
+            .to_string(),
+        "433.92"
+    );
+    assert_eq!(
+        settings.get_field("a").cloned().unwrap().to_string(),
+        "1000"
+    );
+
+    for s in ["f = 5Q", "f = 5kHzz", "f = 5k2", "f = 5.5Q"] {
+        let (settings, errors) = Settings::new(s);
+        assert!(!errors.is_empty(), "{}", s);
+        assert!(settings.get::<f64>("f").is_none(), "{}", s);
+    }
+}
+
+#[test]
+fn pretty_serialization() {
+    let (settings, errors) = Settings::new("frequency = 433.92\nb = \"text\"\nsamplerate = 2\n");
+    assert!(errors.is_empty());
+
+    let aligned = settings
+        .pretty_serialize(SerializeOptions {
+            align: true,
+            sort: true,
+        })
+        .unwrap();
+    assert_eq!(
+        aligned,
+        "b          = \"text\"\nfrequency  = 433.92\nsamplerate = 2\n"
+    );
+    let columns: Vec<_> = aligned.lines().map(|l| l.find('=').unwrap()).collect();
+    assert!(columns.iter().all(|&c| c == columns[0]));
+
+    // the same fields inserted in another order give the same output
+    let (reordered, _) = Settings::new("samplerate = 2\nb = \"text\"\nfrequency = 433.92\n");
+    let options = SerializeOptions {
+        align: false,
+        sort: true,
+    };
+    assert_eq!(
+        settings.pretty_serialize(options).unwrap(),
+        reordered.pretty_serialize(options).unwrap()
+    );
+    assert_eq!(
+        reordered.pretty_serialize(options).unwrap(),
+        "b = \"text\"\nfrequency = 433.92\nsamplerate = 2\n"
+    );
+
+    // the output is read back the same
+    let (parsed, errors) = Settings::new(&aligned);
+    assert!(errors.is_empty());
+    assert_eq!(parsed.get::<String>("b").unwrap(), "text");
+    assert_eq!(parsed.get::<f64>("samplerate").unwrap(), 2.0);
+}
+
+#[test]
+fn error_snippet() {
+    let (_settings, errors) = Settings::new("samplerate = 2\nfrequency = 433.92Mhz\n");
+    assert_eq!(errors.len(), 1);
+
+    // the compact display is unchanged
+    assert!(errors[0].to_string().starts_with("1:18 Unknown unit 'Mhz'"));
+
+    let snippet = errors[0].with_source().to_string();
+    let lines: Vec<&str> = snippet.lines().collect();
+    assert_eq!(lines[1], " --> 2:19");
+    assert_eq!(lines[3], "2 | frequency = 433.92Mhz");
+    assert_eq!(lines[4], "  |                   ^");
+    // the caret is under the first letter of the unit
+    assert_eq!(lines[3].chars().nth(lines[4].len() - 1), Some('M'));
+
+    let (_settings, errors) = Settings::new("\tbad name = 1");
+    assert_eq!(
+        errors[0].with_source().to_string().lines().last(),
+        Some("  | \t^")
+    );
+}
+
+#[test]
+fn lists() {
+    let s = r#"
+    numbers = [1, 2.5, .5, 3k]
+    favorites = [
+        [433.92, 2.048, "Baudot"], # a comment between the items
+        [144.8, 1, "None"],
+    ]
+    empty = []
+    mixed = ["a", [], 1]
+    "#;
+    let (settings, errors) = Settings::new(s);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    assert_eq!(
+        settings.get::<Vec<f64>>("numbers").unwrap(),
+        [1.0, 2.5, 0.5, 3000.0]
+    );
+    assert_eq!(settings.get::<Vec<f64>>("empty").unwrap(), []);
+    // every item has to convert
+    assert!(settings.get::<Vec<f64>>("mixed").is_none());
+    assert!(settings.get::<f64>("numbers").is_none());
+
+    let favorites = settings.get::<Vec<Vec<String>>>("favorites").unwrap();
+    assert_eq!(favorites[0], ["433.92", "2.048", "Baudot"]);
+    assert_eq!(favorites[1], ["144.8", "1", "None"]);
+
+    // written the same way it is read
+    let serialized = settings
+        .pretty_serialize(SerializeOptions {
+            align: false,
+            sort: true,
+        })
+        .unwrap();
+    assert!(
+        serialized.contains("favorites = [[433.92, 2.048, \"Baudot\"], [144.8, 1, \"None\"]]\n")
+    );
+    let (parsed, errors) = Settings::new(&serialized);
+    assert!(errors.is_empty());
+    assert_eq!(
+        parsed.get::<Vec<Vec<String>>>("favorites").unwrap(),
+        favorites
+    );
+
+    // the missing ']' is noticed at the next statement which is still parsed
+    let (settings, errors) = Settings::new("a = [1, 2\nb = 3\n");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().starts_with("1:0 Expected ',' or ']'"));
+    assert_eq!(settings.get::<f64>("b"), Some(3.0));
+
+    let (_, errors) = Settings::new("a = [1, 2\n");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().starts_with("0:4 Unclosed list."));
+
+    for s in ["a = [1 2]", "a = [x]", "a = [1,, 2]", "a = [\"unclosed]"] {
+        let (settings, errors) = Settings::new(s);
+        assert!(!errors.is_empty(), "{}", s);
+        assert!(settings.get::<Vec<f64>>("a").is_none(), "{}", s);
+    }
+}
+
+#[test]
+fn number_at_the_end() {
+    // the end of the buffer and a comment end a number just like whitespace does,
+    // before the values were parsed with the list items these were errors
+    for s in [
+        "a = 1",
+        "a = 1#comment",
+        "a = 1.0",
+        "a = 1.5#comment",
+        "a = .5",
+        "a = 1k",
+        "a = 1k#comment",
+    ] {
+        let (settings, errors) = Settings::new(s);
+        assert!(errors.is_empty(), "{} {:?}", s, errors);
+        assert!(settings.get::<f64>("a").is_some(), "{}", s);
+    }
+    assert_eq!(
+        Settings::new("a = 1.5#comment").0.get::<f64>("a"),
+        Some(1.5)
+    );
+
+    // anything else still ends the number with an error
+    for s in ["a = 1,", "a = 1]", "a = 1.5\"", "a = 1.5.#comment"] {
+        let (settings, errors) = Settings::new(s);
+        assert!(!errors.is_empty(), "{}", s);
+        assert!(settings.get::<f64>("a").is_none(), "{}", s);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use rustfft::FftNum;
+
+use crate::{
+    dsp::window_functions::WindowKind, fft_shift, worker::worker_manager::ReceiverState, FftData,
+    SpectrumData,
+};
+
+// the file starts with this so that something else isn't mistaken for a snapshot, the last byte is the version
+const MAGIC: &[u8; 8] = b"RTSNAP\x00\x01";
+
+// a spectrum captured for golden-file tests of the processing chain, it doesn't depend on the precision of the fft
+// the file is little endian: magic, samplerate f64, center frequency f64, window name (u8 length + utf8), bin count u32, bins f64
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrumSnapshot {
+    pub samplerate: f64,
+    pub center_frequency: f64,
+    pub window: WindowKind,
+    // the power spectral density of every bin in dBFS/Hz, from the lowest frequency to the highest
+    pub psd_db: Vec<f64>,
+}
+
+impl SpectrumSnapshot {
+    pub fn new<T: FftNum + Into<f64>>(data: &FftData<T>, receiver: &ReceiverState) -> Self {
+        let mut psd_db: Vec<f64> = (0..data.get_output().len())
+            .map(|bin| 10.0 * data.psd(bin).log10())
+            .collect();
+        fft_shift(&mut psd_db);
+
+        Self {
+            samplerate: data.get_samplerate(),
+            center_frequency: receiver.frequency,
+            window: data.get_window_kind(),
+            psd_db,
+        }
+    }
+    pub fn from_spectrum(data: &SpectrumData, receiver: &ReceiverState) -> Self {
+        match data {
+            SpectrumData::Single(data) => Self::new(data, receiver),
+            SpectrumData::Double(data) => Self::new(data, receiver),
+        }
+    }
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let name = self.window.name().as_bytes();
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.samplerate.to_le_bytes())?;
+        writer.write_all(&self.center_frequency.to_le_bytes())?;
+        writer.write_all(&[name.len() as u8])?;
+        writer.write_all(name)?;
+        writer.write_all(&(self.psd_db.len() as u32).to_le_bytes())?;
+        for value in &self.psd_db {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let invalid = |desc: &str| io::Error::new(io::ErrorKind::InvalidData, desc);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a spectrum snapshot or an unsupported version"));
+        }
+
+        let samplerate = read_f64(reader)?;
+        let center_frequency = read_f64(reader)?;
+
+        let mut len = [0; 1];
+        reader.read_exact(&mut len)?;
+        let mut name = vec![0; len[0] as usize];
+        reader.read_exact(&mut name)?;
+        let window = std::str::from_utf8(&name)
+            .ok()
+            .and_then(WindowKind::from_name)
+            .ok_or_else(|| invalid("Unknown window"))?;
+
+        let mut count = [0; 4];
+        reader.read_exact(&mut count)?;
+        let psd_db = (0..u32::from_le_bytes(count))
+            .map(|_| read_f64(reader))
+            .collect::<io::Result<Vec<f64>>>()?;
+
+        Ok(Self {
+            samplerate,
+            center_frequency,
+            window,
+            psd_db,
+        })
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+    // the largest difference between the bins in dB, None if the snapshots weren't taken the same way
+    pub fn max_difference_db(&self, other: &Self) -> Option<f64> {
+        if self.samplerate != other.samplerate
+            || self.center_frequency != other.center_frequency
+            || self.window != other.window
+            || self.psd_db.len() != other.psd_db.len()
+        {
+            return None;
+        }
+
+        let difference = self
+            .psd_db
+            .iter()
+            .zip(&other.psd_db)
+            // an empty bin is -inf in both, which is no difference at all
+            .map(|(&a, &b)| if a == b { 0.0 } else { (a - b).abs() })
+            // NaN doesn't compare as larger, it is turned into inf so that it can't pass
+            .map(|d| if d.is_nan() { f64::INFINITY } else { d })
+            .fold(0.0, f64::max);
+
+        Some(difference)
+    }
+    pub fn approx_eq(&self, other: &Self, tolerance_db: f64) -> bool {
+        self.max_difference_db(other)
+            .map_or(false, |difference| difference <= tolerance_db)
+    }
+    // the frequency in Hz of the bin at index of psd_db, DC is at len / 2 after the shift
+    pub fn frequency(&self, index: usize) -> f64 {
+        let len = self.psd_db.len();
+        self.center_frequency + (index as f64 - (len / 2) as f64) * self.samplerate / len as f64
+    }
+    // a row with the frequency and the density of every bin for spreadsheets
+    // an empty bin has no value instead of -inf which the spreadsheets don't read
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frequency_hz,psd_dbfs_hz\n");
+
+        for (i, psd) in self.psd_db.iter().enumerate() {
+            if psd.is_finite() {
+                csv += &format!("{:.1},{:.2}\n", self.frequency(i), psd);
+            } else {
+                csv += &format!("{:.1},\n", self.frequency(i));
+            }
+        }
+
+        csv
+    }
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+fn tone_snapshot(double_precision: bool, amplitude: f32) -> SpectrumSnapshot {
+    use crate::generator::SignalGenerator;
+
+    let receiver = ReceiverState::builder()
+        .samplerate(48000.0)
+        .frequency(433.92e6)
+        .build()
+        .unwrap();
+
+    // a tone 20 bins above the center with a bit of noise so that no bin is empty
+    let len = 512;
+    let samples = SignalGenerator::new_tone(
+        receiver.samplerate,
+        20.0 * receiver.samplerate / len as f64,
+        len,
+    )
+    .amplitude(amplitude as f64)
+    .noise(1e-3)
+    .samples();
+
+    let mut data = SpectrumData::new(len, double_precision);
+    data.set_window(WindowKind::BlackmanHaris);
+    data.process(&samples, receiver.samplerate);
+
+    SpectrumSnapshot::from_spectrum(&data, &receiver)
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let snapshot = tone_snapshot(true, 1.0);
+    assert_eq!(snapshot.window, WindowKind::BlackmanHaris);
+    assert_eq!(snapshot.center_frequency, 433.92e6);
+
+    // the tone ends up right of the center
+    let peak = (0..snapshot.psd_db.len())
+        .max_by(|&a, &b| snapshot.psd_db[a].partial_cmp(&snapshot.psd_db[b]).unwrap())
+        .unwrap();
+    assert_eq!(peak, 256 + 20);
+
+    let mut bytes = Vec::new();
+    snapshot.write(&mut bytes).unwrap();
+    assert_eq!(
+        bytes.len(),
+        8 + 8 + 8 + 1 + "BlackmanHaris".len() + 4 + 512 * 8
+    );
+    assert_eq!(SpectrumSnapshot::read(&mut &bytes[..]).unwrap(), snapshot);
+
+    let path = std::env::temp_dir().join(format!("radiothing_snapshot_{}", std::process::id()));
+    snapshot.save(&path).unwrap();
+    let loaded = SpectrumSnapshot::load(&path);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.unwrap(), snapshot);
+
+    // anything else is rejected instead of being read as garbage
+    assert!(SpectrumSnapshot::read(&mut &b"RTSNAP\x00\x02"[..]).is_err());
+    assert!(SpectrumSnapshot::read(&mut &bytes[..(bytes.len() - 1)]).is_err());
+}
+
+#[test]
+fn snapshot_tolerance() {
+    let double = tone_snapshot(true, 1.0);
+    let single = tone_snapshot(false, 1.0);
+
+    // the precision of the fft only makes a tiny difference
+    assert!(double.approx_eq(&single, 0.1));
+
+    // a 6 dB weaker tone doesn't pass
+    let weaker = tone_snapshot(true, 0.5);
+    let difference = double.max_difference_db(&weaker).unwrap();
+    assert!((difference - 6.02).abs() < 0.1, "{}", difference);
+    assert!(!double.approx_eq(&weaker, 1.0));
+
+    // snapshots taken differently aren't compared at all
+    let mut retuned = double.clone();
+    retuned.center_frequency += 1000.0;
+    assert_eq!(double.max_difference_db(&retuned), None);
+    assert!(!double.approx_eq(&retuned, f64::INFINITY));
+
+    let mut broken = double.clone();
+    broken.psd_db[3] = f64::NAN;
+    assert!(!double.approx_eq(&broken, 1e9));
+}
+
+#[test]
+fn snapshot_csv() {
+    let snapshot = SpectrumSnapshot {
+        samplerate: 48000.0,
+        center_frequency: 433.92e6,
+        window: WindowKind::Rectangular,
+        psd_db: vec![-100.0, -90.25, -3.14159, f64::NEG_INFINITY],
+    };
+
+    // the bins are 12 kHz apart with DC at the third one
+    assert_eq!(snapshot.frequency(0), 433.92e6 - 24000.0);
+    assert_eq!(snapshot.frequency(2), 433.92e6);
+    assert_eq!(snapshot.frequency(3), 433.92e6 + 12000.0);
+
+    assert_eq!(
+        snapshot.to_csv(),
+        "frequency_hz,psd_dbfs_hz\n\
+         433896000.0,-100.00\n\
+         433908000.0,-90.25\n\
+         433920000.0,-3.14\n\
+         433932000.0,\n"
+    );
+
+    // DC stays in the middle for odd lengths like in fft_shift
+    let odd = SpectrumSnapshot {
+        psd_db: vec![0.0; 5],
+        samplerate: 5.0,
+        center_frequency: 0.0,
+        ..snapshot.clone()
+    };
+    assert_eq!(
+        (0..5).map(|i| odd.frequency(i)).collect::<Vec<_>>(),
+        [-2.0, -1.0, 0.0, 1.0, 2.0]
+    );
+
+    let peak = tone_snapshot(false, 1.0);
+    let row = peak.to_csv().lines().nth(1 + 256 + 20).unwrap().to_owned();
+    assert!(row.starts_with(&format!("{:.1},", 433.92e6 + 20.0 * 48000.0 / 512.0)));
+}
@@ -0,0 +1,130 @@
+// the units the frequency can be displayed and entered in, the device always works in Hz
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrequencyUnit {
+    Hz,
+    KHz,
+    MHz,
+}
+
+pub const FREQUENCY_UNITS: &[FrequencyUnit] =
+    &[FrequencyUnit::Hz, FrequencyUnit::KHz, FrequencyUnit::MHz];
+
+impl FrequencyUnit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrequencyUnit::Hz => "Hz",
+            FrequencyUnit::KHz => "kHz",
+            FrequencyUnit::MHz => "MHz",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        FREQUENCY_UNITS.iter().copied().find(|u| u.name() == name)
+    }
+    // how many Hz is one of this unit
+    pub fn multiplier(&self) -> f64 {
+        match self {
+            FrequencyUnit::Hz => 1.0,
+            FrequencyUnit::KHz => 1_000.0,
+            FrequencyUnit::MHz => 1_000_000.0,
+        }
+    }
+    // the number of decimals needed to show the value with 1 Hz resolution
+    pub fn decimals(&self) -> i32 {
+        match self {
+            FrequencyUnit::Hz => 0,
+            FrequencyUnit::KHz => 3,
+            FrequencyUnit::MHz => 6,
+        }
+    }
+    // the step of the spinbox arrows
+    pub fn step(&self) -> f64 {
+        match self {
+            FrequencyUnit::Hz => 1.0,
+            FrequencyUnit::KHz => 0.1,
+            FrequencyUnit::MHz => 0.001,
+        }
+    }
+    pub fn to_hz(&self, value: f64) -> f64 {
+        value * self.multiplier()
+    }
+    pub fn from_hz(&self, hz: f64) -> f64 {
+        hz / self.multiplier()
+    }
+    // convert a value displayed in `self` to the same frequency displayed in `to`
+    pub fn rescale(&self, value: f64, to: FrequencyUnit) -> f64 {
+        to.from_hz(self.to_hz(value))
+    }
+    // the step is scaled too, it's a frequency difference
+    pub fn rescale_ranges(
+        &self,
+        ranges: &[soapysdr::Range],
+        to: FrequencyUnit,
+    ) -> Vec<soapysdr::Range> {
+        ranges
+            .iter()
+            .map(|r| soapysdr::Range {
+                minimum: self.rescale(r.minimum, to),
+                maximum: self.rescale(r.maximum, to),
+                step: self.rescale(r.step, to),
+            })
+            .collect()
+    }
+}
+
+// the samplerate and the bandwidth are always displayed in MSps and MHz, the device uses Sps and Hz
+pub fn to_mega(value: f64) -> f64 {
+    FrequencyUnit::MHz.from_hz(value)
+}
+pub fn from_mega(value: f64) -> f64 {
+    FrequencyUnit::MHz.to_hz(value)
+}
+
+#[test]
+fn frequency_unit_rescale() {
+    use FrequencyUnit::*;
+
+    assert_eq!(MHz.rescale(10.0, KHz), 10_000.0);
+    assert_eq!(MHz.rescale(10.0, Hz), 10_000_000.0);
+    assert_eq!(KHz.rescale(14_070.5, Hz), 14_070_500.0);
+    assert_eq!(Hz.rescale(433_920_000.0, MHz), 433.92);
+
+    // the absolute frequency doesn't change when going back and forth
+    for &from in FREQUENCY_UNITS {
+        for &to in FREQUENCY_UNITS {
+            let value = from.from_hz(145_800_000.0);
+            let back = to.rescale(from.rescale(value, to), from);
+            assert!((back - value).abs() < 1e-9 * value);
+        }
+    }
+
+    assert_eq!(FrequencyUnit::from_name("kHz"), Some(KHz));
+    assert_eq!(FrequencyUnit::from_name(""), None);
+}
+
+#[test]
+fn mega_conversions() {
+    assert_eq!(to_mega(2_048_000.0), 2.048);
+    assert_eq!(from_mega(2.048), 2_048_000.0);
+    assert_eq!(from_mega(to_mega(433_920_000.0)), 433_920_000.0);
+
+    // the same as dividing every field by a million like the receive group used to
+    let ranges = [
+        soapysdr::Range {
+            minimum: 24_000_000.0,
+            maximum: 1_766_000_000.0,
+            step: 1.0,
+        },
+        soapysdr::Range {
+            minimum: 250_000.0,
+            maximum: 250_000.0,
+            step: 0.0,
+        },
+    ];
+    let scaled = FrequencyUnit::Hz.rescale_ranges(&ranges, FrequencyUnit::MHz);
+    assert_eq!(scaled.len(), ranges.len());
+    for (scaled, range) in scaled.iter().zip(&ranges) {
+        assert_eq!(scaled.minimum, range.minimum / 1_000_000.0);
+        assert_eq!(scaled.maximum, range.maximum / 1_000_000.0);
+        assert_eq!(scaled.step, range.step / 1_000_000.0);
+    }
+}
@@ -0,0 +1,287 @@
+use super::{
+    sdr_device::{SdrBackend, SdrDevice, SdrStream},
+    worker::RxFormat,
+};
+
+use std::{
+    cell::{Cell, RefCell},
+    f64::consts::PI,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rustfft::num_complex::Complex;
+use soapysdr::{Args, Error, ErrorCode, Range};
+
+// the tone the mock stream produces is this fraction of the samplerate above the center frequency
+pub const MOCK_TONE_OFFSET: f64 = 1.0 / 8.0;
+pub const MOCK_MTU: usize = 4096;
+pub const MOCK_CLOCK_SOURCES: &[&str] = &["internal", "external"];
+
+fn range(minimum: f64, maximum: f64) -> Range {
+    Range {
+        minimum,
+        maximum,
+        step: 0.0,
+    }
+}
+
+fn mock_error(message: &str) -> Error {
+    Error {
+        code: ErrorCode::Other,
+        message: message.to_owned(),
+    }
+}
+
+// pretends to have `count` devices, all of them can be opened any number of times
+pub struct MockBackend {
+    count: usize,
+    // how many streams the opened devices have created, shared with every one of them
+    streams_opened: Arc<AtomicUsize>,
+}
+
+impl MockBackend {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            streams_opened: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    // the counter keeps counting after the backend was moved into a worker
+    pub fn streams_opened(&self) -> Arc<AtomicUsize> {
+        self.streams_opened.clone()
+    }
+}
+
+impl SdrBackend for MockBackend {
+    fn enumerate(&self, _args: &str) -> Result<Vec<Args>, Error> {
+        Ok((0..self.count)
+            .map(|i| {
+                let mut args = Args::new();
+                args.set("driver", "mock");
+                args.set("label", format!("Mock device {}", i));
+                args.set("serial", format!("mock{}", i));
+                args
+            })
+            .collect())
+    }
+    fn open(&self, args: Args) -> Result<Box<dyn SdrDevice>, Error> {
+        match args.get("driver") {
+            Some("mock") => Ok(Box::new(MockDevice {
+                streams_opened: self.streams_opened.clone(),
+                ..MockDevice::new()
+            })),
+            _ => Err(mock_error("Not a mock device")),
+        }
+    }
+}
+
+// a single channel receiver which remembers whatever it is set to and streams a tone, the values are applied exactly
+pub struct MockDevice {
+    samplerate: Cell<f64>,
+    frequency: Cell<f64>,
+    bandwidth: Cell<f64>,
+    gain: Cell<f64>,
+    clock_source: RefCell<String>,
+    streams_opened: Arc<AtomicUsize>,
+}
+
+impl MockDevice {
+    pub fn new() -> Self {
+        Self {
+            samplerate: Cell::new(1e6),
+            frequency: Cell::new(100e6),
+            bandwidth: Cell::new(1e6),
+            gain: Cell::new(0.0),
+            clock_source: RefCell::new(MOCK_CLOCK_SOURCES[0].to_owned()),
+            streams_opened: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    fn check_channel(&self, channel: usize) -> Result<(), Error> {
+        if channel == 0 {
+            Ok(())
+        } else {
+            Err(mock_error("No such channel"))
+        }
+    }
+}
+
+impl Default for MockDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SdrDevice for MockDevice {
+    fn driver_key(&self) -> Result<String, Error> {
+        Ok("mock".to_owned())
+    }
+    fn hardware_key(&self) -> Result<String, Error> {
+        Ok("mock".to_owned())
+    }
+    fn hardware_info(&self) -> Result<Args, Error> {
+        Ok(Args::new())
+    }
+    fn num_channels(&self) -> Result<usize, Error> {
+        Ok(1)
+    }
+    fn channel_info(&self, channel: usize) -> Result<Args, Error> {
+        self.check_channel(channel)?;
+        Ok(Args::new())
+    }
+    fn list_clock_sources(&self) -> Result<Vec<String>, Error> {
+        Ok(MOCK_CLOCK_SOURCES.iter().map(|s| s.to_string()).collect())
+    }
+    fn clock_source(&self) -> Result<String, Error> {
+        Ok(self.clock_source.borrow().clone())
+    }
+    fn set_clock_source(&self, name: &str) -> Result<(), Error> {
+        if !MOCK_CLOCK_SOURCES.contains(&name) {
+            return Err(mock_error("No such clock source"));
+        }
+        self.clock_source.replace(name.to_owned());
+        Ok(())
+    }
+
+    fn sample_rate_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        self.check_channel(channel)?;
+        Ok(vec![range(0.25e6, 3.2e6)])
+    }
+    fn bandwidth_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        self.check_channel(channel)?;
+        Ok(vec![range(0.25e6, 3.2e6)])
+    }
+    fn frequency_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        self.check_channel(channel)?;
+        Ok(vec![range(24e6, 1766e6)])
+    }
+    fn gain_range(&self, channel: usize) -> Result<Range, Error> {
+        self.check_channel(channel)?;
+        Ok(range(0.0, 50.0))
+    }
+
+    fn antennas(&self, channel: usize) -> Result<Vec<String>, Error> {
+        self.check_channel(channel)?;
+        Ok(vec!["RX".to_owned()])
+    }
+    fn set_antenna(&self, channel: usize, _name: &str) -> Result<(), Error> {
+        self.check_channel(channel)
+    }
+    fn set_gain_mode(&self, channel: usize, _automatic: bool) -> Result<(), Error> {
+        self.check_channel(channel)
+    }
+    fn set_dc_offset_mode(&self, channel: usize, _automatic: bool) -> Result<(), Error> {
+        self.check_channel(channel)
+    }
+    fn set_gain(&self, channel: usize, gain: f64) -> Result<(), Error> {
+        self.check_channel(channel)?;
+        self.gain.set(gain);
+        Ok(())
+    }
+    fn set_frequency(&self, channel: usize, frequency: f64) -> Result<(), Error> {
+        self.check_channel(channel)?;
+        self.frequency.set(frequency);
+        Ok(())
+    }
+    fn set_sample_rate(&self, channel: usize, samplerate: f64) -> Result<(), Error> {
+        self.check_channel(channel)?;
+        self.samplerate.set(samplerate);
+        Ok(())
+    }
+    fn set_bandwidth(&self, channel: usize, bandwidth: f64) -> Result<(), Error> {
+        self.check_channel(channel)?;
+        self.bandwidth.set(bandwidth);
+        Ok(())
+    }
+
+    fn gain(&self, channel: usize) -> Result<f64, Error> {
+        self.check_channel(channel)?;
+        Ok(self.gain.get())
+    }
+    fn frequency(&self, channel: usize) -> Result<f64, Error> {
+        self.check_channel(channel)?;
+        Ok(self.frequency.get())
+    }
+    fn sample_rate(&self, channel: usize) -> Result<f64, Error> {
+        self.check_channel(channel)?;
+        Ok(self.samplerate.get())
+    }
+    fn bandwidth(&self, channel: usize) -> Result<f64, Error> {
+        self.check_channel(channel)?;
+        Ok(self.bandwidth.get())
+    }
+
+    fn rx_stream(&self, channel: usize) -> Result<Box<dyn SdrStream>, Error> {
+        self.check_channel(channel)?;
+        self.streams_opened.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::new(MockStream::new()))
+    }
+}
+
+// a phase continuous tone at MOCK_TONE_OFFSET which is returned as fast as it is read
+// reading it before it is activated is an error like it is with most drivers
+pub struct MockStream {
+    active: bool,
+    phase: f64,
+}
+
+impl MockStream {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Default for MockStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SdrStream for MockStream {
+    fn mtu(&self) -> Result<usize, Error> {
+        Ok(MOCK_MTU)
+    }
+    fn activate(&mut self) -> Result<(), Error> {
+        self.active = true;
+        Ok(())
+    }
+    fn deactivate(&mut self) -> Result<(), Error> {
+        self.active = false;
+        Ok(())
+    }
+    fn read(&mut self, buffer: &mut [Complex<RxFormat>], _timeout_us: i64) -> Result<usize, Error> {
+        if !self.active {
+            return Err(Error {
+                code: ErrorCode::StreamError,
+                message: "The stream is not active".to_owned(),
+            });
+        }
+
+        let count = buffer.len().min(MOCK_MTU);
+        for sample in &mut buffer[..count] {
+            *sample = Complex::from_polar(0.5, self.phase as RxFormat);
+            self.phase = (self.phase + 2.0 * PI * MOCK_TONE_OFFSET) % (2.0 * PI);
+        }
+
+        Ok(count)
+    }
+}
+
+// a stand in for the read of a stream which never reads more than `mtu`, the samples are a ramp so that every
+// buffer and window can be told apart by its first sample
+pub fn ramp_read(mtu: usize) -> impl FnMut(&mut [Complex<RxFormat>]) -> Result<usize, ()> {
+    let mut next = 0.0;
+    move |dst| {
+        let len = dst.len().min(mtu);
+        for sample in &mut dst[..len] {
+            *sample = Complex::new(next, 0.0);
+            next += 1.0;
+        }
+        Ok(len)
+    }
+}
@@ -1,2 +1,4 @@
+pub mod mock_device;
+pub mod sdr_device;
 pub mod worker;
 pub mod worker_manager;
@@ -0,0 +1,164 @@
+use super::worker::RxFormat;
+
+use rustfft::num_complex::Complex;
+use soapysdr::{Args, Device, Direction::Rx, Error, Range, RxStream};
+
+// the parts of a SoapySDR device the worker uses, it only ever receives so the direction is left out
+// the worker doesn't care what is behind it, in the tests it is a MockDevice
+pub trait SdrDevice {
+    fn driver_key(&self) -> Result<String, Error>;
+    fn hardware_key(&self) -> Result<String, Error>;
+    fn hardware_info(&self) -> Result<Args, Error>;
+    fn num_channels(&self) -> Result<usize, Error>;
+    fn channel_info(&self, channel: usize) -> Result<Args, Error>;
+    // the clock is shared by all the channels
+    fn list_clock_sources(&self) -> Result<Vec<String>, Error>;
+    fn clock_source(&self) -> Result<String, Error>;
+    fn set_clock_source(&self, name: &str) -> Result<(), Error>;
+
+    fn sample_rate_range(&self, channel: usize) -> Result<Vec<Range>, Error>;
+    fn bandwidth_range(&self, channel: usize) -> Result<Vec<Range>, Error>;
+    fn frequency_range(&self, channel: usize) -> Result<Vec<Range>, Error>;
+    fn gain_range(&self, channel: usize) -> Result<Range, Error>;
+
+    fn antennas(&self, channel: usize) -> Result<Vec<String>, Error>;
+    fn set_antenna(&self, channel: usize, name: &str) -> Result<(), Error>;
+    fn set_gain_mode(&self, channel: usize, automatic: bool) -> Result<(), Error>;
+    fn set_dc_offset_mode(&self, channel: usize, automatic: bool) -> Result<(), Error>;
+    fn set_gain(&self, channel: usize, gain: f64) -> Result<(), Error>;
+    fn set_frequency(&self, channel: usize, frequency: f64) -> Result<(), Error>;
+    fn set_sample_rate(&self, channel: usize, samplerate: f64) -> Result<(), Error>;
+    fn set_bandwidth(&self, channel: usize, bandwidth: f64) -> Result<(), Error>;
+
+    fn gain(&self, channel: usize) -> Result<f64, Error>;
+    fn frequency(&self, channel: usize) -> Result<f64, Error>;
+    fn sample_rate(&self, channel: usize) -> Result<f64, Error>;
+    fn bandwidth(&self, channel: usize) -> Result<f64, Error>;
+
+    fn rx_stream(&self, channel: usize) -> Result<Box<dyn SdrStream>, Error>;
+}
+
+pub trait SdrStream {
+    fn mtu(&self) -> Result<usize, Error>;
+    fn activate(&mut self) -> Result<(), Error>;
+    fn deactivate(&mut self) -> Result<(), Error>;
+    // returns the number of samples written to the start of `buffer`
+    fn read(&mut self, buffer: &mut [Complex<RxFormat>], timeout_us: i64) -> Result<usize, Error>;
+}
+
+// finds the devices and opens them, the worker creates every device through it
+pub trait SdrBackend {
+    fn enumerate(&self, args: &str) -> Result<Vec<Args>, Error>;
+    fn open(&self, args: Args) -> Result<Box<dyn SdrDevice>, Error>;
+}
+
+// the actual hardware
+pub struct SoapyBackend;
+
+impl SdrBackend for SoapyBackend {
+    fn enumerate(&self, args: &str) -> Result<Vec<Args>, Error> {
+        soapysdr::enumerate(args)
+    }
+    fn open(&self, args: Args) -> Result<Box<dyn SdrDevice>, Error> {
+        Ok(Box::new(Device::new(args)?))
+    }
+}
+
+impl SdrDevice for Device {
+    fn driver_key(&self) -> Result<String, Error> {
+        Device::driver_key(self)
+    }
+    fn hardware_key(&self) -> Result<String, Error> {
+        Device::hardware_key(self)
+    }
+    fn hardware_info(&self) -> Result<Args, Error> {
+        Device::hardware_info(self)
+    }
+    fn num_channels(&self) -> Result<usize, Error> {
+        Device::num_channels(self, Rx)
+    }
+    fn channel_info(&self, channel: usize) -> Result<Args, Error> {
+        Device::channel_info(self, Rx, channel)
+    }
+    fn list_clock_sources(&self) -> Result<Vec<String>, Error> {
+        Device::list_clock_sources(self)
+    }
+    fn clock_source(&self) -> Result<String, Error> {
+        Device::get_clock_source(self)
+    }
+    fn set_clock_source(&self, name: &str) -> Result<(), Error> {
+        Device::set_clock_source(self, name)
+    }
+
+    fn sample_rate_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        Device::get_sample_rate_range(self, Rx, channel)
+    }
+    fn bandwidth_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        Device::bandwidth_range(self, Rx, channel)
+    }
+    fn frequency_range(&self, channel: usize) -> Result<Vec<Range>, Error> {
+        Device::frequency_range(self, Rx, channel)
+    }
+    fn gain_range(&self, channel: usize) -> Result<Range, Error> {
+        Device::gain_range(self, Rx, channel)
+    }
+
+    fn antennas(&self, channel: usize) -> Result<Vec<String>, Error> {
+        Device::antennas(self, Rx, channel)
+    }
+    fn set_antenna(&self, channel: usize, name: &str) -> Result<(), Error> {
+        Device::set_antenna(self, Rx, channel, name)
+    }
+    fn set_gain_mode(&self, channel: usize, automatic: bool) -> Result<(), Error> {
+        Device::set_gain_mode(self, Rx, channel, automatic)
+    }
+    fn set_dc_offset_mode(&self, channel: usize, automatic: bool) -> Result<(), Error> {
+        Device::set_dc_offset_mode(self, Rx, channel, automatic)
+    }
+    fn set_gain(&self, channel: usize, gain: f64) -> Result<(), Error> {
+        Device::set_gain(self, Rx, channel, gain)
+    }
+    fn set_frequency(&self, channel: usize, frequency: f64) -> Result<(), Error> {
+        // no tuning args, the driver picks how to split the frequency between its oscillators
+        Device::set_frequency(self, Rx, channel, frequency, ())
+    }
+    fn set_sample_rate(&self, channel: usize, samplerate: f64) -> Result<(), Error> {
+        Device::set_sample_rate(self, Rx, channel, samplerate)
+    }
+    fn set_bandwidth(&self, channel: usize, bandwidth: f64) -> Result<(), Error> {
+        Device::set_bandwidth(self, Rx, channel, bandwidth)
+    }
+
+    fn gain(&self, channel: usize) -> Result<f64, Error> {
+        Device::gain(self, Rx, channel)
+    }
+    fn frequency(&self, channel: usize) -> Result<f64, Error> {
+        Device::frequency(self, Rx, channel)
+    }
+    fn sample_rate(&self, channel: usize) -> Result<f64, Error> {
+        Device::sample_rate(self, Rx, channel)
+    }
+    fn bandwidth(&self, channel: usize) -> Result<f64, Error> {
+        Device::bandwidth(self, Rx, channel)
+    }
+
+    fn rx_stream(&self, channel: usize) -> Result<Box<dyn SdrStream>, Error> {
+        let stream: RxStream<Complex<RxFormat>> = Device::rx_stream(self, &[channel])?;
+        Ok(Box::new(stream))
+    }
+}
+
+impl SdrStream for RxStream<Complex<RxFormat>> {
+    fn mtu(&self) -> Result<usize, Error> {
+        RxStream::mtu(self)
+    }
+    fn activate(&mut self) -> Result<(), Error> {
+        RxStream::activate(self, None)
+    }
+    fn deactivate(&mut self) -> Result<(), Error> {
+        RxStream::deactivate(self, None)
+    }
+    fn read(&mut self, buffer: &mut [Complex<RxFormat>], timeout_us: i64) -> Result<usize, Error> {
+        RxStream::read(self, &[buffer], timeout_us)
+    }
+}
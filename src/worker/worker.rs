@@ -1,28 +1,44 @@
-use super::worker_manager::ReceiverState;
+use super::{
+    sdr_device::{SdrBackend, SdrDevice, SdrStream, SoapyBackend},
+    worker_manager::ReceiverState,
+};
 use crate::{
-    decoder::Decoder,
-    dsp::{fir_filter::FirFilter, multistage_fir::MultistageFir},
-    worker::worker_manager::{ChannelInfo, ValueRanges},
-    FftData,
+    decoder::{Decoder, DecoderError, DecoderParams},
+    dsp::{
+        fir_filter::FirFilter,
+        iq_balance::IqCorrector,
+        multistage_fir::MultistageFir,
+        ring_buffer::{overlap_advance, RingBuffer},
+        rtty_decode::DecodedChar,
+        window_functions::WindowKind,
+    },
+    iq_source::{IqFormat, IqWriter},
+    recording::Recorder,
+    worker::worker_manager::{ChannelInfo, DeviceInfo, ValueRanges},
+    FftData, SpectrumData,
 };
 
 use std::{
     any::Any,
+    collections::VecDeque,
     error::Error,
     fmt::Display,
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
     rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
     usize,
 };
 
 use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
 use num_traits::Zero;
 use rustfft::num_complex::Complex;
-use soapysdr::{Args, Device, Direction::Rx, RxStream};
+use soapysdr::{Args, ErrorCode};
 
 #[derive(Debug)]
 pub enum DeviceBoundCommand {
@@ -30,18 +46,151 @@ pub enum DeviceBoundCommand {
     CreateDevice { index: usize },
     RefreshDevices { args: String },
     SetReceiver(ReceiverState),
-    RequestData { data: FftData<RxFormat> },
+    // overlap is the fraction (0.0..1.0) of the window which is shared with the next request
+    RequestData { data: SpectrumData, overlap: f32 },
     SetDecoder { decoder: Decoder },
+    // changes the parameters of the current decoder without resetting it
+    UpdateDecoderParams { params: DecoderParams },
+    ClearDecoder,
+    // buffers whose snr is below the threshold (in dB) are not decoded
+    SetSquelch { threshold: f32 },
+    // for debugging, the decoder sends the sliced bits of every buffer it processes
+    SetRawBits { enabled: bool },
+    // how much of the received IQ is kept while nothing is recorded, a recording starts with it
+    SetPreTrigger { seconds: f32 },
+    // writes the received IQ to the file, starting with the pre-trigger samples
+    StartRecording { path: PathBuf, format: IqFormat },
+    StopRecording,
 }
 #[derive(Debug)]
 pub enum GuiBoundEvent {
     WorkerReset,
-    DeviceCreated { channels_info: Vec<ChannelInfo> },
+    DeviceCreated {
+        device_info: DeviceInfo,
+        channels_info: Vec<ChannelInfo>,
+    },
     DeviceDestroyed,
-    Error(soapysdr::Error),
-    RefreshedDevices { list: Vec<String> },
-    DecodedChars { data: String }, // TODO
-    DataReady { data: FftData<RxFormat> },
+    // CreateDevice failed and there is no device, like when the device isn't in the list of the worker anymore
+    DeviceNotCreated {
+        message: String,
+    },
+    Error {
+        kind: DeviceErrorKind,
+        error: soapysdr::Error,
+    },
+    // the worker refused a command, for example a receiver configuration it can't use
+    WorkerError {
+        message: &'static str,
+    },
+    RefreshedDevices {
+        list: Vec<String>,
+        // the serial of every device in the list, empty if it has none
+        serials: Vec<String>,
+    },
+    DecodedChars {
+        data: Vec<DecodedChar>,
+    },
+    // the demodulated phase change of every sample the decoder processed, in radians
+    DemodTrace {
+        samples: Vec<f32>,
+    },
+    // the bits sliced from the demodulated signal before they are framed into characters, only sent if enabled
+    RawBits {
+        bits: Vec<bool>,
+    },
+    // demodulated audio, mono samples between -1 and 1 at the samplerate the decoder decimated to
+    AudioReady {
+        samples: Vec<f32>,
+        samplerate: f64,
+    },
+    DecoderFailed {
+        kind: DecoderError,
+    },
+    // the decimation the filter of the decoder achieved, sent every time the decoder is configured
+    DecoderConfigured {
+        decimation: u32,
+    },
+    // the values the driver actually applied, they can differ from the requested ones because of rounding
+    ReceiverConfigured {
+        actual: ReceiverState,
+    },
+    DataReady {
+        data: SpectrumData,
+    },
+    // the snr (in dB) of the last buffer and whether it was kept from the decoder
+    SignalLevel {
+        snr: f32,
+        squelched: bool,
+    },
+    // how far the strongest signal is from the center frequency in Hz, sent together with SignalLevel
+    CarrierOffset {
+        offset: f64,
+    },
+    // the frequency of the strongest tone in Hz, measured by the tone measurement decoder every buffer
+    Measurement {
+        freq_hz: f64,
+    },
+    // the number of stream reads since the receiver was configured and how many of them overflowed
+    StreamStats {
+        overflows: u64,
+        read_count: u64,
+    },
+    // the time spent in every stage of the processing during the last `elapsed`, sent together with StreamStats
+    WorkerStats {
+        times: ProcessingTimes,
+        elapsed: Duration,
+    },
+    // the number of pre-trigger samples the recording started with
+    RecordingStarted {
+        pre_trigger_samples: usize,
+    },
+    // also sent when writing the recording failed, with the error
+    RecordingStopped {
+        samples: u64,
+        error: Option<String>,
+    },
+    // the long commands are bracketed by these two, the ack is sent after the command's own events or its failure
+    CommandStarted {
+        command: LongCommand,
+    },
+    CommandAck {
+        command: LongCommand,
+    },
+}
+
+// the commands which can take a while, the driver may need seconds to open a device or look for them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LongCommand {
+    CreateDevice,
+    RefreshDevices,
+}
+
+// how bad an error from the driver is, the gui reacts to each kind differently
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeviceErrorKind {
+    // the read took too long, the stream may still recover
+    Timeout,
+    // some samples were lost or damaged but the stream goes on
+    SamplesDropped,
+    // the device is gone or its stream is broken, it has to be recreated
+    Fatal,
+    // anything else, the receiving is stopped but the device is kept
+    Other,
+}
+
+impl DeviceErrorKind {
+    pub fn from_error(e: &soapysdr::Error) -> Self {
+        match e.code {
+            // some drivers report an unplugged device like this
+            ErrorCode::NotSupported | ErrorCode::Timeout if e.message == "Lost" => {
+                DeviceErrorKind::Fatal
+            }
+            ErrorCode::Timeout => DeviceErrorKind::Timeout,
+            ErrorCode::Overflow | ErrorCode::Corruption => DeviceErrorKind::SamplesDropped,
+            ErrorCode::StreamError => DeviceErrorKind::Fatal,
+            _ => DeviceErrorKind::Other,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -49,7 +198,7 @@ enum DeviceWorkerError {
     MainThreadTerminated,
     SoapyError(soapysdr::Error),
     WorkerError(&'static str),
-    DecoderError(&'static str),
+    DecoderError(DecoderError),
 }
 
 impl Display for DeviceWorkerError {
@@ -79,15 +228,339 @@ impl<T> From<crossbeam_channel::SendError<T>> for DeviceWorkerError {
     }
 }
 
+impl From<DecoderError> for DeviceWorkerError {
+    fn from(error: DecoderError) -> Self {
+        DeviceWorkerError::DecoderError(error)
+    }
+}
+
 impl From<&'static str> for DeviceWorkerError {
     fn from(error: &'static str) -> Self {
         DeviceWorkerError::WorkerError(error)
     }
 }
 
-const RECEIVE_TIMEOUT_US: i64 = 200_000; // 200 miliseconds
+fn clone_args(a: &Args) -> Args {
+    let mut c = Args::new();
+    for (k, v) in a {
+        c.set(k, v)
+    }
+    c
+}
+
+// not every driver provides a label, compose one from the other keys then
+pub(crate) fn device_name(args: &Args) -> String {
+    if let Some(label) = args.get("label").filter(|l| !l.is_empty()) {
+        return label.to_owned();
+    }
+
+    let name = ["driver", "device", "serial"]
+        .iter()
+        .filter_map(|&key| args.get(key).filter(|v| !v.is_empty()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if name.is_empty() {
+        args.to_string()
+    } else {
+        name
+    }
+}
+
+// the serial identifies the device even when its label changes, like after plugging it into another port
+pub(crate) fn device_serial(args: &Args) -> String {
+    args.get("serial").unwrap_or_default().to_owned()
+}
+
+// the output of '--list-devices', one numbered device per line followed by its indented args
+pub(crate) fn format_device_list(devices: &[Args]) -> String {
+    if devices.is_empty() {
+        return "No devices found\n".to_owned();
+    }
+
+    let mut out = String::new();
+    for (i, args) in devices.iter().enumerate() {
+        out += &format!("{}: {}\n", i, device_name(args));
+        for (key, value) in args {
+            out += &format!("    {} = {}\n", key, value);
+        }
+    }
+
+    out
+}
+
+// the state is checked before it gets to the device, anything below 1 Sps would divide by zero in receive_time_us
+fn check_receiver_state(state: &ReceiverState) -> Result<(), &'static str> {
+    if !(state.samplerate >= 1.0) {
+        return Err("The receiver samplerate has to be at least 1 Sps.");
+    }
+    Ok(())
+}
+
+// how long it takes the device to produce `samples` samples
+fn receive_time_us(samples: usize, samplerate: f64) -> u64 {
+    samples as u64 * 1000_000 / samplerate as u64
+}
+
+// how many samples the device produces in `time_us`, the inverse of receive_time_us
+fn receive_samples(time_us: u64, samplerate: f64) -> usize {
+    (time_us as f64 * samplerate / 1000_000.0).ceil() as usize
+}
+
+// the memory is the limit here, a second of 10 MSps takes 80 MB
+fn pre_trigger_samples(seconds: f32, samplerate: f64) -> usize {
+    (seconds.max(0.0) as f64 * samplerate).ceil() as usize
+}
+
+// a slow usb link can occasionally take longer than the timeout, the read is retried this many times before the error is sent
+const RECEIVE_TIMEOUT_RETRIES: u32 = 3;
+
+// the time a read waits for a whole mtu of samples, add an extra milisecond just to be safe
+fn read_timeout_us(mtu_receive_time_us: u64, receive_timeout_us: u64) -> i64 {
+    (mtu_receive_time_us + 1000).max(receive_timeout_us) as i64
+}
+
+// without a receiver there is no stream waiting, the commands are handled in slices of this long
+const IDLE_EVENT_BUDGET_MS: u64 = 5;
+
+// how long the commands are handled before the worker goes back to the stream
+// by default it's the time the next mtu takes to arrive, so a burst of commands can't overflow the device
+fn event_budget(receive_state: Option<&ReceiverState>, mtu_receive_time_us: u64) -> Duration {
+    match receive_state {
+        Some(state) if state.event_budget_us > 0 => Duration::from_micros(state.event_budget_us),
+        Some(_) => Duration::from_micros(mtu_receive_time_us),
+        None => Duration::from_millis(IDLE_EVENT_BUDGET_MS),
+    }
+}
+
+// the read timeout is doubled at most this many times, a stream which doesn't deliver even then is an error
+const READ_BACKOFF_MAX_DOUBLINGS: u32 = 3;
+
+// the timeout of the stream reads grows with every read that timed out and shrinks back with every one that didn't
+// so a link with a slow moment is waited out while a misconfigured stream still errors once the growth stops
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ReadBackoff {
+    doublings: u32,
+}
+
+impl ReadBackoff {
+    // `base_us` is the timeout without any backoff, the one from read_timeout_us
+    fn timeout_us(&self, base_us: i64) -> i64 {
+        base_us << self.doublings
+    }
+    fn timed_out(&mut self) {
+        self.doublings = (self.doublings + 1).min(READ_BACKOFF_MAX_DOUBLINGS);
+    }
+    fn succeeded(&mut self) {
+        self.doublings = self.doublings.saturating_sub(1);
+    }
+}
+
+// retries `read` while it times out, any other error is returned right away
+// a timeout with the message "Lost" means the device is gone, there is no point in waiting for it
+// `read` gets the timeout in us, it is longer with every timeout before it
+fn retry_timeouts<T>(
+    retries: u32,
+    backoff: &mut ReadBackoff,
+    base_us: i64,
+    mut read: impl FnMut(i64) -> Result<T, soapysdr::Error>,
+) -> Result<T, soapysdr::Error> {
+    let mut attempt = 0;
+    loop {
+        let timeout = backoff.timeout_us(base_us);
+        match read(timeout) {
+            Err(e) if e.code == ErrorCode::Timeout && e.message != "Lost" => {
+                backoff.timed_out();
+                if attempt == retries {
+                    return Err(e);
+                }
+
+                attempt += 1;
+                log::debug!(
+                    "Read timed out after {} us, retrying ({}/{})",
+                    timeout,
+                    attempt,
+                    retries
+                );
+            }
+            result => {
+                if result.is_ok() {
+                    backoff.succeeded();
+                }
+                return result;
+            }
+        }
+    }
+}
+
+// how many stream reads worth of samples are kept for RequestData before the oldest ones are dropped
+const REQUEST_RING_MTUS: usize = 16;
+// the length of the fft the signal level is measured with, only the start of every buffer is measured
+const LEVEL_FFT_LEN: usize = 1024;
+// the level is measured for every buffer but the gui doesn't need to know that often
+const LEVEL_REPORT_INTERVAL_MS: u64 = 100;
+// the overflows are only interesting over a longer time, they are reported less often
+const STATS_REPORT_INTERVAL_MS: u64 = 1000;
 pub type RxFormat = f32;
 
+// counts the reads of the receive stream and how many of them overflowed because the host couldn't keep up
+pub(crate) struct StreamCounters {
+    pub(crate) overflows: u64,
+    pub(crate) read_count: u64,
+    interval: Duration,
+    last_report: Option<Instant>,
+}
+
+impl StreamCounters {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            overflows: 0,
+            read_count: 0,
+            interval,
+            last_report: None,
+        }
+    }
+    pub(crate) fn count_read(&mut self, overflow: bool) {
+        self.read_count += 1;
+        if overflow {
+            self.overflows += 1;
+        }
+    }
+    // returns the event with the totals if the last one was sent at least `interval` ago
+    pub(crate) fn report(&mut self, now: Instant) -> Option<GuiBoundEvent> {
+        if let Some(last) = self.last_report {
+            if now.duration_since(last) < self.interval {
+                return None;
+            }
+        }
+
+        self.last_report = Some(now);
+        Some(GuiBoundEvent::StreamStats {
+            overflows: self.overflows,
+            read_count: self.read_count,
+        })
+    }
+    // the next report is still sent on time
+    pub(crate) fn clear(&mut self) {
+        self.overflows = 0;
+        self.read_count = 0;
+    }
+}
+
+// the stages of the processing whose time is measured
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProcessingStage {
+    // waiting for and copying the samples of the receive stream
+    Read,
+    // the spectra of the requests and of the level measurement
+    Fft,
+    // the decimation filter, it is run by the decoder so its time is also a part of the decoder's
+    Filter,
+    Decoder,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProcessingTimes {
+    pub read: Duration,
+    pub fft: Duration,
+    pub filter: Duration,
+    pub decoder: Duration,
+}
+
+impl ProcessingTimes {
+    pub fn add(&mut self, stage: ProcessingStage, duration: Duration) {
+        let time = match stage {
+            ProcessingStage::Read => &mut self.read,
+            ProcessingStage::Fft => &mut self.fft,
+            ProcessingStage::Filter => &mut self.filter,
+            ProcessingStage::Decoder => &mut self.decoder,
+        };
+        *time += duration;
+    }
+}
+
+// sums up the time of every stage over an interval, the times are reset with every report
+// a stage is timed once per call and the calls are per buffer or stream read, not per sample,
+// so the clock is read a few thousand times a second at most
+pub(crate) struct ProcessingStats {
+    times: ProcessingTimes,
+    interval: Duration,
+    interval_start: Option<Instant>,
+}
+
+impl ProcessingStats {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            times: ProcessingTimes::default(),
+            interval,
+            interval_start: None,
+        }
+    }
+    pub(crate) fn add(&mut self, stage: ProcessingStage, duration: Duration) {
+        self.times.add(stage, duration);
+    }
+    // returns the event with the times once the interval is over
+    // the first call only starts the interval, the times before it can't be related to any wall clock time
+    pub(crate) fn report(&mut self, now: Instant) -> Option<GuiBoundEvent> {
+        let start = match self.interval_start {
+            Some(start) => start,
+            None => {
+                self.clear();
+                self.interval_start = Some(now);
+                return None;
+            }
+        };
+
+        let elapsed = now.duration_since(start);
+        if elapsed < self.interval {
+            return None;
+        }
+
+        self.interval_start = Some(now);
+        Some(GuiBoundEvent::WorkerStats {
+            times: std::mem::take(&mut self.times),
+            elapsed,
+        })
+    }
+    // the next report starts a new interval
+    pub(crate) fn clear(&mut self) {
+        self.times = ProcessingTimes::default();
+        self.interval_start = None;
+    }
+}
+
+// the fractional index of the strongest bin, refined by fitting a parabola through it and its neighbours
+// the neighbours wrap around like the bins of an fft do, the fit is done on the logarithm of the powers
+// which is exact for a gaussian peak and close enough for the main lobe of the usual windows
+// returns None if there is nothing but zeroes
+pub fn interpolate_peak(powers: &[RxFormat]) -> Option<f64> {
+    let len = powers.len();
+    let peak = (0..len)
+        .filter(|&i| powers[i] > 0.0)
+        .max_by(|&a, &b| powers[a].partial_cmp(&powers[b]).unwrap())?;
+
+    let ln = |i: usize| (powers[i].max(RxFormat::MIN_POSITIVE) as f64).ln();
+    let (a, b, c) = (ln((peak + len - 1) % len), ln(peak), ln((peak + 1) % len));
+
+    let denominator = a - 2.0 * b + c;
+    // a flat top can't be refined
+    let delta = if denominator < 0.0 {
+        (0.5 * (a - c) / denominator).max(-0.5).min(0.5)
+    } else {
+        0.0
+    };
+
+    Some(peak as f64 + delta)
+}
+
+// the frequency of a (fractional) bin of an unshifted fft, the upper half of the bins are the negative frequencies
+pub fn bin_frequency(bin: f64, len: usize, samplerate: f64) -> f64 {
+    let len = len as f64;
+    let bin = if bin >= len / 2.0 { bin - len } else { bin };
+
+    bin * samplerate / len
+}
+
 pub struct DeviceWorker {
     // this is an atomic bool rather than a message in the channel because there may be multiple data requests queued at a time
     // this was mostly implemented to quickly react to
@@ -97,24 +570,52 @@ pub struct DeviceWorker {
     pub(crate) receiver: Receiver<DeviceBoundCommand>,
     pub(crate) sender: Sender<GuiBoundEvent>,
 
+    // where the devices come from, SoapySDR unless the worker is driven by a mock
+    backend: Box<dyn SdrBackend>,
     pub(crate) available_devices: Option<Vec<Args>>,
-    pub(crate) device: Option<Device>,
+    pub(crate) device: Option<Box<dyn SdrDevice>>,
 
     pub(crate) receive_state: Option<ReceiverState>,
-    pub(crate) receive_stream: Option<RxStream<Complex<RxFormat>>>,
+    pub(crate) receive_stream: Option<Box<dyn SdrStream>>,
     pub(crate) mtu: usize,
     pub(crate) mtu_receive_time_us: u64,
+    pub(crate) read_backoff: ReadBackoff,
+    // the samples received right after a retune are still transient, this many more are left out of the RequestData samples
+    pub(crate) settle_samples_left: usize,
+    // the number of samples the decoder processes at once, never less than the mtu
+    pub(crate) receive_size: usize,
 
     pub(crate) decoder: Option<Decoder>,
 
     pub(crate) working_memory: Vec<Complex<RxFormat>>,
     pub(crate) memory_receive_offset: usize,
     pub(crate) memory_received_count: usize,
-    pub(crate) data_request_offset: usize,
+    // the decoder destroys working_memory in place so RequestData is served from a copy of the received samples
+    // this way the requests see every sample exactly once even when their length doesn't divide the mtu
+    pub(crate) request_ring: RingBuffer<Complex<RxFormat>>,
+    // the window read from request_ring before it is converted to the precision of the requested fft
+    pub(crate) request_samples: Vec<Complex<RxFormat>>,
+    // (data, overlap) of the RequestData commands waiting for samples, several can be in flight and they are served in order
+    pub(crate) pending_requests: VecDeque<(SpectrumData, f32)>,
 
     pub(crate) decimation_fir_cache: Vec<(u32, Rc<FirFilter>)>,
     // this is here because it is not Send so it cannot be a part of the Decoder struct
     pub(crate) current_fir_filter: Option<MultistageFir<Complex<RxFormat>>>,
+
+    // set by the iq_correction of the receiver state, it keeps its estimate for as long as the correction stays enabled
+    pub(crate) iq_corrector: Option<IqCorrector>,
+
+    pub(crate) squelch_threshold: f32,
+    pub(crate) squelched: bool,
+    pub(crate) raw_bits: bool,
+    level_fft: FftData<RxFormat>,
+    level_powers: Vec<RxFormat>,
+    last_level_report: Option<Instant>,
+    pub(crate) stream_counters: StreamCounters,
+    pub(crate) processing_stats: ProcessingStats,
+    // the pre-trigger is set in seconds, the recorder gets it in samples once the samplerate is known
+    pub(crate) pre_trigger_s: f32,
+    pub(crate) recorder: Recorder<BufWriter<File>>,
 }
 
 impl DeviceWorker {
@@ -122,38 +623,344 @@ impl DeviceWorker {
         receiver: Receiver<DeviceBoundCommand>,
         sender: Sender<GuiBoundEvent>,
         receive_enable_flag: Arc<AtomicBool>,
+    ) -> Self {
+        Self::with_backend(
+            receiver,
+            sender,
+            receive_enable_flag,
+            Box::new(SoapyBackend),
+        )
+    }
+    pub fn with_backend(
+        receiver: Receiver<DeviceBoundCommand>,
+        sender: Sender<GuiBoundEvent>,
+        receive_enable_flag: Arc<AtomicBool>,
+        backend: Box<dyn SdrBackend>,
     ) -> Self {
         Self {
             receive_enable_flag,
             receive_stream_active: false,
             receiver,
             sender,
+            backend,
             available_devices: None,
             device: None,
             receive_state: None,
             receive_stream: None,
             mtu: 0,
             mtu_receive_time_us: 0,
+            read_backoff: ReadBackoff::default(),
+            settle_samples_left: 0,
+            receive_size: 0,
             decoder: None,
             working_memory: Vec::new(),
             memory_receive_offset: 0,
             memory_received_count: 0,
-            data_request_offset: 0,
+            request_ring: RingBuffer::new(0),
+            request_samples: Vec::new(),
+            pending_requests: VecDeque::new(),
             decimation_fir_cache: Vec::new(),
             current_fir_filter: None,
+            iq_corrector: None,
+            squelch_threshold: 0.0,
+            squelched: false,
+            raw_bits: false,
+            level_fft: {
+                let mut fft = FftData::new(LEVEL_FFT_LEN);
+                fft.set_window(WindowKind::BlackmanHaris);
+                fft
+            },
+            level_powers: Vec::with_capacity(LEVEL_FFT_LEN),
+            last_level_report: None,
+            stream_counters: StreamCounters::new(Duration::from_millis(STATS_REPORT_INTERVAL_MS)),
+            processing_stats: ProcessingStats::new(Duration::from_millis(STATS_REPORT_INTERVAL_MS)),
+            pre_trigger_s: 0.0,
+            recorder: Recorder::new(0),
         }
     }
-    fn error_process(&mut self) -> Result<(), DeviceWorkerError> {
-        fn clone_args(a: &Args) -> Args {
-            let mut c = Args::new();
-            for (k, v) in a {
-                c.set(k, v)
+    // makes space for receive_size fresh samples at the end of working_memory and returns the index they start at
+    pub(crate) fn reserve_receive_memory(&mut self) -> usize {
+        let min_len = self.memory_receive_offset + self.receive_size;
+        if self.working_memory.len() < min_len {
+            self.working_memory.resize(min_len, Complex::zero());
+        }
+
+        let start = self.working_memory.len() - self.receive_size;
+        self.memory_receive_offset = start;
+
+        start
+    }
+    // the samples from before the stream was paused aren't continuous with the new ones and may be long outdated
+    // so the requests wait for the first read after the activation instead of being served those
+    pub(crate) fn stream_activated(&mut self) {
+        self.receive_stream_active = true;
+        self.request_ring.clear();
+    }
+    // reads more samples into the buffer in working_memory, `read` gets the part of the buffer which is still free
+    // returns true once receive_size samples were accumulated, memory_received_count is then reset by whoever processes them
+    pub(crate) fn receive_buffer<E>(
+        &mut self,
+        read: impl FnOnce(&mut [Complex<RxFormat>]) -> Result<usize, E>,
+    ) -> Result<bool, E> {
+        // the previous buffer was processed (or left full because processing failed), start a new one
+        if self.memory_received_count == 0 || self.memory_received_count >= self.receive_size {
+            self.memory_received_count = 0;
+            self.reserve_receive_memory();
+        }
+
+        let start = self.memory_receive_offset + self.memory_received_count;
+        let end = self.memory_receive_offset + self.receive_size;
+        let read = read(&mut self.working_memory[start..end])?;
+
+        // both the spectrum and the decoder get the corrected samples
+        if let Some(corrector) = &mut self.iq_corrector {
+            corrector.process(&mut self.working_memory[start..(start + read)]);
+        }
+
+        // the recording is stopped if the write fails, the receiving goes on
+        if let Err(e) = self
+            .recorder
+            .push(&self.working_memory[start..(start + read)])
+        {
+            log::error!("Failed to write the recording: {}", e);
+            let _ = self.sender.send(GuiBoundEvent::RecordingStopped {
+                samples: self.recorder.written(),
+                error: Some(e.to_string()),
+            });
+        }
+
+        self.memory_received_count += read;
+
+        // the decoder copes with the transients on its own, only the requests skip them
+        let skip = self.settle_samples_left.min(read);
+        self.settle_samples_left -= skip;
+        self.request_ring
+            .push(&self.working_memory[(start + skip)..(start + read)]);
+
+        Ok(self.memory_received_count >= self.receive_size)
+    }
+    // the ratio of the strongest bin to the median one in dB, the median is a good enough estimate of the noise floor
+    // and the frequency of the strongest bin relative to the center in Hz
+    // returns None if the buffer is too short to be measured
+    fn measure_level(&mut self) -> Option<(f32, f64)> {
+        if self.memory_received_count < LEVEL_FFT_LEN {
+            return None;
+        }
+
+        let start = self.memory_receive_offset;
+        self.level_fft
+            .set_input(&self.working_memory[start..(start + LEVEL_FFT_LEN)]);
+        self.level_fft.process(0.0);
+
+        self.level_powers.clear();
+        self.level_powers
+            .extend(self.level_fft.get_output().iter().map(|c| c.norm_sqr()));
+
+        // before the powers are reordered by the median search
+        let samplerate = self.receive_state.as_ref().map_or(0.0, |s| s.samplerate);
+        let offset = interpolate_peak(&self.level_powers)
+            .map_or(0.0, |bin| bin_frequency(bin, LEVEL_FFT_LEN, samplerate));
+
+        let peak = self.level_powers.iter().cloned().fold(0.0, RxFormat::max);
+        let (_, &mut median, _) = self
+            .level_powers
+            .select_nth_unstable_by(LEVEL_FFT_LEN / 2, |a, b| a.partial_cmp(b).unwrap());
+
+        // nothing but zeroes was received
+        if median <= 0.0 {
+            return Some((0.0, offset));
+        }
+
+        Some((10.0 * (peak / median).log10(), offset))
+    }
+    // answers the pending requests in the order they were made for as long as request_ring has samples for them
+    // every request gets the window following the one of the previous request
+    pub(crate) fn serve_requests(
+        &mut self,
+    ) -> Result<(), crossbeam_channel::SendError<GuiBoundEvent>> {
+        while let Some((len, overlap)) = self
+            .pending_requests
+            .front()
+            .map(|(data, overlap)| (data.len(), *overlap))
+        {
+            // a request longer than the ring could never be served
+            if self.request_ring.capacity() < len {
+                self.request_ring.set_capacity(len * 2);
+            }
+
+            self.request_samples.resize(len, Complex::zero());
+            if !self
+                .request_ring
+                .read(&mut self.request_samples, overlap_advance(len, overlap))
+            {
+                return Ok(());
+            }
+
+            let (mut data, _) = self.pending_requests.pop_front().unwrap();
+            let samplerate = self.receive_state.as_ref().map_or(0.0, |s| s.samplerate);
+            let start = Instant::now();
+            data.process(&self.request_samples, samplerate);
+            self.processing_stats
+                .add(ProcessingStage::Fft, start.elapsed());
+
+            self.sender.send(GuiBoundEvent::DataReady { data })?;
+        }
+
+        Ok(())
+    }
+    // runs the decoder on the accumulated buffer unless it is squelched, then starts a new buffer
+    pub(crate) fn process_buffer(&mut self) -> Result<(), DecoderError> {
+        let start = Instant::now();
+        let level = self.measure_level();
+        self.processing_stats
+            .add(ProcessingStage::Fft, start.elapsed());
+
+        if let Some((snr, offset)) = level {
+            let squelched = snr < self.squelch_threshold;
+
+            let report = squelched != self.squelched
+                || self.last_level_report.map_or(true, |last| {
+                    last.elapsed() >= Duration::from_millis(LEVEL_REPORT_INTERVAL_MS)
+                });
+            if report {
+                self.last_level_report = Some(Instant::now());
+                let _ = self
+                    .sender
+                    .send(GuiBoundEvent::SignalLevel { snr, squelched });
+                let _ = self.sender.send(GuiBoundEvent::CarrierOffset { offset });
             }
-            c
+
+            self.squelched = squelched;
+        }
+
+        if !self.squelched {
+            // this horrible thing is needed to satisfy the borrowchecker
+            if let Some(mut decoder) = self.decoder.take() {
+                let start = Instant::now();
+                let result = decoder.process(self);
+                self.processing_stats
+                    .add(ProcessingStage::Decoder, start.elapsed());
+                result?;
+
+                self.decoder = Some(decoder);
+            }
+        }
+
+        self.memory_received_count = 0;
+
+        Ok(())
+    }
+    // the gui gets CommandStarted before the command is run and CommandAck after it, also when it failed
+    fn long_command(
+        &mut self,
+        command: LongCommand,
+        run: impl FnOnce(&mut Self) -> Result<(), DeviceWorkerError>,
+    ) -> Result<(), DeviceWorkerError> {
+        self.sender
+            .send(GuiBoundEvent::CommandStarted { command })?;
+        let result = run(self);
+        self.sender.send(GuiBoundEvent::CommandAck { command })?;
+
+        result
+    }
+    // a device which can't be created is reported with DeviceNotCreated instead of an error so that the manager
+    // forgets it, it is sent before the CommandAck after which the device group offers creating a device again
+    fn create_device(&mut self, index: usize) -> Result<(), DeviceWorkerError> {
+        match self.open_device(index) {
+            Err(DeviceWorkerError::MainThreadTerminated) => {
+                Err(DeviceWorkerError::MainThreadTerminated)
+            }
+            Err(e) => {
+                let message = e.to_string().trim_end().to_owned();
+                log::error!("Error creating the device: {}", message);
+
+                self.sender
+                    .send(GuiBoundEvent::DeviceNotCreated { message })?;
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+    fn open_device(&mut self, index: usize) -> Result<(), DeviceWorkerError> {
+        assert!(self.device.is_none());
+
+        // the manager can be behind the list, like when it restores a device after a reset
+        let args = match self
+            .available_devices
+            .as_ref()
+            .and_then(|devices| devices.get(index))
+        {
+            Some(args) => clone_args(args),
+            None => return Err("The device is not in the list of available devices.".into()),
+        };
+
+        log::info!("Creating device ({})", args);
+        let serial = device_serial(&args);
+        let dev = self.backend.open(args)?;
+
+        let device_info = DeviceInfo {
+            serial,
+            driver: dev.driver_key()?,
+            hardware: dev.hardware_key()?,
+            hardware_info: dev
+                .hardware_info()?
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            clock_sources: dev.list_clock_sources()?,
+        };
+
+        log::debug!("Device info: {:#?}", device_info);
+
+        let num_channels = dev.num_channels()?;
+        let mut channels_info = Vec::with_capacity(num_channels as usize);
+
+        for i in 0..dev.num_channels()? {
+            let info = dev
+                .channel_info(i)?
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+
+            let ranges = ValueRanges {
+                samplerate: dev.sample_rate_range(i)?,
+                bandwidth: dev.bandwidth_range(i)?,
+                frequency: dev.frequency_range(i)?,
+                gain: dev.gain_range(i)?,
+            };
+
+            channels_info.push(ChannelInfo { ranges, info })
         }
 
-        let mut delay_event = None;
+        self.sender.send(GuiBoundEvent::DeviceCreated {
+            device_info,
+            channels_info,
+        })?;
+        self.device = Some(dev);
+
+        Ok(())
+    }
+    fn refresh_devices(&mut self, args: &str) -> Result<(), DeviceWorkerError> {
+        let available = self.backend.enumerate(args)?;
+        let names = available.iter().map(device_name).collect::<Vec<_>>();
+        let serials = available.iter().map(device_serial).collect();
+
+        // the refresh request is possibly sent very frequently if auto_select is true
+        // avoid spamming empty messages if there is nothing to report
+        if !names.is_empty() {
+            log::info!("Available devices: {:#?}", names);
+        }
+
+        self.available_devices = Some(available);
+
+        self.sender.send(GuiBoundEvent::RefreshedDevices {
+            list: names,
+            serials,
+        })?;
 
+        Ok(())
+    }
+    fn error_process(&mut self) -> Result<(), DeviceWorkerError> {
         loop {
             let receive = self.receive_enable_flag.load(Ordering::SeqCst);
 
@@ -161,89 +968,84 @@ impl DeviceWorker {
             if let Some(stream) = self.receive_stream.as_mut() {
                 match (receive, self.receive_stream_active) {
                     (true, false) => {
-                        stream.activate(None)?;
-                        self.receive_stream_active = true;
+                        stream.activate()?;
+                        self.stream_activated();
                     }
                     (false, true) => {
-                        stream.deactivate(None)?;
+                        stream.deactivate()?;
                         self.receive_stream_active = false;
                     }
                     _ => {}
                 }
             }
 
+            let mut buffer_full = false;
             if self.receive_stream.is_some() && self.receive_stream_active {
-                let min_len = self.memory_receive_offset + self.mtu;
-                if self.working_memory.len() < min_len {
-                    self.working_memory.resize(min_len, Complex::zero());
-                }
+                // the stream has to be taken out so that the worker can be borrowed mutably
+                let mut stream = self.receive_stream.take().unwrap();
+                let timeout = read_timeout_us(
+                    self.mtu_receive_time_us,
+                    self.receive_state
+                        .as_ref()
+                        .map_or(0, |s| s.receive_timeout_us),
+                );
+                let mut backoff = self.read_backoff;
+                let read_start = Instant::now();
+                let result = self.receive_buffer(|dst| {
+                    retry_timeouts(RECEIVE_TIMEOUT_RETRIES, &mut backoff, timeout, |timeout| {
+                        stream.read(dst, timeout)
+                    })
+                });
+                let now = Instant::now();
+                self.processing_stats
+                    .add(ProcessingStage::Read, now.duration_since(read_start));
+                self.receive_stream = Some(stream);
+                self.read_backoff = backoff;
 
-                let start = self.working_memory.len() - self.mtu;
-                let dst = &mut self.working_memory[start..];
-                self.memory_receive_offset = start;
-
-                let read = self
-                    .receive_stream
-                    .as_mut()
-                    .unwrap()
-                    .read(&mut [dst], self.mtu_receive_time_us as i64 + 1000)?; // add an extra milisecond just to be safe
+                let overflow = matches!(&result, Err(e) if e.code == ErrorCode::Overflow);
+                self.stream_counters.count_read(overflow);
+                if let Some(event) = self.stream_counters.report(now) {
+                    self.sender.send(event)?;
+                }
+                if let Some(event) = self.processing_stats.report(now) {
+                    self.sender.send(event)?;
+                }
 
-                self.memory_received_count = read;
-                self.data_request_offset = 0;
+                buffer_full = match result {
+                    // the partially filled buffer isn't continuous anymore, it is dropped and the receiving goes on
+                    Err(error)
+                        if DeviceErrorKind::from_error(&error)
+                            == DeviceErrorKind::SamplesDropped =>
+                    {
+                        self.memory_received_count = 0;
+                        self.sender.send(GuiBoundEvent::Error {
+                            kind: DeviceErrorKind::SamplesDropped,
+                            error,
+                        })?;
+                        false
+                    }
+                    result => result?,
+                };
             }
 
             let start = std::time::Instant::now();
-            let duration = match self.receive_state {
-                Some(_) => Duration::from_micros(self.mtu_receive_time_us),
-                None => Duration::from_millis(5),
-            };
+            let duration = event_budget(self.receive_state.as_ref(), self.mtu_receive_time_us);
 
             'process_events: loop {
-                let event = match delay_event.take() {
-                    Some(event) => Some(event),
-                    None => match self.receiver.try_recv() {
-                        Ok(event) => Some(event),
-                        Err(TryRecvError::Empty) => None,
-                        Err(TryRecvError::Disconnected) => {
-                            return Err(DeviceWorkerError::MainThreadTerminated)
-                        }
-                    },
+                let event = match self.receiver.try_recv() {
+                    Ok(event) => Some(event),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => {
+                        return Err(DeviceWorkerError::MainThreadTerminated)
+                    }
                 };
 
                 if let Some(event) = event {
                     match event {
                         DeviceBoundCommand::CreateDevice { index } => {
-                            assert!(self.device.is_none());
-                            assert!(self.available_devices.is_some());
-
-                            let args = clone_args(&self.available_devices.as_ref().unwrap()[index]);
-
-                            log::info!("Creating device ({})", args);
-                            let dev = Device::new(args)?;
-
-                            let num_channels = dev.num_channels(Rx)?;
-                            let mut channels_info = Vec::with_capacity(num_channels as usize);
-
-                            for i in 0..dev.num_channels(Rx)? {
-                                let info = dev
-                                    .channel_info(Rx, i)?
-                                    .into_iter()
-                                    .map(|(key, value)| (key.to_string(), value.to_string()))
-                                    .collect();
-
-                                let ranges = ValueRanges {
-                                    samplerate: dev.get_sample_rate_range(Rx, i)?,
-                                    bandwidth: dev.bandwidth_range(Rx, i)?,
-                                    frequency: dev.frequency_range(Rx, i)?,
-                                    gain: dev.gain_range(Rx, i)?,
-                                };
-
-                                channels_info.push(ChannelInfo { ranges, info })
-                            }
-
-                            self.sender
-                                .send(GuiBoundEvent::DeviceCreated { channels_info })?;
-                            self.device = Some(dev);
+                            self.long_command(LongCommand::CreateDevice, |s| {
+                                s.create_device(index)
+                            })?;
                         }
                         DeviceBoundCommand::DestroyDevice => {
                             self.receive_enable_flag.store(false, Ordering::SeqCst);
@@ -252,29 +1054,25 @@ impl DeviceWorker {
                             self.receive_state = None;
                             self.device = None;
                             self.decoder = None;
+                            self.request_ring.clear();
+                            // they would never be served, the manager forgets them when it receives DeviceDestroyed
+                            self.pending_requests.clear();
+                            self.stop_recording()?;
+                            self.recorder.clear_pre_trigger();
 
                             self.sender.send(GuiBoundEvent::DeviceDestroyed)?;
                         }
                         DeviceBoundCommand::RefreshDevices { args } => {
-                            let available = soapysdr::enumerate(args.as_str())?;
-                            let names = available
-                                .iter()
-                                .map(|d| d.get("label").unwrap().to_owned())
-                                .collect::<Vec<_>>();
-
-                            // the refresh request is possibly sent very frequently if auto_select is true
-                            // avoid spamming empty messages if there is nothing to report
-                            if !names.is_empty() {
-                                log::info!("Available devices: {:#?}", names);
-                            }
-
-                            self.available_devices = Some(available);
-
-                            self.sender
-                                .send(GuiBoundEvent::RefreshedDevices { list: names })?;
+                            self.long_command(LongCommand::RefreshDevices, |s| {
+                                s.refresh_devices(&args)
+                            })?;
                         }
                         DeviceBoundCommand::SetReceiver(state) => {
-                            assert!(self.device.is_some());
+                            check_receiver_state(&state)?;
+                            // a restore sends the configuration right after the device, which may have failed to be created
+                            if self.device.is_none() {
+                                return Err("There is no device to configure.".into());
+                            }
 
                             log::trace!("Configuring receiver:\n{:#?}", state);
 
@@ -286,6 +1084,12 @@ impl DeviceWorker {
                                 gain,
                                 automatic_gain,
                                 automatic_dc_offset,
+                                iq_correction,
+                                clock_source,
+                                buffer_samples,
+                                settle_us,
+                                receive_timeout_us,
+                                event_budget_us,
                             } = state.clone();
 
                             // this is because changing channels after the device was created is unimplemented
@@ -294,18 +1098,23 @@ impl DeviceWorker {
 
                             let dev = self.device.as_ref().unwrap();
 
+                            // the tuner needs a moment to settle after these change, gain changes are fine
+                            let retuned = self.receive_state.as_ref().map_or(true, |s| {
+                                s.frequency != frequency || s.samplerate != samplerate
+                            });
+
                             // this is the first SetReceiver command after this Device was created
                             if self.receive_state.is_none() {
                                 let antenna = dev
-                                    .antennas(Rx, channel)?
+                                    .antennas(channel)?
                                     .pop()
                                     .ok_or("No receiving antennas on device.")?; // I know it should be antennae
 
                                 log::debug!("Selecting antenna '{}'", antenna);
 
-                                dev.set_antenna(Rx, channel, antenna)?;
+                                dev.set_antenna(channel, &antenna)?;
 
-                                let stream = dev.rx_stream(&[channel])?;
+                                let stream = dev.rx_stream(channel)?;
                                 self.receive_stream = Some(stream);
                             }
 
@@ -320,63 +1129,196 @@ impl DeviceWorker {
                                 }
                             }
 
+                            // the clock goes first, the tuning depends on the reference
+                            // a source which isn't requested anymore is left as it is, the driver has no notion of a default one
+                            let clock_changed = self
+                                .receive_state
+                                .as_ref()
+                                .map_or(true, |s| s.clock_source != clock_source);
+                            if let (true, Some(name)) = (clock_changed, &clock_source) {
+                                log::debug!("Selecting clock source '{}'", name);
+                                dev.set_clock_source(name)?;
+                            }
+
                             // this is the result of excessive bikeshedding
                             if_differs!(
-                                automatic_gain, dev.set_gain_mode(Rx, channel, automatic_gain)?;
-                                automatic_dc_offset, dev.set_dc_offset_mode(Rx, channel, automatic_dc_offset)?;
-                                gain,       dev.set_gain(Rx, channel, gain)?;
-                                frequency,  dev.set_frequency(Rx, channel, frequency, ())?; // FIXME are the args neccessary for anything?
-                                samplerate, dev.set_sample_rate(Rx, channel, samplerate)?;
-                                bandwidth,  dev.set_bandwidth(Rx, channel, bandwidth)?;
+                                automatic_gain, dev.set_gain_mode(channel, automatic_gain)?;
+                                automatic_dc_offset, dev.set_dc_offset_mode(channel, automatic_dc_offset)?;
+                                gain,       dev.set_gain(channel, gain)?;
+                                frequency,  dev.set_frequency(channel, frequency)?;
+                                samplerate, dev.set_sample_rate(channel, samplerate)?;
+                                bandwidth,  dev.set_bandwidth(channel, bandwidth)?;
                             );
 
+                            // some drivers only pick up the new samplerate when the stream is created again
+                            // the flag is left as it is so the stream is activated again at the start of the next loop if it should be
+                            let samplerate_changed = self
+                                .receive_state
+                                .as_ref()
+                                .map_or(false, |s| s.samplerate != samplerate);
+                            if samplerate_changed {
+                                if let Some(mut stream) = self.receive_stream.take() {
+                                    if self.receive_stream_active {
+                                        stream.deactivate()?;
+                                    }
+                                }
+                                self.receive_stream_active = false;
+
+                                log::debug!("Recreating the receive stream for the new samplerate");
+                                self.receive_stream = Some(dev.rx_stream(channel)?);
+                            }
+
+                            let actual = ReceiverState {
+                                channel,
+                                samplerate: dev.sample_rate(channel)?,
+                                frequency: dev.frequency(channel)?,
+                                // 0 means the gui doesn't set the bandwidth, keep it that way so the states compare equal
+                                bandwidth: if bandwidth > 0.0 {
+                                    dev.bandwidth(channel)?
+                                } else {
+                                    bandwidth
+                                },
+                                gain: dev.gain(channel)?,
+                                automatic_gain,
+                                automatic_dc_offset,
+                                iq_correction,
+                                // None is kept for the same reason as the bandwidth
+                                clock_source: match clock_source {
+                                    Some(_) => Some(dev.clock_source()?),
+                                    None => None,
+                                },
+                                buffer_samples,
+                                settle_us,
+                                receive_timeout_us,
+                                event_budget_us,
+                            };
+
+                            log::trace!("Receiver configured:\n{:#?}", actual);
+
+                            self.sender
+                                .send(GuiBoundEvent::ReceiverConfigured { actual })?;
+
                             self.mtu = self.receive_stream.as_ref().unwrap().mtu()?;
                             self.receive_state = Some(state);
-                            self.mtu_receive_time_us =
-                                self.mtu as u64 * 1000_000 / samplerate as u64;
+                            // the buffered samples belong to the previous configuration
+                            self.request_ring.clear();
+                            self.request_ring.set_capacity(self.mtu * REQUEST_RING_MTUS);
+                            // the overflows depend on the samplerate, the user wants to see those of the new one
+                            self.stream_counters.clear();
+                            self.processing_stats.clear();
+                            self.recorder.clear_pre_trigger();
+                            self.recorder.set_pre_trigger_len(pre_trigger_samples(
+                                self.pre_trigger_s,
+                                samplerate,
+                            ));
+                            self.mtu_receive_time_us = receive_time_us(self.mtu, samplerate);
+                            // the timeouts of the previous configuration say nothing about this one
+                            self.read_backoff = ReadBackoff::default();
+                            if retuned {
+                                self.settle_samples_left = receive_samples(settle_us, samplerate);
+                            }
+                            self.receive_size = buffer_samples.max(self.mtu);
+                            // a partially filled buffer belongs to the previous configuration too
+                            self.memory_received_count = 0;
+                            if iq_correction != self.iq_corrector.is_some() {
+                                self.iq_corrector = iq_correction.then(IqCorrector::new);
+                            }
 
                             // everyone loves the option dance (yes it's actually called that)
                             if let Some(mut decoder) = self.decoder.take() {
-                                decoder
-                                    .configuration_changed(self, false)
-                                    .map_err(|e| DeviceWorkerError::DecoderError(e))?;
+                                decoder.configuration_changed(self, false)?;
 
                                 self.decoder = Some(decoder);
                             }
 
                             continue;
                         }
-                        DeviceBoundCommand::RequestData { mut data } => {
-                            let len = data.get_input().len();
+                        DeviceBoundCommand::RequestData { data, overlap } => {
+                            self.pending_requests.push_back((data, overlap));
+                            self.serve_requests()?;
+                        }
+                        DeviceBoundCommand::SetDecoder { mut decoder } => {
+                            log::trace!("Configuring decoder:\n{:#?}", decoder);
 
-                            let offset = self.memory_receive_offset + self.data_request_offset;
+                            let prev = self.decoder.take();
+                            decoder.init(self, prev)?;
+                            decoder.configuration_changed(self, true)?;
+                            self.decoder = Some(decoder);
+                            // the buffer was moved to make room for the filter
+                            self.memory_received_count = 0;
+                        }
+                        DeviceBoundCommand::UpdateDecoderParams { params } => {
+                            log::trace!("Updating decoder parameters:\n{:#?}", params);
 
-                            if offset < self.working_memory.len() {
-                                self.data_request_offset += len;
+                            if let Some(mut decoder) = self.decoder.take() {
+                                decoder.update_params(self, params)?;
+                                self.decoder = Some(decoder);
+                                // the buffer could have been moved to make room for the new filter
+                                self.memory_received_count = 0;
+                            }
+                        }
+                        DeviceBoundCommand::SetSquelch { threshold } => {
+                            log::trace!("Setting the squelch threshold to {} dB", threshold);
+
+                            self.squelch_threshold = threshold;
+                        }
+                        DeviceBoundCommand::SetRawBits { enabled } => {
+                            log::trace!("Setting the raw bits to {}", enabled);
 
-                                data.get_input_mut().copy_from_slice(
-                                    &mut self.working_memory[offset..(len + offset)],
-                                );
-                                let samplerate = self.receive_state.as_ref().unwrap().samplerate;
-                                data.process(samplerate);
+                            self.raw_bits = enabled;
+                        }
+                        DeviceBoundCommand::SetPreTrigger { seconds } => {
+                            log::trace!("Setting the pre-trigger to {} s", seconds);
 
-                                self.sender.send(GuiBoundEvent::DataReady { data })?;
-                            } else {
-                                delay_event = Some(DeviceBoundCommand::RequestData { data });
-                                break 'process_events;
+                            self.pre_trigger_s = seconds;
+                            let samplerate =
+                                self.receive_state.as_ref().map_or(0.0, |s| s.samplerate);
+                            self.recorder
+                                .set_pre_trigger_len(pre_trigger_samples(seconds, samplerate));
+                        }
+                        DeviceBoundCommand::StartRecording { path, format } => {
+                            log::info!(
+                                "Recording {} to '{}'",
+                                format.name(),
+                                path.to_string_lossy()
+                            );
+
+                            self.stop_recording()?;
+                            let result = File::create(&path).and_then(|file| {
+                                let writer = IqWriter::new(BufWriter::new(file), format);
+                                self.recorder.start(writer)
+                            });
+
+                            match result {
+                                Ok(pre_trigger_samples) => {
+                                    self.sender.send(GuiBoundEvent::RecordingStarted {
+                                        pre_trigger_samples,
+                                    })?;
+                                }
+                                Err(e) => {
+                                    log::error!(
+                                        "Failed to start the recording '{}': {}",
+                                        path.to_string_lossy(),
+                                        e
+                                    );
+                                    self.sender.send(GuiBoundEvent::RecordingStopped {
+                                        samples: 0,
+                                        error: Some(e.to_string()),
+                                    })?;
+                                }
                             }
                         }
-                        DeviceBoundCommand::SetDecoder { mut decoder } => {
-                            log::trace!("Configuring decoder:\n{:#?}", decoder);
+                        DeviceBoundCommand::StopRecording => self.stop_recording()?,
+                        DeviceBoundCommand::ClearDecoder => {
+                            log::trace!("Clearing decoder");
 
-                            let prev = self.decoder.take();
-                            decoder
-                                .init(self, prev)
-                                .map_err(|e| DeviceWorkerError::DecoderError(e))?;
-                            decoder
-                                .configuration_changed(self, true)
-                                .map_err(|e| DeviceWorkerError::DecoderError(e))?;
-                            self.decoder = Some(decoder);
+                            self.decoder = None;
+                            self.current_fir_filter = None;
+
+                            // the memory reserved in front of the received samples was only needed for the filter
+                            self.working_memory.clear();
+                            self.memory_receive_offset = 0;
+                            self.memory_received_count = 0;
                         }
                     }
                 // no message was received
@@ -390,30 +1332,61 @@ impl DeviceWorker {
                 }
             }
 
-            if self.receive_stream.is_some() && self.receive_stream_active {
-                // this horrible thing is needed to satisfy the borrowchecker
-                if let Some(mut decoder) = self.decoder.take() {
-                    decoder
-                        .process(self)
-                        .map_err(|e| DeviceWorkerError::DecoderError(e))?;
+            // the requests which were waiting for the samples just received
+            self.serve_requests()?;
 
-                    self.decoder = Some(decoder);
-                }
+            // the events could have reset the buffer
+            if buffer_full && self.memory_received_count >= self.receive_size {
+                self.process_buffer()?;
             }
         }
     }
+    // flushes the file and lets the gui know, does nothing if nothing is being recorded
+    fn stop_recording(&mut self) -> Result<(), crossbeam_channel::SendError<GuiBoundEvent>> {
+        let error = match self.recorder.stop() {
+            None => return Ok(()),
+            Some(Ok(_)) => None,
+            Some(Err(e)) => {
+                log::error!("Failed to finish the recording: {}", e);
+                Some(e.to_string())
+            }
+        };
+
+        self.sender.send(GuiBoundEvent::RecordingStopped {
+            samples: self.recorder.written(),
+            error,
+        })
+    }
+    // sends the error to the gui, returns false if the main thread is gone and the worker should exit
+    fn report_error(&mut self, result: Result<(), DeviceWorkerError>) -> bool {
+        let event = match result {
+            Err(DeviceWorkerError::MainThreadTerminated) => return false,
+            Err(DeviceWorkerError::SoapyError(e)) => {
+                let kind = DeviceErrorKind::from_error(&e);
+                GuiBoundEvent::Error { kind, error: e }
+            }
+            Err(DeviceWorkerError::WorkerError(message)) => {
+                log::error!("Worker error: {}", message);
+                GuiBoundEvent::WorkerError { message }
+            }
+            // the failed decoder was taken out of self.decoder before the error was returned so it is already dropped
+            Err(DeviceWorkerError::DecoderError(kind)) => {
+                log::error!("Decoder failed: {}", kind);
+
+                self.current_fir_filter = None;
+                GuiBoundEvent::DecoderFailed { kind }
+            }
+            Ok(_) => unreachable!(), // the error_process() function only ever returns through null coalescing operators and as such always an error
+        };
+
+        self.sender.send(event).is_ok()
+    }
     pub fn process(mut self) {
         loop {
             let result = self.error_process();
 
-            match result {
-                Err(DeviceWorkerError::MainThreadTerminated) => return,
-                Err(DeviceWorkerError::SoapyError(e)) => {
-                    if let Err(_) = self.sender.send(GuiBoundEvent::Error(e)) {
-                        return;
-                    }
-                }
-                _ => unreachable!(), // the error_process() function only ever returns through null coalescing operators and as such always an error
+            if !self.report_error(result) {
+                return;
             }
 
             // sleep some time so that the main thread has time to handle the error and possibly disable this thread
@@ -423,3 +1396,591 @@ impl DeviceWorker {
         }
     }
 }
+
+#[test]
+fn device_name_without_label() {
+    let mut args = Args::new();
+    args.set("driver", "rtlsdr");
+    args.set("serial", "00000001");
+    assert_eq!(device_name(&args), "rtlsdr 00000001");
+
+    args.set("label", "Generic RTL2832U OEM :: 00000001");
+    assert_eq!(device_name(&args), "Generic RTL2832U OEM :: 00000001");
+
+    let mut args = Args::new();
+    args.set("addr", "192.168.1.10");
+    assert!(!device_name(&args).is_empty());
+}
+
+#[test]
+fn device_list_format() {
+    assert_eq!(format_device_list(&[]), "No devices found\n");
+
+    let mut rtl = Args::new();
+    rtl.set("driver", "rtlsdr");
+    rtl.set("label", "Generic RTL2832U OEM :: 00000001");
+    rtl.set("serial", "00000001");
+
+    let mut remote = Args::new();
+    remote.set("driver", "remote");
+    remote.set("remote", "tcp://192.168.1.10:55132");
+
+    assert_eq!(
+        format_device_list(&[rtl, remote]),
+        "\
+0: Generic RTL2832U OEM :: 00000001
+    driver = rtlsdr
+    label = Generic RTL2832U OEM :: 00000001
+    serial = 00000001
+1: remote
+    driver = remote
+    remote = tcp://192.168.1.10:55132
+"
+    );
+}
+
+#[test]
+fn samplerate_change_rebuilds_stream() {
+    use super::mock_device::{MockBackend, MOCK_MTU};
+
+    let backend = MockBackend::new(1);
+    let streams_opened = backend.streams_opened();
+    let flag = Arc::new(AtomicBool::new(false));
+    let (_, receiver) = crossbeam_channel::unbounded();
+    let (sender, _events) = crossbeam_channel::unbounded();
+    let mut worker = DeviceWorker::with_backend(receiver, sender, flag.clone(), Box::new(backend));
+
+    // the worker loop handles the commands and returns once their sender is gone
+    let run = |worker: &mut DeviceWorker, commands: Vec<DeviceBoundCommand>| {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for command in commands {
+            sender.send(command).unwrap();
+        }
+        drop(sender);
+        worker.receiver = receiver;
+        let result = worker.error_process();
+        assert!(matches!(
+            result,
+            Err(DeviceWorkerError::MainThreadTerminated)
+        ));
+    };
+    let state = |samplerate, gain| {
+        ReceiverState::builder()
+            .samplerate(samplerate)
+            .frequency(100e6)
+            .gain_db(gain)
+            .build()
+            .unwrap()
+    };
+
+    run(
+        &mut worker,
+        vec![
+            DeviceBoundCommand::RefreshDevices {
+                args: String::new(),
+            },
+            DeviceBoundCommand::CreateDevice { index: 0 },
+            DeviceBoundCommand::SetReceiver(state(1e6, 0.0)),
+        ],
+    );
+    assert_eq!(streams_opened.load(Ordering::SeqCst), 1);
+    assert_eq!(worker.mtu_receive_time_us, receive_time_us(MOCK_MTU, 1e6));
+
+    flag.store(true, Ordering::SeqCst);
+    run(&mut worker, Vec::new());
+    assert!(worker.receive_stream_active);
+
+    // the stream is read right before the command, the new one is left for the next loop to activate
+    run(
+        &mut worker,
+        vec![DeviceBoundCommand::SetReceiver(state(2e6, 0.0))],
+    );
+    assert_eq!(streams_opened.load(Ordering::SeqCst), 2);
+    assert!(!worker.receive_stream_active);
+    assert!(flag.load(Ordering::SeqCst));
+    assert_eq!(worker.mtu, MOCK_MTU);
+    assert_eq!(worker.mtu_receive_time_us, receive_time_us(MOCK_MTU, 2e6));
+
+    // reading the new stream would fail if it wasn't activated
+    run(&mut worker, Vec::new());
+    assert!(worker.receive_stream_active);
+
+    // the stream is kept when the samplerate stays the same
+    run(
+        &mut worker,
+        vec![DeviceBoundCommand::SetReceiver(state(2e6, 20.0))],
+    );
+    assert_eq!(streams_opened.load(Ordering::SeqCst), 2);
+    assert!(worker.receive_stream_active);
+}
+
+// the events the worker sends to the gui are received by the returned receiver
+#[cfg(test)]
+pub(crate) fn test_worker() -> (DeviceWorker, Receiver<GuiBoundEvent>) {
+    let (_, receiver) = crossbeam_channel::unbounded();
+    let (sender, events) = crossbeam_channel::unbounded();
+
+    (
+        DeviceWorker::new(receiver, sender, Arc::new(AtomicBool::new(false))),
+        events,
+    )
+}
+
+#[test]
+fn buffer_accumulates_reads() {
+    use super::mock_device::ramp_read;
+
+    let (mut worker, _events) = test_worker();
+
+    worker.mtu = 100;
+    worker.receive_size = 250;
+    worker.request_ring.set_capacity(1000);
+
+    let mut read = ramp_read(worker.mtu);
+
+    assert_eq!(worker.receive_buffer(&mut read), Ok(false));
+    assert_eq!(worker.receive_buffer(&mut read), Ok(false));
+    assert_eq!(worker.receive_buffer(&mut read), Ok(true));
+    assert_eq!(worker.memory_received_count, 250);
+
+    let start = worker.memory_receive_offset;
+    let buffer = &worker.working_memory[start..(start + 250)];
+    assert!(buffer.iter().enumerate().all(|(i, s)| s.re == i as f32));
+
+    // the next read starts a new buffer
+    assert_eq!(worker.receive_buffer(&mut read), Ok(false));
+    assert_eq!(worker.memory_received_count, 100);
+    assert_eq!(worker.request_ring.len(), 350);
+}
+
+#[test]
+fn requests_wait_for_settling() {
+    use super::mock_device::ramp_read;
+
+    let (mut worker, _events) = test_worker();
+
+    worker.mtu = 100;
+    worker.receive_size = 100;
+    worker.request_ring.set_capacity(1000);
+    // what SetReceiver does after a retune, 1 ms at 250 kSps
+    worker.settle_samples_left = receive_samples(1000, 250_000.0);
+    assert_eq!(worker.settle_samples_left, 250);
+
+    let mut read = ramp_read(worker.mtu);
+
+    // the decoder still gets every buffer
+    for _ in 0..3 {
+        assert_eq!(worker.receive_buffer(&mut read), Ok(true));
+    }
+    assert_eq!(worker.request_ring.len(), 50);
+
+    // a request made right after the retune has to wait
+    let mut request = vec![Complex::zero(); 100];
+    assert!(!worker.request_ring.read(&mut request, 100));
+
+    assert_eq!(worker.receive_buffer(&mut read), Ok(true));
+    assert!(worker.request_ring.read(&mut request, 100));
+    // the first sample after the settling time
+    assert_eq!(request[0].re, 250.0);
+}
+
+#[test]
+fn requests_wait_for_activation() {
+    use super::mock_device::ramp_read;
+
+    let (mut worker, events) = test_worker();
+
+    worker.mtu = 100;
+    worker.receive_size = 100;
+    worker.request_ring.set_capacity(1000);
+
+    let mut read = ramp_read(worker.mtu);
+
+    // received before the stream was paused
+    for _ in 0..2 {
+        assert_eq!(worker.receive_buffer(&mut read), Ok(true));
+    }
+    worker.receive_stream_active = false;
+
+    worker.stream_activated();
+    worker
+        .pending_requests
+        .push_back((SpectrumData::new(100, false), 0.0));
+    worker.serve_requests().unwrap();
+
+    // nothing was read since the activation, the request isn't served the old samples
+    assert_eq!(worker.pending_requests.len(), 1);
+    assert!(events.try_recv().is_err());
+
+    assert_eq!(worker.receive_buffer(&mut read), Ok(true));
+    worker.serve_requests().unwrap();
+
+    assert!(worker.pending_requests.is_empty());
+    assert!(matches!(
+        events.try_recv(),
+        Ok(GuiBoundEvent::DataReady { .. })
+    ));
+    // the first sample read after the activation
+    assert_eq!(worker.request_samples[0].re, 200.0);
+}
+
+// decodes "RYRYRY" sent as 50 Bd baudot with a bit of noise at 48 kSps, returns the decoded text and the last reported level
+#[cfg(test)]
+fn decode_with_squelch(threshold: f32) -> (String, Option<(f32, bool)>) {
+    use crate::generator::SignalGenerator;
+
+    let samplerate = 48000.0;
+    let (mut worker, events) = test_worker();
+
+    worker.receive_state = Some(
+        ReceiverState::builder()
+            .samplerate(samplerate)
+            .frequency(0.0)
+            .build()
+            .unwrap(),
+    );
+    worker.mtu = 4096;
+    worker.receive_size = 4096;
+    worker.squelch_threshold = threshold;
+
+    let mut decoder = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0);
+    decoder.init(&mut worker, None).unwrap();
+    decoder.configuration_changed(&mut worker, true).unwrap();
+    worker.decoder = Some(decoder);
+
+    let samples = SignalGenerator::new_baudot(samplerate, "RYRYRY", 50.0, 1.5, 425.0, 0.0)
+        .noise(0.01)
+        .samples();
+
+    let mut text = String::new();
+    let mut level = None;
+    for chunk in samples.chunks(4096) {
+        worker
+            .receive_buffer(|dst| -> Result<usize, ()> {
+                dst[..chunk.len()].copy_from_slice(chunk);
+                Ok(chunk.len())
+            })
+            .unwrap();
+        worker.process_buffer().unwrap();
+
+        for event in events.try_iter() {
+            match event {
+                GuiBoundEvent::DecodedChars { data } => text.extend(data.iter().map(|c| c.ch)),
+                GuiBoundEvent::SignalLevel { snr, squelched } => level = Some((snr, squelched)),
+                _ => {}
+            }
+        }
+    }
+
+    (text, level)
+}
+
+#[test]
+fn peak_interpolation() {
+    let len = 1024;
+    let samplerate = 48000.0;
+
+    for &bin in &[20.3, 100.5, 511.9, 1024.0 - 40.25] {
+        let mut fft = FftData::<RxFormat>::new(len);
+        fft.set_window(WindowKind::BlackmanHaris);
+        let samples: Vec<Complex<RxFormat>> = (0..len)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * bin * i as f64 / len as f64;
+                Complex::from_polar(0.5, phase as RxFormat)
+            })
+            .collect();
+        fft.set_input(&samples);
+        fft.process(samplerate);
+
+        let powers: Vec<RxFormat> = fft.get_output().iter().map(|c| c.norm_sqr()).collect();
+        let estimate = interpolate_peak(&powers).unwrap();
+
+        // the plain peak bin is up to half a bin off, the interpolation gets within a few percent of one
+        assert!((estimate - bin).abs() < 0.05, "{} {}", bin, estimate);
+        let expected = bin_frequency(bin, len, samplerate);
+        let error = (bin_frequency(estimate, len, samplerate) - expected).abs();
+        assert!(error < 0.05 * samplerate / len as f64, "{}", error);
+    }
+
+    // the last bins are just below the center
+    assert_eq!(
+        bin_frequency(1024.0 - 40.25, len, samplerate),
+        -40.25 * 46.875
+    );
+    assert_eq!(interpolate_peak(&[0.0; 16]), None);
+}
+
+#[test]
+fn squelch_gates_decoding() {
+    let (text, level) = decode_with_squelch(0.0);
+    let (snr, squelched) = level.unwrap();
+    assert!(text.contains("RYRY"));
+    assert!(snr > 30.0);
+    assert!(!squelched);
+
+    // the same signal is below the threshold now
+    let (text, level) = decode_with_squelch(snr + 10.0);
+    assert!(text.is_empty());
+    assert!(level.unwrap().1);
+}
+
+#[test]
+fn timeouts_are_retried() {
+    let error = |code, message: &str| soapysdr::Error {
+        code,
+        message: message.to_owned(),
+    };
+
+    // a transient timeout is retried until the read succeeds
+    let mut attempts = 0;
+    let result = retry_timeouts(3, &mut ReadBackoff::default(), 1000, |_| {
+        attempts += 1;
+        match attempts {
+            1 | 2 => Err(error(ErrorCode::Timeout, "")),
+            _ => Ok(attempts),
+        }
+    });
+    assert_eq!(result.unwrap(), 3);
+
+    // a persistent one is returned once the retries run out
+    let mut attempts = 0;
+    let result: Result<(), _> = retry_timeouts(3, &mut ReadBackoff::default(), 1000, |_| {
+        attempts += 1;
+        Err(error(ErrorCode::Timeout, ""))
+    });
+    assert_eq!(result.unwrap_err().code, ErrorCode::Timeout);
+    assert_eq!(attempts, 4);
+
+    // other errors and a lost device are not retried
+    for e in &[
+        error(ErrorCode::Overflow, ""),
+        error(ErrorCode::Timeout, "Lost"),
+    ] {
+        let mut attempts = 0;
+        let mut backoff = ReadBackoff::default();
+        let result: Result<(), _> = retry_timeouts(3, &mut backoff, 1000, |_| {
+            attempts += 1;
+            Err(e.clone())
+        });
+        assert_eq!(result.unwrap_err().code, e.code);
+        assert_eq!(attempts, 1);
+        assert_eq!(backoff, ReadBackoff::default());
+    }
+
+    // the configured timeout can only make the read wait longer than the mtu takes to arrive
+    assert_eq!(read_timeout_us(5_000, 200_000), 200_000);
+    assert_eq!(read_timeout_us(500_000, 200_000), 501_000);
+    assert_eq!(read_timeout_us(5_000, 0), 6_000);
+}
+
+#[test]
+fn read_timeout_backoff() {
+    let timeout = soapysdr::Error {
+        code: ErrorCode::Timeout,
+        message: String::new(),
+    };
+    let base = 6_000;
+    let mut backoff = ReadBackoff::default();
+
+    // every read of the sequence succeeds or times out, the timeouts it was given are collected
+    let run = |backoff: &mut ReadBackoff, results: &[bool]| -> (Result<(), ErrorCode>, Vec<i64>) {
+        let mut timeouts = Vec::new();
+        let mut results = results.iter();
+        let result = retry_timeouts(3, backoff, base, |timeout_us| {
+            timeouts.push(timeout_us);
+            match results.next() {
+                Some(true) => Ok(()),
+                _ => Err(timeout.clone()),
+            }
+        });
+        (result.map_err(|e| e.code), timeouts)
+    };
+
+    // the first read uses the mtu derived timeout
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![6_000]));
+
+    // a hiccup doubles the timeout and the success after it halves it again
+    assert_eq!(
+        run(&mut backoff, &[false, true]),
+        (Ok(()), vec![6_000, 12_000])
+    );
+    assert_eq!(backoff.timeout_us(base), 6_000);
+
+    // a stream which doesn't deliver grows the timeout up to the cap and errors
+    assert_eq!(
+        run(&mut backoff, &[]),
+        (Err(ErrorCode::Timeout), vec![6_000, 12_000, 24_000, 48_000])
+    );
+    assert_eq!(backoff.timeout_us(base), base << READ_BACKOFF_MAX_DOUBLINGS);
+    assert_eq!(
+        run(&mut backoff, &[]),
+        (
+            Err(ErrorCode::Timeout),
+            vec![48_000, 48_000, 48_000, 48_000]
+        )
+    );
+
+    // the successful reads shrink it back one step each
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![48_000]));
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![24_000]));
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![12_000]));
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![6_000]));
+    assert_eq!(run(&mut backoff, &[true]), (Ok(()), vec![6_000]));
+}
+
+#[test]
+fn worker_event_budget() {
+    let state = ReceiverState::builder()
+        .samplerate(1e6)
+        .frequency(100e6)
+        .build()
+        .unwrap();
+    assert_eq!(
+        event_budget(Some(&state), 4_096),
+        Duration::from_micros(4_096)
+    );
+
+    let state = ReceiverState {
+        event_budget_us: 20_000,
+        ..state
+    };
+    assert_eq!(
+        event_budget(Some(&state), 4_096),
+        Duration::from_micros(20_000)
+    );
+    assert_eq!(
+        event_budget(None, 4_096),
+        Duration::from_millis(IDLE_EVENT_BUDGET_MS)
+    );
+}
+
+#[test]
+fn stream_stats_interval() {
+    let interval = Duration::from_millis(1000);
+    let mut counters = StreamCounters::new(interval);
+    let start = Instant::now();
+
+    let totals = |event: Option<GuiBoundEvent>| match event {
+        Some(GuiBoundEvent::StreamStats {
+            overflows,
+            read_count,
+        }) => Some((overflows, read_count)),
+        None => None,
+        _ => unreachable!(),
+    };
+
+    // the first read is reported right away
+    counters.count_read(false);
+    assert_eq!(totals(counters.report(start)), Some((0, 1)));
+
+    // every third read overflows
+    for i in 0..9 {
+        counters.count_read(i % 3 == 0);
+    }
+    assert_eq!(totals(counters.report(start + interval / 2)), None);
+    assert_eq!(totals(counters.report(start + interval)), Some((3, 10)));
+    assert_eq!(totals(counters.report(start + interval * 3 / 2)), None);
+
+    // a new receiver configuration starts counting from zero
+    counters.clear();
+    counters.count_read(true);
+    assert_eq!(totals(counters.report(start + interval * 2)), Some((1, 1)));
+}
+
+#[test]
+fn processing_stats_interval() {
+    let interval = Duration::from_millis(1000);
+    let mut stats = ProcessingStats::new(interval);
+    let start = Instant::now();
+    let ms = Duration::from_millis;
+
+    let times = |event: Option<GuiBoundEvent>| match event {
+        Some(GuiBoundEvent::WorkerStats { times, elapsed }) => Some((times, elapsed)),
+        None => None,
+        _ => unreachable!(),
+    };
+
+    // the times before the first report aren't part of any interval
+    stats.add(ProcessingStage::Read, ms(500));
+    assert_eq!(times(stats.report(start)), None);
+
+    for _ in 0..10 {
+        stats.add(ProcessingStage::Read, ms(20));
+        stats.add(ProcessingStage::Fft, ms(5));
+        stats.add(ProcessingStage::Filter, ms(3));
+        stats.add(ProcessingStage::Decoder, ms(8));
+    }
+    assert_eq!(times(stats.report(start + interval / 2)), None);
+    assert_eq!(
+        times(stats.report(start + ms(1200))),
+        Some((
+            ProcessingTimes {
+                read: ms(200),
+                fft: ms(50),
+                filter: ms(30),
+                decoder: ms(80),
+            },
+            ms(1200)
+        ))
+    );
+
+    // every report starts from zero
+    stats.add(ProcessingStage::Decoder, ms(1));
+    assert_eq!(
+        times(stats.report(start + ms(2200))),
+        Some((
+            ProcessingTimes {
+                decoder: ms(1),
+                ..ProcessingTimes::default()
+            },
+            ms(1000)
+        ))
+    );
+
+    // and so does a new receiver configuration
+    stats.add(ProcessingStage::Fft, ms(7));
+    stats.clear();
+    assert_eq!(times(stats.report(start + ms(5000))), None);
+    stats.add(ProcessingStage::Fft, ms(2));
+    assert_eq!(
+        times(stats.report(start + ms(6000))),
+        Some((
+            ProcessingTimes {
+                fft: ms(2),
+                ..ProcessingTimes::default()
+            },
+            ms(1000)
+        ))
+    );
+}
+
+#[test]
+fn zero_samplerate_rejected() {
+    let (commands, receiver) = crossbeam_channel::unbounded();
+    let (sender, events) = crossbeam_channel::unbounded();
+    let mut worker = DeviceWorker::new(receiver, sender, Arc::new(AtomicBool::new(false)));
+
+    // the default settings have no samplerate, it is zeroed after the builder so that the builder doesn't catch it
+    let state = ReceiverState {
+        samplerate: 0.0,
+        ..ReceiverState::builder()
+            .samplerate(1e6)
+            .frequency(100e6)
+            .build()
+            .unwrap()
+    };
+    commands
+        .send(DeviceBoundCommand::SetReceiver(state))
+        .unwrap();
+
+    // the command is refused before it gets anywhere near the device or the division
+    let result = worker.error_process();
+    assert!(matches!(result, Err(DeviceWorkerError::WorkerError(_))));
+    assert!(worker.receive_state.is_none());
+
+    assert!(worker.report_error(result));
+    match events.try_recv() {
+        Ok(GuiBoundEvent::WorkerError { message }) => assert!(message.contains("samplerate")),
+        event => panic!("{:?}", event),
+    }
+}
@@ -6,7 +6,7 @@ use std::{
     mem::ManuallyDrop,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{self, JoinHandle},
     time::{Duration, Instant},
@@ -15,9 +15,11 @@ use std::{
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use soapysdr::Range;
 
+use crate::app_settings::DEFAULT_SETTINGS;
+use crate::decoder::Decoder;
 use crate::worker::worker::DeviceWorker;
 
-use super::worker::{DeviceBoundCommand, GuiBoundEvent};
+use super::worker::{DeviceBoundCommand, GuiBoundEvent, LongCommand};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ReceiverState {
@@ -28,14 +30,215 @@ pub struct ReceiverState {
     pub gain: f64,
     pub automatic_gain: bool,
     pub automatic_dc_offset: bool,
+    // corrects the IQ imbalance in software, for receivers which don't do it themselves
+    pub iq_correction: bool,
+    // the name of the reference the device is clocked from, one of DeviceInfo::clock_sources, None leaves it to the driver
+    pub clock_source: Option<String>,
+    // how many samples are accumulated before the decoder runs, if it is less than the mtu the stream is processed in mtu chunks
+    pub buffer_samples: usize,
+    // how long the samples are left out of RequestData after the frequency or samplerate changes
+    pub settle_us: u64,
+    // how long a stream read waits for the samples before it times out, it is never shorter than the mtu takes to arrive
+    pub receive_timeout_us: u64,
+    // how long the worker handles commands between two stream reads, 0 is the time the mtu takes to arrive
+    pub event_budget_us: u64,
 }
 
-#[derive(Debug)]
+impl ReceiverState {
+    pub fn builder() -> ReceiverStateBuilder {
+        ReceiverStateBuilder {
+            channel: 0,
+            samplerate: None,
+            frequency: None,
+            // 0 leaves the bandwidth to the driver
+            bandwidth: 0.0,
+            gain: DEFAULT_SETTINGS.gain,
+            automatic_gain: DEFAULT_SETTINGS.automatic_gain,
+            automatic_dc_offset: DEFAULT_SETTINGS.automatic_dc_offset,
+            iq_correction: DEFAULT_SETTINGS.iq_correction,
+            clock_source: None,
+            buffer_samples: DEFAULT_SETTINGS.buffer_samples as usize,
+            settle_us: DEFAULT_SETTINGS.settle_us as u64,
+            receive_timeout_us: DEFAULT_SETTINGS.receive_timeout_us as u64,
+            event_budget_us: DEFAULT_SETTINGS.worker_event_budget_us as u64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReceiverStateError {
+    // the samplerate and the frequency have no default, they always have to be set
+    MissingField(&'static str),
+    // the value is not finite or out of its range
+    InvalidValue(&'static str),
+}
+
+impl Display for ReceiverStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiverStateError::MissingField(name) => {
+                writeln!(f, "The receiver {} is not set.", name)
+            }
+            ReceiverStateError::InvalidValue(name) => {
+                writeln!(f, "The receiver {} is invalid.", name)
+            }
+        }
+    }
+}
+
+impl Error for ReceiverStateError {}
+
+// the fields which aren't set keep the values from DEFAULT_SETTINGS
+#[derive(Clone, Debug)]
+pub struct ReceiverStateBuilder {
+    channel: usize,
+    samplerate: Option<f64>,
+    frequency: Option<f64>,
+    bandwidth: f64,
+    gain: f64,
+    automatic_gain: bool,
+    automatic_dc_offset: bool,
+    iq_correction: bool,
+    clock_source: Option<String>,
+    buffer_samples: usize,
+    settle_us: u64,
+    receive_timeout_us: u64,
+    event_budget_us: u64,
+}
+
+impl ReceiverStateBuilder {
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+    pub fn samplerate(mut self, hz: f64) -> Self {
+        self.samplerate = Some(hz);
+        self
+    }
+    pub fn frequency(mut self, hz: f64) -> Self {
+        self.frequency = Some(hz);
+        self
+    }
+    pub fn bandwidth(mut self, hz: f64) -> Self {
+        self.bandwidth = hz;
+        self
+    }
+    pub fn gain_db(mut self, db: f64) -> Self {
+        self.gain = db;
+        self
+    }
+    pub fn automatic_gain(mut self, enabled: bool) -> Self {
+        self.automatic_gain = enabled;
+        self
+    }
+    pub fn automatic_dc_offset(mut self, enabled: bool) -> Self {
+        self.automatic_dc_offset = enabled;
+        self
+    }
+    pub fn iq_correction(mut self, enabled: bool) -> Self {
+        self.iq_correction = enabled;
+        self
+    }
+    // an empty name leaves the clock to the driver
+    pub fn clock_source(mut self, name: &str) -> Self {
+        self.clock_source = Some(name.to_owned()).filter(|name| !name.is_empty());
+        self
+    }
+    pub fn buffer_samples(mut self, samples: usize) -> Self {
+        self.buffer_samples = samples;
+        self
+    }
+    pub fn settle_us(mut self, us: u64) -> Self {
+        self.settle_us = us;
+        self
+    }
+    pub fn receive_timeout_us(mut self, us: u64) -> Self {
+        self.receive_timeout_us = us;
+        self
+    }
+    pub fn event_budget_us(mut self, us: u64) -> Self {
+        self.event_budget_us = us;
+        self
+    }
+    pub fn build(&self) -> Result<ReceiverState, ReceiverStateError> {
+        use ReceiverStateError::*;
+
+        let samplerate = self.samplerate.ok_or(MissingField("samplerate"))?;
+        let frequency = self.frequency.ok_or(MissingField("frequency"))?;
+
+        if !(samplerate.is_finite() && samplerate > 0.0) {
+            return Err(InvalidValue("samplerate"));
+        }
+        if !(frequency.is_finite() && frequency >= 0.0) {
+            return Err(InvalidValue("frequency"));
+        }
+        if !(self.bandwidth.is_finite() && self.bandwidth >= 0.0) {
+            return Err(InvalidValue("bandwidth"));
+        }
+        if !self.gain.is_finite() {
+            return Err(InvalidValue("gain"));
+        }
+
+        Ok(ReceiverState {
+            channel: self.channel,
+            samplerate,
+            frequency,
+            bandwidth: self.bandwidth,
+            gain: self.gain,
+            automatic_gain: self.automatic_gain,
+            automatic_dc_offset: self.automatic_dc_offset,
+            iq_correction: self.iq_correction,
+            clock_source: self.clock_source.clone(),
+            buffer_samples: self.buffer_samples,
+            settle_us: self.settle_us,
+            receive_timeout_us: self.receive_timeout_us,
+            event_budget_us: self.event_budget_us,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct ChannelInfo {
     pub ranges: ValueRanges,
     pub info: Vec<(String, String)>, // (key, value)
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    // from the args the device was created with, empty if the driver doesn't report one
+    pub serial: String,
+    pub driver: String,
+    pub hardware: String,
+    pub hardware_info: Vec<(String, String)>, // (key, value)
+    // the references the device can be clocked from, empty if it can't be changed
+    pub clock_sources: Vec<String>,
+}
+
+impl DeviceInfo {
+    // a human readable summary of the device, mostly useful for bug reports
+    pub fn describe(&self, channels_info: &[ChannelInfo]) -> String {
+        let mut string = format!("Driver: {}\nHardware: {}\n", self.driver, self.hardware);
+
+        for (key, value) in &self.hardware_info {
+            string += &format!("{}: {}\n", key, value);
+        }
+
+        if !self.clock_sources.is_empty() {
+            string += &format!("Clock sources: {}\n", self.clock_sources.join(", "));
+        }
+
+        for (i, channel) in channels_info.iter().enumerate() {
+            string += &format!("Channel {}\n", i);
+
+            for (key, value) in &channel.info {
+                string += &format!("  {}: {}\n", key, value);
+            }
+        }
+
+        string
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ValueRanges {
     pub samplerate: Vec<Range>,
@@ -67,6 +270,68 @@ impl Display for WorkerPoisoned {
 }
 impl Error for WorkerPoisoned {}
 
+// what happened to a command the tap of the manager sees
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TappedCommand {
+    Sent,
+    // it is sent after the delay, then it is seen again as sent
+    Scheduled { delay_ms: u64 },
+    // the state of the manager didn't allow it, send_command returned BadState
+    Refused,
+}
+
+// a device of a refreshed list, the serial identifies it across refreshes, the label only if it has no serial
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ListedDevice {
+    label: String,
+    serial: String,
+}
+
+impl ListedDevice {
+    // the index of this device in a refreshed list, None if it isn't in it
+    fn find(&self, list: &[String], serials: &[String]) -> Option<usize> {
+        if self.serial.is_empty() {
+            list.iter().position(|label| *label == self.label)
+        } else {
+            serials.iter().position(|serial| *serial == self.serial)
+        }
+    }
+}
+
+// what a reset sets up again on the new worker
+pub(crate) struct Restore {
+    device: ListedDevice,
+    receiver_state: Option<ReceiverState>,
+    decoder: Option<Decoder>,
+}
+
+impl Restore {
+    // the commands which bring the new worker to where the old one was, none if the device isn't in the list
+    fn commands(&self, list: &[String], serials: &[String]) -> Vec<DeviceBoundCommand> {
+        let index = match self.device.find(list, serials) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+
+        let mut commands = vec![DeviceBoundCommand::CreateDevice { index }];
+
+        if let Some(state) = &self.receiver_state {
+            commands.push(DeviceBoundCommand::SetReceiver(state.clone()));
+
+            if let Some(decoder) = &self.decoder {
+                commands.push(DeviceBoundCommand::SetDecoder {
+                    decoder: decoder.unconfigured_copy(),
+                });
+            }
+        }
+
+        commands
+    }
+}
+
+// sees every command going through the manager, for inspecting what the gui sends to the worker
+pub type CommandTap = Box<dyn FnMut(TappedCommand, &DeviceBoundCommand)>;
+
 struct ScheduledCommandEntry {
     command: DeviceBoundCommand,
     trigger_time: u64,
@@ -92,7 +357,15 @@ impl PartialEq for ScheduledCommandEntry {
 
 impl Eq for ScheduledCommandEntry {}
 
+pub const DEFAULT_WORKER_NAME: &str = "Worker thread";
+
+// the names of the worker threads of all the existing managers, a name is listed once for every manager using it
+static ACTIVE_WORKERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// the device each manager has open by the name of its worker, so that another session doesn't select it too
+static OPEN_DEVICES: Mutex<Vec<(String, ListedDevice)>> = Mutex::new(Vec::new());
+
 struct InnerDeviceManager {
+    pub(crate) name: String,
     pub(crate) thread: ManuallyDrop<JoinHandle<()>>,
     pub(crate) sender: ManuallyDrop<Sender<DeviceBoundCommand>>,
     pub(crate) receive_enable_flag: Arc<AtomicBool>,
@@ -102,16 +375,29 @@ struct InnerDeviceManager {
     pub(crate) receiver_valid: bool,
     pub(crate) decoder_valid: bool,
     pub(crate) refreshing_devices: bool,
+    // between the CommandStarted and CommandAck events of a long command
+    pub(crate) running_command: Option<LongCommand>,
     pub(crate) data_requests_in_flight: usize,
 
     pub(crate) receiver_state: Option<ReceiverState>,
+    // what the worker was set up with, a reset sends it again to the new worker
+    pub(crate) refresh_args: String,
+    pub(crate) device: Option<ListedDevice>,
+    pub(crate) decoder: Option<Decoder>,
+    // the devices of the last refresh, CreateDevice picks one of them by its index
+    pub(crate) listed_devices: Vec<ListedDevice>,
+    // set by a reset until the new worker lists the devices, the device is then looked up in the new list
+    pub(crate) pending_restore: Option<Restore>,
 
     pub(crate) start_time: Instant,
     pub(crate) scheduled_commands: BinaryHeap<ScheduledCommandEntry>,
+
+    // None unless something is inspecting the commands, then it is the only cost
+    pub(crate) command_tap: Option<CommandTap>,
 }
 
 impl InnerDeviceManager {
-    fn new() -> Self {
+    fn new(name: &str) -> Self {
         let (gui_sender_channel, gui_receive_channel) = crossbeam_channel::unbounded();
         let (device_sender_channel, device_receive_channel) = crossbeam_channel::unbounded();
 
@@ -119,7 +405,7 @@ impl InnerDeviceManager {
         let receive_enable_flag_c = receive_enable_flag.clone();
 
         let thread = thread::Builder::new()
-            .name("Worker thread".to_owned())
+            .name(name.to_owned())
             .spawn(move || {
                 let worker = DeviceWorker::new(
                     device_receive_channel,
@@ -131,7 +417,10 @@ impl InnerDeviceManager {
             })
             .unwrap();
 
+        ACTIVE_WORKERS.lock().unwrap().push(name.to_owned());
+
         Self {
+            name: name.to_owned(),
             thread: ManuallyDrop::new(thread),
             sender: ManuallyDrop::new(device_sender_channel),
             receive_enable_flag: receive_enable_flag_c,
@@ -141,12 +430,20 @@ impl InnerDeviceManager {
             receiver_valid: false,
             decoder_valid: false,
             refreshing_devices: false,
+            running_command: None,
             data_requests_in_flight: 0,
 
             receiver_state: None,
+            refresh_args: String::new(),
+            device: None,
+            decoder: None,
+            listed_devices: Vec::new(),
+            pending_restore: None,
 
             start_time: Instant::now(),
             scheduled_commands: BinaryHeap::new(),
+
+            command_tap: None,
         }
     }
     fn check_state_by_command(&self, command: &DeviceBoundCommand) -> Result<(), DeviceError> {
@@ -177,26 +474,61 @@ impl InnerDeviceManager {
                 check_state!(self.device_valid);
                 check_state!(self.receiver_valid);
             }
+            DeviceBoundCommand::UpdateDecoderParams { .. } => {
+                check_state!(self.device_valid);
+                check_state!(self.decoder_valid);
+            }
+            DeviceBoundCommand::ClearDecoder => {
+                check_state!(self.device_valid);
+            }
+            DeviceBoundCommand::SetSquelch { .. } => {}
+            DeviceBoundCommand::SetRawBits { .. } => {}
+            DeviceBoundCommand::SetPreTrigger { .. } => {}
+            DeviceBoundCommand::StartRecording { .. } => {
+                check_state!(self.device_valid);
+                check_state!(self.receiver_valid);
+            }
+            DeviceBoundCommand::StopRecording => {}
         }
 
         Ok(())
     }
     fn modify_state_by_command(&mut self, command: &DeviceBoundCommand) {
         match command {
-            DeviceBoundCommand::DestroyDevice => {
-                self.device_valid = false;
-                self.receiver_valid = false;
-                self.decoder_valid = false;
-                self.receiver_state = None;
+            DeviceBoundCommand::DestroyDevice => self.forget_device(),
+            DeviceBoundCommand::CreateDevice { index } => {
+                self.device_valid = true;
+                self.set_device(self.listed_devices.get(*index).cloned());
+                // a device created in the meantime takes the place of the restored one
+                self.pending_restore = None;
             }
-            DeviceBoundCommand::CreateDevice { .. } => self.device_valid = true,
             DeviceBoundCommand::RequestData { .. } => self.data_requests_in_flight += 1,
-            DeviceBoundCommand::RefreshDevices { .. } => self.refreshing_devices = true,
+            DeviceBoundCommand::RefreshDevices { args } => {
+                self.refreshing_devices = true;
+                self.refresh_args = args.clone();
+            }
             DeviceBoundCommand::SetReceiver(state) => {
                 self.receiver_valid = true;
                 self.receiver_state = Some(state.clone());
             }
-            DeviceBoundCommand::SetDecoder { .. } => self.decoder_valid = true,
+            DeviceBoundCommand::SetDecoder { decoder } => {
+                self.decoder_valid = true;
+                self.decoder = Some(decoder.unconfigured_copy());
+            }
+            DeviceBoundCommand::UpdateDecoderParams { params } => {
+                if let Some(decoder) = &mut self.decoder {
+                    decoder.set_params(*params);
+                }
+            }
+            DeviceBoundCommand::ClearDecoder => {
+                self.decoder_valid = false;
+                self.decoder = None;
+            }
+            DeviceBoundCommand::SetSquelch { .. } => {}
+            DeviceBoundCommand::SetRawBits { .. } => {}
+            DeviceBoundCommand::SetPreTrigger { .. } => {}
+            DeviceBoundCommand::StartRecording { .. } => {}
+            DeviceBoundCommand::StopRecording => {}
         }
     }
     fn modify_state_by_received_event(&mut self, event: &GuiBoundEvent) {
@@ -204,11 +536,114 @@ impl InnerDeviceManager {
             // this event is not sent by the device
             GuiBoundEvent::WorkerReset => unreachable!(),
             GuiBoundEvent::DeviceCreated { .. } => {}
-            GuiBoundEvent::DeviceDestroyed => {}
-            GuiBoundEvent::RefreshedDevices { .. } => self.refreshing_devices = false,
+            // the worker drops the requests it didn't answer yet, their DataReady would have been received before this
+            GuiBoundEvent::DeviceDestroyed => self.data_requests_in_flight = 0,
+            // what was sent after the CreateDevice is refused by the worker, like the configuration of a restore
+            GuiBoundEvent::DeviceNotCreated { .. } => self.forget_device(),
+            GuiBoundEvent::RefreshedDevices { list, serials } => {
+                self.refreshing_devices = false;
+                self.listed_devices = list
+                    .iter()
+                    .zip(serials)
+                    .map(|(label, serial)| ListedDevice {
+                        label: label.clone(),
+                        serial: serial.clone(),
+                    })
+                    .collect();
+            }
             GuiBoundEvent::DataReady { .. } => self.data_requests_in_flight -= 1,
-            GuiBoundEvent::Error(_) => {}
+            GuiBoundEvent::Error { .. } => {}
+            // the worker only refuses receiver configurations, the next one is sent from scratch
+            GuiBoundEvent::WorkerError { .. } => {
+                self.receiver_valid = false;
+                self.receiver_state = None;
+            }
             GuiBoundEvent::DecodedChars { .. } => {}
+            GuiBoundEvent::DemodTrace { .. } => {}
+            GuiBoundEvent::RawBits { .. } => {}
+            GuiBoundEvent::AudioReady { .. } => {}
+            GuiBoundEvent::DecoderFailed { .. } => {
+                self.decoder_valid = false;
+                self.decoder = None;
+            }
+            GuiBoundEvent::DecoderConfigured { .. } => {}
+            // compare the next requested state against what the device really uses
+            GuiBoundEvent::ReceiverConfigured { actual } => {
+                self.receiver_state = Some(actual.clone())
+            }
+            GuiBoundEvent::SignalLevel { .. } => {}
+            GuiBoundEvent::CarrierOffset { .. } => {}
+            GuiBoundEvent::Measurement { .. } => {}
+            GuiBoundEvent::StreamStats { .. } => {}
+            GuiBoundEvent::WorkerStats { .. } => {}
+            GuiBoundEvent::RecordingStarted { .. } => {}
+            GuiBoundEvent::RecordingStopped { .. } => {}
+            GuiBoundEvent::CommandStarted { command } => self.running_command = Some(*command),
+            // a failed refresh doesn't send RefreshedDevices, the ack comes either way
+            GuiBoundEvent::CommandAck { command } => {
+                self.running_command = None;
+                if *command == LongCommand::RefreshDevices {
+                    self.refreshing_devices = false;
+                }
+            }
+        }
+    }
+    fn forget_device(&mut self) {
+        self.device_valid = false;
+        self.receiver_valid = false;
+        self.decoder_valid = false;
+        self.receiver_state = None;
+        self.set_device(None);
+        self.decoder = None;
+    }
+    fn set_device(&mut self, device: Option<ListedDevice>) {
+        let mut open = OPEN_DEVICES.lock().unwrap();
+        open.retain(|(name, _)| *name != self.name);
+        if let Some(device) = &device {
+            open.push((self.name.clone(), device.clone()));
+        }
+
+        self.device = device;
+    }
+    // what a new worker needs to get to where this one is, None without a device
+    fn restore(&self) -> Option<Restore> {
+        let device = self.device.clone()?;
+        let receiver_state = self.receiver_state.clone();
+        let decoder = match receiver_state {
+            Some(_) => self.decoder.as_ref().map(Decoder::unconfigured_copy),
+            None => None,
+        };
+
+        Some(Restore {
+            device,
+            receiver_state,
+            decoder,
+        })
+    }
+    // finishes a reset once the new worker has listed the devices, the restore is dropped if the device is gone
+    fn continue_restore(&mut self, event: &GuiBoundEvent) {
+        let (list, serials) = match event {
+            GuiBoundEvent::RefreshedDevices { list, serials } => (list, serials),
+            _ => return,
+        };
+        let restore = match self.pending_restore.take() {
+            Some(restore) => restore,
+            None => return,
+        };
+
+        let commands = restore.commands(list, serials);
+        if commands.is_empty() {
+            log::warn!(
+                "The device {} is not available anymore, it is not restored",
+                restore.device.label
+            );
+        }
+
+        for command in commands {
+            if let Err(e) = self.send_command(command) {
+                log::error!("Failed to restore the configuration of the worker: {}", e);
+                break;
+            }
         }
     }
     /// Returns the earliest time in ms for a next command to send
@@ -234,26 +669,73 @@ impl InnerDeviceManager {
         // this function needs to be called to process any commands that were enqueued in the meantime, this is fine for me
         return 5;
     }
+    fn tap(&mut self, tapped: TappedCommand, command: &DeviceBoundCommand) {
+        if let Some(tap) = &mut self.command_tap {
+            tap(tapped, command);
+        }
+    }
     fn send_command(&mut self, command: DeviceBoundCommand) -> Result<(), DeviceError> {
-        self.check_state_by_command(&command)?;
+        if let Err(e) = self.check_state_by_command(&command) {
+            self.tap(TappedCommand::Refused, &command);
+            return Err(e);
+        }
+        self.tap(TappedCommand::Sent, &command);
         self.modify_state_by_command(&command);
 
+        // a refresh sent right away, like from the refresh button, replaces the one auto select scheduled
+        if let DeviceBoundCommand::RefreshDevices { .. } = command {
+            self.unschedule_refresh();
+        }
+
         self.sender
             .send(command)
             .map_err(|_| DeviceError::WorkerPoisoned)
     }
     fn schedule_command(&mut self, command: DeviceBoundCommand, delay_ms: u64) {
+        // a refresh with the same args as one scheduled or in flight would return the same list
+        // one with other args lists other devices, it takes the place of a scheduled one
+        if let DeviceBoundCommand::RefreshDevices { args } = &command {
+            if (self.refreshing_devices && self.refresh_args == *args)
+                || self.refresh_scheduled_with(args)
+            {
+                log::trace!("A device refresh is already pending, not scheduling another one");
+                return;
+            }
+            self.unschedule_refresh();
+        }
+
+        self.tap(TappedCommand::Scheduled { delay_ms }, &command);
+
         let trigger_time = self.start_time.elapsed().as_millis() as u64 + delay_ms;
         self.scheduled_commands.push(ScheduledCommandEntry {
             command,
             trigger_time,
         });
     }
+    fn refresh_scheduled(&self) -> bool {
+        self.scheduled_commands
+            .iter()
+            .any(|entry| matches!(entry.command, DeviceBoundCommand::RefreshDevices { .. }))
+    }
+    fn refresh_scheduled_with(&self, args: &str) -> bool {
+        self.scheduled_commands.iter().any(|entry| {
+            matches!(&entry.command, DeviceBoundCommand::RefreshDevices { args: scheduled } if scheduled == args)
+        })
+    }
+    fn unschedule_refresh(&mut self) {
+        if self.refresh_scheduled() {
+            self.scheduled_commands = std::mem::take(&mut self.scheduled_commands)
+                .into_iter()
+                .filter(|entry| !matches!(entry.command, DeviceBoundCommand::RefreshDevices { .. }))
+                .collect();
+        }
+    }
     fn try_receive(&mut self) -> Result<Option<GuiBoundEvent>, WorkerPoisoned> {
         let event = self.receiver.try_recv();
 
         if let Ok(event) = event.as_ref() {
             self.modify_state_by_received_event(event);
+            self.continue_restore(event);
         }
 
         match event {
@@ -277,13 +759,35 @@ impl Drop for InnerDeviceManager {
 
         // after the thread has exited it can be joined
         let _ = thread.join();
+
+        let mut workers = ACTIVE_WORKERS.lock().unwrap();
+        if let Some(i) = workers.iter().position(|name| *name == self.name) {
+            workers.remove(i);
+        }
+        drop(workers);
+
+        // a reset restores the device under the same name once the new worker has it open again
+        if self.device.is_some() {
+            self.set_device(None);
+        }
     }
 }
 
 pub struct DeviceManager(RefCell<InnerDeviceManager>);
 impl DeviceManager {
     pub fn new() -> Self {
-        Self(RefCell::new(InnerDeviceManager::new()))
+        Self::new_named(DEFAULT_WORKER_NAME)
+    }
+    // every manager owns an independent worker thread, the name is used for the thread so it should be unique
+    pub fn new_named(name: &str) -> Self {
+        Self(RefCell::new(InnerDeviceManager::new(name)))
+    }
+    // the names of the worker threads of all the managers which currently exist
+    pub fn active_workers() -> Vec<String> {
+        ACTIVE_WORKERS.lock().unwrap().clone()
+    }
+    pub fn get_name(&self) -> String {
+        self.0.borrow().name.clone()
     }
     pub fn get_device_valid(&self) -> bool {
         self.0.borrow().device_valid
@@ -291,15 +795,45 @@ impl DeviceManager {
     pub fn get_receiver_valid(&self) -> bool {
         self.0.borrow().receiver_valid
     }
+    pub fn get_decoder_valid(&self) -> bool {
+        self.0.borrow().decoder_valid
+    }
     pub fn get_refreshing_devices(&self) -> bool {
         self.0.borrow().refreshing_devices
     }
+    pub fn get_running_command(&self) -> Option<LongCommand> {
+        self.0.borrow().running_command
+    }
     pub fn get_data_requests_in_flight(&self) -> usize {
         self.0.borrow().data_requests_in_flight
     }
+    // the requests in flight and the ones scheduled to be sent later
+    pub fn get_data_requests_pending(&self) -> usize {
+        let inner = self.0.borrow();
+        let scheduled = inner
+            .scheduled_commands
+            .iter()
+            .filter(|entry| matches!(entry.command, DeviceBoundCommand::RequestData { .. }))
+            .count();
+
+        inner.data_requests_in_flight + scheduled
+    }
     pub fn get_receiver_state(&self) -> Option<ReceiverState> {
         self.0.borrow().receiver_state.clone()
     }
+    // for every device of a refreshed list whether the manager of another worker has it open
+    pub fn opened_elsewhere(&self, list: &[String], serials: &[String]) -> Vec<bool> {
+        let inner = self.0.borrow();
+        let open = OPEN_DEVICES.lock().unwrap();
+
+        (0..list.len())
+            .map(|i| {
+                open.iter().any(|(name, device)| {
+                    *name != inner.name && device.find(list, serials) == Some(i)
+                })
+            })
+            .collect()
+    }
     pub fn send_command(&self, command: DeviceBoundCommand) -> Result<(), DeviceError> {
         self.0.borrow_mut().send_command(command)
     }
@@ -312,6 +846,11 @@ impl DeviceManager {
     pub fn try_receive(&self) -> Result<Option<GuiBoundEvent>, WorkerPoisoned> {
         self.0.borrow_mut().try_receive()
     }
+    // the tap is called with every command sent or scheduled from now on, None removes it
+    // it is kept when the worker is reset
+    pub fn set_command_tap(&self, tap: Option<CommandTap>) {
+        self.0.borrow_mut().command_tap = tap;
+    }
 
     pub fn set_receive_enabled(&self, enabled: bool) {
         self.0
@@ -320,13 +859,899 @@ impl DeviceManager {
             .store(enabled, Ordering::SeqCst);
     }
 
+    // replaces the worker with a fresh one of the same name
+    // the borrow is taken first so that if the manager is still in use this panics like any other RefCell borrow
+    // before a new worker is spawned, the old worker is joined only after it was swapped out and the borrow released
+    // so nothing can observe a half dropped manager
+    // the device, the receiver and the decoder of the old worker are set up again so that a panic heals by itself
     pub fn reset(&self) {
-        let ptr = self.0.as_ptr();
+        let mut inner = self.0.borrow_mut();
+        let mut new = InnerDeviceManager::new(&inner.name);
+        new.command_tap = inner.command_tap.take();
 
-        unsafe {
-            ptr.drop_in_place();
-            let new = InnerDeviceManager::new();
-            ptr.write(new);
+        // the device is created once the new worker has listed the devices, its index may have changed
+        if let Some(restore) = inner.restore() {
+            let refresh = DeviceBoundCommand::RefreshDevices {
+                args: inner.refresh_args.clone(),
+            };
+            match new.send_command(refresh) {
+                Ok(_) => new.pending_restore = Some(restore),
+                Err(e) => log::error!("Failed to restore the configuration of the worker: {}", e),
+            }
+        }
+
+        let old = std::mem::replace(&mut *inner, new);
+
+        drop(inner);
+        drop(old);
+    }
+}
+
+// handles the events from `next` in order until there are none left or `budget` has passed, returns how many were handled
+// at least one is handled even if it takes the whole budget, so an event queued behind any number of DataReady is
+// reached after at most that many polls
+pub fn drain_events<E>(
+    budget: Duration,
+    next: impl FnMut() -> Option<E>,
+    handle: impl FnMut(E),
+) -> usize {
+    drain_events_with_clock(budget, Instant::now, next, handle)
+}
+
+// the same with the time read from `now`, the budget can be tested without waiting for it
+fn drain_events_with_clock<E>(
+    budget: Duration,
+    mut now: impl FnMut() -> Instant,
+    mut next: impl FnMut() -> Option<E>,
+    mut handle: impl FnMut(E),
+) -> usize {
+    let start = now();
+    let mut count = 0;
+
+    while let Some(event) = next() {
+        handle(event);
+        count += 1;
+
+        if now().duration_since(start) > budget {
+            break;
+        }
+    }
+
+    count
+}
+
+// the time each of the sessions gets to handle its events in a frame of `frame_ms`
+// 0 takes a third of the frame and leaves the rest to drawing and the input
+pub fn frame_event_budget(event_budget_ms: u32, frame_ms: u64, sessions: usize) -> Duration {
+    let total = match event_budget_ms {
+        0 => Duration::from_millis(frame_ms) / 3,
+        ms => Duration::from_millis(ms as u64),
+    };
+    total / sessions.max(1) as u32
+}
+
+#[test]
+fn device_info_round_trip() {
+    let device_info = DeviceInfo {
+        serial: "00000001".to_owned(),
+        driver: "rtlsdr".to_owned(),
+        hardware: "R820T".to_owned(),
+        hardware_info: vec![
+            (
+                "origin".to_owned(),
+                "https://github.com/pothosware/SoapyRTLSDR".to_owned(),
+            ),
+            ("tuner".to_owned(), "Rafael Micro R820T".to_owned()),
+        ],
+        clock_sources: Vec::new(),
+    };
+    let channels_info = vec![ChannelInfo {
+        ranges: ValueRanges {
+            samplerate: Vec::new(),
+            frequency: Vec::new(),
+            bandwidth: Vec::new(),
+            gain: Range {
+                minimum: 0.0,
+                maximum: 49.6,
+                step: 0.0,
+            },
+        },
+        info: vec![("full_duplex".to_owned(), "false".to_owned())],
+    }];
+
+    let event = GuiBoundEvent::DeviceCreated {
+        device_info: device_info.clone(),
+        channels_info,
+    };
+
+    match event {
+        GuiBoundEvent::DeviceCreated {
+            device_info: received,
+            channels_info,
+        } => {
+            assert_eq!(received, device_info);
+            assert_eq!(
+                received.describe(&channels_info),
+                "Driver: rtlsdr\n\
+                Hardware: R820T\n\
+                origin: https://github.com/pothosware/SoapyRTLSDR\n\
+                tuner: Rafael Micro R820T\n\
+                Channel 0\n  \
+                full_duplex: false\n"
+            );
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn clear_decoder_state() {
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+
+    // there is no device to clear the decoder on
+    assert!(manager
+        .check_state_by_command(&DeviceBoundCommand::ClearDecoder)
+        .is_err());
+
+    let commands = vec![
+        DeviceBoundCommand::CreateDevice { index: 0 },
+        DeviceBoundCommand::SetReceiver(
+            ReceiverState::builder()
+                .samplerate(2_048_000.0)
+                .frequency(434_000_000.0)
+                .bandwidth(1_536_000.0)
+                .gain_db(20.0)
+                .build()
+                .unwrap(),
+        ),
+        DeviceBoundCommand::SetDecoder {
+            decoder: Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0),
+        },
+    ];
+
+    // only the state machine is tested, nothing is actually sent to the worker
+    for command in &commands {
+        assert!(manager.check_state_by_command(command).is_ok());
+        manager.modify_state_by_command(command);
+    }
+    assert!(manager.decoder_valid);
+
+    let clear = DeviceBoundCommand::ClearDecoder;
+    assert!(manager.check_state_by_command(&clear).is_ok());
+    manager.modify_state_by_command(&clear);
+
+    assert!(!manager.decoder_valid);
+    assert!(manager.device_valid);
+    assert!(manager.receiver_valid);
+}
+
+#[test]
+fn receiver_configured_read_back() {
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+
+    let requested = ReceiverState::builder()
+        .samplerate(2_000_000.0)
+        .frequency(434_000_123.0)
+        .bandwidth(1_500_000.0)
+        .gain_db(30.0)
+        .automatic_dc_offset(true)
+        .build()
+        .unwrap();
+    // what a driver which rounds the frequency and gain would report back
+    let actual = ReceiverState {
+        frequency: 434_000_000.0,
+        gain: 29.7,
+        ..requested.clone()
+    };
+
+    manager.modify_state_by_command(&DeviceBoundCommand::CreateDevice { index: 0 });
+    let command = DeviceBoundCommand::SetReceiver(requested.clone());
+    assert!(manager.check_state_by_command(&command).is_ok());
+    manager.modify_state_by_command(&command);
+    assert_eq!(manager.receiver_state.as_ref(), Some(&requested));
+
+    manager.modify_state_by_received_event(&GuiBoundEvent::ReceiverConfigured {
+        actual: actual.clone(),
+    });
+    assert_eq!(manager.receiver_state, Some(actual));
+    assert!(manager.receiver_valid);
+}
+
+#[test]
+fn independent_workers() {
+    let a = DeviceManager::new_named("Worker thread A");
+    let b = DeviceManager::new_named("Worker thread B");
+
+    let workers = DeviceManager::active_workers();
+    assert!(workers.contains(&a.get_name()));
+    assert!(workers.contains(&b.get_name()));
+
+    // the worker answers DestroyDevice even without a device, the state machine is bypassed to send it anyway
+    a.0.borrow()
+        .sender
+        .send(DeviceBoundCommand::DestroyDevice)
+        .unwrap();
+
+    let event =
+        a.0.borrow()
+            .receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+    assert!(matches!(event, GuiBoundEvent::DeviceDestroyed));
+
+    // give a misrouted event time to arrive
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(b.try_receive().unwrap().is_none());
+    assert!(a.try_receive().unwrap().is_none());
+
+    drop(b);
+    let workers = DeviceManager::active_workers();
+    assert!(workers.contains(&a.get_name()));
+    assert!(!workers.contains(&"Worker thread B".to_owned()));
+}
+
+#[test]
+fn receiver_state_builder() {
+    let state = ReceiverState::builder()
+        .samplerate(2.4e6)
+        .frequency(433.92e6)
+        .build()
+        .unwrap();
+
+    // everything which wasn't set comes from the default settings
+    assert_eq!(state.channel, 0);
+    assert_eq!(state.bandwidth, 0.0);
+    assert_eq!(state.gain, DEFAULT_SETTINGS.gain);
+    assert_eq!(state.automatic_gain, DEFAULT_SETTINGS.automatic_gain);
+    assert_eq!(
+        state.automatic_dc_offset,
+        DEFAULT_SETTINGS.automatic_dc_offset
+    );
+    assert_eq!(state.iq_correction, DEFAULT_SETTINGS.iq_correction);
+    assert_eq!(state.clock_source, None);
+    assert_eq!(
+        state.buffer_samples,
+        DEFAULT_SETTINGS.buffer_samples as usize
+    );
+    assert_eq!(state.settle_us, DEFAULT_SETTINGS.settle_us as u64);
+    assert_eq!(
+        state.receive_timeout_us,
+        DEFAULT_SETTINGS.receive_timeout_us as u64
+    );
+    assert_eq!(
+        state.event_budget_us,
+        DEFAULT_SETTINGS.worker_event_budget_us as u64
+    );
+
+    let state = ReceiverState::builder()
+        .samplerate(1e6)
+        .frequency(145.8e6)
+        .bandwidth(750e3)
+        .gain_db(20.0)
+        .automatic_gain(true)
+        .automatic_dc_offset(true)
+        .iq_correction(true)
+        .clock_source("external")
+        .buffer_samples(8192)
+        .settle_us(0)
+        .receive_timeout_us(50_000)
+        .event_budget_us(2_000)
+        .build()
+        .unwrap();
+    assert_eq!(
+        state,
+        ReceiverState {
+            channel: 0,
+            samplerate: 1e6,
+            frequency: 145.8e6,
+            bandwidth: 750e3,
+            gain: 20.0,
+            automatic_gain: true,
+            automatic_dc_offset: true,
+            iq_correction: true,
+            clock_source: Some("external".to_owned()),
+            buffer_samples: 8192,
+            settle_us: 0,
+            receive_timeout_us: 50_000,
+            event_budget_us: 2_000,
+        }
+    );
+
+    use ReceiverStateError::*;
+    let builder = ReceiverState::builder();
+    assert_eq!(
+        builder.clone().frequency(100e6).build(),
+        Err(MissingField("samplerate"))
+    );
+    assert_eq!(
+        builder.clone().samplerate(1e6).build(),
+        Err(MissingField("frequency"))
+    );
+
+    let valid = builder.samplerate(1e6).frequency(100e6);
+    let invalid = [
+        (valid.clone().samplerate(0.0), "samplerate"),
+        (valid.clone().samplerate(f64::NAN), "samplerate"),
+        (valid.clone().frequency(-1.0), "frequency"),
+        (valid.clone().bandwidth(-1.0), "bandwidth"),
+        (valid.clone().gain_db(f64::INFINITY), "gain"),
+    ];
+    for (builder, name) in &invalid {
+        assert_eq!(builder.build(), Err(InvalidValue(name)));
+    }
+}
+
+#[test]
+fn reset_while_borrowed() {
+    let name = "Worker thread reset";
+    let count = || {
+        DeviceManager::active_workers()
+            .iter()
+            .filter(|w| *w == name)
+            .count()
+    };
+    let manager = DeviceManager::new_named(name);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _inner = manager.0.borrow();
+        manager.reset();
+    }));
+    assert!(result.is_err());
+    assert_eq!(count(), 1);
+
+    // the manager was left alone and can still be reset
+    manager.reset();
+    assert_eq!(manager.get_name(), name);
+    assert_eq!(count(), 1);
+    assert!(manager.try_receive().unwrap().is_none());
+
+    drop(manager);
+    assert_eq!(count(), 0);
+}
+
+#[test]
+fn pipelined_data_requests() {
+    use crate::worker::mock_device::ramp_read;
+    use crate::SpectrumData;
+
+    const IN_FLIGHT: usize = 4;
+    const LEN: usize = 64;
+
+    let request = || DeviceBoundCommand::RequestData {
+        data: SpectrumData::new(LEN, false),
+        overlap: 0.0,
+    };
+
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    manager.device_valid = true;
+    manager.receiver_valid = true;
+
+    // the commands go through the state machine of the manager and straight into the worker
+    let (mut worker, events) = crate::worker::worker::test_worker();
+    worker.mtu = 100;
+    worker.receive_size = 100;
+    worker.request_ring.set_capacity(1000);
+
+    for _ in 0..IN_FLIGHT {
+        let command = request();
+        assert!(manager.check_state_by_command(&command).is_ok());
+        manager.modify_state_by_command(&command);
+
+        if let DeviceBoundCommand::RequestData { data, overlap } = command {
+            worker.pending_requests.push_back((data, overlap));
+        }
+    }
+    assert_eq!(manager.data_requests_in_flight, IN_FLIGHT);
+
+    // nothing was received yet
+    worker.serve_requests().unwrap();
+    assert!(events.is_empty());
+
+    // every window can be told apart by its first sample
+    let mut read = ramp_read(worker.mtu);
+
+    let mut windows = Vec::new();
+    for _ in 0..10 {
+        worker.receive_buffer(&mut read).unwrap();
+        worker.serve_requests().unwrap();
+
+        for event in events.try_iter() {
+            manager.modify_state_by_received_event(&event);
+
+            if let GuiBoundEvent::DataReady { data } = event {
+                let (mut signal, mut spectrum) = (Vec::new(), Vec::new());
+                data.copy_to_f32(&mut signal, &mut spectrum);
+                windows.push(signal[0].re);
+            }
+        }
+    }
+
+    // consecutive windows in the order they were requested
+    assert_eq!(windows, [0.0, 64.0, 128.0, 192.0]);
+    assert_eq!(manager.data_requests_in_flight, 0);
+    assert!(worker.pending_requests.is_empty());
+
+    // the requests the worker drops together with the device aren't counted forever
+    manager.modify_state_by_command(&request());
+    manager.modify_state_by_command(&request());
+    manager.modify_state_by_received_event(&GuiBoundEvent::DeviceDestroyed);
+    assert_eq!(manager.data_requests_in_flight, 0);
+}
+
+#[test]
+fn coalesced_device_refresh() {
+    let refresh = || DeviceBoundCommand::RefreshDevices {
+        args: String::new(),
+    };
+    let scheduled_refreshes = |manager: &InnerDeviceManager| {
+        manager
+            .scheduled_commands
+            .iter()
+            .filter(|entry| matches!(entry.command, DeviceBoundCommand::RefreshDevices { .. }))
+            .count()
+    };
+
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+
+    // scheduled twice before the first one fires
+    manager.schedule_command(refresh(), 1000);
+    manager.schedule_command(refresh(), 1000);
+    assert_eq!(scheduled_refreshes(&manager), 1);
+
+    // other commands are still scheduled next to it
+    manager.schedule_command(DeviceBoundCommand::ClearDecoder, 1000);
+    assert_eq!(manager.scheduled_commands.len(), 2);
+
+    // sending one right away takes the place of the scheduled one
+    manager.send_command(refresh()).unwrap();
+    assert!(manager.refreshing_devices);
+    assert_eq!(scheduled_refreshes(&manager), 0);
+    assert_eq!(manager.scheduled_commands.len(), 1);
+
+    // nothing is scheduled while one is in flight
+    manager.schedule_command(refresh(), 1000);
+    assert_eq!(scheduled_refreshes(&manager), 0);
+
+    manager.modify_state_by_received_event(&GuiBoundEvent::RefreshedDevices {
+        list: Vec::new(),
+        serials: Vec::new(),
+    });
+    manager.schedule_command(refresh(), 1000);
+    assert_eq!(scheduled_refreshes(&manager), 1);
+}
+
+#[test]
+fn refresh_with_other_args_is_kept() {
+    let refresh = |args: &str| DeviceBoundCommand::RefreshDevices {
+        args: args.to_string(),
+    };
+    let scheduled_args = |manager: &InnerDeviceManager| -> Vec<String> {
+        manager
+            .scheduled_commands
+            .iter()
+            .filter_map(|entry| match &entry.command {
+                DeviceBoundCommand::RefreshDevices { args } => Some(args.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+
+    // the newer args replace the scheduled refresh instead of being dropped
+    manager.schedule_command(refresh(""), 1000);
+    manager.schedule_command(refresh("driver=rtlsdr"), 1000);
+    assert_eq!(scheduled_args(&manager), vec!["driver=rtlsdr"]);
+
+    // while one is in flight only a refresh with the same args is merged
+    manager.send_command(refresh("driver=rtlsdr")).unwrap();
+    manager.schedule_command(refresh("driver=rtlsdr"), 1000);
+    assert!(scheduled_args(&manager).is_empty());
+    manager.schedule_command(refresh("driver=hackrf"), 1000);
+    assert_eq!(scheduled_args(&manager), vec!["driver=hackrf"]);
+}
+
+#[test]
+fn failed_refresh_is_acknowledged() {
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    let command = LongCommand::RefreshDevices;
+
+    manager
+        .send_command(DeviceBoundCommand::RefreshDevices {
+            args: String::new(),
+        })
+        .unwrap();
+    manager.modify_state_by_received_event(&GuiBoundEvent::CommandStarted { command });
+    assert_eq!(manager.running_command, Some(command));
+
+    // no RefreshedDevices comes if the enumeration fails, the ack still ends the refresh
+    manager.modify_state_by_received_event(&GuiBoundEvent::CommandAck { command });
+    assert_eq!(manager.running_command, None);
+    assert!(!manager.refreshing_devices);
+}
+
+#[test]
+fn drain_respects_budget() {
+    use std::{cell::Cell, collections::VecDeque};
+
+    let budget = Duration::from_millis(20);
+    let slow = Duration::from_millis(8);
+
+    // the clock only moves while the events are handled
+    let clock = Cell::new(Instant::now());
+    let now = || clock.get();
+
+    // a flood of slow spectra with a single other event behind them
+    let mut queue: VecDeque<&str> = std::iter::repeat("DataReady").take(10).collect();
+    queue.push_back("DeviceDestroyed");
+
+    let mut handled = Vec::new();
+    let mut counts = Vec::new();
+    while !queue.is_empty() {
+        let count = drain_events_with_clock(
+            budget,
+            now,
+            || queue.pop_front(),
+            |event| {
+                if event == "DataReady" {
+                    clock.set(clock.get() + slow);
+                }
+                handled.push(event);
+            },
+        );
+        counts.push(count);
+    }
+
+    // the drain stops with the first event past the budget, 24 ms here, and the queue moved on every poll
+    assert_eq!(counts, [3, 3, 3, 2]);
+    // nothing is skipped or reordered
+    assert_eq!(handled.len(), 11);
+    assert_eq!(handled.last(), Some(&"DeviceDestroyed"));
+
+    // an empty queue returns right away, a zero budget still handles one event
+    assert_eq!(
+        drain_events_with_clock(budget, now, || None::<()>, |_| ()),
+        0
+    );
+    let mut events = vec![1, 2, 3];
+    let count = drain_events_with_clock(
+        Duration::from_secs(0),
+        now,
+        || events.pop(),
+        |_| clock.set(clock.get() + Duration::from_millis(1)),
+    );
+    assert_eq!(count, 1);
+    assert_eq!(events, [1, 2]);
+}
+
+#[test]
+fn event_budget_per_session() {
+    assert_eq!(frame_event_budget(0, 15, 1), Duration::from_millis(5));
+    assert_eq!(frame_event_budget(8, 16, 1), Duration::from_millis(8));
+    assert_eq!(frame_event_budget(8, 16, 4), Duration::from_millis(2));
+    // no sessions is the same as one
+    assert_eq!(frame_event_budget(6, 16, 0), Duration::from_millis(6));
+}
+
+#[test]
+fn reset_restores_configuration() {
+    use crate::decoder::DecoderParams;
+
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    // nothing to restore without a device
+    assert!(manager.restore().is_none());
+
+    let state = ReceiverState::builder()
+        .samplerate(2_048_000.0)
+        .frequency(434_000_000.0)
+        .build()
+        .unwrap();
+    let commands = vec![
+        DeviceBoundCommand::RefreshDevices {
+            args: "driver=rtlsdr".to_owned(),
+        },
+        DeviceBoundCommand::CreateDevice { index: 2 },
+        DeviceBoundCommand::SetReceiver(state.clone()),
+        DeviceBoundCommand::SetDecoder {
+            decoder: Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 0.0),
+        },
+        DeviceBoundCommand::UpdateDecoderParams {
+            params: DecoderParams::Baudot {
+                baudrate: 45.45,
+                stop_bits: 1.5,
+                shift: 170.0,
+                cutoff_hz: 0.0,
+                transition_width: 0.1,
+                forced_decim: None,
+                freq_offset_hz: 1000.0,
+            },
+        },
+    ];
+    // only the state machine is used, nothing is actually sent to the worker
+    manager.modify_state_by_command(&commands[0]);
+    manager.modify_state_by_received_event(&GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0001", "HackRF One", "RTL-SDR :: 0002"]),
+        serials: strings(&["0001", "", "0002"]),
+    });
+    for command in &commands[1..] {
+        manager.modify_state_by_command(command);
+    }
+
+    let restore = manager.restore().unwrap();
+    assert_eq!(manager.refresh_args, "driver=rtlsdr");
+
+    // the new worker lists the device somewhere else, it is found by its serial
+    let list = strings(&["RTL-SDR :: 0002", "RTL-SDR :: 0001"]);
+    let serials = strings(&["0002", "0001"]);
+    let restored = restore.commands(&list, &serials);
+    match &restored[..] {
+        [DeviceBoundCommand::CreateDevice { index: 0 }, DeviceBoundCommand::SetReceiver(restored_state), DeviceBoundCommand::SetDecoder {
+            decoder:
+                Decoder::BaudotDecoder {
+                    baudrate,
+                    shift,
+                    freq_offset_hz,
+                    ..
+                },
+        }] => {
+            assert_eq!(restored_state, &state);
+            // the parameters changed while running are kept
+            assert_eq!((*baudrate, *shift, *freq_offset_hz), (45.45, 170.0, 1000.0));
         }
+        _ => panic!("unexpected commands {:?}", restored),
     }
+
+    // the commands are valid in this order for a fresh manager
+    let mut fresh = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    for command in &restored {
+        assert!(fresh.check_state_by_command(command).is_ok());
+        fresh.modify_state_by_command(command);
+    }
+    assert!(fresh.device_valid && fresh.receiver_valid && fresh.decoder_valid);
+
+    // a decoder the worker dropped isn't restored
+    manager.modify_state_by_received_event(&GuiBoundEvent::DecoderFailed {
+        kind: crate::decoder::DecoderError::NotConfigured,
+    });
+    assert_eq!(
+        manager.restore().unwrap().commands(&list, &serials).len(),
+        2
+    );
+
+    manager.modify_state_by_command(&DeviceBoundCommand::DestroyDevice);
+    assert!(manager.restore().is_none());
+}
+
+#[test]
+fn restore_finds_device_by_serial() {
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let device = |label: &str, serial: &str| ListedDevice {
+        label: label.to_owned(),
+        serial: serial.to_owned(),
+    };
+
+    let list = strings(&["RTL-SDR :: 0001", "HackRF One", "RTL-SDR :: 0002"]);
+    let serials = strings(&["0001", "", "0002"]);
+
+    // the serial wins even if the label points elsewhere
+    assert_eq!(
+        device("RTL-SDR :: 0001", "0002").find(&list, &serials),
+        Some(2)
+    );
+    // a device without a serial is found by its label
+    assert_eq!(device("HackRF One", "").find(&list, &serials), Some(1));
+    // a device which is gone isn't replaced by another one
+    assert_eq!(
+        device("RTL-SDR :: 0003", "0003").find(&list, &serials),
+        None
+    );
+    assert_eq!(device("Airspy", "").find(&list, &serials), None);
+    assert_eq!(device("HackRF One", "").find(&[], &[]), None);
+}
+
+#[test]
+fn restore_skips_missing_device() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let mut manager = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    manager.modify_state_by_received_event(&GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0002", "RTL-SDR :: 0003"]),
+        serials: strings(&["0001", "0002", "0003"]),
+    });
+    manager.modify_state_by_command(&DeviceBoundCommand::CreateDevice { index: 2 });
+
+    // the new manager of a reset, the worker crashed because the device at index 2 was unplugged
+    let mut fresh = InnerDeviceManager::new(DEFAULT_WORKER_NAME);
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let s = sent.clone();
+    fresh.command_tap = Some(Box::new(move |_, command| {
+        s.borrow_mut().push(format!("{:?}", command))
+    }));
+    fresh.pending_restore = manager.restore();
+
+    // events other than the list don't finish the restore
+    fresh.continue_restore(&GuiBoundEvent::DeviceDestroyed);
+    assert!(fresh.pending_restore.is_some());
+
+    // index 2 doesn't exist anymore, nothing is created instead of the wrong device or a panic
+    let event = GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0002"]),
+        serials: strings(&["0001", "0002"]),
+    };
+    fresh.modify_state_by_received_event(&event);
+    fresh.continue_restore(&event);
+    assert!(fresh.pending_restore.is_none());
+    assert!(!fresh.device_valid);
+    assert!(sent.borrow().is_empty());
+
+    // the restore is done once, a later list with the device doesn't create it behind the user's back
+    let event = GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0003"]),
+        serials: strings(&["0003"]),
+    };
+    fresh.continue_restore(&event);
+    assert!(sent.borrow().is_empty());
+
+    // when it is listed, it is created by its new index
+    fresh.pending_restore = manager.restore();
+    fresh.modify_state_by_received_event(&event);
+    fresh.continue_restore(&event);
+    assert!(fresh.device_valid);
+    assert_eq!(*sent.borrow(), ["CreateDevice { index: 0 }"]);
+}
+
+#[test]
+fn restore_forgets_device_the_worker_refuses() {
+    use std::time::{Duration, Instant};
+
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let name = "Worker thread restore refused";
+
+    let mut manager = InnerDeviceManager::new(name);
+    manager.modify_state_by_received_event(&GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0003"]),
+        serials: strings(&["0001", "0003"]),
+    });
+    manager.modify_state_by_command(&DeviceBoundCommand::CreateDevice { index: 1 });
+    manager.modify_state_by_command(&DeviceBoundCommand::SetReceiver(
+        ReceiverState::builder()
+            .samplerate(2_048_000.0)
+            .frequency(434_000_000.0)
+            .build()
+            .unwrap(),
+    ));
+    let restore = manager.restore();
+    drop(manager);
+
+    // the manager has the serial in its list, the worker never refreshed and doesn't have the device
+    let mut fresh = InnerDeviceManager::new(name);
+    fresh.pending_restore = restore;
+    let event = GuiBoundEvent::RefreshedDevices {
+        list: strings(&["RTL-SDR :: 0003"]),
+        serials: strings(&["0003"]),
+    };
+    fresh.modify_state_by_received_event(&event);
+    fresh.continue_restore(&event);
+    assert!(fresh.device_valid && fresh.receiver_valid);
+
+    // the receiver of the restore is refused after the device
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut received = Vec::new();
+    while !matches!(received.last(), Some(GuiBoundEvent::WorkerError { .. })) {
+        assert!(Instant::now() < deadline, "{:?}", received);
+        match fresh.try_receive().unwrap() {
+            Some(event) => received.push(event),
+            None => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+    match &received[..] {
+        [GuiBoundEvent::CommandStarted { .. }, GuiBoundEvent::DeviceNotCreated { message }, GuiBoundEvent::CommandAck { .. }, GuiBoundEvent::WorkerError { .. }] =>
+        {
+            assert!(message.contains("list"))
+        }
+        events => panic!("{:?}", events),
+    }
+
+    // nothing is left of the device, it can be created again
+    assert!(!fresh.device_valid);
+    assert!(!fresh.receiver_valid);
+    assert!(fresh.device.is_none());
+    assert!(fresh.receiver_state.is_none());
+    assert!(!OPEN_DEVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(open, _)| open == name));
+    assert!(fresh
+        .check_state_by_command(&DeviceBoundCommand::CreateDevice { index: 0 })
+        .is_ok());
+}
+
+#[test]
+fn command_tap_sees_commands() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let manager = DeviceManager::new_named("Worker thread command tap");
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let s = seen.clone();
+    manager.set_command_tap(Some(Box::new(move |tapped, command| {
+        s.borrow_mut().push(format!("{:?} {:?}", tapped, command));
+    })));
+
+    // like the device group at startup with automatic selection and a user moving the squelch
+    manager.schedule_command(
+        DeviceBoundCommand::RefreshDevices {
+            args: "driver=none".to_owned(),
+        },
+        0,
+    );
+    assert!(manager
+        .send_command(DeviceBoundCommand::ClearDecoder)
+        .is_err());
+    manager
+        .send_command(DeviceBoundCommand::SetSquelch { threshold: 6.0 })
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(5));
+    manager.poll_scheduled_commands();
+
+    manager.set_command_tap(None);
+    manager
+        .send_command(DeviceBoundCommand::SetRawBits { enabled: true })
+        .unwrap();
+
+    assert_eq!(
+        *seen.borrow(),
+        [
+            "Scheduled { delay_ms: 0 } RefreshDevices { args: \"driver=none\" }",
+            "Refused ClearDecoder",
+            "Sent SetSquelch { threshold: 6.0 }",
+            "Sent RefreshDevices { args: \"driver=none\" }",
+        ]
+    );
+}
+
+#[test]
+fn open_devices_are_skipped_by_other_managers() {
+    let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let list = strings(&["RTL-SDR :: 0001", "RTL-SDR :: 0002"]);
+    let serials = strings(&["0001", "0002"]);
+
+    let a = DeviceManager::new_named("Worker thread open devices A");
+    let b = DeviceManager::new_named("Worker thread open devices B");
+    let refreshed = GuiBoundEvent::RefreshedDevices {
+        list: list.clone(),
+        serials: serials.clone(),
+    };
+    a.0.borrow_mut().modify_state_by_received_event(&refreshed);
+    b.0.borrow_mut().modify_state_by_received_event(&refreshed);
+
+    a.0.borrow_mut()
+        .modify_state_by_command(&DeviceBoundCommand::CreateDevice { index: 1 });
+
+    // only the other manager sees the device as taken, also in a list in another order
+    assert_eq!(a.opened_elsewhere(&list, &serials), [false, false]);
+    assert_eq!(b.opened_elsewhere(&list, &serials), [false, true]);
+    let reordered = strings(&["RTL-SDR :: 0002", "RTL-SDR :: 0001"]);
+    let reordered_serials = strings(&["0002", "0001"]);
+    assert_eq!(
+        b.opened_elsewhere(&reordered, &reordered_serials),
+        [true, false]
+    );
+
+    a.0.borrow_mut()
+        .modify_state_by_command(&DeviceBoundCommand::DestroyDevice);
+    assert_eq!(b.opened_elsewhere(&list, &serials), [false, false]);
+
+    // a dropped manager doesn't keep its device
+    a.0.borrow_mut()
+        .modify_state_by_command(&DeviceBoundCommand::CreateDevice { index: 0 });
+    assert_eq!(b.opened_elsewhere(&list, &serials), [true, false]);
+    drop(a);
+    assert_eq!(b.opened_elsewhere(&list, &serials), [false, false]);
 }
@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use radiothing_qt::{
+    dsp::{fir_filter::FirFilter, multistage_fir::MultistageFir, window_functions::WindowKind},
+    generator::SignalGenerator,
+};
+use rustfft::num_complex::Complex32;
+
+const SAMPLERATE: f32 = 48000.0;
+const CHUNK: usize = 4096;
+
+fn tone(freq: f32, len: usize) -> Vec<Complex32> {
+    SignalGenerator::new_tone(SAMPLERATE as f64, freq as f64, len).samples()
+}
+
+// feeds the samples through the filter in chunks the same way the decoder does, returns all of the output
+fn decimate(filter: &mut MultistageFir<Complex32>, samples: &[Complex32]) -> Vec<Complex32> {
+    let reserve = filter.min_buffer_reserve();
+    let mut buffer = vec![Complex32::new(0.0, 0.0); reserve + CHUNK];
+    let mut output = Vec::new();
+
+    for chunk in samples.chunks(CHUNK) {
+        let len = reserve + chunk.len();
+        buffer[reserve..len].copy_from_slice(chunk);
+
+        let (start, count) = filter.apply(&mut buffer[..len], reserve);
+        output.extend_from_slice(&buffer[start..start + count]);
+    }
+
+    output
+}
+
+// the mean amplitude after the filters have settled
+fn settled_amplitude(output: &[Complex32]) -> f32 {
+    let settled = &output[output.len() / 2..];
+    settled.iter().map(|c| c.norm()).sum::<f32>() / settled.len() as f32
+}
+
+#[test]
+fn fir_in_place_keeps_dc() {
+    let fir = FirFilter::new_lowpass(1.0, 0.1, 0.05, WindowKind::BlackmanHaris);
+
+    let mut buffer = vec![1.0f32; 1000];
+    let (output_len, leftover_len) = fir.apply_in_place(&mut buffer, 4);
+
+    assert_eq!(output_len, (1000 - fir.len()) / 4 + 1);
+    assert_eq!(output_len * 4 + leftover_len, 1000);
+    for &sample in &buffer[..output_len] {
+        assert!((sample - 1.0).abs() < 1e-3);
+    }
+
+    // too short to produce anything, everything is left over
+    let mut short = vec![1.0f32; fir.len() - 1];
+    assert_eq!(fir.apply_in_place(&mut short, 4), (0, fir.len() - 1));
+}
+
+#[test]
+fn decimation_pipeline() {
+    // the same as the 50 Bd baudot decoder at 48 kSps, 16 samples per bit with a 425 Hz shift
+    let mut cache = Vec::new();
+    let new_filter = |cache: &mut Vec<(u32, Rc<FirFilter>)>| {
+        MultistageFir::<Complex32>::new_multistage_decim_precise(
+            60,
+            WindowKind::BlackmanHaris,
+            cache,
+            425.0 / SAMPLERATE as f64,
+            0.1,
+        )
+    };
+
+    let (mut filter, factor) = new_filter(&mut cache);
+    assert_eq!(factor, 60);
+
+    let len = 40 * CHUNK;
+    let passed = decimate(&mut filter, &tone(212.5, len));
+    // every input sample ends up in the output except for the ones still held by the filter
+    assert!(passed.len() <= len / factor as usize);
+    assert!(passed.len() + filter.taps() >= len / factor as usize);
+    assert!((settled_amplitude(&passed) - 0.5).abs() < 0.05);
+
+    // far outside of the passband, the filter shares the cached stages with the first one
+    let (mut filter, _) = new_filter(&mut cache);
+    let stopped = decimate(&mut filter, &tone(5000.0, len));
+    assert!(settled_amplitude(&stopped) < 0.005);
+}
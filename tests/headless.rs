@@ -0,0 +1,60 @@
+use std::process::Command;
+
+use radiothing_qt::{
+    generator::SignalGenerator,
+    iq_source::{IqFormat, IqWriter},
+};
+
+const SAMPLERATE: f64 = 48000.0;
+const BAUDRATE: f64 = 50.0;
+const SHIFT: f64 = 425.0;
+
+// the recording is interleaved little-endian cf32 like the ones the headless mode reads
+fn baudot_capture(text: &str) -> Vec<u8> {
+    let samples =
+        SignalGenerator::new_baudot(SAMPLERATE, text, BAUDRATE, 1.5, SHIFT, 0.0).samples();
+
+    let mut writer = IqWriter::new(Vec::new(), IqFormat::Cf32);
+    writer.write_samples(&samples).unwrap();
+    writer.into_inner()
+}
+
+#[test]
+fn headless_baudot_decode() {
+    let text = "THE QUICK BROWN FOX";
+    let path = std::env::temp_dir().join("radiothing_headless_baudot.cf32");
+    std::fs::write(&path, baudot_capture(text)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_radiothing-qt"))
+        .arg("--headless")
+        .args(&["--input", path.to_str().unwrap()])
+        .args(&["--samplerate", &SAMPLERATE.to_string()])
+        .args(&["--mode", "baudot"])
+        .args(&["--baudrate", &BAUDRATE.to_string()])
+        .args(&["--stop-bits", "1.5"])
+        .args(&["--shift", &SHIFT.to_string()])
+        .output()
+        .unwrap();
+
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(text));
+}
+
+#[test]
+fn headless_generator_loopback() {
+    // figures and lowercase go through the shifts of the encoder and the decoder
+    let output = Command::new(env!("CARGO_BIN_EXE_radiothing-qt"))
+        .arg("--headless")
+        .args(&["--generate", "cq de rt-1 73 k"])
+        .args(&["--samplerate", &SAMPLERATE.to_string()])
+        .args(&["--baudrate", "75"])
+        .args(&["--shift", "170"])
+        .args(&["--offset", "1000"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("CQ DE RT-1 73 K"));
+}
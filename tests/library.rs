@@ -0,0 +1,23 @@
+use num_traits::Zero;
+use radiothing_qt::{
+    decoder::Decoder, generator::SignalGenerator, headless::HeadlessDecoder,
+    iq_source::SampleSource,
+};
+use rustfft::num_complex::Complex32;
+
+#[test]
+fn decode_buffer() {
+    let samplerate = 48000.0;
+
+    // a buffer of samples from anywhere, here they happen to be generated
+    let mut generator =
+        SignalGenerator::new_baudot(samplerate, "RYRY DE LIBRARY", 50.0, 1.5, 425.0, 1000.0);
+    let mut samples = vec![Complex32::zero(); generator.sample_count()];
+    assert_eq!(generator.read_samples(&mut samples).unwrap(), samples.len());
+
+    let baudot = Decoder::new_baudot(50.0, 1.5, 425.0, 0.0, 0.1, None, 1000.0);
+    let mut decoder = HeadlessDecoder::new(baudot, samplerate, 0.0).unwrap();
+
+    let text = decoder.decode_all(&mut &samples[..]).unwrap();
+    assert!(text.contains("RYRY DE LIBRARY"), "{:?}", text);
+}
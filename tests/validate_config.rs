@@ -0,0 +1,52 @@
+use std::process::Command;
+
+use radiothing_qt::app_settings::DEFAULT_SETTINGS;
+
+fn validate(path: &std::path::Path) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_radiothing-qt"))
+        .args(&["--validate-config", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn validate_good_and_bad_config() {
+    // the pid keeps test runs of other checkouts from using the same files
+    let dir = std::env::temp_dir();
+    let path = |name: &str| {
+        dir.join(format!(
+            "radiothing_validate_{}_{}.txt",
+            name,
+            std::process::id()
+        ))
+    };
+
+    // the default config, the same one --create-config writes, has to pass
+    let good = path("good");
+    std::fs::write(&good, DEFAULT_SETTINGS.pretty_serialize()).unwrap();
+
+    let (valid, output) = validate(&good);
+    assert!(valid, "{}", output);
+    assert!(output.contains("is a valid configuration"));
+
+    let bad = path("bad");
+    std::fs::write(&bad, "samplerate = 2\nfrequency = 433.92Mhz\n").unwrap();
+
+    let (valid, output) = validate(&bad);
+    assert!(!valid);
+    assert!(output.contains("Found 1 error(s)"), "{}", output);
+    assert!(output.contains("2 | frequency = 433.92Mhz"), "{}", output);
+    assert!(output.contains("  |                   ^"), "{}", output);
+
+    // a missing file is an error too
+    let (valid, _) = validate(&path("missing"));
+    assert!(!valid);
+
+    let _ = std::fs::remove_file(&good);
+    let _ = std::fs::remove_file(&bad);
+}
@@ -0,0 +1,309 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use radiothing_qt::{
+    worker::{
+        mock_device::{MockBackend, MOCK_CLOCK_SOURCES, MOCK_TONE_OFFSET},
+        worker::{DeviceBoundCommand, DeviceWorker, GuiBoundEvent, LongCommand},
+        worker_manager::ReceiverState,
+    },
+    SpectrumData,
+};
+
+// a worker on its own thread with MockBackend::new(count) behind it, it stops when the command sender is dropped
+fn spawn_worker(
+    count: usize,
+) -> (
+    Sender<DeviceBoundCommand>,
+    Receiver<GuiBoundEvent>,
+    Arc<AtomicBool>,
+    JoinHandle<()>,
+) {
+    let (commands, receiver) = crossbeam_channel::unbounded();
+    let (sender, events) = crossbeam_channel::unbounded();
+    let flag = Arc::new(AtomicBool::new(false));
+
+    let worker_flag = flag.clone();
+    let thread = thread::spawn(move || {
+        DeviceWorker::with_backend(
+            receiver,
+            sender,
+            worker_flag,
+            Box::new(MockBackend::new(count)),
+        )
+        .process()
+    });
+
+    (commands, events, flag, thread)
+}
+
+// the level, stream and processing reports come on their own while receiving, they aren't part of the transitions
+fn next_event(events: &Receiver<GuiBoundEvent>) -> GuiBoundEvent {
+    loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            GuiBoundEvent::StreamStats { .. }
+            | GuiBoundEvent::WorkerStats { .. }
+            | GuiBoundEvent::SignalLevel { .. }
+            | GuiBoundEvent::CarrierOffset { .. } => continue,
+            event => return event,
+        }
+    }
+}
+
+// the events of a long command without its CommandStarted and CommandAck, which have to be around them
+fn long_command_events(
+    events: &Receiver<GuiBoundEvent>,
+    command: LongCommand,
+) -> Vec<GuiBoundEvent> {
+    match next_event(events) {
+        GuiBoundEvent::CommandStarted { command: started } => assert_eq!(started, command),
+        event => panic!("{:?}", event),
+    }
+
+    let mut inner = Vec::new();
+    loop {
+        match next_event(events) {
+            GuiBoundEvent::CommandAck { command: acked } => {
+                assert_eq!(acked, command);
+                return inner;
+            }
+            GuiBoundEvent::CommandStarted { .. } => panic!("Long commands can't overlap"),
+            event => inner.push(event),
+        }
+    }
+}
+
+fn create_device(commands: &Sender<DeviceBoundCommand>, events: &Receiver<GuiBoundEvent>) {
+    commands
+        .send(DeviceBoundCommand::RefreshDevices {
+            args: String::new(),
+        })
+        .unwrap();
+    match &long_command_events(events, LongCommand::RefreshDevices)[..] {
+        [GuiBoundEvent::RefreshedDevices { list, serials }] => {
+            assert_eq!(list, &["Mock device 0"]);
+            assert_eq!(serials, &["mock0"]);
+        }
+        events => panic!("{:?}", events),
+    }
+
+    commands
+        .send(DeviceBoundCommand::CreateDevice { index: 0 })
+        .unwrap();
+    match &long_command_events(events, LongCommand::CreateDevice)[..] {
+        [GuiBoundEvent::DeviceCreated {
+            device_info,
+            channels_info,
+        }] => {
+            assert_eq!(device_info.driver, "mock");
+            assert_eq!(device_info.serial, "mock0");
+            assert_eq!(device_info.clock_sources, MOCK_CLOCK_SOURCES);
+            assert_eq!(channels_info.len(), 1);
+        }
+        events => panic!("{:?}", events),
+    }
+}
+
+#[test]
+fn create_configure_request_destroy() {
+    let (commands, events, flag, thread) = spawn_worker(1);
+    create_device(&commands, &events);
+
+    let state = ReceiverState::builder()
+        .samplerate(1e6)
+        .frequency(145e6)
+        .gain_db(20.0)
+        .build()
+        .unwrap();
+    commands
+        .send(DeviceBoundCommand::SetReceiver(state.clone()))
+        .unwrap();
+    match next_event(&events) {
+        GuiBoundEvent::ReceiverConfigured { actual } => assert_eq!(actual, state),
+        event => panic!("{:?}", event),
+    }
+
+    // the request is only served once the stream is running
+    let len = 1024;
+    commands
+        .send(DeviceBoundCommand::RequestData {
+            data: SpectrumData::new(len, false),
+            overlap: 0.0,
+        })
+        .unwrap();
+    flag.store(true, Ordering::SeqCst);
+    match next_event(&events) {
+        GuiBoundEvent::DataReady { data } => {
+            assert_eq!(data.get_samplerate(), 1e6);
+
+            let peak = (0..len)
+                .max_by(|&a, &b| data.psd_db(a).partial_cmp(&data.psd_db(b)).unwrap())
+                .unwrap();
+            assert_eq!(peak, (len as f64 * MOCK_TONE_OFFSET) as usize);
+        }
+        event => panic!("{:?}", event),
+    }
+
+    commands.send(DeviceBoundCommand::DestroyDevice).unwrap();
+    match next_event(&events) {
+        GuiBoundEvent::DeviceDestroyed => {}
+        event => panic!("{:?}", event),
+    }
+    assert!(!flag.load(Ordering::SeqCst));
+
+    // the device can be created again after it was destroyed
+    create_device(&commands, &events);
+
+    drop(commands);
+    thread.join().unwrap();
+}
+
+#[test]
+fn refused_receiver_keeps_device() {
+    let (commands, events, _, thread) = spawn_worker(1);
+    create_device(&commands, &events);
+
+    let mut state = ReceiverState::builder()
+        .samplerate(1e6)
+        .frequency(145e6)
+        .build()
+        .unwrap();
+    state.samplerate = 0.0;
+    commands
+        .send(DeviceBoundCommand::SetReceiver(state.clone()))
+        .unwrap();
+    match next_event(&events) {
+        GuiBoundEvent::WorkerError { .. } => {}
+        event => panic!("{:?}", event),
+    }
+
+    // the device is still there and takes a valid configuration
+    state.samplerate = 2e6;
+    commands
+        .send(DeviceBoundCommand::SetReceiver(state.clone()))
+        .unwrap();
+    match next_event(&events) {
+        GuiBoundEvent::ReceiverConfigured { actual } => assert_eq!(actual, state),
+        event => panic!("{:?}", event),
+    }
+
+    drop(commands);
+    thread.join().unwrap();
+}
+
+#[test]
+fn long_commands_are_acknowledged() {
+    let (commands, events, _, thread) = spawn_worker(0);
+
+    // nothing is found but the refresh still completes
+    commands
+        .send(DeviceBoundCommand::RefreshDevices {
+            args: String::new(),
+        })
+        .unwrap();
+    match &long_command_events(&events, LongCommand::RefreshDevices)[..] {
+        [GuiBoundEvent::RefreshedDevices { list, .. }] => assert!(list.is_empty()),
+        events => panic!("{:?}", events),
+    }
+
+    // the commands in between aren't bracketed
+    commands
+        .send(DeviceBoundCommand::SetSquelch { threshold: 0.0 })
+        .unwrap();
+    commands
+        .send(DeviceBoundCommand::RefreshDevices {
+            args: String::new(),
+        })
+        .unwrap();
+    assert_eq!(
+        long_command_events(&events, LongCommand::RefreshDevices).len(),
+        1
+    );
+
+    drop(commands);
+    thread.join().unwrap();
+}
+
+#[test]
+fn clock_source_is_applied() {
+    let (commands, events, _, thread) = spawn_worker(1);
+    create_device(&commands, &events);
+
+    let builder = ReceiverState::builder().samplerate(1e6).frequency(145e6);
+    let configure = |state: &ReceiverState| {
+        commands
+            .send(DeviceBoundCommand::SetReceiver(state.clone()))
+            .unwrap();
+        next_event(&events)
+    };
+
+    // the driver's choice is left alone and isn't reported
+    let state = builder.clone().build().unwrap();
+    match configure(&state) {
+        GuiBoundEvent::ReceiverConfigured { actual } => assert_eq!(actual.clock_source, None),
+        event => panic!("{:?}", event),
+    }
+
+    let state = builder.clone().clock_source("external").build().unwrap();
+    match configure(&state) {
+        GuiBoundEvent::ReceiverConfigured { actual } => assert_eq!(actual, state),
+        event => panic!("{:?}", event),
+    }
+
+    // a source the device doesn't have is an error from the driver
+    let state = builder.clone().clock_source("gpsdo").build().unwrap();
+    match configure(&state) {
+        GuiBoundEvent::Error { .. } => {}
+        event => panic!("{:?}", event),
+    }
+
+    drop(commands);
+    thread.join().unwrap();
+}
+
+#[test]
+fn missing_device_index_is_refused() {
+    let (commands, events, _, thread) = spawn_worker(1);
+    create_device(&commands, &events);
+
+    // like a restore after a reset, the device at index 1 existed before the device list changed
+    commands.send(DeviceBoundCommand::DestroyDevice).unwrap();
+    assert!(matches!(
+        next_event(&events),
+        GuiBoundEvent::DeviceDestroyed
+    ));
+    commands
+        .send(DeviceBoundCommand::CreateDevice { index: 1 })
+        .unwrap();
+    match &long_command_events(&events, LongCommand::CreateDevice)[..] {
+        [GuiBoundEvent::DeviceNotCreated { message }] => assert!(message.contains("list")),
+        events => panic!("{:?}", events),
+    }
+
+    // the configuration sent by the restore after the device is refused
+    let state = ReceiverState::builder()
+        .samplerate(1e6)
+        .frequency(145e6)
+        .build()
+        .unwrap();
+    commands
+        .send(DeviceBoundCommand::SetReceiver(state))
+        .unwrap();
+    match next_event(&events) {
+        GuiBoundEvent::WorkerError { message } => assert!(message.contains("no device")),
+        event => panic!("{:?}", event),
+    }
+
+    // the worker didn't panic, the listed device can still be created
+    create_device(&commands, &events);
+
+    drop(commands);
+    thread.join().unwrap();
+}